@@ -1,3 +1,4 @@
+use bytes::{Bytes, BytesMut};
 use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
 use ignix::*;
 
@@ -7,11 +8,13 @@ fn bench_exec_set_get(c: &mut Criterion) {
         b.iter_batched(
             || Shard::new(0, None),
             |shard| {
+                let mut out = BytesMut::with_capacity(64);
                 for i in 0..1_000_000u32 {
-                    let k = format!("k{}", i).into_bytes();
-                    let v = format!("v{}", i).into_bytes();
-                    let _ = shard.exec(Cmd::Set(k.clone(), v));
-                    let _ = shard.exec(Cmd::Get(k));
+                    let k = Bytes::from(format!("k{}", i));
+                    let v = Bytes::from(format!("v{}", i));
+                    shard.exec(Cmd::Set(k.clone(), v), &mut out);
+                    shard.exec(Cmd::Get(k), &mut out);
+                    out.clear();
                 }
                 black_box(shard)
             },
@@ -21,5 +24,44 @@ fn bench_exec_set_get(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, bench_exec_set_get);
+fn bench_exec_incr(c: &mut Criterion) {
+    let mut group = c.benchmark_group("exec");
+    group.bench_function("incr_existing", |b| {
+        let shard = Shard::new(0, None);
+        // Start from a string counter; the first INCR converts it to an int
+        shard.dict.set(Bytes::from_static(b"counter"), Value::Str(Bytes::from_static(b"0")));
+        let key = Bytes::from_static(b"counter");
+        let mut out = BytesMut::with_capacity(64);
+        b.iter(|| {
+            shard.exec(Cmd::Incr(key.clone()), &mut out);
+            out.clear();
+        });
+        black_box(&shard);
+    });
+    group.finish();
+}
+
+fn bench_exec_append(c: &mut Criterion) {
+    let mut group = c.benchmark_group("exec");
+    // Grows one string in place; a copy per APPEND would make this quadratic
+    group.bench_function("append_10k", |b| {
+        let chunk = Bytes::from_static(b"0123456789abcdef");
+        b.iter_batched(
+            || Shard::new(0, None),
+            |shard| {
+                let key = Bytes::from_static(b"log");
+                let mut out = BytesMut::with_capacity(64);
+                for _ in 0..10_000 {
+                    shard.exec(Cmd::Append(key.clone(), chunk.clone()), &mut out);
+                    out.clear();
+                }
+                black_box(shard)
+            },
+            BatchSize::SmallInput,
+        );
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_exec_set_get, bench_exec_incr, bench_exec_append);
 criterion_main!(benches);
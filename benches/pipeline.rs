@@ -0,0 +1,117 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use ignix::*;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::Duration;
+
+const PIPELINE: usize = 256;
+
+const LARGE_VALUE: usize = 1024 * 1024;
+
+// Workers, shards and client connections of the shared vs owned comparison
+const CORES: usize = 8;
+
+// Start an in-process server with the given configuration
+fn start_server(config: Config) -> SocketAddr {
+    start_set(ShardSet::from(Shard::with_config(0, None, config)))
+}
+
+// Start an in-process server around a shard set
+fn start_set(set: ShardSet) -> SocketAddr {
+    let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    std::thread::spawn(move || {
+        let _ = run_shard(0, addr, set);
+    });
+    for _ in 0..200 {
+        if TcpStream::connect(addr).is_ok() {
+            return addr;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    panic!("server did not start");
+}
+
+// Send a pipeline of SETs and wait for every +OK
+fn pipelined_sets(stream: &mut TcpStream, batch: &[u8]) {
+    stream.write_all(batch).unwrap();
+    let expected = PIPELINE * b"+OK\r\n".len();
+    let mut buf = vec![0u8; expected];
+    stream.read_exact(&mut buf).unwrap();
+}
+
+fn bench_flush_strategy(c: &mut Criterion) {
+    let mut batch = Vec::new();
+    for i in 0..PIPELINE {
+        let key = format!("key:{}", i);
+        batch.extend_from_slice(format!("*3\r\n$3\r\nSET\r\n${}\r\n{}\r\n$5\r\nvalue\r\n", key.len(), key).as_bytes());
+    }
+
+    let mut group = c.benchmark_group("pipelined_set");
+    group.throughput(Throughput::Elements(PIPELINE as u64));
+    for (name, strategy) in [("immediate", FlushStrategy::Immediate), ("coalesced", FlushStrategy::Coalesced)] {
+        let config = Config { flush_strategy: strategy, ..Default::default() };
+        let mut stream = TcpStream::connect(start_server(config)).unwrap();
+        stream.set_nodelay(true).unwrap();
+        group.bench_function(name, |b| b.iter(|| pipelined_sets(&mut stream, &batch)));
+    }
+    group.finish();
+}
+
+// One 1MB SET per iteration with fixed and adaptive read sizes
+fn bench_read_buffer(c: &mut Criterion) {
+    let mut request = format!("*3\r\n$3\r\nSET\r\n$3\r\nbig\r\n${}\r\n", LARGE_VALUE).into_bytes();
+    request.resize(request.len() + LARGE_VALUE, b'x');
+    request.extend_from_slice(b"\r\n");
+
+    let mut group = c.benchmark_group("large_set");
+    group.throughput(Throughput::Bytes(LARGE_VALUE as u64));
+    for (name, size, max) in [
+        ("fixed_4k", 4096, 4096),
+        ("fixed_64k", 64 * 1024, 64 * 1024),
+        ("adaptive_4k_256k", 4096, 256 * 1024),
+    ] {
+        let config = Config { read_buf_size: size, read_buf_max: max, ..Default::default() };
+        let mut stream = TcpStream::connect(start_server(config)).unwrap();
+        stream.set_nodelay(true).unwrap();
+        let mut reply = [0u8; 5];
+        group.bench_function(name, |b| b.iter(|| {
+            stream.write_all(&request).unwrap();
+            stream.read_exact(&mut reply).unwrap();
+        }));
+    }
+    group.finish();
+}
+
+// SET-heavy load from CORES connections at once: every worker sharing
+// every shard's DashMap, against each worker owning one shard
+fn bench_owned_shards(c: &mut Criterion) {
+    let mut group = c.benchmark_group("set_heavy");
+    group.throughput(Throughput::Elements((CORES * PIPELINE) as u64));
+    for (name, owned) in [("shared", false), ("owned", true)] {
+        let config = Config { shards: CORES, worker_threads: CORES, owned_shards: owned, ..Default::default() };
+        let addr = start_set(ShardSet::with_config(None, config));
+        let mut clients: Vec<_> = (0..CORES)
+            .map(|c| {
+                let mut batch = Vec::new();
+                for i in 0..PIPELINE {
+                    let key = format!("c{}:key:{}", c, i);
+                    batch.extend_from_slice(format!("*3\r\n$3\r\nSET\r\n${}\r\n{}\r\n$5\r\nvalue\r\n", key.len(), key).as_bytes());
+                }
+                let stream = TcpStream::connect(addr).unwrap();
+                stream.set_nodelay(true).unwrap();
+                (stream, batch)
+            })
+            .collect();
+        group.bench_function(name, |b| b.iter(|| {
+            std::thread::scope(|s| {
+                for (stream, batch) in clients.iter_mut() {
+                    s.spawn(move || pipelined_sets(stream, batch));
+                }
+            })
+        }));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_flush_strategy, bench_read_buffer, bench_owned_shards);
+criterion_main!(benches);
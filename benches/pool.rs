@@ -0,0 +1,41 @@
+use bytes::BytesMut;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ignix::pool::{BufPool, DEFAULT_MAX_POOLED_CAPACITY, DEFAULT_POOL_SIZE};
+
+const READ_BUF: usize = 4096;
+
+// Simulate a short-lived connection: read a command, write a reply, close
+fn connection_cycle(rbuf: &mut BytesMut, wbuf: &mut BytesMut) {
+    rbuf.extend_from_slice(b"*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n");
+    wbuf.extend_from_slice(b"$5\r\nvalue\r\n");
+    black_box((&rbuf, &wbuf));
+}
+
+fn bench_connect_disconnect(c: &mut Criterion) {
+    let mut group = c.benchmark_group("conn_churn");
+    group.bench_function("fresh_buffers", |b| {
+        b.iter(|| {
+            for _ in 0..1000 {
+                let mut rbuf = BytesMut::with_capacity(READ_BUF);
+                let mut wbuf = BytesMut::new();
+                connection_cycle(&mut rbuf, &mut wbuf);
+            }
+        });
+    });
+    group.bench_function("pooled_buffers", |b| {
+        let mut pool = BufPool::new(READ_BUF, DEFAULT_POOL_SIZE, DEFAULT_MAX_POOLED_CAPACITY);
+        b.iter(|| {
+            for _ in 0..1000 {
+                let mut rbuf = pool.acquire();
+                let mut wbuf = pool.acquire();
+                connection_cycle(&mut rbuf, &mut wbuf);
+                pool.release(rbuf);
+                pool.release(wbuf);
+            }
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_connect_disconnect);
+criterion_main!(benches);
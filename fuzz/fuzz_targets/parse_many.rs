@@ -0,0 +1,35 @@
+//! Fuzz `parse_many` with arbitrary input, fed in one piece and in chunks
+//!
+//! The first byte picks the chunk size, the rest is the RESP stream. The
+//! parser must never panic, and must produce the same commands (and the
+//! same framing error, if any) however the stream is split.
+//!
+//! Run with `cargo fuzz run parse_many` from this directory; the seeds in
+//! `corpus/parse_many` are also replayed by `tests/parser_fuzz.rs`.
+
+#![no_main]
+
+use bytes::BytesMut;
+use ignix::{parse_many, Cmd};
+use libfuzzer_sys::fuzz_target;
+
+/// Commands parsed from `chunks` fed one after another, and the framing
+/// error that stopped parsing
+fn parse_chunks<'a>(chunks: impl Iterator<Item = &'a [u8]>) -> (Vec<Cmd>, Option<String>) {
+    let mut buf = BytesMut::new();
+    let mut cmds = Vec::new();
+    for chunk in chunks {
+        buf.extend_from_slice(chunk);
+        if let Err(e) = parse_many(&mut buf, &mut cmds) {
+            return (cmds, Some(e.to_string()));
+        }
+    }
+    (cmds, None)
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&split, stream)) = data.split_first() else { return };
+    let whole = parse_chunks(std::iter::once(stream));
+    let chunked = parse_chunks(stream.chunks(split as usize % 32 + 1));
+    assert_eq!(whole, chunked);
+});
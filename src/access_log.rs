@@ -0,0 +1,79 @@
+/*!
+ * Access Log
+ *
+ * Optional audit trail of commands that were denied (an error reply whose
+ * code is a refusal: NOAUTH, READONLY, OOM, WRONGTYPE, ...) or that ran
+ * longer than a threshold. Each entry is one JSON object logged through
+ * the `log` crate under the `ignix::access` target, so the existing
+ * `env_logger` setup routes it (`RUST_LOG=ignix::access=info`).
+ *
+ * Nothing here runs unless `Config::access_log` is set.
+ */
+
+use crate::config::Config;
+use std::time::Duration;
+
+/// `log` target of access log entries
+pub const ACCESS_LOG_TARGET: &str = "ignix::access";
+
+/// Error codes that mean the command was refused rather than malformed
+const DENIAL_CODES: &[&str] = &["NOAUTH", "NOPERM", "READONLY", "OOM", "WRONGTYPE", "MISCONF"];
+
+/// The denial code of a command's reply, if it was refused
+///
+/// # Arguments
+/// * `reply` - The RESP reply written for the command
+pub fn denial_code(reply: &[u8]) -> Option<&'static str> {
+    let code = reply.strip_prefix(b"-")?;
+    let end = code.iter().position(|&b| b == b' ' || b == b'\r').unwrap_or(code.len());
+    DENIAL_CODES.iter().copied().find(|c| c.as_bytes() == &code[..end])
+}
+
+/// Render one access log entry as a JSON object
+///
+/// # Arguments
+/// * `addr` - Client address
+/// * `cmd` - Command name
+/// * `reason` - Denial code, or `slow`
+/// * `elapsed` - Time spent executing the command
+pub fn access_entry(addr: &str, cmd: &str, reason: &str, elapsed: Duration) -> String {
+    format!(
+        "{{\"addr\":\"{}\",\"db\":0,\"cmd\":\"{}\",\"reason\":\"{}\",\"duration_us\":{}}}",
+        escape(addr),
+        escape(cmd),
+        reason,
+        elapsed.as_micros()
+    )
+}
+
+/// Log a command if it was denied or slow
+///
+/// # Arguments
+/// * `config` - Server configuration (`access_log_slower_than`)
+/// * `addr` - Client address
+/// * `cmd` - Command name
+/// * `reply` - The RESP reply written for the command
+/// * `elapsed` - Time spent executing the command
+pub fn record(config: &Config, addr: &str, cmd: &str, reply: &[u8], elapsed: Duration) {
+    let slow = config.access_log_slower_than > 0 && elapsed.as_micros() >= config.access_log_slower_than as u128;
+    let reason = match denial_code(reply) {
+        Some(code) => code,
+        None if slow => "slow",
+        None => return,
+    };
+    log::info!(target: ACCESS_LOG_TARGET, "{}", access_entry(addr, cmd, reason, elapsed));
+}
+
+/// Escape a string for a JSON string literal
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
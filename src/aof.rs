@@ -6,12 +6,23 @@
  * to disk for crash recovery.
  */
 
+use crate::protocol::{parse_many_with, Cmd, ProtoLimits, SetExpiry, Value};
+use crate::replay::ReplayStats;
+use crate::shard::Shard;
+use crate::storage::{Dict, WallClock};
 use anyhow::*;
-use crossbeam::channel::{bounded, Sender};
-use std::io::Write;
+use bytes::{BufMut, Bytes, BytesMut};
+use crossbeam::channel::{bounded, select, Receiver, Sender};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use std::result::Result::{Ok, Err};
 
+/// How often the writer retries pending bytes after a failed write
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
 /// Handle for writing to the AOF (Append-Only File)
 /// 
 /// This handle allows async writing to the AOF file through a background
@@ -20,6 +31,75 @@ use std::result::Result::{Ok, Err};
 pub struct AofHandle {
     /// Channel sender for sending commands to the AOF writer thread
     tx: Sender<Vec<u8>>,
+    /// Requests other than entries (shutdown, rewrite steps)
+    ctl: Sender<Control>,
+    /// Writer status shared with the background thread
+    status: Arc<AofStatus>,
+    /// Durability of each write
+    policy: AofPolicy,
+}
+
+/// When the AOF writer makes appended entries durable (`appendfsync`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AofPolicy {
+    /// `sync_data` after every write command, before it is acknowledged
+    Always,
+    /// `sync_data` about once a second
+    #[default]
+    EverySec,
+    /// Never `sync_data`; the OS writes dirty pages back when it likes
+    No,
+}
+
+/// Requests to the writer thread besides entries to append
+enum Control {
+    /// Write everything queued and `sync_data`; acknowledged once durable
+    Sync(Sender<()>),
+    /// Write everything queued, sync and exit; acknowledged once done
+    Shutdown(Sender<()>),
+    /// Write everything queued so far, then start copying new entries
+    /// aside for the rewritten file; acknowledged once started
+    BeginRewrite(Sender<()>),
+    /// Append the entries copied aside to the snapshot file at this path
+    /// and move it over the AOF; replies with the new file length
+    FinishRewrite(PathBuf, Sender<Result<u64>>),
+    /// Drop the entries copied aside (the snapshot failed)
+    AbortRewrite,
+}
+
+/// State shared between an `AofHandle` and its writer thread
+#[derive(Default)]
+struct AofStatus {
+    /// File path of the AOF
+    path: PathBuf,
+    /// Length of the AOF's committed contents
+    size: AtomicU64,
+    /// Length of the AOF after the last rewrite (or when it was opened)
+    base_size: AtomicU64,
+    /// Set while a background rewrite is running
+    rewriting: AtomicBool,
+    /// Set while the last write or fsync failed; cleared once pending
+    /// bytes have been written successfully again
+    failed: AtomicBool,
+    /// Failpoint: make every write/fsync fail with an injected I/O error
+    #[cfg(any(test, feature = "failpoints"))]
+    inject_error: AtomicBool,
+}
+
+impl AofStatus {
+    #[cfg(any(test, feature = "failpoints"))]
+    fn check_failpoint(&self) -> std::io::Result<()> {
+        if self.inject_error.load(Ordering::Relaxed) {
+            return Err(std::io::Error::other("injected AOF write error"));
+        }
+        std::io::Result::Ok(())
+    }
+
+    #[cfg(not(any(test, feature = "failpoints")))]
+    #[inline(always)]
+    fn check_failpoint(&self) -> std::io::Result<()> {
+        std::io::Result::Ok(())
+    }
 }
 
 /// Spawn a background AOF writer thread
@@ -29,69 +109,522 @@ pub struct AofHandle {
 /// 
 /// # Arguments
 /// * `path` - File path for the AOF file
+/// * `policy` - When entries are synced, trading throughput for
+///   durability:
+///   - [`AofPolicy::Always`]: [`AofHandle::write`] returns only once the
+///     entry is on disk, so an acknowledged write survives a power loss;
+///     every write command waits for an fsync (writes queued together
+///     share one)
+///   - [`AofPolicy::EverySec`]: writes return at once and are synced
+///     about every 1000ms, losing at most about a second of writes
+///   - [`AofPolicy::No`]: never syncs; fastest, and how much is lost on a
+///     power loss depends on the OS's writeback (a process crash loses
+///     nothing already written)
 /// 
 /// # Returns
 /// * `AofHandle` for sending commands to be logged
 /// 
 /// # Behavior
 /// * Commands are buffered and written to disk
-/// * File is flushed and synced every 1000ms for durability
-/// * Thread continues until the handle is dropped
-pub fn spawn_aof_writer(path: &str) -> Result<AofHandle> {
+/// * The file is synced as `policy` says
+/// * On a write or fsync error the partial write is truncated away, the
+///   handle reports [`AofHandle::is_failed`] and the unwritten bytes are
+///   retried every 100ms until the disk accepts them again
+/// * Thread continues until [`AofHandle::shutdown`] is called or every
+///   handle is dropped; either way queued entries are written and synced
+///   before it exits
+pub fn spawn_aof_writer(path: &str, policy: AofPolicy) -> Result<AofHandle> {
     // Bounded channel to provide backpressure under heavy write load
     let (tx, rx) = bounded::<Vec<u8>>(4096);
-    let path = path.to_string();
+    let (ctl, ctl_rx) = bounded::<Control>(1);
+
+    // Open AOF file in append mode, create if doesn't exist
+    let f = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("open aof {}", path))?;
+    let len = f.metadata()?.len();
+    let status = Arc::new(AofStatus {
+        path: path.into(),
+        size: AtomicU64::new(len),
+        base_size: AtomicU64::new(len),
+        ..Default::default()
+    });
+    let shared = status.clone();
     
     // Spawn dedicated AOF writer thread
     std::thread::Builder::new()
         .name("aof-writer".into())
         .spawn(move || {
-            // Open AOF file in append mode, create if doesn't exist
-            let mut f = std::fs::OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(&path)
-                .expect("open aof");
-            
+            let mut w = AofWriter { f, len, pending: Vec::new(), rewrite: None, status: shared };
             let mut last = Instant::now();
             
             // Main AOF writer loop
             loop {
-                match rx.recv() {
-                    Ok(buf) => {
-                        let _ = f.write_all(&buf);
-                        if last.elapsed() >= Duration::from_millis(1000) {
-                            let _ = f.flush();
-                            let _ = f.sync_data();
-                            last = Instant::now();
+                let wait = if w.pending.is_empty() { Duration::MAX } else { RETRY_INTERVAL };
+                select! {
+                    recv(rx) -> msg => match msg {
+                        Ok(buf) => {
+                            w.append(&buf);
+                            if policy == AofPolicy::EverySec && last.elapsed() >= Duration::from_millis(1000) {
+                                w.sync();
+                                last = Instant::now();
+                            }
+                        }
+                        // All handles dropped and the queue is empty
+                        Err(_) => {
+                            w.finish(&rx);
+                            break;
                         }
-                    }
-                    // Channel closed: drain finished; perform final flush and exit
-                    Err(_) => {
-                        let _ = f.flush();
-                        let _ = f.sync_data();
-                        break;
-                    }
+                    },
+                    recv(ctl_rx) -> msg => match msg {
+                        Ok(Control::Sync(ack)) => {
+                            w.drain(&rx);
+                            w.sync();
+                            let _ = ack.send(());
+                        }
+                        Ok(Control::BeginRewrite(ack)) => {
+                            w.begin_rewrite(&rx);
+                            let _ = ack.send(());
+                        }
+                        Ok(Control::FinishRewrite(tmp, ack)) => {
+                            let _ = ack.send(w.finish_rewrite(&tmp));
+                        }
+                        Ok(Control::AbortRewrite) => w.rewrite = None,
+                        Ok(Control::Shutdown(ack)) => {
+                            w.finish(&rx);
+                            let _ = ack.send(());
+                            break;
+                        }
+                        Err(_) => {
+                            w.finish(&rx);
+                            break;
+                        }
+                    },
+                    // Retry bytes left over from a failed write
+                    default(wait) => w.append(&[]),
                 }
             }
         })?;
     
-    Ok(AofHandle { tx })
+    Ok(AofHandle { tx, ctl, status, policy })
+}
+
+/// File side of the background writer
+struct AofWriter {
+    f: std::fs::File,
+    /// Length of the file's committed (fully written) contents
+    len: u64,
+    /// Bytes that could not be written yet because of an I/O error
+    pending: Vec<u8>,
+    /// Entries appended since a rewrite took its snapshot, for the
+    /// rewritten file
+    rewrite: Option<Vec<u8>>,
+    status: Arc<AofStatus>,
+}
+
+impl AofWriter {
+    /// Append an entry, writing any previously failed bytes first
+    fn append(&mut self, buf: &[u8]) {
+        if let Some(rewrite) = &mut self.rewrite {
+            rewrite.extend_from_slice(buf);
+        }
+        if self.pending.is_empty() {
+            if let Err(e) = self.write(buf) {
+                self.fail(e);
+                self.pending.extend_from_slice(buf);
+            }
+            return;
+        }
+
+        self.pending.extend_from_slice(buf);
+        let pending = std::mem::take(&mut self.pending);
+        match self.write(&pending) {
+            Ok(()) => self.status.failed.store(false, Ordering::Relaxed),
+            Err(e) => {
+                self.fail(e);
+                self.pending = pending;
+            }
+        }
+    }
+
+    /// Write bytes completely, or roll the file back to its last good length
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        if buf.is_empty() {
+            return std::io::Result::Ok(());
+        }
+        let res = self.status.check_failpoint().and_then(|_| self.f.write_all(buf));
+        match res {
+            Ok(()) => {
+                self.len += buf.len() as u64;
+                self.status.size.store(self.len, Ordering::Relaxed);
+                std::io::Result::Ok(())
+            }
+            Err(e) => {
+                // Don't leave half an entry behind: it would desync replay
+                let _ = self.f.set_len(self.len);
+                Err(e)
+            }
+        }
+    }
+
+    /// Append every entry already queued
+    fn drain(&mut self, rx: &Receiver<Vec<u8>>) {
+        while let Ok(buf) = rx.try_recv() {
+            self.append(&buf);
+        }
+    }
+
+    /// Write everything still queued, then flush and fsync
+    fn finish(&mut self, rx: &Receiver<Vec<u8>>) {
+        self.drain(rx);
+        // One last attempt for bytes held back by an earlier error
+        self.append(&[]);
+        self.sync();
+    }
+
+    /// Write everything queued so far, then copy later entries aside
+    /// 
+    /// Runs while the shard holds writes back, so the queue holds exactly
+    /// the entries the rewrite's snapshot already reflects.
+    fn begin_rewrite(&mut self, rx: &Receiver<Vec<u8>>) {
+        self.drain(rx);
+        self.rewrite = Some(Vec::new());
+    }
+
+    /// Complete the snapshot file with the entries copied aside, move it
+    /// over the AOF and continue appending to it
+    fn finish_rewrite(&mut self, tmp: &Path) -> Result<u64> {
+        let tail = self.rewrite.take().unwrap_or_default();
+        let res = (|| -> std::io::Result<std::fs::File> {
+            let mut f = std::fs::OpenOptions::new().append(true).open(tmp)?;
+            f.write_all(&tail)?;
+            f.sync_data()?;
+            std::fs::rename(tmp, &self.status.path)?;
+            std::io::Result::Ok(f)
+        })();
+        let f = match res {
+            Ok(f) => f,
+            Err(e) => {
+                let _ = std::fs::remove_file(tmp);
+                return Err(e).with_context(|| format!("installing rewritten aof {}", tmp.display()));
+            }
+        };
+        self.len = f.metadata()?.len();
+        self.f = f;
+        // Entries that failed to reach the old file are in the new one
+        self.pending.clear();
+        self.status.failed.store(false, Ordering::Relaxed);
+        self.status.size.store(self.len, Ordering::Relaxed);
+        self.status.base_size.store(self.len, Ordering::Relaxed);
+        Ok(self.len)
+    }
+
+    /// Flush and fsync the file
+    fn sync(&mut self) {
+        let res = self
+            .status
+            .check_failpoint()
+            .and_then(|_| self.f.flush())
+            .and_then(|_| self.f.sync_data());
+        if let Err(e) = res {
+            self.fail(e);
+        }
+    }
+
+    fn fail(&self, e: std::io::Error) {
+        if !self.status.failed.swap(true, Ordering::Relaxed) {
+            log::error!("AOF write error, refusing writes until it recovers: {}", e);
+        }
+    }
 }
 
 impl AofHandle {
+    /// Create a handle whose entries are delivered to a channel instead of a file
+    /// 
+    /// Used by tests to inspect exactly what would be appended to the AOF.
+    #[cfg(test)]
+    pub(crate) fn capture() -> (AofHandle, crossbeam::channel::Receiver<Vec<u8>>) {
+        let (tx, rx) = crossbeam::channel::unbounded();
+        let (ctl, _) = bounded(1);
+        (AofHandle { tx, ctl, status: Arc::default(), policy: AofPolicy::No }, rx)
+    }
+
+    /// Length of the AOF file
+    #[inline]
+    pub fn size(&self) -> u64 {
+        self.status.size.load(Ordering::Relaxed)
+    }
+
+    /// Length of the AOF right after the last rewrite, or when it was
+    /// opened if it was never rewritten
+    #[inline]
+    pub fn base_size(&self) -> u64 {
+        self.status.base_size.load(Ordering::Relaxed)
+    }
+
+    /// Check whether a background rewrite is running
+    #[inline]
+    pub fn is_rewriting(&self) -> bool {
+        self.status.rewriting.load(Ordering::Acquire)
+    }
+
+    /// First step of a background rewrite
+    /// 
+    /// Waits until the writer has appended every entry queued so far;
+    /// from then on it also copies each new entry aside for the rewritten
+    /// file. The caller must snapshot the dataset before another entry
+    /// is written (the shard holds write commands back meanwhile) and
+    /// hand the snapshot to [`AofHandle::finish_rewrite`].
+    /// 
+    /// # Returns
+    /// * `false` if a rewrite is already running or the writer is gone
+    pub(crate) fn begin_rewrite(&self) -> bool {
+        if self.status.rewriting.swap(true, Ordering::AcqRel) {
+            return false;
+        }
+        let (ack, done) = bounded(1);
+        if self.ctl.send(Control::BeginRewrite(ack)).is_err() || done.recv().is_err() {
+            self.status.rewriting.store(false, Ordering::Release);
+            return false;
+        }
+        true
+    }
+
+    /// Second step of a background rewrite
+    /// 
+    /// Writes the snapshot to a temporary file on an `aof-rewrite`
+    /// thread, then has the writer append the entries copied aside since
+    /// [`AofHandle::begin_rewrite`] and rename the file over the AOF. On
+    /// failure the old AOF is left as it was.
+    /// 
+    /// # Arguments
+    /// * `entries` - The dataset as of `begin_rewrite` (see [`Dict::entries`])
+    pub(crate) fn finish_rewrite(&self, entries: Vec<(Bytes, Value, Option<Instant>)>) {
+        let handle = self.clone();
+        let spawned = std::thread::Builder::new().name("aof-rewrite".into()).spawn(move || {
+            let tmp = rewrite_temp_path(&handle.status.path);
+            let res = write_snapshot(&tmp, entries).and_then(|_| {
+                let (ack, done) = bounded(1);
+                handle
+                    .ctl
+                    .send(Control::FinishRewrite(tmp.clone(), ack))
+                    .map_err(|_| anyhow!("aof writer is gone"))?;
+                done.recv().map_err(|_| anyhow!("aof writer is gone"))?
+            });
+            match res {
+                Ok(len) => log::info!("AOF rewritten: {} bytes", len),
+                Err(e) => {
+                    let _ = handle.ctl.send(Control::AbortRewrite);
+                    let _ = std::fs::remove_file(&tmp);
+                    log::error!("AOF rewrite failed: {:#}", e);
+                }
+            }
+            handle.status.rewriting.store(false, Ordering::Release);
+        });
+        if let Err(e) = spawned {
+            let _ = self.ctl.send(Control::AbortRewrite);
+            self.status.rewriting.store(false, Ordering::Release);
+            log::error!("AOF rewrite failed: {}", e);
+        }
+    }
+
+    /// Check whether the writer is currently unable to persist entries
+    /// 
+    /// While this is `true` the shard refuses write commands with a
+    /// `-MISCONF` error, like Redis does on AOF write errors.
+    #[inline]
+    pub fn is_failed(&self) -> bool {
+        self.status.failed.load(Ordering::Relaxed)
+    }
+
+    /// Failpoint: make the writer's writes and fsyncs fail (or succeed again)
+    /// 
+    /// Only available in tests or with the `failpoints` feature.
+    #[cfg(any(test, feature = "failpoints"))]
+    pub fn inject_write_error(&self, on: bool) {
+        self.status.inject_error.store(on, Ordering::Relaxed);
+    }
+
+    /// Stop the writer after persisting everything queued so far
+    /// 
+    /// Blocks until every entry written through any clone of this handle
+    /// before the call has been appended, flushed and `sync_data`'d, and
+    /// the writer thread has exited. Entries written afterwards are
+    /// discarded.
+    pub fn shutdown(&self) {
+        let (ack, done) = bounded(1);
+        if self.ctl.send(Control::Shutdown(ack)).is_ok() {
+            // An error means the writer is already gone
+            let _ = done.recv();
+        }
+    }
+
     /// Write a command to the AOF
     /// 
-    /// Sends the command bytes to the background writer thread.
-    /// This is non-blocking and returns immediately.
+    /// Sends the command bytes to the background writer thread. Under
+    /// [`AofPolicy::Always`] this waits until the writer has synced them;
+    /// otherwise it returns immediately.
     /// 
     /// # Arguments
     /// * `bytes` - RESP-formatted command bytes to write
     #[inline]
     pub fn write(&self, bytes: &[u8]) {
         // Send to background thread, ignore errors (channel closed)
-        let _ = self.tx.send(bytes.to_vec());
+        if self.tx.send(bytes.to_vec()).is_err() || self.policy != AofPolicy::Always {
+            return;
+        }
+        // The writer drains the queue, entry included, before syncing
+        let (ack, done) = bounded(1);
+        if self.ctl.send(Control::Sync(ack)).is_ok() {
+            let _ = done.recv();
+        }
+    }
+}
+
+/// Bytes of the AOF read at a time while loading it
+const LOAD_CHUNK: usize = 1 << 20;
+
+/// Replay an AOF into a shard
+/// 
+/// Run at startup, before the server accepts clients. Every command in
+/// the file is executed through [`Shard::exec`] with propagation
+/// suppressed, so nothing is logged again even if the shard already has
+/// an AOF handle attached.
+/// 
+/// The file is read and run [`LOAD_CHUNK`] bytes at a time, and parsed
+/// without the client-facing protocol limits ([`ProtoLimits::trusted`]):
+/// values written before `proto-max-bulk-len` was lowered still load.
+/// 
+/// # Arguments
+/// * `path` - File path of the AOF
+/// * `shard` - Shard to load into
+/// 
+/// # Returns
+/// * Counts and timing of the load; a missing file loads nothing
+/// * An error on a read failure or a framing error before the end of the
+///   file; the commands before it have run. A final command cut short (a
+///   crash mid-write) is not an error: loading stops after the last
+///   complete command and a warning is logged
+pub fn load_aof(path: &str, shard: &Shard) -> Result<ReplayStats> {
+    load_aof_into(path, std::slice::from_ref(shard), |cmd, out| shard.exec(cmd, out))
+}
+
+/// [`load_aof`] for shards sharing one AOF, running each command through
+/// `exec` (which routes it to one of them)
+pub(crate) fn load_aof_into(path: &str, shards: &[Shard], mut exec: impl FnMut(Cmd, &mut BytesMut)) -> Result<ReplayStats> {
+    let start = Instant::now();
+    let mut file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(ReplayStats::default()),
+        Err(e) => return Err(e).with_context(|| format!("reading aof {}", path)),
+    };
+    let mut stats = ReplayStats::default();
+    let mut buf = BytesMut::new();
+    let mut cmds = Vec::new();
+    let mut out = BytesMut::new();
+    shards.iter().for_each(|s| s.set_loading(true));
+    let res = (|| -> Result<()> {
+        loop {
+            // Commands already parsed hold on to their part of the old
+            // buffer; whatever is left over moves to the new one
+            buf.reserve(LOAD_CHUNK);
+            let read = std::io::copy(&mut (&mut file).take(LOAD_CHUNK as u64), &mut (&mut buf).writer())
+                .with_context(|| format!("reading aof {}", path))?;
+            if read == 0 {
+                return Ok(());
+            }
+            stats.bytes += read as u64;
+            parse_many_with(&mut buf, &mut cmds, &ProtoLimits::trusted())
+                .with_context(|| format!("aof {} is corrupt after {} bytes", path, stats.bytes - buf.len() as u64))?;
+            for cmd in cmds.drain(..) {
+                exec(cmd, &mut out);
+                out.clear();
+                stats.commands += 1;
+            }
+        }
+    })();
+    shards.iter().for_each(|s| s.set_loading(false));
+    res?;
+
+    if !buf.is_empty() {
+        log::warn!(
+            "aof {} ends with {} bytes of an incomplete command; ignoring them",
+            path,
+            buf.len()
+        );
+        stats.bytes -= buf.len() as u64;
+    }
+    stats.elapsed = start.elapsed();
+    Ok(stats)
+}
+
+/// Compact an AOF into the commands that rebuild a dataset
+/// 
+/// Writes one SET per key (with `PXAT` for keys that expire) to a
+/// temporary file next to `path`, syncs it and renames it over `path`,
+/// so a crash at any point leaves either the old or the new file. No
+/// writer may be appending to `path` meanwhile: a running server
+/// rewrites through `BGREWRITEAOF`, which also keeps the writes made
+/// during the rewrite.
+/// 
+/// # Arguments
+/// * `path` - File path of the AOF
+/// * `dict` - Dataset the AOF should rebuild
+/// 
+/// # Returns
+/// * Length of the new file
+pub fn rewrite(path: &str, dict: &Dict) -> Result<u64> {
+    let tmp = rewrite_temp_path(Path::new(path));
+    let len = write_snapshot(&tmp, dict.entries())?;
+    if let Err(e) = std::fs::rename(&tmp, path) {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(e).with_context(|| format!("replacing aof {}", path));
     }
+    Ok(len)
+}
+
+/// Temporary file a rewrite of the AOF at `path` is written to
+fn rewrite_temp_path(path: &Path) -> PathBuf {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".rewrite.tmp");
+    tmp.into()
+}
+
+/// Write a dataset snapshot as SET commands to a new, synced file
+/// 
+/// Deadlines are logged absolute (`PXAT`), like EXPIRE is, all converted
+/// to Unix time through a single clock reading so they keep their order.
+/// 
+/// # Returns
+/// * Length of the file
+fn write_snapshot(tmp: &Path, entries: Vec<(Bytes, Value, Option<Instant>)>) -> Result<u64> {
+    let clock = WallClock::now();
+    let res = (|| -> std::io::Result<u64> {
+        let mut w = std::io::BufWriter::new(std::fs::File::create(tmp)?);
+        for (k, v, deadline) in entries {
+            let expiry = match deadline {
+                Some(d) => match clock.unix_ms_of(d) {
+                    // Expired while the snapshot was being written
+                    unix_ms if unix_ms <= clock.unix_ms() => continue,
+                    unix_ms => Some(SetExpiry::PxAt(unix_ms as u64)),
+                },
+                None => None,
+            };
+            let v = match v {
+                Value::Str(s) | Value::Blob(s) => s,
+                Value::Int(i) => Bytes::from(i.to_string()),
+            };
+            w.write_all(&emit_aof_set_with(&k, &v, expiry))?;
+        }
+        let f = w.into_inner().map_err(|e| e.into_error())?;
+        f.sync_data()?;
+        f.metadata().map(|m| m.len())
+    })();
+    res.with_context(|| format!("writing aof snapshot {}", tmp.display())).inspect_err(|_| {
+        let _ = std::fs::remove_file(tmp);
+    })
 }
 
 //
@@ -101,6 +634,29 @@ impl AofHandle {
 // The format is human-readable and compatible with Redis AOF files.
 //
 
+/// Encode a command as a RESP array of bulk strings
+/// 
+/// Arguments are copied byte for byte, so binary (non-UTF-8) keys and
+/// values are logged exactly as they were received.
+/// 
+/// # Arguments
+/// * `args` - Command name followed by its arguments
+pub fn emit_aof_command(args: &[&[u8]]) -> Vec<u8> {
+    let len = args.iter().map(|a| a.len() + 16).sum::<usize>() + 16;
+    let mut out = Vec::with_capacity(len);
+    out.push(b'*');
+    out.extend_from_slice(args.len().to_string().as_bytes());
+    out.extend_from_slice(b"\r\n");
+    for arg in args {
+        out.push(b'$');
+        out.extend_from_slice(arg.len().to_string().as_bytes());
+        out.extend_from_slice(b"\r\n");
+        out.extend_from_slice(arg);
+        out.extend_from_slice(b"\r\n");
+    }
+    out
+}
+
 /// Generate AOF entry for SET command
 /// 
 /// Creates a RESP-formatted SET command for AOF logging.
@@ -110,14 +666,25 @@ impl AofHandle {
 /// * `k` - Key bytes
 /// * `v` - Value bytes
 pub fn emit_aof_set(k: &[u8], v: &[u8]) -> Vec<u8> {
-    format!(
-        "*3\r\n$3\r\nSET\r\n${}\r\n{}\r\n${}\r\n{}\r\n",
-        k.len(),
-        String::from_utf8_lossy(k),
-        v.len(),
-        String::from_utf8_lossy(v)
-    )
-    .into_bytes()
+    emit_aof_command(&[b"SET", k, v])
+}
+
+/// Generate AOF entry for a SET with an expiry modifier
+/// 
+/// NX/XX/GET are left out: the entry is only logged when the value was
+/// stored. Shards pass EX/PX in as the absolute PXAT they came to, so the
+/// replay keeps the original deadline; KEEPTTL is logged as is.
+/// 
+/// # Arguments
+/// * `k` - Key bytes
+/// * `v` - Value bytes
+/// * `expiry` - PXAT / KEEPTTL modifier (or EX / PX), if any
+pub fn emit_aof_set_with(k: &[u8], v: &[u8], expiry: Option<SetExpiry>) -> Vec<u8> {
+    let Some(expiry) = expiry else { return emit_aof_set(k, v) };
+    let args = expiry.args();
+    let mut all: Vec<&[u8]> = vec![b"SET", k, v];
+    all.extend(args.iter().map(|a| &a[..]));
+    emit_aof_command(&all)
 }
 
 /// Generate AOF entry for RENAME command
@@ -128,14 +695,7 @@ pub fn emit_aof_set(k: &[u8], v: &[u8]) -> Vec<u8> {
 /// * `a` - Old key bytes
 /// * `b` - New key bytes
 pub fn emit_aof_rename(a: &[u8], b: &[u8]) -> Vec<u8> {
-    format!(
-        "*3\r\n$6\r\nRENAME\r\n${}\r\n{}\r\n${}\r\n{}\r\n",
-        a.len(),
-        String::from_utf8_lossy(a),
-        b.len(),
-        String::from_utf8_lossy(b)
-    )
-    .into_bytes()
+    emit_aof_command(&[b"RENAME", a, b])
 }
 
 /// Generate AOF entry for INCR command
@@ -145,15 +705,70 @@ pub fn emit_aof_rename(a: &[u8], b: &[u8]) -> Vec<u8> {
 /// # Arguments
 /// * `k` - Key bytes to increment
 pub fn emit_aof_incr(k: &[u8]) -> Vec<u8> {
-    format!(
-        "*2\r\n$4\r\nINCR\r\n${}\r\n{}\r\n",
-        k.len(),
-        String::from_utf8_lossy(k)
-    )
-    .into_bytes()
+    emit_aof_command(&[b"INCR", k])
+}
+
+/// Generate AOF entry for INCRBY command
+/// 
+/// DECR and DECRBY are logged as the INCRBY with the negated delta.
+/// 
+/// # Arguments
+/// * `k` - Key bytes
+/// * `delta` - Amount added
+pub fn emit_aof_incrby(k: &[u8], delta: i64) -> Vec<u8> {
+    let delta = delta.to_string();
+    emit_aof_command(&[b"INCRBY", k, delta.as_bytes()])
+}
+
+/// Generate AOF entry for FLUSHALL command
+pub fn emit_aof_flushall() -> Vec<u8> {
+    emit_aof_command(&[b"FLUSHALL"])
+}
+
+/// Generate AOF entry for SETBIT command
+/// 
+/// # Arguments
+/// * `k` - Key bytes
+/// * `offset` - Bit offset
+/// * `bit` - New bit value
+pub fn emit_aof_setbit(k: &[u8], offset: u64, bit: bool) -> Vec<u8> {
+    let offset = offset.to_string();
+    emit_aof_command(&[b"SETBIT", k, offset.as_bytes(), if bit { b"1" } else { b"0" }])
+}
+
+/// Generate AOF entry for APPEND command
+/// 
+/// # Arguments
+/// * `k` - Key bytes
+/// * `v` - Appended bytes
+pub fn emit_aof_append(k: &[u8], v: &[u8]) -> Vec<u8> {
+    emit_aof_command(&[b"APPEND", k, v])
+}
+
+/// Generate AOF entry for PEXPIREAT command
+/// 
+/// EXPIRE is logged this way: the deadline is absolute, so replaying the
+/// log after a restart doesn't extend it by the downtime, and a key whose
+/// deadline passed meanwhile is deleted by the replay.
+/// 
+/// # Arguments
+/// * `k` - Key bytes
+/// * `unix_ms` - Deadline as Unix time in milliseconds
+pub fn emit_aof_pexpireat(k: &[u8], unix_ms: i64) -> Vec<u8> {
+    let unix_ms = unix_ms.to_string();
+    emit_aof_command(&[b"PEXPIREAT", k, unix_ms.as_bytes()])
 }
 
-use bytes::Bytes;
+/// Generate AOF entry for DEL command
+/// 
+/// # Arguments
+/// * `keys` - The keys that were removed
+pub fn emit_aof_del(keys: &[Bytes]) -> Vec<u8> {
+    let mut args: Vec<&[u8]> = Vec::with_capacity(1 + keys.len());
+    args.push(b"DEL");
+    args.extend(keys.iter().map(|k| &k[..]));
+    emit_aof_command(&args)
+}
 
 /// Generate AOF entry for MSET command
 /// 
@@ -163,19 +778,406 @@ use bytes::Bytes;
 /// # Arguments
 /// * `pairs` - Vector of (key, value) byte pairs
 pub fn emit_aof_mset(pairs: &[(Bytes, Bytes)]) -> Vec<u8> {
-    // Calculate total arguments: command + (key + value) * pairs
-    let mut s = format!("*{}\r\n$4\r\nMSET\r\n", 1 + pairs.len() * 2);
-    
-    // Add each key-value pair
+    let mut args: Vec<&[u8]> = Vec::with_capacity(1 + pairs.len() * 2);
+    args.push(b"MSET");
     for (k, v) in pairs {
-        s.push_str(&format!(
-            "${}\r\n{}\r\n${}\r\n{}\r\n",
-            k.len(),
-            String::from_utf8_lossy(k),
-            v.len(),
-            String::from_utf8_lossy(v)
-        ));
+        args.push(k);
+        args.push(v);
     }
-    
-    s.into_bytes()
-}
\ No newline at end of file
+    emit_aof_command(&args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{Cmd, SetOptions};
+    use crate::shard::Shard;
+    use bytes::BytesMut;
+
+    fn wait_for(cond: impl Fn() -> bool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if cond() {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    #[test]
+    fn injected_write_error_refuses_writes_until_recovery() {
+        let path = std::env::temp_dir().join(format!("ignix-failpoint-{}.aof", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let aof = spawn_aof_writer(path.to_str().unwrap(), AofPolicy::EverySec).unwrap();
+        let shard = Shard::new(0, Some(aof.clone()));
+        let mut out = BytesMut::new();
+
+        aof.inject_write_error(true);
+        shard.exec(Cmd::Set(Bytes::from_static(b"a"), Bytes::from_static(b"1")), &mut out);
+        assert_eq!(&out[..], b"+OK\r\n");
+        assert!(wait_for(|| aof.is_failed()), "writer never reported the failure");
+
+        // Writes are refused, reads keep working
+        out.clear();
+        shard.exec(Cmd::Set(Bytes::from_static(b"b"), Bytes::from_static(b"2")), &mut out);
+        assert!(out.starts_with(b"-MISCONF"), "{:?}", out);
+        out.clear();
+        shard.exec(Cmd::Get(Bytes::from_static(b"a")), &mut out);
+        assert_eq!(&out[..], b"$1\r\n1\r\n");
+
+        // Once the disk recovers the pending entry is written and writes resume
+        aof.inject_write_error(false);
+        assert!(wait_for(|| !aof.is_failed()), "writer never recovered");
+        assert!(wait_for(|| std::fs::read(&path).unwrap() == emit_aof_set(b"a", b"1")));
+        out.clear();
+        shard.exec(Cmd::Set(Bytes::from_static(b"b"), Bytes::from_static(b"2")), &mut out);
+        assert_eq!(&out[..], b"+OK\r\n");
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn shutdown_drains_queued_entries_to_disk() {
+        let path = std::env::temp_dir().join(format!("ignix-shutdown-{}.aof", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let aof = spawn_aof_writer(path.to_str().unwrap(), AofPolicy::EverySec).unwrap();
+        let shard = Shard::new(0, Some(aof.clone()));
+        let mut out = BytesMut::new();
+
+        let mut expected = Vec::new();
+        for i in 0..1000 {
+            let k = format!("key:{}", i);
+            shard.exec(Cmd::Set(Bytes::from(k.clone()), Bytes::from_static(b"v")), &mut out);
+            expected.extend_from_slice(&emit_aof_set(k.as_bytes(), b"v"));
+        }
+        aof.shutdown();
+
+        // Everything is on disk as soon as shutdown returns
+        assert_eq!(std::fs::read(&path).unwrap(), expected);
+        // A second shutdown finds the writer gone and returns immediately
+        aof.shutdown();
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn load_replays_the_file_without_logging_it_again() {
+        let path = std::env::temp_dir().join(format!("ignix-load-{}.aof", std::process::id()));
+        let mut file = emit_aof_set(b"a", b"1");
+        file.extend_from_slice(&emit_aof_incr(b"a"));
+        file.extend_from_slice(&emit_aof_set(b"b", b"x"));
+        file.extend_from_slice(&emit_aof_del(&[Bytes::from_static(b"b")]));
+        std::fs::write(&path, &file).unwrap();
+
+        let aof = spawn_aof_writer(path.to_str().unwrap(), AofPolicy::EverySec).unwrap();
+        let shard = Shard::new(0, Some(aof.clone()));
+        let stats = load_aof(path.to_str().unwrap(), &shard).unwrap();
+        assert_eq!(stats.commands, 4);
+        assert_eq!(shard.dict.get(b"a"), Some(crate::protocol::Value::Int(2)));
+        assert!(!shard.dict.exists(b"b"));
+        assert_eq!(shard.master_repl_offset(), 0);
+
+        // Writes after the load are logged as usual
+        let mut out = BytesMut::new();
+        shard.exec(Cmd::Set(Bytes::from_static(b"c"), Bytes::from_static(b"3")), &mut out);
+        aof.shutdown();
+        file.extend_from_slice(&emit_aof_set(b"c", b"3"));
+        assert_eq!(std::fs::read(&path).unwrap(), file);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn load_stops_at_a_truncated_final_command() {
+        let path = std::env::temp_dir().join(format!("ignix-truncated-{}.aof", std::process::id()));
+        let mut file = emit_aof_set(b"a", b"1");
+        let complete = file.len() as u64;
+        let cut = emit_aof_set(b"b", b"2");
+        file.extend_from_slice(&cut[..cut.len() - 3]);
+        std::fs::write(&path, &file).unwrap();
+
+        let shard = Shard::new(0, None);
+        let stats = load_aof(path.to_str().unwrap(), &shard).unwrap();
+        assert_eq!(stats.commands, 1);
+        assert_eq!(stats.bytes, complete);
+        assert!(shard.dict.exists(b"a"));
+        assert!(!shard.dict.exists(b"b"));
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn load_ignores_the_client_protocol_limits() {
+        let path = std::env::temp_dir().join(format!("ignix-load-limits-{}.aof", std::process::id()));
+        // Larger than a read chunk, so the value spans several
+        let big = vec![b'x'; 3 * LOAD_CHUNK + 17];
+        let mut file = emit_aof_set(b"small", b"1");
+        file.extend_from_slice(&emit_aof_set(b"big", &big));
+        file.extend_from_slice(&emit_aof_set(b"after", b"2"));
+        std::fs::write(&path, &file).unwrap();
+
+        let mut config = crate::config::Config::default();
+        config.proto.max_bulk_len = 1024;
+        let shard = Shard::with_config(0, None, config);
+        let stats = load_aof(path.to_str().unwrap(), &shard).unwrap();
+        assert_eq!((stats.commands, stats.bytes), (3, file.len() as u64));
+        assert_eq!(shard.dict.get(b"big"), Some(Value::Str(Bytes::from(big))));
+        assert!(shard.dict.exists(b"after"));
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn load_of_a_missing_file_is_empty() {
+        let path = std::env::temp_dir().join(format!("ignix-missing-{}.aof", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let shard = Shard::new(0, None);
+        assert_eq!(load_aof(path.to_str().unwrap(), &shard).unwrap().commands, 0);
+    }
+
+    #[test]
+    fn rewrite_compacts_to_one_set_per_key() {
+        let path = std::env::temp_dir().join(format!("ignix-rewrite-{}.aof", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut file = Vec::new();
+        for i in 0..100 {
+            file.extend_from_slice(&emit_aof_set(b"a", i.to_string().as_bytes()));
+        }
+        file.extend_from_slice(&emit_aof_incr(b"n"));
+        file.extend_from_slice(&emit_aof_set(b"t", b"x"));
+        file.extend_from_slice(&emit_aof_pexpireat(b"t", WallClock::now().unix_ms() + 100_000));
+        std::fs::write(path, &file).unwrap();
+
+        let shard = Shard::new(0, None);
+        load_aof(path, &shard).unwrap();
+        let len = rewrite(path, &shard.dict).unwrap();
+        assert_eq!(std::fs::metadata(path).unwrap().len(), len);
+        assert!(len < file.len() as u64 / 10, "{} bytes", len);
+
+        let reloaded = Shard::new(0, None);
+        assert_eq!(load_aof(path, &reloaded).unwrap().commands, 3);
+        assert_eq!(reloaded.dict.get(b"a"), Some(Value::Int(99)));
+        assert_eq!(reloaded.dict.get(b"n"), Some(Value::Int(1)));
+        let ttl = reloaded.dict.ttl(b"t").unwrap().unwrap();
+        assert!(ttl > Duration::from_secs(90) && ttl <= Duration::from_secs(100), "{:?}", ttl);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn bgrewriteaof_keeps_writes_made_during_the_rewrite() {
+        let path = std::env::temp_dir().join(format!("ignix-bgrewrite-{}.aof", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+        let aof = spawn_aof_writer(path, AofPolicy::EverySec).unwrap();
+        let config = crate::config::Config { aof_rewrite_percentage: 0, ..Default::default() };
+        let shard = Arc::new(Shard::with_config(0, Some(aof.clone()), config));
+        let mut out = BytesMut::new();
+        for _ in 0..1000 {
+            shard.exec(Cmd::Set(Bytes::from_static(b"k"), Bytes::from_static(b"v")), &mut out);
+        }
+
+        // Counters keep moving while the rewrite runs
+        let writer = {
+            let shard = shard.clone();
+            std::thread::spawn(move || {
+                let mut out = BytesMut::new();
+                for _ in 0..2000 {
+                    shard.exec(Cmd::Incr(Bytes::from_static(b"n")), &mut out);
+                }
+            })
+        };
+        out.clear();
+        shard.exec(Cmd::BgRewriteAof, &mut out);
+        assert_eq!(&out[..], b"+Background append only file rewriting started\r\n");
+        writer.join().unwrap();
+        assert!(wait_for(|| !aof.is_rewriting()), "rewrite never finished");
+        aof.shutdown();
+
+        let reloaded = Shard::new(0, None);
+        load_aof(path, &reloaded).unwrap();
+        assert_eq!(reloaded.dict.get(b"n"), Some(Value::Int(2000)));
+        assert_eq!(reloaded.dict.get(b"k"), Some(Value::Str(Bytes::from_static(b"v"))));
+        // The 1000 SETs collapsed into one
+        let logged = 1000 * emit_aof_set(b"k", b"v").len() + 2000 * emit_aof_incr(b"n").len();
+        assert!(std::fs::metadata(path).unwrap().len() < logged as u64);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn aof_is_rewritten_automatically_once_it_doubles() {
+        let path = std::env::temp_dir().join(format!("ignix-autorewrite-{}.aof", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+        let aof = spawn_aof_writer(path, AofPolicy::EverySec).unwrap();
+        let config = crate::config::Config { aof_rewrite_min_size: 1024, ..Default::default() };
+        let shard = Shard::with_config(0, Some(aof.clone()), config);
+        let entry = emit_aof_set(b"k", b"v").len() as u64;
+        let written = std::cell::Cell::new(0);
+        // The trigger is checked on writes, against what the writer has
+        // appended so far: keep writing until it fires
+        assert!(wait_for(|| {
+            shard.exec(Cmd::Set(Bytes::from_static(b"k"), Bytes::from_static(b"v")), &mut BytesMut::new());
+            written.set(written.get() + 1);
+            aof.base_size() > 0
+        }), "never rewritten");
+        assert!(written.get() * entry >= 1024);
+        assert!(wait_for(|| !aof.is_rewriting()), "rewrite never finished");
+        aof.shutdown();
+
+        assert!(std::fs::metadata(path).unwrap().len() < written.get() * entry);
+        let reloaded = Shard::new(0, None);
+        load_aof(path, &reloaded).unwrap();
+        assert_eq!(reloaded.dict.get(b"k"), Some(Value::Str(Bytes::from_static(b"v"))));
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn bgrewriteaof_needs_an_aof() {
+        let shard = Shard::new(0, None);
+        let mut out = BytesMut::new();
+        shard.exec(Cmd::BgRewriteAof, &mut out);
+        assert_eq!(&out[..], b"-ERR Append only file is disabled\r\n");
+    }
+
+    #[test]
+    fn always_policy_syncs_before_the_write_returns() {
+        let path = std::env::temp_dir().join(format!("ignix-always-{}.aof", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let aof = spawn_aof_writer(path.to_str().unwrap(), AofPolicy::Always).unwrap();
+        let shard = Shard::new(0, Some(aof.clone()));
+        let mut out = BytesMut::new();
+
+        let mut expected = Vec::new();
+        for i in 0..10 {
+            let k = format!("key:{}", i);
+            shard.exec(Cmd::Set(Bytes::from(k.clone()), Bytes::from_static(b"v")), &mut out);
+            // On disk as soon as the command has executed, no waiting
+            expected.extend_from_slice(&emit_aof_set(k.as_bytes(), b"v"));
+            assert_eq!(std::fs::read(&path).unwrap(), expected);
+        }
+        aof.shutdown();
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn no_policy_still_appends_every_entry() {
+        let path = std::env::temp_dir().join(format!("ignix-nosync-{}.aof", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let aof = spawn_aof_writer(path.to_str().unwrap(), AofPolicy::No).unwrap();
+        let shard = Shard::new(0, Some(aof.clone()));
+        let mut out = BytesMut::new();
+
+        shard.exec(Cmd::Set(Bytes::from_static(b"a"), Bytes::from_static(b"1")), &mut out);
+        assert!(wait_for(|| std::fs::read(&path).unwrap() == emit_aof_set(b"a", b"1")));
+        aof.shutdown();
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn deleted_keys_stay_deleted_after_reload() {
+        let path = std::env::temp_dir().join(format!("ignix-del-reload-{}.aof", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+        let aof = spawn_aof_writer(path, AofPolicy::EverySec).unwrap();
+        let shard = Shard::new(0, Some(aof.clone()));
+        let mut out = BytesMut::new();
+        for k in ["a", "b", "c"] {
+            shard.exec(Cmd::Set(Bytes::from(k), Bytes::from_static(b"v")), &mut out);
+        }
+        shard.exec(Cmd::Del(vec![Bytes::from_static(b"a"), Bytes::from_static(b"missing")]), &mut out);
+        shard.exec(Cmd::Unlink(vec![Bytes::from_static(b"b")]), &mut out);
+        aof.shutdown();
+
+        let reloaded = Shard::new(0, None);
+        load_aof(path, &reloaded).unwrap();
+        assert!(!reloaded.dict.exists(b"a"));
+        assert!(!reloaded.dict.exists(b"b"));
+        assert!(reloaded.dict.exists(b"c"));
+        assert_eq!(reloaded.dict.len(), 1);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn expired_keys_stay_gone_after_reload() {
+        let path = std::env::temp_dir().join(format!("ignix-expire-reload-{}.aof", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+        let aof = spawn_aof_writer(path, AofPolicy::EverySec).unwrap();
+        let shard = Shard::new(0, Some(aof.clone()));
+        let mut out = BytesMut::new();
+        for k in ["short", "long"] {
+            shard.exec(Cmd::Set(Bytes::from(k), Bytes::from_static(b"v")), &mut out);
+        }
+        shard.exec(Cmd::Expire(Bytes::from_static(b"short"), 1), &mut out);
+        shard.exec(Cmd::Expire(Bytes::from_static(b"long"), 100), &mut out);
+        aof.shutdown();
+
+        // Replayed once the first deadline has passed: the key must not
+        // come back with its time to live started over
+        std::thread::sleep(Duration::from_millis(1100));
+        let reloaded = Shard::new(0, None);
+        load_aof(path, &reloaded).unwrap();
+        assert!(!reloaded.dict.exists(b"short"));
+        let ttl = reloaded.dict.ttl(b"long").unwrap().unwrap();
+        assert!(ttl > Duration::from_secs(90) && ttl <= Duration::from_secs(99), "{:?}", ttl);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn set_with_expiry_keeps_its_deadline_after_reload() {
+        let path = std::env::temp_dir().join(format!("ignix-set-px-reload-{}.aof", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+        let aof = spawn_aof_writer(path, AofPolicy::EverySec).unwrap();
+        let shard = Shard::new(0, Some(aof.clone()));
+        let mut out = BytesMut::new();
+        let set = |k: &'static [u8], expiry| Cmd::SetWith(Bytes::from_static(k), Bytes::from_static(b"v"), SetOptions { expiry, ..Default::default() });
+        shard.exec(set(b"short", Some(SetExpiry::Px(200))), &mut out);
+        shard.exec(set(b"long", Some(SetExpiry::Ex(100))), &mut out);
+        shard.exec(set(b"long", Some(SetExpiry::KeepTtl)), &mut out);
+        aof.shutdown();
+
+        let log = std::fs::read(path).unwrap();
+        assert_eq!(log.windows(6).filter(|w| w == b"\r\nPXAT").count(), 2);
+        assert!(log.windows(7).any(|w| w == b"KEEPTTL"));
+
+        std::thread::sleep(Duration::from_millis(300));
+        let reloaded = Shard::new(0, None);
+        load_aof(path, &reloaded).unwrap();
+        assert!(!reloaded.dict.exists(b"short"));
+        let ttl = reloaded.dict.ttl(b"long").unwrap().unwrap();
+        assert!(ttl > Duration::from_secs(90) && ttl < Duration::from_secs(100), "{:?}", ttl);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn rewritten_deadlines_are_absolute() {
+        let path = std::env::temp_dir().join(format!("ignix-rewrite-pxat-{}.aof", std::process::id()));
+        let path = path.to_str().unwrap();
+        let dict = Dict::default();
+        dict.set(Bytes::from_static(b"short"), Value::Int(1));
+        dict.set(Bytes::from_static(b"long"), Value::Int(1));
+        dict.expire_at(b"short", Instant::now() + Duration::from_millis(200));
+        dict.expire_at(b"long", Instant::now() + Duration::from_secs(100));
+        rewrite(path, &dict).unwrap();
+        let log = std::fs::read(path).unwrap();
+        assert_eq!(log.windows(6).filter(|w| w == b"\r\nPXAT").count(), 2);
+
+        std::thread::sleep(Duration::from_millis(300));
+        let reloaded = Shard::new(0, None);
+        load_aof(path, &reloaded).unwrap();
+        assert!(!reloaded.dict.exists(b"short"));
+        assert!(reloaded.dict.exists(b"long"));
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -2,13 +2,13 @@
  * Ignix Server Main Entry Point
  * 
  * This is the main executable that starts the Ignix key-value server.
- * It initializes logging, creates the storage shard, optionally enables
+ * It initializes logging, creates the storage shards, optionally enables
  * AOF persistence, and starts the main server event loop.
  */
 
 use anyhow::*;
 use ignix::*;
-use std::net::ToSocketAddrs;
+use std::result::Result::{Ok, Err};
 
 #[global_allocator]
 static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
@@ -17,36 +17,59 @@ static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 /// 
 /// Initializes the server components and starts the main event loop:
 /// 1. Initialize logging system
-/// 2. Parse server address
+/// 2. Parse the command line
 /// 3. Create AOF writer (if possible)
-/// 4. Create storage shard
-/// 5. Start server event loop
+/// 4. Create storage shards
+/// 5. Replay the AOF into them
+/// 6. Start server event loop
 fn main() -> Result<()> {
     // Initialize logging - respects RUST_LOG environment variable
     // Example: RUST_LOG=debug cargo run --release
     env_logger::init();
     
     // Parse arguments
-    let args: Vec<String> = std::env::args().collect();
-    let use_uring = args.iter().any(|a| a == "--backend=uring");
+    let opts = match parse_args(std::env::args().skip(1)) {
+        Ok(CliAction::Run(opts)) => *opts,
+        Ok(CliAction::Help) => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("ignix: {}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+    let addr = opts.addr;
+    let use_uring = opts.uring;
 
-    // Parse the default server address (0.0.0.0:7379)
-    let addr = DEFAULT_ADDR.to_socket_addrs()?.next().unwrap();
-    
     // Try to create AOF writer for persistence
     // If this fails, server will run without persistence (in-memory only)
-    let aof = aof::spawn_aof_writer("ignix.aof").ok();
+    let aof = opts.aof_path.as_deref().and_then(|path| match aof::spawn_aof_writer(path, opts.appendfsync) {
+        Ok(aof) => Some(aof),
+        Err(e) => {
+            log::warn!("running without an AOF: {:#}", e);
+            None
+        }
+    });
     
-    // Create the main storage shard with ID 0
-    // Currently Ignix uses a single shard, but architecture supports multiple
-    let shard = shard::Shard::new(0, aof);
+    // Create the storage shards (`--shards`, one by default); keys are
+    // routed to the shard owning them
+    let shards = ShardSet::with_config(aof, opts.config);
+
+    // Restore the dataset from the AOF before serving any client
+    if let Some(path) = &opts.aof_path {
+        let loaded = shards.load_aof(path)?;
+        if loaded.commands > 0 {
+            log::info!("loaded {} commands from {} in {:?}", loaded.commands, path, loaded.elapsed);
+        }
+    }
 
     // Print startup message
     println!("ignix running on {}", addr);
     
     #[cfg(target_os = "linux")]
     if use_uring {
-        return net_uring::run_shard(0, addr, shard);
+        return net_uring::run_shard(0, addr, shards);
     }
 
     if use_uring {
@@ -55,5 +78,5 @@ fn main() -> Result<()> {
 
     // Start the main server event loop
     // This call blocks until the server is shut down
-    net::run_shard(0, addr, shard)
+    net::run_shard(0, addr, shards)
 }
\ No newline at end of file
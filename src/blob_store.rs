@@ -0,0 +1,149 @@
+/*!
+ * Blob Store
+ *
+ * Optional home for large values outside the process heap (feature
+ * `blob-store`, Linux only). Values of at least `Config::blob_threshold`
+ * bytes are copied into an append-only region of a memory-mapped file,
+ * and the shard keeps a `Value::Blob` whose `Bytes` points into the
+ * mapping. GET and friends read it like any other string, and the page
+ * cache rather than the allocator decides what stays resident.
+ *
+ * Regions are never reused in place: when the last reference to a blob
+ * is dropped its bytes are counted as dead, for a later compaction to
+ * reclaim. A full store makes the shard fall back to inline values.
+ */
+
+use anyhow::*;
+use bytes::Bytes;
+use parking_lot::Mutex;
+use std::fs::OpenOptions;
+use std::os::fd::AsRawFd;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// The shared file mapping
+#[derive(Debug)]
+struct Mapping {
+    ptr: *mut u8,
+    len: usize,
+    /// Bytes of blobs no longer referenced
+    dead: AtomicUsize,
+}
+
+// SAFETY: the mapping lives until the last `Arc` is dropped; regions are
+// written once, before a `Bytes` referencing them is handed out, and are
+// only read afterwards
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: `ptr`/`len` came from a successful mmap
+        unsafe { libc::munmap(self.ptr.cast(), self.len) };
+    }
+}
+
+/// One stored value: owner of the `Bytes` handed to the dict
+struct BlobRef {
+    map: Arc<Mapping>,
+    offset: usize,
+    len: usize,
+}
+
+impl AsRef<[u8]> for BlobRef {
+    fn as_ref(&self) -> &[u8] {
+        // SAFETY: the region was reserved for this blob and fully written
+        unsafe { std::slice::from_raw_parts(self.map.ptr.add(self.offset), self.len) }
+    }
+}
+
+impl Drop for BlobRef {
+    fn drop(&mut self) {
+        self.map.dead.fetch_add(self.len, Ordering::Relaxed);
+    }
+}
+
+/// Append-only store of large values in a memory-mapped file
+#[derive(Debug)]
+pub struct BlobStore {
+    map: Arc<Mapping>,
+    /// End of the used part of the file
+    next: Mutex<usize>,
+}
+
+impl BlobStore {
+    /// Create (or truncate) the backing file and map it
+    ///
+    /// # Arguments
+    /// * `path` - Backing file
+    /// * `capacity` - File size: the total bytes of blobs it can hold
+    pub fn open(path: &str, capacity: usize) -> Result<Self> {
+        ensure!(capacity > 0, "blob store capacity must be positive");
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .with_context(|| format!("opening blob store {}", path))?;
+        file.set_len(capacity as u64).with_context(|| format!("sizing blob store {}", path))?;
+        // SAFETY: a fresh shared mapping of a file we just sized; the file
+        // may be closed once mapped
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                capacity,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error()).with_context(|| format!("mapping blob store {}", path));
+        }
+        let map = Arc::new(Mapping { ptr: ptr.cast(), len: capacity, dead: AtomicUsize::new(0) });
+        Ok(Self { map, next: Mutex::new(0) })
+    }
+
+    /// Copy a value into the store
+    ///
+    /// # Returns
+    /// * `Bytes` backed by the mapping
+    /// * `None` when the store has no room left
+    pub fn store(&self, data: &[u8]) -> Option<Bytes> {
+        let offset = {
+            let mut next = self.next.lock();
+            let offset = *next;
+            if self.map.len - offset < data.len() {
+                return None;
+            }
+            *next += data.len();
+            offset
+        };
+        // SAFETY: [offset, offset + len) is inside the mapping and reserved
+        // for this call alone
+        unsafe { std::ptr::copy_nonoverlapping(data.as_ptr(), self.map.ptr.add(offset), data.len()) };
+        Some(Bytes::from_owner(BlobRef { map: self.map.clone(), offset, len: data.len() }))
+    }
+
+    /// Bytes of the file written so far
+    pub fn used(&self) -> usize {
+        *self.next.lock()
+    }
+
+    /// Bytes of blobs that were deleted or replaced (awaiting compaction)
+    pub fn dead(&self) -> usize {
+        self.map.dead.load(Ordering::Relaxed)
+    }
+
+    /// Bytes of blobs still referenced
+    pub fn live(&self) -> usize {
+        self.used().saturating_sub(self.dead())
+    }
+
+    /// Size of the backing file
+    pub fn capacity(&self) -> usize {
+        self.map.len
+    }
+}
@@ -0,0 +1,181 @@
+/*!
+ * Command Line
+ *
+ * Options of the `ignix` binary. Each flag takes its value either as the
+ * next argument (`--port 7000`) or inline (`--port=7000`); anything not
+ * given keeps the built-in default, so a bare `ignix` still listens on
+ * `DEFAULT_ADDR` and persists to `ignix.aof`.
+ */
+
+use crate::aof::AofPolicy;
+use crate::config::Config;
+use anyhow::*;
+use std::net::{IpAddr, SocketAddr};
+use std::result::Result::Ok;
+
+/// Default AOF file, relative to the working directory
+pub const DEFAULT_AOF_PATH: &str = "ignix.aof";
+
+/// Usage message printed for `--help` and on bad input
+pub const USAGE: &str = "\
+Usage: ignix [OPTIONS]
+
+Options:
+  --bind <ADDR>            IP address to listen on [default: 0.0.0.0]
+  --port <PORT>            TCP port to listen on [default: 7379]
+  --aof-path <FILE>        Append only file [default: ignix.aof]
+  --no-aof                 Run in memory only, without an append only file
+  --appendfsync <POLICY>   always, everysec or no [default: everysec]
+  --unixsocket <PATH>      Also accept clients on a Unix domain socket
+  --tls-cert-file <FILE>   PEM certificate chain (with --tls-key-file)
+  --tls-key-file <FILE>    PEM private key (with --tls-cert-file)
+  --backend <BACKEND>      mio or uring (Linux only) [default: mio]
+  --shards <N>             Shards the keyspace is split into [default: 1]
+  --owned-shards           One worker per shard, the only one running its keys' commands
+  --maxmemory <BYTES>      Dataset size limit, e.g. 100mb (0 for none) [default: 0]
+  --maxmemory-policy <P>   noeviction or allkeys-lru [default: noeviction]
+  -h, --help               Print this message";
+
+/// Everything the binary needs to start a server
+#[derive(Debug, Clone)]
+pub struct ServerOptions {
+    /// TCP address to listen on
+    pub addr: SocketAddr,
+    /// Append only file; `None` with `--no-aof`
+    pub aof_path: Option<String>,
+    /// When the AOF writer syncs
+    pub appendfsync: AofPolicy,
+    /// Use the io_uring backend instead of mio
+    pub uring: bool,
+    /// Runtime settings handed to the shards
+    pub config: Config,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        Self {
+            addr: crate::DEFAULT_ADDR.parse().expect("DEFAULT_ADDR is a socket address"),
+            aof_path: Some(DEFAULT_AOF_PATH.into()),
+            appendfsync: AofPolicy::default(),
+            uring: false,
+            config: Config::default(),
+        }
+    }
+}
+
+/// What the command line asks for
+#[derive(Debug, Clone)]
+pub enum CliAction {
+    /// Start a server
+    Run(Box<ServerOptions>),
+    /// Print [`USAGE`] and exit
+    Help,
+}
+
+/// Parse the binary's arguments
+///
+/// # Arguments
+/// * `args` - Arguments without the program name
+///
+/// # Returns
+/// * The action to take, or an error describing the first bad argument
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<CliAction> {
+    let mut opts = ServerOptions::default();
+    let mut bind = opts.addr.ip();
+    let mut port = opts.addr.port();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+            _ => (arg, None),
+        };
+        // The flag's value, inline or from the next argument
+        let mut value = || match inline.clone() {
+            Some(v) => Ok(v),
+            None => args.next().ok_or_else(|| anyhow!("{} needs a value", flag)),
+        };
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(CliAction::Help),
+            "--bind" => {
+                let v = value()?;
+                bind = v.parse::<IpAddr>().map_err(|_| anyhow!("invalid bind address '{}'", v))?;
+            }
+            "--port" => {
+                let v = value()?;
+                port = match v.parse::<u16>() {
+                    Ok(p) if p > 0 => p,
+                    _ => bail!("invalid port '{}'", v),
+                };
+            }
+            "--aof-path" => opts.aof_path = Some(value()?),
+            "--no-aof" => {
+                ensure!(inline.is_none(), "--no-aof takes no value");
+                opts.aof_path = None;
+            }
+            "--appendfsync" => {
+                let v = value()?;
+                opts.appendfsync = match v.to_ascii_lowercase().as_str() {
+                    "always" => AofPolicy::Always,
+                    "everysec" => AofPolicy::EverySec,
+                    "no" => AofPolicy::No,
+                    _ => bail!("invalid appendfsync policy '{}'", v),
+                };
+            }
+            "--unixsocket" => opts.config.unixsocket = Some(value()?),
+            "--tls-cert-file" => opts.config.tls_cert_file = Some(value()?),
+            "--tls-key-file" => opts.config.tls_key_file = Some(value()?),
+            "--backend" => {
+                opts.uring = match value()?.as_str() {
+                    "mio" => false,
+                    "uring" => true,
+                    v => bail!("invalid backend '{}'", v),
+                };
+            }
+            "--shards" => {
+                let v = value()?;
+                opts.config.shards = match v.parse::<usize>() {
+                    Ok(n) if n > 0 => n,
+                    _ => bail!("invalid shard count '{}'", v),
+                };
+            }
+            "--owned-shards" => {
+                ensure!(inline.is_none(), "--owned-shards takes no value");
+                opts.config.owned_shards = true;
+            }
+            "--maxmemory" => {
+                let v = value()?;
+                opts.config.maxmemory = parse_memory(&v).ok_or_else(|| anyhow!("invalid maxmemory '{}'", v))?;
+            }
+            "--maxmemory-policy" => opts.config.maxmemory_policy = value()?.parse()?,
+            _ => bail!("unknown argument '{}'", flag),
+        }
+    }
+
+    ensure!(
+        opts.config.tls_cert_file.is_some() == opts.config.tls_key_file.is_some(),
+        "--tls-cert-file and --tls-key-file must be given together"
+    );
+    opts.addr = SocketAddr::new(bind, port);
+    Ok(CliAction::Run(Box::new(opts)))
+}
+
+/// Parse a byte count the way Redis reads memory settings: a plain
+/// number, or one suffixed with `k`, `m`, `g` (powers of 1000) or `kb`,
+/// `mb`, `gb` (powers of 1024), in any case
+fn parse_memory(v: &str) -> Option<usize> {
+    let lower = v.to_ascii_lowercase();
+    let split = lower.find(|c: char| !c.is_ascii_digit()).unwrap_or(lower.len());
+    let (digits, unit) = lower.split_at(split);
+    let scale: usize = match unit {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    digits.parse::<usize>().ok()?.checked_mul(scale)
+}
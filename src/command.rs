@@ -0,0 +1,603 @@
+/*!
+ * Command Table
+ *
+ * Central registry of every command Ignix understands. Each entry carries
+ * the Redis-style metadata (arity, flags, key positions) used for argument
+ * validation, introspection and key-based routing, plus the function that
+ * turns a validated argument vector into a `Cmd`.
+ *
+ * The table itself is static; `rename-command` is layered on top of it by
+ * a `Renames` set built when the configuration is loaded.
+ */
+
+use crate::error::CmdError;
+use crate::pause::PauseMode;
+use crate::protocol::{write_array_len, write_bulk, write_integer, write_simple, ClusterSubcommand, Cmd, LcsOptions, SetCond, SetExpiry, SetOptions, SCAN_DEFAULT_COUNT};
+use anyhow::*;
+use bytes::{Bytes, BytesMut};
+
+/// Command modifies the keyspace
+pub const WRITE: &str = "write";
+/// Command never modifies the keyspace
+pub const READONLY: &str = "readonly";
+/// Command may grow memory usage
+pub const DENYOOM: &str = "denyoom";
+/// Command runs in O(1) or O(log N)
+pub const FAST: &str = "fast";
+/// Administrative command
+pub const ADMIN: &str = "admin";
+
+/// Static description of a command
+pub struct CommandSpec {
+    /// Uppercase command name
+    pub name: &'static str,
+    /// Redis arity: N means exactly N arguments (including the name),
+    /// -N means at least N
+    pub arity: i32,
+    /// Redis command flags (`write`, `readonly`, ...)
+    pub flags: &'static [&'static str],
+    /// Position of the first key argument (0 if the command takes no keys)
+    pub first_key: i32,
+    /// Position of the last key argument (-1 means the last argument)
+    pub last_key: i32,
+    /// Distance between key arguments
+    pub step: i32,
+    /// Build the command from its argument vector (arity already checked)
+    parse: fn(Vec<Bytes>) -> Result<Cmd>,
+}
+
+impl CommandSpec {
+    /// Check whether the spec carries the given flag
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.contains(&flag)
+    }
+
+    /// Write the `COMMAND INFO` entry for this command:
+    /// `[name, arity, [flags...], first-key, last-key, step]`
+    pub fn write_info(&self, out: &mut BytesMut) {
+        write_array_len(6, out);
+        write_bulk(self.name.to_ascii_lowercase().as_bytes(), out);
+        write_integer(self.arity as i64, out);
+        write_array_len(self.flags.len(), out);
+        for flag in self.flags {
+            write_simple(flag, out);
+        }
+        write_integer(self.first_key as i64, out);
+        write_integer(self.last_key as i64, out);
+        write_integer(self.step as i64, out);
+    }
+
+    /// Check an argument count (including the command name) against the arity
+    pub fn arity_ok(&self, argc: usize) -> bool {
+        if self.arity >= 0 {
+            argc == self.arity as usize
+        } else {
+            argc >= self.arity.unsigned_abs() as usize
+        }
+    }
+}
+
+/// All supported commands, sorted by name for binary search
+pub static COMMANDS: &[CommandSpec] = &[
+    CommandSpec { name: "APPEND", arity: 3, flags: &[WRITE, DENYOOM, FAST], first_key: 1, last_key: 1, step: 1, parse: parse_append },
+    CommandSpec { name: "BGREWRITEAOF", arity: 1, flags: &[ADMIN], first_key: 0, last_key: 0, step: 0, parse: parse_bgrewriteaof },
+    CommandSpec { name: "CLIENT", arity: -2, flags: &[ADMIN], first_key: 0, last_key: 0, step: 0, parse: parse_client },
+    CommandSpec { name: "CLUSTER", arity: -2, flags: &[], first_key: 0, last_key: 0, step: 0, parse: parse_cluster },
+    CommandSpec { name: "COMMAND", arity: -2, flags: &[], first_key: 0, last_key: 0, step: 0, parse: parse_command },
+    CommandSpec { name: "CONFIG", arity: -2, flags: &[ADMIN], first_key: 0, last_key: 0, step: 0, parse: parse_config },
+    CommandSpec { name: "DBSIZE", arity: 1, flags: &[READONLY, FAST], first_key: 0, last_key: 0, step: 0, parse: parse_dbsize },
+    CommandSpec { name: "DEBUG", arity: -2, flags: &[ADMIN], first_key: 0, last_key: 0, step: 0, parse: parse_debug },
+    CommandSpec { name: "DECR", arity: 2, flags: &[WRITE, DENYOOM, FAST], first_key: 1, last_key: 1, step: 1, parse: parse_decr },
+    CommandSpec { name: "DECRBY", arity: 3, flags: &[WRITE, DENYOOM, FAST], first_key: 1, last_key: 1, step: 1, parse: parse_decrby },
+    CommandSpec { name: "DEL", arity: -2, flags: &[WRITE], first_key: 1, last_key: -1, step: 1, parse: parse_del },
+    CommandSpec { name: "EXISTS", arity: -2, flags: &[READONLY, FAST], first_key: 1, last_key: -1, step: 1, parse: parse_exists },
+    CommandSpec { name: "EXPIRE", arity: 3, flags: &[WRITE, FAST], first_key: 1, last_key: 1, step: 1, parse: parse_expire },
+    CommandSpec { name: "FLUSHALL", arity: -1, flags: &[WRITE], first_key: 0, last_key: 0, step: 0, parse: parse_flushall },
+    CommandSpec { name: "FLUSHDB", arity: -1, flags: &[WRITE], first_key: 0, last_key: 0, step: 0, parse: parse_flushdb },
+    CommandSpec { name: "GET", arity: 2, flags: &[READONLY, FAST], first_key: 1, last_key: 1, step: 1, parse: parse_get },
+    CommandSpec { name: "GETBIT", arity: 3, flags: &[READONLY, FAST], first_key: 1, last_key: 1, step: 1, parse: parse_getbit },
+    CommandSpec { name: "GETSET", arity: 3, flags: &[WRITE, DENYOOM, FAST], first_key: 1, last_key: 1, step: 1, parse: parse_getset },
+    CommandSpec { name: "INCR", arity: 2, flags: &[WRITE, DENYOOM, FAST], first_key: 1, last_key: 1, step: 1, parse: parse_incr },
+    CommandSpec { name: "INCRBY", arity: 3, flags: &[WRITE, DENYOOM, FAST], first_key: 1, last_key: 1, step: 1, parse: parse_incrby },
+    CommandSpec { name: "INFO", arity: -1, flags: &[], first_key: 0, last_key: 0, step: 0, parse: parse_info },
+    CommandSpec { name: "KEYS", arity: 2, flags: &[READONLY], first_key: 0, last_key: 0, step: 0, parse: parse_keys },
+    CommandSpec { name: "LCS", arity: -3, flags: &[READONLY], first_key: 1, last_key: 2, step: 1, parse: parse_lcs },
+    CommandSpec { name: "MEMORY", arity: -2, flags: &[], first_key: 0, last_key: 0, step: 0, parse: parse_memory },
+    CommandSpec { name: "MGET", arity: -2, flags: &[READONLY, FAST], first_key: 1, last_key: -1, step: 1, parse: parse_mget },
+    CommandSpec { name: "MONITOR", arity: 1, flags: &[ADMIN], first_key: 0, last_key: 0, step: 0, parse: parse_monitor },
+    CommandSpec { name: "MSET", arity: -3, flags: &[WRITE, DENYOOM], first_key: 1, last_key: -1, step: 2, parse: parse_mset },
+    CommandSpec { name: "OBJECT", arity: -2, flags: &[READONLY], first_key: 2, last_key: 2, step: 1, parse: parse_object },
+    CommandSpec { name: "PEXPIREAT", arity: 3, flags: &[WRITE, FAST], first_key: 1, last_key: 1, step: 1, parse: parse_pexpireat },
+    CommandSpec { name: "PING", arity: 1, flags: &[FAST], first_key: 0, last_key: 0, step: 0, parse: parse_ping },
+    CommandSpec { name: "RENAME", arity: 3, flags: &[WRITE], first_key: 1, last_key: 2, step: 1, parse: parse_rename },
+    CommandSpec { name: "SCAN", arity: -2, flags: &[READONLY], first_key: 0, last_key: 0, step: 0, parse: parse_scan },
+    CommandSpec { name: "SET", arity: -3, flags: &[WRITE, DENYOOM], first_key: 1, last_key: 1, step: 1, parse: parse_set },
+    CommandSpec { name: "SETBIT", arity: 4, flags: &[WRITE, DENYOOM], first_key: 1, last_key: 1, step: 1, parse: parse_setbit },
+    CommandSpec { name: "TTL", arity: 2, flags: &[READONLY, FAST], first_key: 1, last_key: 1, step: 1, parse: parse_ttl },
+    CommandSpec { name: "UNLINK", arity: -2, flags: &[WRITE, FAST], first_key: 1, last_key: -1, step: 1, parse: parse_unlink },
+];
+
+/// Longest command name in the table
+const MAX_NAME_LEN: usize = 16;
+
+/// Find the spec for a command name (case-insensitive, no allocation)
+pub fn lookup(name: &[u8]) -> Option<&'static CommandSpec> {
+    if name.len() > MAX_NAME_LEN {
+        return None;
+    }
+    let mut upper = [0u8; MAX_NAME_LEN];
+    for (d, s) in upper.iter_mut().zip(name) {
+        *d = s.to_ascii_uppercase();
+    }
+    let upper = &upper[..name.len()];
+    COMMANDS
+        .binary_search_by(|spec| spec.name.as_bytes().cmp(upper))
+        .ok()
+        .map(|i| &COMMANDS[i])
+}
+
+/// Turn a request's argument vector into a command
+///
+/// Unknown commands, arity violations and malformed arguments are returned
+/// as `Cmd::Invalid` carrying the Redis-compatible error text, so the
+/// request is consumed and answered instead of poisoning the stream.
+pub fn dispatch(items: Vec<Bytes>) -> Cmd {
+    Renames::new().dispatch(items)
+}
+
+/// Renamed and disabled commands (`rename-command`)
+///
+/// A command renamed to a new name only answers to that name; one renamed
+/// to `""` is disabled. Either way its original name becomes an unknown
+/// command.
+#[derive(Debug, Clone, Default)]
+pub struct Renames {
+    /// Uppercase new name and the table name of the command it reaches
+    aliases: Vec<(Vec<u8>, &'static str)>,
+    /// Commands no longer reachable by their table name
+    hidden: Vec<&'static str>,
+}
+
+impl Renames {
+    /// An empty set: every command answers to its table name
+    pub const fn new() -> Self {
+        Self { aliases: Vec::new(), hidden: Vec::new() }
+    }
+
+    /// Rename `name` to `new_name`, or disable it if `new_name` is empty
+    ///
+    /// # Arguments
+    /// * `name` - Table name of the command (case-insensitive)
+    /// * `new_name` - Name the command answers to from now on, or `""`
+    ///
+    /// # Returns
+    /// * An error if `name` is unknown or already renamed, or if
+    ///   `new_name` is taken by another command
+    pub fn rename(&mut self, name: &str, new_name: &str) -> Result<()> {
+        let spec = lookup(name.as_bytes()).ok_or_else(|| anyhow!("unknown command '{}' in rename-command", name))?;
+        if self.hidden.contains(&spec.name) {
+            bail!("command '{}' is already renamed", name);
+        }
+        if !new_name.is_empty() {
+            if self.lookup(new_name.as_bytes()).is_some() {
+                bail!("target command name '{}' already exists", new_name);
+            }
+            self.aliases.push((new_name.to_ascii_uppercase().into_bytes(), spec.name));
+        }
+        self.hidden.push(spec.name);
+        Ok(())
+    }
+
+    /// Look a request's command name up through the renames
+    pub fn lookup(&self, name: &[u8]) -> Option<&'static CommandSpec> {
+        if let Some((_, target)) = self.aliases.iter().find(|(alias, _)| alias.eq_ignore_ascii_case(name)) {
+            return lookup(target.as_bytes());
+        }
+        lookup(name).filter(|spec| !self.hidden.contains(&spec.name))
+    }
+
+    /// [`dispatch`] with the renames applied
+    pub fn dispatch(&self, items: Vec<Bytes>) -> Cmd {
+        let Some(spec) = self.lookup(&items[0]) else {
+            return Cmd::Invalid(unknown_command(&items));
+        };
+        if !spec.arity_ok(items.len()) {
+            return Cmd::Invalid(CmdError::WrongArity(spec.name.to_ascii_lowercase()).to_string());
+        }
+        (spec.parse)(items).unwrap_or_else(|e| Cmd::Invalid(e.to_string()))
+    }
+}
+
+/// Redis' error text for an unknown command
+fn unknown_command(items: &[Bytes]) -> String {
+    let mut msg = format!(
+        "ERR unknown command '{}', with args beginning with:",
+        String::from_utf8_lossy(&items[0])
+    );
+    for arg in &items[1..] {
+        msg.push_str(&format!(" '{}'", String::from_utf8_lossy(arg)));
+    }
+    msg
+}
+
+/// Redis' error text for an unknown subcommand
+fn unknown_subcommand(cmd: &str, sub: &[u8]) -> Error {
+    anyhow!(
+        "ERR unknown subcommand '{}'. Try {} HELP.",
+        String::from_utf8_lossy(sub),
+        cmd
+    )
+}
+
+//
+// Argument parsers
+//
+// Each parser receives the full argument vector (name included) with the
+// arity already validated against its table entry.
+//
+
+fn parse_ping(_: Vec<Bytes>) -> Result<Cmd> {
+    Ok(Cmd::Ping)
+}
+
+fn parse_get(mut items: Vec<Bytes>) -> Result<Cmd> {
+    Ok(Cmd::Get(items.swap_remove(1)))
+}
+
+fn parse_set(items: Vec<Bytes>) -> Result<Cmd> {
+    let mut opts = SetOptions::default();
+    let mut args = items[3..].iter();
+    while let Some(arg) = args.next() {
+        if arg.eq_ignore_ascii_case(b"NX") && opts.cond.is_none() {
+            opts.cond = Some(SetCond::Nx);
+        } else if arg.eq_ignore_ascii_case(b"XX") && opts.cond.is_none() {
+            opts.cond = Some(SetCond::Xx);
+        } else if arg.eq_ignore_ascii_case(b"GET") {
+            opts.get = true;
+        } else if arg.eq_ignore_ascii_case(b"KEEPTTL") && opts.expiry.is_none() {
+            opts.expiry = Some(SetExpiry::KeepTtl);
+        } else if [&b"EX"[..], b"PX", b"PXAT"].iter().any(|m| arg.eq_ignore_ascii_case(m)) && opts.expiry.is_none() {
+            let n = args.next().ok_or(CmdError::Syntax)?;
+            let n = std::str::from_utf8(n)
+                .ok()
+                .and_then(|s| s.parse::<i64>().ok())
+                .ok_or(CmdError::NotInteger)?;
+            if n <= 0 {
+                bail!(CmdError::InvalidExpire("set".into()));
+            }
+            opts.expiry = Some(if arg.eq_ignore_ascii_case(b"EX") {
+                SetExpiry::Ex(n as u64)
+            } else if arg.eq_ignore_ascii_case(b"PX") {
+                SetExpiry::Px(n as u64)
+            } else {
+                SetExpiry::PxAt(n as u64)
+            });
+        } else {
+            bail!(CmdError::Syntax);
+        }
+    }
+    let mut it = items.into_iter().skip(1);
+    let (k, v) = (it.next().unwrap(), it.next().unwrap());
+    if opts == SetOptions::default() {
+        return Ok(Cmd::Set(k, v));
+    }
+    Ok(Cmd::SetWith(k, v, opts))
+}
+
+fn parse_getset(items: Vec<Bytes>) -> Result<Cmd> {
+    let mut it = items.into_iter().skip(1);
+    Ok(Cmd::GetSet(it.next().unwrap(), it.next().unwrap()))
+}
+
+fn parse_del(mut items: Vec<Bytes>) -> Result<Cmd> {
+    items.remove(0);
+    Ok(Cmd::Del(items))
+}
+
+fn parse_unlink(mut items: Vec<Bytes>) -> Result<Cmd> {
+    items.remove(0);
+    Ok(Cmd::Unlink(items))
+}
+
+fn parse_rename(items: Vec<Bytes>) -> Result<Cmd> {
+    let mut it = items.into_iter().skip(1);
+    Ok(Cmd::Rename(it.next().unwrap(), it.next().unwrap()))
+}
+
+fn parse_exists(mut items: Vec<Bytes>) -> Result<Cmd> {
+    items.remove(0);
+    Ok(Cmd::Exists(items))
+}
+
+fn parse_incr(mut items: Vec<Bytes>) -> Result<Cmd> {
+    Ok(Cmd::Incr(items.swap_remove(1)))
+}
+
+/// Parse the delta of INCRBY/DECRBY
+fn parse_delta(arg: &[u8]) -> Result<i64> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .ok_or(CmdError::NotInteger.into())
+}
+
+fn parse_incrby(mut items: Vec<Bytes>) -> Result<Cmd> {
+    let delta = parse_delta(&items[2])?;
+    Ok(Cmd::IncrBy(items.swap_remove(1), delta))
+}
+
+fn parse_decr(mut items: Vec<Bytes>) -> Result<Cmd> {
+    Ok(Cmd::Decr(items.swap_remove(1)))
+}
+
+fn parse_decrby(mut items: Vec<Bytes>) -> Result<Cmd> {
+    let delta = parse_delta(&items[2])?;
+    Ok(Cmd::DecrBy(items.swap_remove(1), delta))
+}
+
+fn parse_expire(mut items: Vec<Bytes>) -> Result<Cmd> {
+    let secs = std::str::from_utf8(&items[2])
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .ok_or(CmdError::NotInteger)?;
+    Ok(Cmd::Expire(items.swap_remove(1), secs))
+}
+
+fn parse_pexpireat(mut items: Vec<Bytes>) -> Result<Cmd> {
+    let unix_ms = std::str::from_utf8(&items[2])
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .ok_or(CmdError::NotInteger)?;
+    Ok(Cmd::PExpireAt(items.swap_remove(1), unix_ms))
+}
+
+fn parse_ttl(mut items: Vec<Bytes>) -> Result<Cmd> {
+    Ok(Cmd::Ttl(items.swap_remove(1)))
+}
+
+fn parse_append(items: Vec<Bytes>) -> Result<Cmd> {
+    let mut it = items.into_iter().skip(1);
+    Ok(Cmd::Append(it.next().unwrap(), it.next().unwrap()))
+}
+
+fn parse_lcs(items: Vec<Bytes>) -> Result<Cmd> {
+    let mut opts = LcsOptions::default();
+    let mut i = 3;
+    while i < items.len() {
+        match &items[i] {
+            s if s.eq_ignore_ascii_case(b"LEN") => opts.len = true,
+            s if s.eq_ignore_ascii_case(b"IDX") => opts.idx = true,
+            s if s.eq_ignore_ascii_case(b"WITHMATCHLEN") => opts.with_match_len = true,
+            s if s.eq_ignore_ascii_case(b"MINMATCHLEN") && i + 1 < items.len() => {
+                // Negative lengths filter nothing, as in Redis
+                let n = std::str::from_utf8(&items[i + 1])
+                    .ok()
+                    .and_then(|s| s.parse::<i64>().ok())
+                    .ok_or(CmdError::NotInteger)?;
+                opts.min_match_len = n.max(0) as u64;
+                i += 1;
+            }
+            _ => bail!(CmdError::Syntax),
+        }
+        i += 1;
+    }
+    if opts.len && opts.idx {
+        bail!(CmdError::LcsLenAndIdx);
+    }
+    let mut it = items.into_iter().skip(1);
+    Ok(Cmd::Lcs(it.next().unwrap(), it.next().unwrap(), opts))
+}
+
+fn parse_mget(mut items: Vec<Bytes>) -> Result<Cmd> {
+    items.remove(0);
+    Ok(Cmd::MGet(items))
+}
+
+fn parse_mset(items: Vec<Bytes>) -> Result<Cmd> {
+    // MSET requires an odd number of args (command + key-value pairs)
+    if items.len().is_multiple_of(2) {
+        bail!(CmdError::WrongArity("mset".into()));
+    }
+    let mut it = items.into_iter().skip(1);
+    let mut pairs = Vec::with_capacity(it.len() / 2);
+    while let (Some(k), Some(v)) = (it.next(), it.next()) {
+        pairs.push((k, v));
+    }
+    Ok(Cmd::MSet(pairs))
+}
+
+/// Parse a bit offset; the upper bound depends on configuration and is
+/// checked at execution time
+fn parse_bit_offset(arg: &[u8]) -> Result<u64> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .ok_or(CmdError::BitOffset.into())
+}
+
+fn parse_setbit(items: Vec<Bytes>) -> Result<Cmd> {
+    let offset = parse_bit_offset(&items[2])?;
+    let bit = match &items[3][..] {
+        b"0" => false,
+        b"1" => true,
+        _ => bail!(CmdError::BitValue),
+    };
+    let mut it = items.into_iter();
+    Ok(Cmd::SetBit(it.nth(1).unwrap(), offset, bit))
+}
+
+fn parse_getbit(mut items: Vec<Bytes>) -> Result<Cmd> {
+    let offset = parse_bit_offset(&items[2])?;
+    Ok(Cmd::GetBit(items.swap_remove(1), offset))
+}
+
+fn parse_keys(mut items: Vec<Bytes>) -> Result<Cmd> {
+    Ok(Cmd::Keys(items.swap_remove(1)))
+}
+
+fn parse_scan(mut items: Vec<Bytes>) -> Result<Cmd> {
+    let cursor = std::str::from_utf8(&items[1])
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .ok_or(CmdError::InvalidCursor)?;
+    let (mut pattern, mut count) = (None, SCAN_DEFAULT_COUNT);
+    let mut i = 2;
+    while i < items.len() {
+        if i + 1 == items.len() {
+            bail!(CmdError::Syntax);
+        }
+        match &items[i] {
+            s if s.eq_ignore_ascii_case(b"MATCH") => pattern = Some(std::mem::take(&mut items[i + 1])),
+            s if s.eq_ignore_ascii_case(b"COUNT") => {
+                let n = std::str::from_utf8(&items[i + 1])
+                    .ok()
+                    .and_then(|s| s.parse::<i64>().ok())
+                    .ok_or(CmdError::NotInteger)?;
+                if n < 1 {
+                    bail!(CmdError::Syntax);
+                }
+                count = n as usize;
+            }
+            _ => bail!(CmdError::Syntax),
+        }
+        i += 2;
+    }
+    Ok(Cmd::Scan { cursor, pattern, count })
+}
+
+fn parse_monitor(_: Vec<Bytes>) -> Result<Cmd> {
+    Ok(Cmd::Monitor)
+}
+
+fn parse_dbsize(_: Vec<Bytes>) -> Result<Cmd> {
+    Ok(Cmd::DbSize)
+}
+
+fn parse_bgrewriteaof(_: Vec<Bytes>) -> Result<Cmd> {
+    Ok(Cmd::BgRewriteAof)
+}
+
+/// Accept the optional ASYNC/SYNC modifier; flushing is always synchronous
+fn parse_flush_mode(items: &[Bytes]) -> Result<()> {
+    match items {
+        [_] => Ok(()),
+        [_, mode] if mode.eq_ignore_ascii_case(b"ASYNC") || mode.eq_ignore_ascii_case(b"SYNC") => Ok(()),
+        _ => bail!(CmdError::Syntax),
+    }
+}
+
+fn parse_flushdb(items: Vec<Bytes>) -> Result<Cmd> {
+    parse_flush_mode(&items)?;
+    Ok(Cmd::FlushDb)
+}
+
+fn parse_flushall(items: Vec<Bytes>) -> Result<Cmd> {
+    parse_flush_mode(&items)?;
+    Ok(Cmd::FlushAll)
+}
+
+fn parse_info(mut items: Vec<Bytes>) -> Result<Cmd> {
+    match items.len() {
+        1 => Ok(Cmd::Info(None)),
+        2 => Ok(Cmd::Info(items.pop())),
+        _ => bail!(CmdError::Syntax),
+    }
+}
+
+fn parse_command(mut items: Vec<Bytes>) -> Result<Cmd> {
+    if items[1].eq_ignore_ascii_case(b"INFO") {
+        return Ok(Cmd::CommandInfo(items.split_off(2)));
+    }
+    Err(unknown_subcommand("COMMAND", &items[1]))
+}
+
+fn parse_client(items: Vec<Bytes>) -> Result<Cmd> {
+    if items[1].eq_ignore_ascii_case(b"NO-EVICT") && items.len() == 3 {
+        return match &items[2] {
+            s if s.eq_ignore_ascii_case(b"ON") => Ok(Cmd::ClientNoEvict(true)),
+            s if s.eq_ignore_ascii_case(b"OFF") => Ok(Cmd::ClientNoEvict(false)),
+            _ => bail!(CmdError::Syntax),
+        };
+    }
+    if items[1].eq_ignore_ascii_case(b"PAUSE") && (3..=4).contains(&items.len()) {
+        let ms = std::str::from_utf8(&items[2])
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .ok_or(CmdError::Timeout)?;
+        let mode = match items.get(3) {
+            None => PauseMode::All,
+            Some(s) if s.eq_ignore_ascii_case(b"ALL") => PauseMode::All,
+            Some(s) if s.eq_ignore_ascii_case(b"WRITE") => PauseMode::Write,
+            Some(_) => bail!(CmdError::Syntax),
+        };
+        return Ok(Cmd::ClientPause(ms, mode));
+    }
+    if items.len() == 2 {
+        match &items[1] {
+            s if s.eq_ignore_ascii_case(b"UNPAUSE") => return Ok(Cmd::ClientUnpause),
+            s if s.eq_ignore_ascii_case(b"LIST") => return Ok(Cmd::ClientList),
+            s if s.eq_ignore_ascii_case(b"INFO") => return Ok(Cmd::ClientInfo),
+            _ => {}
+        }
+    }
+    Err(unknown_subcommand("CLIENT", &items[1]))
+}
+
+fn parse_cluster(items: Vec<Bytes>) -> Result<Cmd> {
+    let sub = match &items[1] {
+        s if s.eq_ignore_ascii_case(b"INFO") => ClusterSubcommand::Info,
+        s if s.eq_ignore_ascii_case(b"SLOTS") => ClusterSubcommand::Slots,
+        s if s.eq_ignore_ascii_case(b"SHARDS") => ClusterSubcommand::Shards,
+        s if s.eq_ignore_ascii_case(b"NODES") => ClusterSubcommand::Nodes,
+        s => return Err(unknown_subcommand("CLUSTER", s)),
+    };
+    if items.len() != 2 {
+        return Err(unknown_subcommand("CLUSTER", &items[1]));
+    }
+    Ok(Cmd::Cluster(sub))
+}
+
+fn parse_memory(items: Vec<Bytes>) -> Result<Cmd> {
+    if items[1].eq_ignore_ascii_case(b"PURGE") && items.len() == 2 {
+        return Ok(Cmd::MemoryPurge);
+    }
+    Err(unknown_subcommand("MEMORY", &items[1]))
+}
+
+fn parse_config(items: Vec<Bytes>) -> Result<Cmd> {
+    if items[1].eq_ignore_ascii_case(b"RESETSTAT") && items.len() == 2 {
+        return Ok(Cmd::ConfigResetStat);
+    }
+    Err(unknown_subcommand("CONFIG", &items[1]))
+}
+
+fn parse_object(mut items: Vec<Bytes>) -> Result<Cmd> {
+    if items[1].eq_ignore_ascii_case(b"ENCODING") && items.len() == 3 {
+        return Ok(Cmd::ObjectEncoding(items.swap_remove(2)));
+    }
+    Err(unknown_subcommand("OBJECT", &items[1]))
+}
+
+fn parse_debug(mut items: Vec<Bytes>) -> Result<Cmd> {
+    if items[1].eq_ignore_ascii_case(b"OBJECT") && items.len() == 3 {
+        return Ok(Cmd::DebugObject(items.swap_remove(2)));
+    }
+    if items[1].eq_ignore_ascii_case(b"ENCODING") && items.len() == 4 {
+        let mut it = items.into_iter().skip(2);
+        return Ok(Cmd::DebugEncoding(it.next().unwrap(), it.next().unwrap()));
+    }
+    if items[1].eq_ignore_ascii_case(b"PANIC") && items.len() == 2 {
+        return Ok(Cmd::DebugPanic);
+    }
+    if items[1].eq_ignore_ascii_case(b"CHANGE-REPL-ID") && items.len() == 2 {
+        return Ok(Cmd::DebugChangeReplId);
+    }
+    if items[1].eq_ignore_ascii_case(b"PROTOCOL") && items.len() == 3 {
+        return match &items[2] {
+            s if s.eq_ignore_ascii_case(b"PLAIN") => Ok(Cmd::DebugPlainReplies(true)),
+            s if s.eq_ignore_ascii_case(b"RESP") => Ok(Cmd::DebugPlainReplies(false)),
+            _ => bail!(CmdError::Syntax),
+        };
+    }
+    Err(unknown_subcommand("DEBUG", &items[1]))
+}
@@ -0,0 +1,218 @@
+/*!
+ * Server Configuration
+ *
+ * Runtime settings shared by the shard and the network layer. Every field
+ * has a Redis-compatible default, so `Config::default()` describes a stock
+ * server.
+ */
+
+use crate::command::Renames;
+use crate::protocol::ProtoLimits;
+
+/// Default number of bytes a worker reads from one connection per turn
+pub const DEFAULT_READ_BUDGET: usize = 64 * 1024;
+/// Default (and minimum) size of a single socket read
+pub const DEFAULT_READ_BUF_SIZE: usize = 4096;
+/// Default ceiling a busy connection's read size can grow to
+pub const DEFAULT_READ_BUF_MAX: usize = 256 * 1024;
+/// Default unsent reply bytes at which a connection stops being read
+pub const DEFAULT_WRITE_BUF_HIGH_WATER: usize = 8 * 1024 * 1024;
+/// Default unsent reply bytes a throttled connection is read again at
+pub const DEFAULT_WRITE_BUF_LOW_WATER: usize = 1024 * 1024;
+/// Default keys sampled per LRU eviction (Redis' `maxmemory-samples`)
+pub const DEFAULT_MAXMEMORY_SAMPLES: usize = 5;
+
+/// When replies produced by a connection's requests are written out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FlushStrategy {
+    /// Write right after executing the connection's pipeline
+    #[default]
+    Immediate,
+    /// Defer writes until every ready connection has been processed, so
+    /// tiny replies from several reads go out in one syscall
+    Coalesced,
+}
+
+impl std::str::FromStr for FlushStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "immediate" => Ok(Self::Immediate),
+            "coalesced" => Ok(Self::Coalesced),
+            _ => anyhow::bail!("invalid flush-strategy '{}' (expected immediate or coalesced)", s),
+        }
+    }
+}
+
+/// What happens when a write would need memory beyond `maxmemory`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MaxmemoryPolicy {
+    /// Refuse commands that may grow the dataset with `-OOM`
+    #[default]
+    NoEviction,
+    /// Evict the least recently used keys (approximated by sampling)
+    AllKeysLru,
+}
+
+impl MaxmemoryPolicy {
+    /// The policy's name as Redis spells it
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::NoEviction => "noeviction",
+            Self::AllKeysLru => "allkeys-lru",
+        }
+    }
+}
+
+impl std::str::FromStr for MaxmemoryPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "noeviction" => Ok(Self::NoEviction),
+            "allkeys-lru" => Ok(Self::AllKeysLru),
+            _ => anyhow::bail!("invalid maxmemory-policy '{}' (expected noeviction or allkeys-lru)", s),
+        }
+    }
+}
+
+/// Server configuration
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Limits enforced while parsing client requests
+    pub proto: ProtoLimits,
+    /// Bytes read from a single connection before the worker moves on to
+    /// other ready connections (fairness under bulk uploads)
+    pub read_budget: usize,
+    /// `flush-strategy`: when replies are written to the socket
+    pub flush_strategy: FlushStrategy,
+    /// Initial and minimum size of one socket read
+    pub read_buf_size: usize,
+    /// Largest size one socket read grows to for connections that keep
+    /// filling their buffer (equal to `read_buf_size` disables growth)
+    pub read_buf_max: usize,
+    /// `rename-command` entries applied when requests are dispatched
+    pub renamed_commands: Renames,
+    /// `lazyfree-lazy-user-del`: DEL frees large values in the background
+    /// like UNLINK
+    pub lazyfree_lazy_user_del: bool,
+    /// `lazyfree-lazy-server-del`: values replaced by SET, GETSET or MSET
+    /// are freed in the background when large
+    pub lazyfree_lazy_server_del: bool,
+    /// `maxmemory`: key and value bytes the dataset may hold before
+    /// `maxmemory_policy` applies (0 disables the limit)
+    pub maxmemory: usize,
+    /// `maxmemory-policy`: what to do once the dataset reaches
+    /// `maxmemory`
+    pub maxmemory_policy: MaxmemoryPolicy,
+    /// `maxmemory-samples`: keys sampled to pick each LRU victim; more
+    /// is closer to true LRU and slower
+    pub maxmemory_samples: usize,
+    /// `maxmemory-clients`: bytes all connections may buffer together
+    /// before the largest one is disconnected (0 disables the limit)
+    pub maxmemory_clients: usize,
+    /// `tcp-fastopen`: TCP Fast Open queue length on the listener
+    /// (0 disables; Linux only)
+    pub tcp_fastopen: u32,
+    /// `tcp-defer-accept`: seconds the kernel holds a new connection until
+    /// its first data arrives (0 disables; Linux only)
+    pub tcp_defer_accept: u32,
+    /// Expected number of keys: the keyspace is allocated for this many
+    /// up front so loading them doesn't trigger resizes (0 starts empty)
+    pub expected_keys: usize,
+    /// Network worker threads of the mio backend (0 starts one per CPU
+    /// core as reported by `available_parallelism`)
+    pub worker_threads: usize,
+    /// Shards the keyspace is split into (see [`crate::ShardSet`]); 1 keeps
+    /// a single shard
+    pub shards: usize,
+    /// `owned-shards`: the mio backend starts one worker per shard, each
+    /// the only one running commands on the keys of its shard; commands
+    /// are forwarded to the owning worker (`worker_threads` is ignored)
+    pub owned_shards: bool,
+    /// Log denied and slow commands as JSON lines under the
+    /// `ignix::access` log target
+    pub access_log: bool,
+    /// Microseconds a command may run before the access log flags it as
+    /// slow (0 logs denied commands only)
+    pub access_log_slower_than: u64,
+    /// `enable-debug-panic`: DEBUG PANIC panics on purpose to exercise
+    /// panic isolation; off, it is refused with an error
+    pub enable_debug_panic: bool,
+    /// Values of at least this many bytes go to the shard's blob store
+    /// when one is attached (`blob-store` feature; 0 disables)
+    pub blob_threshold: usize,
+    /// `auto-aof-rewrite-percentage`: rewrite the AOF once it has grown by
+    /// this percentage over its size after the last rewrite (0 disables
+    /// automatic rewrites)
+    pub aof_rewrite_percentage: u64,
+    /// `auto-aof-rewrite-min-size`: smallest AOF an automatic rewrite
+    /// considers
+    pub aof_rewrite_min_size: u64,
+    /// `tls-cert-file`: PEM certificate chain; with `tls_key_file` set
+    /// too, clients must connect over TLS (`tls` feature)
+    pub tls_cert_file: Option<String>,
+    /// `tls-key-file`: PEM private key of the certificate
+    pub tls_key_file: Option<String>,
+    /// `unixsocket`: also accept clients on a Unix domain socket at this
+    /// path (mio backend)
+    pub unixsocket: Option<String>,
+    /// Unsent reply bytes at which the mio backend stops reading a
+    /// connection's requests until the client catches up (0 disables)
+    pub write_buf_high_water: usize,
+    /// Unsent reply bytes a throttled connection must drain to before it
+    /// is read again
+    pub write_buf_low_water: usize,
+    /// Unsent reply bytes at which the connection is closed with a
+    /// warning (0 disables, like Redis' `client-output-buffer-limit
+    /// normal 0 0 0`)
+    pub write_buf_hard_limit: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            proto: ProtoLimits::default(),
+            read_budget: DEFAULT_READ_BUDGET,
+            flush_strategy: FlushStrategy::default(),
+            read_buf_size: DEFAULT_READ_BUF_SIZE,
+            read_buf_max: DEFAULT_READ_BUF_MAX,
+            renamed_commands: Renames::new(),
+            lazyfree_lazy_user_del: false,
+            lazyfree_lazy_server_del: false,
+            maxmemory: 0,
+            maxmemory_policy: MaxmemoryPolicy::default(),
+            maxmemory_samples: DEFAULT_MAXMEMORY_SAMPLES,
+            maxmemory_clients: 0,
+            tcp_fastopen: 0,
+            tcp_defer_accept: 0,
+            expected_keys: 0,
+            worker_threads: 0,
+            shards: 1,
+            owned_shards: false,
+            access_log: false,
+            access_log_slower_than: 0,
+            enable_debug_panic: false,
+            blob_threshold: 0,
+            aof_rewrite_percentage: 100,
+            aof_rewrite_min_size: 64 * 1024 * 1024,
+            tls_cert_file: None,
+            tls_key_file: None,
+            unixsocket: None,
+            write_buf_high_water: DEFAULT_WRITE_BUF_HIGH_WATER,
+            write_buf_low_water: DEFAULT_WRITE_BUF_LOW_WATER,
+            write_buf_hard_limit: 0,
+        }
+    }
+}
+
+impl Config {
+    /// Apply a `rename-command <name> <new-name>` directive
+    /// 
+    /// An empty `new_name` disables the command. Renames must be applied
+    /// before the configuration is handed to a shard.
+    pub fn rename_command(&mut self, name: &str, new_name: &str) -> anyhow::Result<()> {
+        self.renamed_commands.rename(name, new_name)
+    }
+}
@@ -0,0 +1,399 @@
+/*!
+ * Connection Handling
+ *
+ * Backend-independent per-connection state and request processing. Both
+ * the mio event loop and the io_uring backend own a `Conn` per client and
+ * only differ in how bytes move between the socket and its buffers, so
+ * parsing, error replies, MONITOR and command execution behave identically
+ * whichever backend serves the connection.
+ */
+
+use crate::access_log;
+use crate::error::CmdError;
+use crate::pool::BufPool;
+use crate::router::{Message, Route};
+use crate::protocol::{parse_many_framed, resp_to_plain, write_bulk, write_error, write_simple, Cmd};
+use crate::shard::Shard;
+use crate::shard_set::ShardSet;
+use crate::stats::ClientStats;
+use bytes::{Bytes, BytesMut};
+use crossbeam::channel::Receiver;
+use mio::Waker;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Adaptive size of a connection's socket reads
+///
+/// A read that fills the whole buffer suggests more data is waiting (e.g. a
+/// large value being uploaded), so the next read doubles in size, up to
+/// `max`. Reads using less than a quarter of the buffer halve it again,
+/// down to `min`, so idle connections don't keep large reads.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ReadSizer {
+    size: usize,
+    min: usize,
+    max: usize,
+}
+
+impl ReadSizer {
+    /// Start at `min`, growing up to `max`
+    pub fn new(min: usize, max: usize) -> Self {
+        let min = min.max(1);
+        Self { size: min, min, max: max.max(min) }
+    }
+
+    /// Number of bytes to request from the next read
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Adjust the size after a read returned `n` bytes
+    #[inline]
+    pub fn record(&mut self, n: usize) {
+        if n >= self.size {
+            self.size = (self.size * 2).min(self.max);
+        } else if n < self.size / 4 {
+            self.size = (self.size / 2).max(self.min);
+        }
+    }
+}
+
+/// A command sent to the worker owning its keys, while its reply is out
+struct Forwarded {
+    /// Whether it was sent inline
+    inline: bool,
+    /// Its name, for the access log
+    name: &'static str,
+    /// When it was sent, if the access log is on
+    start: Option<Instant>,
+}
+
+/// Per-connection state shared by the network backends
+pub(crate) struct Conn {
+    /// Peer address, rendered for the MONITOR feed
+    pub addr: String,
+    /// Bytes received but not yet parsed
+    pub rbuf: BytesMut,
+    /// Replies not yet written to the socket
+    pub wbuf: BytesMut,
+    /// Parsed commands; after a CLIENT PAUSE stopped execution, the ones
+    /// still waiting to run
+    cmds: Vec<Cmd>,
+    /// Whether each of `cmds` was sent inline
+    inline: Vec<bool>,
+    /// Set by DEBUG PROTOCOL PLAIN: answer inline commands in redis-cli
+    /// style instead of RESP
+    plain: bool,
+    /// Framing error seen while commands before it were still paused
+    broken: Option<String>,
+    /// Set while the next command is held back by CLIENT PAUSE: when to
+    /// call `process` again
+    pub paused_until: Option<Instant>,
+    /// Set while execution is held back because `wbuf` reached
+    /// `write_buf_high_water`: the rest of the pipeline runs on the next
+    /// `process` call
+    pub held_back: bool,
+    /// Owned-shard mode: how to reach the workers owning other shards
+    pub route: Option<Route>,
+    /// Set while a command forwarded to the worker owning its keys hasn't
+    /// replied; the rest of the pipeline waits for [`Conn::deliver`]
+    awaiting: Option<Forwarded>,
+    /// Set once a forwarded command's reply was queued: the rest of the
+    /// pipeline runs on the next `process` call
+    pub replied: bool,
+    /// Set once the connection has issued MONITOR
+    pub monitor: Option<Receiver<Bytes>>,
+    /// How much to read from the socket at a time
+    pub read_size: ReadSizer,
+    /// Gauges listed by CLIENT LIST
+    pub stats: Arc<ClientStats>,
+}
+
+impl Conn {
+    /// Create the state for a freshly accepted connection, registering
+    /// it with the shard's CLIENT LIST
+    pub fn new(addr: String, rbuf: BytesMut, wbuf: BytesMut, shard: &Shard) -> Self {
+        let config = &shard.config;
+        Self {
+            stats: shard.stats.register_client(addr.clone()),
+            addr,
+            rbuf,
+            wbuf,
+            cmds: Vec::with_capacity(32),
+            inline: Vec::with_capacity(32),
+            plain: false,
+            broken: None,
+            paused_until: None,
+            held_back: false,
+            route: None,
+            awaiting: None,
+            replied: false,
+            monitor: None,
+            read_size: ReadSizer::new(config.read_buf_size, config.read_buf_max),
+        }
+    }
+
+    /// Whether CLIENT NO-EVICT exempts the connection from eviction
+    #[inline]
+    pub fn no_evict(&self) -> bool {
+        self.stats.no_evict.load(Ordering::Relaxed)
+    }
+
+    /// Bytes waiting in the query and output buffers
+    #[inline]
+    pub fn buffered(&self) -> usize {
+        self.rbuf.len() + self.wbuf.len()
+    }
+
+    /// Parse and execute every complete request in `rbuf`, appending the
+    /// replies to `wbuf`
+    ///
+    /// # Arguments
+    /// * `shards` - Shards executing the commands; the primary supplies
+    ///   the configuration, CLIENT PAUSE and the MONITOR feed
+    /// * `waker` - Waker of the owning event loop, needed to deliver the
+    ///   MONITOR feed; without one MONITOR is answered with an error
+    ///
+    /// A command that panics while executing is answered with
+    /// `-ERR internal error` and ends the connection; the worker and its
+    /// other connections carry on.
+    ///
+    /// Execution stops at the first command held back by CLIENT PAUSE;
+    /// it and the rest of the pipeline stay queued, `paused_until` is set
+    /// and the caller must call `process` again once it has passed.
+    /// Likewise, once `wbuf` holds `write_buf_high_water` bytes the rest
+    /// waits, with `held_back` set, until the caller has written replies
+    /// out and calls `process` again. With a `route`, a command owned by
+    /// another worker is forwarded there and the rest waits for its reply
+    /// (see [`Conn::deliver`]).
+    ///
+    /// # Returns
+    /// * `false` after a framing error or a panicking command: the error
+    ///   reply has been queued and the connection must be closed once
+    ///   `wbuf` is flushed
+    pub fn process(&mut self, shards: &ShardSet, waker: Option<&Arc<Waker>>) -> bool {
+        let shard = shards.primary();
+        // New requests queue up behind any still paused; nothing after a
+        // framing error is parsed
+        if self.broken.is_none() {
+            self.stats.record_qbuf(self.rbuf.len(), self.rbuf.capacity());
+            if let Err(e) = parse_many_framed(&mut self.rbuf, &mut self.cmds, &mut self.inline, &shard.config) {
+                self.broken = Some(format!("ERR {}", e));
+            }
+            self.stats.record_qbuf(self.rbuf.len(), self.rbuf.capacity());
+        }
+        if self.awaiting.is_some() {
+            return true;
+        }
+        self.paused_until = None;
+        self.held_back = false;
+        self.replied = false;
+        let high_water = shard.config.write_buf_high_water;
+        // Commands framed before an error still run
+        let mut done = 0;
+        while done < self.cmds.len() {
+            if high_water > 0 && self.wbuf.len() >= high_water {
+                self.held_back = true;
+                break;
+            }
+            if let Some(until) = shard.pause.blocks(&self.cmds[done]) {
+                self.paused_until = Some(until);
+                break;
+            }
+            // Ping stands in for the taken command until the drain below
+            let cmd = std::mem::replace(&mut self.cmds[done], Cmd::Ping);
+            let inline = self.inline[done];
+            done += 1;
+            if let Some(owner) = self.remote_owner(&cmd, shards) {
+                if self.forward(owner, cmd, inline, shards) {
+                    break;
+                }
+                // The owning worker is gone
+                CmdError::Internal.write(&mut self.wbuf);
+                self.cmds.clear();
+                self.inline.clear();
+                return false;
+            }
+            let mark = self.wbuf.len();
+            let ok = self.run(cmd, shards, waker);
+            if self.plain && inline {
+                self.make_plain(mark);
+            }
+            if !ok {
+                // The rest of the pipeline is discarded
+                self.cmds.clear();
+                self.inline.clear();
+                return false;
+            }
+        }
+        self.cmds.drain(..done);
+        self.inline.drain(..done);
+        if self.paused_until.is_some() || self.held_back || self.awaiting.is_some() {
+            return true;
+        }
+        if let Some(e) = self.broken.take() {
+            // The stream can't be resynchronized after a framing error
+            write_error(&e, &mut self.wbuf);
+            return false;
+        }
+        true
+    }
+
+    /// Queue a forwarded command's reply and let the rest of the pipeline
+    /// run on the next `process` call
+    ///
+    /// # Arguments
+    /// * `reply` - The reply, `None` if the command panicked
+    /// * `shards` - Shards of the server, for the access log settings
+    ///
+    /// # Returns
+    /// * `false` if the command panicked: `-ERR internal error` has been
+    ///   queued and the connection must be closed once `wbuf` is flushed
+    pub fn deliver(&mut self, reply: Option<BytesMut>, shards: &ShardSet) -> bool {
+        let Some(fwd) = self.awaiting.take() else { return true };
+        let Some(reply) = reply else {
+            CmdError::Internal.write(&mut self.wbuf);
+            self.cmds.clear();
+            self.inline.clear();
+            return false;
+        };
+        let mark = self.wbuf.len();
+        self.wbuf.extend_from_slice(&reply);
+        if let Some(start) = fwd.start {
+            access_log::record(&shards.primary().config, &self.addr, fwd.name, &self.wbuf[mark..], start.elapsed());
+        }
+        if self.plain && fwd.inline {
+            self.make_plain(mark);
+        }
+        self.replied = true;
+        true
+    }
+
+    /// The worker owning a command's keys, when that isn't this
+    /// connection's worker
+    fn remote_owner(&self, cmd: &Cmd, shards: &ShardSet) -> Option<usize> {
+        let route = self.route.as_ref()?;
+        shards.owner(cmd).filter(|&owner| owner != route.worker)
+    }
+
+    /// Send a command to the worker owning its keys
+    ///
+    /// # Returns
+    /// * `false` if that worker is gone
+    fn forward(&mut self, owner: usize, cmd: Cmd, inline: bool, shards: &ShardSet) -> bool {
+        let Some(route) = &self.route else { return false };
+        self.feed_monitors(&cmd, shards);
+        let name = cmd.name();
+        let start = shards.primary().config.access_log.then(Instant::now);
+        if !route.router.send(owner, Message::Exec { cmd, from: route.worker, token: route.token }) {
+            return false;
+        }
+        self.awaiting = Some(Forwarded { inline, name, start });
+        true
+    }
+
+    /// Show a command about to run to the MONITOR feed
+    fn feed_monitors(&self, cmd: &Cmd, shards: &ShardSet) {
+        let shard = shards.primary();
+        // Only commands that get to run are shown: unknown and malformed
+        // ones arrive as `Invalid`, paused ones aren't run yet, and those
+        // refused up front (CROSSSLOT, MISCONF, OOM) are checked here
+        if shard.monitors.is_active() && shards.admits(cmd) {
+            shard.monitors.feed(cmd, &self.addr);
+        }
+    }
+
+    /// Rewrite the RESP reply written from `mark` on for telnet users
+    fn make_plain(&mut self, mark: usize) {
+        if let Some(text) = resp_to_plain(&self.wbuf[mark..]) {
+            self.wbuf.truncate(mark);
+            self.wbuf.extend_from_slice(text.as_bytes());
+        }
+    }
+
+    /// Execute one command, handling the connection-level ones here
+    ///
+    /// # Returns
+    /// * `false` if the command panicked; `-ERR internal error` replaces
+    ///   whatever part of its reply was written
+    fn run(&mut self, cmd: Cmd, shards: &ShardSet, waker: Option<&Arc<Waker>>) -> bool {
+        let shard = shards.primary();
+        self.feed_monitors(&cmd, shards);
+        match (cmd, waker) {
+            (Cmd::Monitor, Some(waker)) => {
+                // Flip the connection into monitor mode
+                if self.monitor.is_none() {
+                    self.monitor = Some(shard.monitors.subscribe(waker.clone()));
+                }
+                write_simple("OK", &mut self.wbuf);
+            }
+            (Cmd::ClientNoEvict(on), _) => {
+                self.stats.no_evict.store(on, Ordering::Relaxed);
+                write_simple("OK", &mut self.wbuf);
+            }
+            (Cmd::ClientInfo, _) => {
+                write_bulk(format!("{}\n", self.stats.render()).as_bytes(), &mut self.wbuf);
+            }
+            (Cmd::DebugPlainReplies(plain), _) => {
+                self.plain = plain;
+                write_simple("OK", &mut self.wbuf);
+            }
+            (cmd, _) => {
+                let mark = self.wbuf.len();
+                let name = cmd.name();
+                // Only timed when the access log is on
+                let start = shard.config.access_log.then(Instant::now);
+                let wbuf = &mut self.wbuf;
+                if catch_unwind(AssertUnwindSafe(|| shards.exec(cmd, wbuf))).is_err() {
+                    // Drop whatever part of the reply was written before the panic
+                    self.wbuf.truncate(mark);
+                    CmdError::Internal.write(&mut self.wbuf);
+                    return false;
+                }
+                if let Some(start) = start {
+                    access_log::record(&shard.config, &self.addr, name, &self.wbuf[mark..], start.elapsed());
+                }
+            }
+        }
+        true
+    }
+
+    /// Queue monitor lines delivered since the last call
+    pub fn drain_monitor(&mut self) {
+        let Some(rx) = &self.monitor else { return };
+        while let Ok(line) = rx.try_recv() {
+            self.wbuf.extend_from_slice(&line);
+        }
+    }
+
+    /// Hand the connection's buffers back to a pool
+    pub fn recycle(self, pool: &mut BufPool) {
+        pool.release(self.rbuf);
+        pool.release(self.wbuf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_size_grows_while_reads_fill_the_buffer() {
+        let mut r = ReadSizer::new(4096, 32 * 1024);
+        for expected in [8192, 16384, 32768, 32768] {
+            r.record(r.size());
+            assert_eq!(r.size(), expected);
+        }
+
+        // Moderately full reads keep the size, sparse reads shrink it back
+        r.record(r.size() / 2);
+        assert_eq!(r.size(), 32768);
+        for expected in [16384, 8192, 4096, 4096] {
+            r.record(10);
+            assert_eq!(r.size(), expected);
+        }
+    }
+}
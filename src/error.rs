@@ -0,0 +1,120 @@
+/*!
+ * Command Errors
+ *
+ * The error replies of command parsing and execution, in one place so
+ * their wording stays Redis-compatible. Each variant renders as the
+ * message of a RESP error (`-PREFIX message\r\n`), where the first word
+ * is the error code clients switch on (`ERR`, `WRONGTYPE`, `OOM`, ...).
+ *
+ * `CmdError` implements `std::error::Error`, so parsers can `bail!` with
+ * a variant and the message still ends up in `Cmd::Invalid`.
+ */
+
+use crate::protocol::write_error;
+use bytes::BytesMut;
+use std::fmt;
+
+/// An error reply of a command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CmdError {
+    /// The key holds a value of another type
+    WrongType,
+    /// The command needs an existing key
+    NoSuchKey,
+    /// The value or argument isn't a 64-bit signed integer
+    NotInteger,
+    /// Unknown or misplaced option
+    Syntax,
+    /// A numeric argument is outside the accepted range
+    OutOfRange,
+    /// INCR and friends would overflow an i64
+    Overflow,
+    /// The command would exceed `maxmemory`
+    Oom,
+    /// The resulting string would exceed `proto-max-bulk-len`
+    StringTooLong,
+    /// SETBIT/GETBIT offset is malformed or too large
+    BitOffset,
+    /// SETBIT value isn't 0 or 1
+    BitValue,
+    /// CLIENT PAUSE timeout isn't a non-negative integer
+    Timeout,
+    /// Writes are refused while the AOF is failing
+    Misconf,
+    /// The command panicked while executing
+    Internal,
+    /// BGREWRITEAOF on a server running without an AOF
+    AofDisabled,
+    /// BGREWRITEAOF while a rewrite is already running
+    RewriteInProgress,
+    /// The keys of a multi-key command live on different shards
+    CrossSlot,
+    /// SCAN cursor isn't an unsigned 64-bit integer
+    InvalidCursor,
+    /// LCS was given both LEN and IDX
+    LcsLenAndIdx,
+    /// The LCS table of two strings would exceed `proto-max-bulk-len`
+    LcsTooLarge,
+    /// The expire time of the named (lowercase) command overflows
+    InvalidExpire(String),
+    /// Wrong argument count for the named (lowercase) command
+    WrongArity(String),
+    /// The command can't run on this kind of connection
+    Unsupported(&'static str),
+    /// DEBUG PANIC without `Config::enable_debug_panic`
+    DebugPanicDisabled,
+    /// DEBUG ENCODING found another encoding than expected
+    EncodingMismatch { expected: String, found: &'static str },
+}
+
+impl fmt::Display for CmdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WrongType => f.write_str("WRONGTYPE Operation against a key holding the wrong kind of value"),
+            Self::NoSuchKey => f.write_str("ERR no such key"),
+            Self::NotInteger => f.write_str("ERR value is not an integer or out of range"),
+            Self::Syntax => f.write_str("ERR syntax error"),
+            Self::OutOfRange => f.write_str("ERR value is out of range"),
+            Self::Overflow => f.write_str("ERR increment or decrement would overflow"),
+            Self::Oom => f.write_str("OOM command not allowed when used memory > 'maxmemory'."),
+            Self::StringTooLong => f.write_str("ERR string exceeds maximum allowed size (proto-max-bulk-len)"),
+            Self::BitOffset => f.write_str("ERR bit offset is not an integer or out of range"),
+            Self::BitValue => f.write_str("ERR bit is not an integer or out of range"),
+            Self::Timeout => f.write_str("ERR timeout is not an integer or out of range"),
+            Self::Misconf => f.write_str("MISCONF Errors writing to the AOF file"),
+            Self::Internal => f.write_str("ERR internal error"),
+            Self::AofDisabled => f.write_str("ERR Append only file is disabled"),
+            Self::RewriteInProgress => f.write_str("ERR Background append only file rewriting already in progress"),
+            Self::CrossSlot => f.write_str("CROSSSLOT Keys in request don't hash to the same slot"),
+            Self::InvalidCursor => f.write_str("ERR invalid cursor"),
+            Self::LcsLenAndIdx => f.write_str("ERR If you want both the length and indexes, please just use IDX."),
+            Self::LcsTooLarge => {
+                f.write_str("ERR Insufficient memory, transient memory for LCS exceeds proto-max-bulk-len")
+            }
+            Self::InvalidExpire(cmd) => write!(f, "ERR invalid expire time in '{}' command", cmd),
+            Self::WrongArity(cmd) => write!(f, "ERR wrong number of arguments for '{}' command", cmd),
+            Self::Unsupported(cmd) => write!(f, "ERR {} is not supported on this connection", cmd),
+            Self::DebugPanicDisabled => f.write_str("ERR DEBUG PANIC is disabled (enable-debug-panic)"),
+            Self::EncodingMismatch { expected, found } => {
+                write!(f, "ERR encoding mismatch: expected '{}', found '{}'", expected, found)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CmdError {}
+
+impl CmdError {
+    /// The complete RESP error reply, e.g. `-ERR syntax error\r\n`
+    pub fn to_resp(&self) -> Vec<u8> {
+        let mut out = BytesMut::new();
+        self.write(&mut out);
+        out.to_vec()
+    }
+
+    /// Append the RESP error reply to `out`
+    #[inline]
+    pub fn write(&self, out: &mut BytesMut) {
+        write_error(&self.to_string(), out);
+    }
+}
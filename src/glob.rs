@@ -0,0 +1,88 @@
+/*!
+ * Glob Patterns
+ *
+ * Redis-style glob matching for KEYS and the MATCH option of SCAN: `*`
+ * matches any run of bytes, `?` any single byte, `[abc]` and `[a-z]` a
+ * byte from a set (`[^...]` one outside it), and `\` makes the next byte
+ * literal.
+ *
+ * Keys are binary, so patterns and keys are matched byte by byte, never
+ * as UTF-8 text.
+ */
+
+/// Check whether `key` matches the glob `pattern`
+///
+/// Follows Redis' `stringmatchlen`: an unclosed `[` class runs to the end
+/// of the pattern, a reversed range (`[z-a]`) matches like the ordered
+/// one, and a trailing `\` matches a backslash.
+pub fn glob_match(pattern: &[u8], key: &[u8]) -> bool {
+    let (mut p, mut k) = (0, 0);
+    // Pattern position after the last `*` seen, and the key position
+    // where the bytes it stands for end so far
+    let mut star: Option<(usize, usize)> = None;
+    while k < key.len() {
+        if pattern.get(p) == Some(&b'*') {
+            p += 1;
+            star = Some((p, k));
+            continue;
+        }
+        let next = if p < pattern.len() { match_byte(pattern, p, key[k]) } else { None };
+        if let Some(next) = next {
+            p = next;
+            k += 1;
+            continue;
+        }
+        // Mismatch: let the last `*` swallow one more byte and retry
+        let Some((after_star, swallowed)) = star else { return false };
+        p = after_star;
+        k = swallowed + 1;
+        star = Some((after_star, k));
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Match one key byte against the pattern token (not `*`) at `p`
+///
+/// # Returns
+/// * `Some(next)` - The byte matches; `next` is the position after the token
+/// * `None` - It doesn't
+fn match_byte(pattern: &[u8], p: usize, c: u8) -> Option<usize> {
+    match pattern[p] {
+        b'?' => Some(p + 1),
+        b'[' => match_class(pattern, p + 1, c),
+        b'\\' if p + 1 < pattern.len() => (pattern[p + 1] == c).then_some(p + 2),
+        literal => (literal == c).then_some(p + 1),
+    }
+}
+
+/// Match one key byte against the `[...]` class whose body starts at `p`
+fn match_class(pattern: &[u8], mut p: usize, c: u8) -> Option<usize> {
+    let negate = pattern.get(p) == Some(&b'^');
+    if negate {
+        p += 1;
+    }
+    let mut matched = false;
+    while p < pattern.len() {
+        match pattern[p] {
+            b']' => {
+                p += 1;
+                break;
+            }
+            b'\\' if p + 1 < pattern.len() => {
+                matched |= pattern[p + 1] == c;
+                p += 2;
+            }
+            lo if p + 2 < pattern.len() && pattern[p + 1] == b'-' => {
+                let hi = pattern[p + 2];
+                let (lo, hi) = if lo <= hi { (lo, hi) } else { (hi, lo) };
+                matched |= (lo..=hi).contains(&c);
+                p += 3;
+            }
+            b => {
+                matched |= b == c;
+                p += 1;
+            }
+        }
+    }
+    (matched != negate).then_some(p)
+}
@@ -0,0 +1,99 @@
+/*!
+ * Lazy Freeing
+ *
+ * Dropping a multi-megabyte value releases its allocation on the thread
+ * that drops it, stalling every connection served by that worker. UNLINK,
+ * and DEL or overwrites when the `lazyfree-lazy-*` settings are on, hand
+ * large values to a background thread instead, so the reply goes out
+ * without waiting for the free.
+ */
+
+use crate::protocol::Value;
+use crossbeam::channel::{unbounded, Sender};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+
+/// Values smaller than this are cheaper to free in place than to ship
+/// to the background thread
+pub const LAZYFREE_THRESHOLD: usize = 64 * 1024;
+
+/// Counters shared with the background thread
+#[derive(Debug, Default)]
+struct Counters {
+    /// Values queued but not yet freed
+    pending: AtomicUsize,
+    /// Values freed by the background thread since startup
+    freed: AtomicU64,
+}
+
+/// Handle to the background free thread
+///
+/// The thread is started on the first large free, so shards that never
+/// unlink anything big don't pay for it. It exits once the handle is
+/// dropped and its queue has drained.
+#[derive(Debug, Default)]
+pub struct LazyFree {
+    tx: OnceLock<Option<Sender<Value>>>,
+    counters: Arc<Counters>,
+}
+
+impl LazyFree {
+    /// Release a value, in the background if it is large
+    ///
+    /// # Returns
+    /// * `true` if the value was queued for the background thread
+    /// * `false` if it was small enough to be dropped right away (or the
+    ///   thread couldn't be started)
+    pub fn free(&self, v: Value) -> bool {
+        if value_size(&v) < LAZYFREE_THRESHOLD {
+            return false;
+        }
+        let Some(tx) = self.sender() else { return false };
+        self.counters.pending.fetch_add(1, Ordering::Relaxed);
+        if tx.send(v).is_err() {
+            self.counters.pending.fetch_sub(1, Ordering::Relaxed);
+            return false;
+        }
+        true
+    }
+
+    /// Values waiting to be freed (`lazyfree_pending_objects`)
+    pub fn pending(&self) -> usize {
+        self.counters.pending.load(Ordering::Relaxed)
+    }
+
+    /// Values freed in the background so far (`lazyfreed_objects`)
+    pub fn freed(&self) -> u64 {
+        self.counters.freed.load(Ordering::Relaxed)
+    }
+
+    /// Start the background thread on first use
+    fn sender(&self) -> Option<&Sender<Value>> {
+        self.tx
+            .get_or_init(|| {
+                let (tx, rx) = unbounded::<Value>();
+                let counters = self.counters.clone();
+                std::thread::Builder::new()
+                    .name("ignix-lazyfree".into())
+                    .spawn(move || {
+                        for v in rx {
+                            drop(v);
+                            counters.pending.fetch_sub(1, Ordering::Relaxed);
+                            counters.freed.fetch_add(1, Ordering::Relaxed);
+                        }
+                    })
+                    .ok()
+                    .map(|_| tx)
+            })
+            .as_ref()
+    }
+}
+
+/// Bytes owned by a value's payload
+#[inline]
+fn value_size(v: &Value) -> usize {
+    match v {
+        Value::Int(_) => 0,
+        Value::Str(s) | Value::Blob(s) => s.len(),
+    }
+}
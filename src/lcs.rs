@@ -0,0 +1,97 @@
+/*!
+ * Longest Common Subsequence
+ *
+ * The algorithm behind `LCS key1 key2`: the classic dynamic-programming
+ * table over two byte strings, walked back from the end to recover the
+ * subsequence and the ranges where the strings match contiguously.
+ *
+ * The table holds `(a.len() + 1) * (b.len() + 1)` u32 cells, so callers
+ * bound its size with [`lcs_table_size`] before running it.
+ */
+
+/// A contiguous run of the subsequence, as inclusive byte ranges
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LcsMatch {
+    /// Start and end offsets in the first string
+    pub a: (usize, usize),
+    /// Start and end offsets in the second string
+    pub b: (usize, usize),
+}
+
+impl LcsMatch {
+    /// Number of bytes in the run
+    pub fn len(&self) -> usize {
+        self.a.1 - self.a.0 + 1
+    }
+
+    /// Always false: a run holds at least one byte
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+/// Subsequence shared by two strings and where it occurs in them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Lcs {
+    /// The longest common subsequence
+    pub seq: Vec<u8>,
+    /// Contiguous runs, from the end of the strings to the start (the
+    /// order Redis replies with)
+    pub matches: Vec<LcsMatch>,
+}
+
+/// Bytes of transient memory [`lcs`] needs for two strings
+pub fn lcs_table_size(a_len: usize, b_len: usize) -> Option<usize> {
+    (a_len.checked_add(1)?).checked_mul(b_len.checked_add(1)?)?.checked_mul(std::mem::size_of::<u32>())
+}
+
+/// Compute the longest common subsequence of two strings
+///
+/// # Arguments
+/// * `a` - First string
+/// * `b` - Second string
+///
+/// # Returns
+/// * The subsequence and its contiguous runs
+pub fn lcs(a: &[u8], b: &[u8]) -> Lcs {
+    // cell (i, j) holds the LCS length of a[..i] and b[..j]
+    let width = b.len() + 1;
+    let mut table = vec![0u32; (a.len() + 1) * width];
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            table[i * width + j] = if a[i - 1] == b[j - 1] {
+                table[(i - 1) * width + j - 1] + 1
+            } else {
+                table[(i - 1) * width + j].max(table[i * width + j - 1])
+            };
+        }
+    }
+
+    let mut seq = Vec::with_capacity(table[a.len() * width + b.len()] as usize);
+    let mut matches: Vec<LcsMatch> = Vec::new();
+    // the run being extended backwards, if the last step was a match
+    let mut run: Option<LcsMatch> = None;
+    let (mut i, mut j) = (a.len(), b.len());
+    while i > 0 && j > 0 {
+        if a[i - 1] == b[j - 1] {
+            seq.push(a[i - 1]);
+            run = Some(match run {
+                Some(m) => LcsMatch { a: (i - 1, m.a.1), b: (j - 1, m.b.1) },
+                None => LcsMatch { a: (i - 1, i - 1), b: (j - 1, j - 1) },
+            });
+            i -= 1;
+            j -= 1;
+        } else {
+            matches.extend(run.take());
+            // Ties move along b, as Redis does, so both pick the same runs
+            if table[(i - 1) * width + j] > table[i * width + j - 1] {
+                i -= 1;
+            } else {
+                j -= 1;
+            }
+        }
+    }
+    matches.extend(run);
+    seq.reverse();
+    Lcs { seq, matches }
+}
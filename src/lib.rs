@@ -1,17 +1,53 @@
 // Core modules for Ignix key-value store
 pub mod net_uring;
+pub mod error; // CmdError (error replies of commands)
 pub mod protocol; // RESP parser + encoders + Cmd enum
+pub mod command; // Command table (arity, flags, key specs, parsers)
 pub mod storage; // Dict + Value types for in-memory storage
 pub mod aof; // AOF writer + emit helpers for persistence
 pub mod shard; // Shard::exec (command execution logic)
+pub mod shard_set; // ShardSet (commands routed to the shard owning their keys)
 pub mod net; // bind_reuseport + run_shard (server loop)
+pub mod monitor; // Monitors (MONITOR broadcast feed)
+mod conn; // Conn (per-connection state shared by both backends)
+mod router; // Router (commands forwarded to the worker owning their keys)
+pub mod pool; // BufPool (reusable connection buffers)
+pub mod config; // Config (runtime settings)
+pub mod stats; // Stats (INFO counters)
+pub mod lazyfree; // LazyFree (background freeing of large values)
+pub mod replay; // replay_commands (execute a recorded RESP stream)
+pub mod pause; // ClientPause (CLIENT PAUSE deadline)
+pub mod lcs; // lcs (longest common subsequence for LCS)
+pub mod glob; // glob_match (Redis-style patterns for KEYS and SCAN MATCH)
+pub mod access_log; // record (JSON log of denied and slow commands)
+pub mod cli; // parse_args (command line of the ignix binary)
+#[cfg(all(feature = "blob-store", target_os = "linux"))]
+pub mod blob_store; // BlobStore (large values in a memory-mapped file)
+#[cfg(feature = "tls")]
+pub mod tls; // TlsStream (rustls sessions over the mio sockets)
 
 // Re-export all public items from modules for easier access
 pub use protocol::*;
+pub use error::*;
 pub use storage::*;
 pub use aof::*;
 pub use shard::*;
+pub use shard_set::*;
 pub use net::*;
+pub use monitor::*;
+pub use config::*;
+pub use stats::*;
+pub use lazyfree::*;
+pub use replay::*;
+pub use pause::*;
+pub use lcs::*;
+pub use glob::*;
+pub use access_log::*;
+pub use cli::*;
+#[cfg(all(feature = "blob-store", target_os = "linux"))]
+pub use blob_store::*;
+#[cfg(feature = "tls")]
+pub use tls::*;
 
 // Default server address - Redis-compatible port 7379
 pub const DEFAULT_ADDR: &str = "0.0.0.0:7379";
\ No newline at end of file
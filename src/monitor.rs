@@ -0,0 +1,125 @@
+/*!
+ * MONITOR Feed
+ *
+ * This module implements the broadcast side of the Redis `MONITOR` command.
+ * Connections in monitor mode register a channel plus the `mio::Waker` of
+ * the worker that owns them; every executed command is formatted once and
+ * pushed to all registered channels, waking the owning workers so they can
+ * flush the lines to their sockets.
+ */
+
+use crate::protocol::Cmd;
+use bytes::Bytes;
+use crossbeam::channel::{unbounded, Receiver, Sender};
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A single subscribed monitor connection
+struct Subscriber {
+    /// Channel feeding formatted lines to the monitor connection
+    tx: Sender<Bytes>,
+    /// Waker of the worker event loop owning the connection
+    waker: Arc<mio::Waker>,
+}
+
+/// Registry of connections in monitor mode
+///
+/// Shared by all workers through the `Shard`. The subscriber count is kept
+/// in an atomic so the hot path can skip formatting entirely when nobody
+/// is monitoring.
+#[derive(Default)]
+pub struct Monitors {
+    subs: Mutex<Vec<Subscriber>>,
+    active: AtomicUsize,
+}
+
+impl Monitors {
+    /// Check whether at least one connection is in monitor mode
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed) > 0
+    }
+
+    /// Register a new monitor connection
+    ///
+    /// # Arguments
+    /// * `waker` - Waker of the worker that owns the connection
+    ///
+    /// # Returns
+    /// * Receiver yielding formatted monitor lines (RESP simple strings).
+    ///   Dropping it unsubscribes the connection on the next broadcast.
+    pub fn subscribe(&self, waker: Arc<mio::Waker>) -> Receiver<Bytes> {
+        let (tx, rx) = unbounded();
+        let mut subs = self.subs.lock();
+        subs.push(Subscriber { tx, waker });
+        self.active.store(subs.len(), Ordering::Relaxed);
+        rx
+    }
+
+    /// Broadcast an executed command to every monitor connection
+    ///
+    /// Invalid commands are left out, and so is MONITOR itself, as in
+    /// Redis.
+    ///
+    /// # Arguments
+    /// * `cmd` - Command about to be executed
+    /// * `client` - Address of the issuing client (e.g. `127.0.0.1:5000`)
+    pub fn feed(&self, cmd: &Cmd, client: &str) {
+        if !self.is_active() || matches!(cmd, Cmd::Invalid(_) | Cmd::Monitor) {
+            return;
+        }
+        let line = format_line(cmd, client);
+        let mut subs = self.subs.lock();
+        // Drop subscribers whose connection has gone away
+        subs.retain(|s| {
+            if s.tx.send(line.clone()).is_err() {
+                return false;
+            }
+            let _ = s.waker.wake();
+            true
+        });
+        self.active.store(subs.len(), Ordering::Relaxed);
+    }
+}
+
+/// Format a monitor line: `+<timestamp> [<db> <addr>] "CMD" "arg"...\r\n`
+fn format_line(cmd: &Cmd, client: &str) -> Bytes {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let mut line = format!(
+        "+{}.{:06} [0 {}] \"{}\"",
+        now.as_secs(),
+        now.subsec_micros(),
+        client,
+        cmd.name()
+    )
+    .into_bytes();
+    for arg in cmd.args() {
+        line.extend_from_slice(b" \"");
+        quote_into(&arg, &mut line);
+        line.push(b'"');
+    }
+    line.extend_from_slice(b"\r\n");
+    Bytes::from(line)
+}
+
+/// Escape an argument the way Redis does for MONITOR output
+///
+/// Quotes and backslashes are escaped, common control characters use
+/// their short form and any other non-printable byte becomes `\xHH`.
+fn quote_into(arg: &[u8], out: &mut Vec<u8>) {
+    for &b in arg {
+        match b {
+            b'"' => out.extend_from_slice(b"\\\""),
+            b'\\' => out.extend_from_slice(b"\\\\"),
+            b'\n' => out.extend_from_slice(b"\\n"),
+            b'\r' => out.extend_from_slice(b"\\r"),
+            b'\t' => out.extend_from_slice(b"\\t"),
+            0x20..=0x7e => out.push(b),
+            _ => out.extend_from_slice(format!("\\x{:02x}", b).as_bytes()),
+        }
+    }
+}
@@ -6,20 +6,27 @@
  * using mio for async I/O operations.
  */
 
-use crate::protocol::{parse_many, write_simple, Cmd};
+use crate::config::{Config, FlushStrategy};
+use crate::conn::Conn;
+use crate::pool::{BufPool, DEFAULT_MAX_POOLED_CAPACITY, DEFAULT_POOL_SIZE};
+use crate::router::{run_forwarded, Message, Route, Router};
 use crate::shard::Shard;
+use crate::shard_set::ShardSet;
+use crate::stats::WorkerStats;
 use anyhow::*;
-use bytes::BytesMut;
+use crossbeam::channel::Receiver;
 use hashbrown::HashMap;
 use mio::net::{TcpListener, TcpStream};
-use mio::{Events, Interest, Poll, Token};
+#[cfg(unix)]
+use mio::net::{UnixListener, UnixStream};
+use mio::event::Source;
+use mio::{Events, Interest, Poll, Registry, Token, Waker};
 use std::io::{Read, Write};
 use std::net::SocketAddr;
 use std::result::Result::{Ok, Err};
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
-
-/// Size of read buffer for incoming data
-const READ_BUF: usize = 4096;
+use std::time::{Duration, Instant};
 
 use socket2::{Socket, Domain, Type, Protocol};
 
@@ -28,6 +35,14 @@ use socket2::{Socket, Domain, Type, Protocol};
 /// Uses socket2 to set SO_REUSEPORT, allowing multiple threads to bind
 /// to the same port and share the incoming connection load (kernel load balancing).
 pub fn bind_reuseport(addr: SocketAddr) -> Result<TcpListener> {
+    bind_listener(addr, &Config::default())
+}
+
+/// Bind a SO_REUSEPORT listener with the socket options from `config`
+/// 
+/// `tcp_fastopen` and `tcp_defer_accept` are applied on Linux and
+/// ignored on other platforms.
+pub fn bind_listener(addr: SocketAddr, config: &Config) -> Result<TcpListener> {
     let domain = match addr {
         SocketAddr::V4(_) => Domain::IPV4,
         SocketAddr::V6(_) => Domain::IPV6,
@@ -42,29 +57,102 @@ pub fn bind_reuseport(addr: SocketAddr) -> Result<TcpListener> {
     }
     
     socket.set_nonblocking(true)?;
+    apply_listener_options(&socket, config)?;
     socket.bind(&addr.into())?;
     socket.listen(1024)?;
     
     Ok(TcpListener::from_std(socket.into()))
 }
 
+/// Set TCP_FASTOPEN and TCP_DEFER_ACCEPT as configured
+#[cfg(target_os = "linux")]
+fn apply_listener_options(socket: &Socket, config: &Config) -> Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let set = |name: libc::c_int, value: u32, what: &str| -> Result<()> {
+        let value = value.min(libc::c_int::MAX as u32) as libc::c_int;
+        // SAFETY: the fd is open for the lifetime of `socket` and `value`
+        // outlives the call
+        let rc = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::IPPROTO_TCP,
+                name,
+                &value as *const libc::c_int as *const libc::c_void,
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        if rc != 0 {
+            return Err(std::io::Error::last_os_error()).with_context(|| format!("setting {}", what));
+        }
+        Ok(())
+    };
+    if config.tcp_fastopen > 0 {
+        set(libc::TCP_FASTOPEN, config.tcp_fastopen, "TCP_FASTOPEN")?;
+    }
+    if config.tcp_defer_accept > 0 {
+        set(libc::TCP_DEFER_ACCEPT, config.tcp_defer_accept, "TCP_DEFER_ACCEPT")?;
+    }
+    Ok(())
+}
+
+/// Listener options are Linux-only; elsewhere they are ignored
+#[cfg(not(target_os = "linux"))]
+fn apply_listener_options(_socket: &Socket, _config: &Config) -> Result<()> {
+    Ok(())
+}
+
 /// Run the main server with Multi-Reactor architecture
 /// 
-/// Spawns one thread per CPU core. Each thread runs its own event loop
-/// and accepts connections on the shared port (via SO_REUSEPORT).
-pub fn run_shard(_shard_id: usize, addr: SocketAddr, shard: Shard) -> Result<()> {
-    let shard = Arc::new(shard);
-    let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+/// Spawns `config.worker_threads` threads, or one per CPU core when it
+/// is 0. Each thread runs its own event loop and accepts connections on
+/// the shared port (via SO_REUSEPORT). With `config.unixsocket` set, the
+/// first worker also accepts connections on that Unix domain socket; the
+/// socket file is removed again when the server stops.
+/// 
+/// Takes a single [`Shard`] or a [`ShardSet`]; every worker executes
+/// commands on the shard owning their keys. With `config.owned_shards`,
+/// one worker is started per shard and commands are forwarded to the
+/// worker owning their keys instead.
+pub fn run_shard(_shard_id: usize, addr: SocketAddr, shards: impl Into<ShardSet>) -> Result<()> {
+    let shards = Arc::new(shards.into());
+    let config = &shards.primary().config;
+    let threads = match config.owned_shards {
+        true => shards.shards().len(),
+        false => worker_threads(config),
+    };
+    let tls = tls_config(config)?;
+    let mut unix = match &config.unixsocket {
+        Some(path) => Some(UnixSocket::bind(path)?),
+        None => None,
+    };
     
+    // Every worker's poll exists before any starts, so the owned-shard
+    // router can wake each of them
+    let mut reactors = Vec::with_capacity(threads);
+    for _ in 0..threads {
+        let poll = Poll::new()?;
+        let waker = Arc::new(Waker::new(poll.registry(), WAKER)?);
+        reactors.push((poll, waker));
+    }
+    let inboxes: Vec<Option<Inbox>> = match config.owned_shards {
+        true => {
+            let (router, receivers) = Router::new(reactors.iter().map(|(_, waker)| waker.clone()).collect());
+            receivers.into_iter().map(|rx| Some(Inbox { router: router.clone(), rx })).collect()
+        }
+        false => (0..threads).map(|_| None).collect(),
+    };
+
     println!("🚀 Starting Ignix with {} worker threads (Multi-Reactor)", threads);
     
     let mut handles = Vec::new();
     
-    for id in 0..threads {
-        let shard = shard.clone();
-        let addr = addr;
+    for (id, (reactor, inbox)) in reactors.into_iter().zip(inboxes).enumerate() {
+        let shards = shards.clone();
+        let tls = tls.clone();
+        let unix = unix.as_mut().and_then(|u| u.take_listener());
         handles.push(std::thread::spawn(move || {
-            if let Err(e) = run_worker_loop(id, addr, shard) {
+            if let Err(e) = run_worker_loop(id, addr, shards, tls, unix, reactor, inbox) {
                 eprintln!("Worker {} failed: {}", id, e);
             }
         }));
@@ -78,46 +166,351 @@ pub fn run_shard(_shard_id: usize, addr: SocketAddr, shard: Shard) -> Result<()>
     Ok(())
 }
 
+/// Number of workers to start: the configured count, else one per core
+/// 
+/// Never 0: an unset count falls back to auto-detection, which reports
+/// at least one core.
+fn worker_threads(config: &Config) -> usize {
+    match config.worker_threads {
+        0 => std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4),
+        n => n,
+    }
+}
+
+/// A listening Unix domain socket and the file it is bound to
+#[cfg(unix)]
+struct UnixSocket {
+    path: std::path::PathBuf,
+    /// Handed to the worker that accepts on it
+    listener: Option<UnixListener>,
+}
+
+#[cfg(unix)]
+impl UnixSocket {
+    /// Bind the socket, replacing a stale socket file
+    /// 
+    /// A socket file nobody accepts on is left over from a server that
+    /// didn't shut down cleanly and is removed. A live server's socket,
+    /// or a file that isn't a socket, is an error.
+    fn bind(path: &str) -> Result<Self> {
+        use std::os::unix::fs::FileTypeExt;
+
+        if let Ok(meta) = std::fs::symlink_metadata(path) {
+            ensure!(meta.file_type().is_socket(), "unixsocket {} exists and is not a socket", path);
+            ensure!(
+                std::os::unix::net::UnixStream::connect(path).is_err(),
+                "unixsocket {} is in use by another server",
+                path
+            );
+            std::fs::remove_file(path).with_context(|| format!("removing stale unixsocket {}", path))?;
+        }
+        let listener = UnixListener::bind(path).with_context(|| format!("binding unixsocket {}", path))?;
+        Ok(Self { path: path.into(), listener: Some(listener) })
+    }
+
+    /// The listener, for the first caller only
+    fn take_listener(&mut self) -> Option<UnixListener> {
+        self.listener.take()
+    }
+}
+
+#[cfg(unix)]
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Unix domain sockets need a Unix platform
+#[cfg(not(unix))]
+struct UnixSocket;
+
+#[cfg(not(unix))]
+impl UnixSocket {
+    fn bind(_path: &str) -> Result<Self> {
+        bail!("unixsocket is only supported on Unix platforms")
+    }
+
+    fn take_listener(&mut self) -> Option<UnixListener> {
+        None
+    }
+}
+
+/// Placeholder listener type where Unix sockets don't exist
+#[cfg(not(unix))]
+type UnixListener = std::convert::Infallible;
+
+/// Server-side TLS settings shared by every worker
+#[cfg(feature = "tls")]
+type TlsConfig = Arc<rustls::ServerConfig>;
+/// Without the `tls` feature there are none
+#[cfg(not(feature = "tls"))]
+#[derive(Clone)]
+enum TlsConfig {}
+
+/// Load the TLS settings when `tls_cert_file` and `tls_key_file` are set
+#[cfg(feature = "tls")]
+fn tls_config(config: &Config) -> Result<Option<TlsConfig>> {
+    match (&config.tls_cert_file, &config.tls_key_file) {
+        (Some(cert), Some(key)) => crate::tls::server_config(cert, key).map(Some),
+        (None, None) => Ok(None),
+        _ => bail!("tls_cert_file and tls_key_file must be set together"),
+    }
+}
+
+/// Refuse a TLS configuration the build can't serve
+#[cfg(not(feature = "tls"))]
+fn tls_config(config: &Config) -> Result<Option<TlsConfig>> {
+    ensure!(
+        config.tls_cert_file.is_none() && config.tls_key_file.is_none(),
+        "TLS is configured but ignix was built without the `tls` feature"
+    );
+    Ok(None)
+}
+
+/// A client's socket: plaintext, or a TLS session over it
+enum Stream {
+    Plain(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<crate::tls::TlsStream>),
+}
+
+impl Stream {
+    /// Wrap an accepted socket as the listener is configured
+    fn accept(sock: TcpStream, tls: Option<&TlsConfig>) -> Result<Self> {
+        match tls {
+            #[cfg(feature = "tls")]
+            Some(config) => Ok(Stream::Tls(Box::new(crate::tls::TlsStream::new(sock, config.clone())?))),
+            #[cfg(not(feature = "tls"))]
+            Some(never) => match *never {},
+            None => Ok(Stream::Plain(sock)),
+        }
+    }
+
+    /// Whether bytes are queued beneath the reply buffer (TLS records the
+    /// socket didn't take yet)
+    fn wants_write(&self) -> bool {
+        match self {
+            Stream::Plain(_) => false,
+            #[cfg(unix)]
+            Stream::Unix(_) => false,
+            #[cfg(feature = "tls")]
+            Stream::Tls(s) => s.wants_write(),
+        }
+    }
+
+    /// Send bytes queued beneath the reply buffer
+    fn send_pending(&mut self) -> std::io::Result<()> {
+        match self {
+            Stream::Plain(_) => std::io::Result::Ok(()),
+            #[cfg(unix)]
+            Stream::Unix(_) => std::io::Result::Ok(()),
+            #[cfg(feature = "tls")]
+            Stream::Tls(s) => s.send_pending(),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Plain(s) => s.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(s) => s.read(buf),
+            #[cfg(feature = "tls")]
+            Stream::Tls(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Plain(s) => s.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(s) => s.write(buf),
+            #[cfg(feature = "tls")]
+            Stream::Tls(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Stream::Plain(s) => s.flush(),
+            #[cfg(unix)]
+            Stream::Unix(s) => s.flush(),
+            #[cfg(feature = "tls")]
+            Stream::Tls(s) => s.flush(),
+        }
+    }
+}
+
+impl Source for Stream {
+    fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> std::io::Result<()> {
+        match self {
+            Stream::Plain(s) => s.register(registry, token, interests),
+            #[cfg(unix)]
+            Stream::Unix(s) => s.register(registry, token, interests),
+            #[cfg(feature = "tls")]
+            Stream::Tls(s) => s.sock.register(registry, token, interests),
+        }
+    }
+
+    fn reregister(&mut self, registry: &Registry, token: Token, interests: Interest) -> std::io::Result<()> {
+        match self {
+            Stream::Plain(s) => s.reregister(registry, token, interests),
+            #[cfg(unix)]
+            Stream::Unix(s) => s.reregister(registry, token, interests),
+            #[cfg(feature = "tls")]
+            Stream::Tls(s) => s.sock.reregister(registry, token, interests),
+        }
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> std::io::Result<()> {
+        match self {
+            Stream::Plain(s) => s.deregister(registry),
+            #[cfg(unix)]
+            Stream::Unix(s) => s.deregister(registry),
+            #[cfg(feature = "tls")]
+            Stream::Tls(s) => s.sock.deregister(registry),
+        }
+    }
+}
+
+/// Per-connection state owned by a worker
+struct Client {
+    sock: Stream,
+    conn: Conn,
+    /// Queued for another read turn after exhausting its read budget
+    backlogged: bool,
+    /// Has replies waiting for the end-of-iteration flush (coalesced mode)
+    dirty: bool,
+    /// Buffered bytes last added to the worker's `client_memory`
+    mem: usize,
+    /// Queued to resume once CLIENT PAUSE lets its next command run
+    paused: bool,
+    /// Not read from until its unsent replies drain below
+    /// `write_buf_low_water`
+    throttled: bool,
+}
+
+impl Client {
+    /// Hand the connection's buffers back to the worker's pool
+    fn recycle(self, pool: &mut BufPool) {
+        self.conn.recycle(pool);
+    }
+
+    /// Bring the worker's `client_memory` gauge up to date for this client
+    fn account(&mut self, worker: &WorkerStats) {
+        let now = self.conn.buffered();
+        if now > self.mem {
+            worker.client_memory.fetch_add(now - self.mem, Ordering::Relaxed);
+        } else {
+            worker.client_memory.fetch_sub(self.mem - now, Ordering::Relaxed);
+        }
+        self.mem = now;
+    }
+
+    /// Queue the client in `paused` if CLIENT PAUSE held back its commands
+    fn park(&mut self, t: usize, paused: &mut Vec<usize>) {
+        if self.conn.paused_until.is_some() && !self.paused {
+            self.paused = true;
+            paused.push(t);
+        }
+    }
+}
+
+/// A worker's inbox of messages from the other workers, in owned-shard
+/// mode
+struct Inbox {
+    router: Arc<Router>,
+    rx: Receiver<Message>,
+}
+
+/// Token reserved for the listener
+const LISTENER: Token = Token(0);
+/// Token reserved for the worker's waker (MONITOR feed, owned-shard inbox)
+const WAKER: Token = Token(usize::MAX);
+/// Token reserved for the Unix domain socket listener
+const UNIX_LISTENER: Token = Token(usize::MAX - 1);
+
 /// Main event loop for a single worker thread
-fn run_worker_loop(id: usize, addr: SocketAddr, shard: Arc<Shard>) -> Result<()> {
-    let mut poll = Poll::new()?;
+/// 
+/// `reactor` is the worker's poll with its waker registered under
+/// [`WAKER`]; `inbox` is set in owned-shard mode, where worker `id` owns
+/// shard `id`.
+fn run_worker_loop(
+    id: usize,
+    addr: SocketAddr,
+    shards: Arc<ShardSet>,
+    tls: Option<TlsConfig>,
+    mut unix: Option<UnixListener>,
+    reactor: (Poll, Arc<Waker>),
+    inbox: Option<Inbox>,
+) -> Result<()> {
+    // Server-wide state (configuration, INFO counters) lives on the primary
+    let shard = shards.primary();
+    let (mut poll, waker) = reactor;
     let mut events = Events::with_capacity(1024);
     
     // Each worker binds its own listener to the same port (SO_REUSEPORT)
-    let mut listener = bind_reuseport(addr)?;
+    let mut listener = bind_listener(addr, &shard.config)?;
     
-    const LISTENER: Token = Token(0);
     poll.registry().register(&mut listener, LISTENER, Interest::READABLE)?;
+    #[cfg(unix)]
+    if let Some(unix) = &mut unix {
+        poll.registry().register(unix, UNIX_LISTENER, Interest::READABLE)?;
+    }
+    #[cfg(not(unix))]
+    let _ = &mut unix;
     
-    // Client state: (socket, read_buf, write_buf, cmd_buf)
-    let mut clients: HashMap<usize, (TcpStream, BytesMut, BytesMut, Vec<Cmd>)> = HashMap::new();
+    let mut clients: HashMap<usize, Client> = HashMap::new();
+    // Connection gauges reported by INFO and used for client eviction
+    let worker = shard.stats.register_worker(Some(waker.clone()));
     let mut next_tok: usize = 1;
+    // Reusable connection buffers, borrowed on accept and returned on close
+    let mut pool = BufPool::new(shard.config.read_buf_size, DEFAULT_POOL_SIZE, DEFAULT_MAX_POOLED_CAPACITY);
     
-    // Buffer for reading from socket
-    let mut tmp_buf = [0u8; READ_BUF];
+    // Buffer for reading from socket, sized for the largest adaptive read
+    let mut tmp_buf = vec![0u8; shard.config.read_buf_max.max(shard.config.read_buf_size)];
+    // Connections with unread input left after their read budget ran out:
+    // `backlog` is served this iteration, `pending` collects the next one
+    let mut backlog: Vec<usize> = Vec::new();
+    let mut pending: Vec<usize> = Vec::new();
+    // Connections whose replies are deferred to the end of the iteration
+    let mut dirty: Vec<usize> = Vec::new();
+    // Connections with commands held back by CLIENT PAUSE
+    let mut paused: Vec<usize> = Vec::new();
 
     loop {
-        poll.poll(&mut events, None)?;
+        // Don't block while some connection still has input waiting, and
+        // wake up when the earliest pause ends
+        let resume_at = paused.iter().filter_map(|t| clients.get(t)?.conn.paused_until).min();
+        let timeout = if !backlog.is_empty() {
+            Some(Duration::ZERO)
+        } else {
+            resume_at.map(|at| at.saturating_duration_since(Instant::now()))
+        };
+        poll.poll(&mut events, timeout)?;
         
         for ev in events.iter() {
             match ev.token() {
                 LISTENER => loop {
                     match listener.accept() {
-                        Ok((mut sock, _)) => {
+                        Ok((sock, peer)) => {
                             sock.set_nodelay(true).ok();
-                            let tok = next_tok;
-                            next_tok = next_tok.wrapping_add(1);
-                            if next_tok == 0 { next_tok = 1; } // Skip 0 (LISTENER)
-
-                            // Register client socket for READABLE only initially
-                            poll.registry().register(
-                                &mut sock,
-                                Token(tok),
-                                Interest::READABLE,
-                            )?;
-                            
-                            // println!("Worker {} accepted connection {}", id, tok);
-                            clients.insert(tok, (sock, BytesMut::with_capacity(READ_BUF), BytesMut::new(), Vec::with_capacity(32)));
+                            let sock = match Stream::accept(sock, tls.as_ref()) {
+                                Ok(sock) => sock,
+                                Err(e) => {
+                                    eprintln!("Worker {} accept err: {}", id, e);
+                                    continue;
+                                }
+                            };
+                            let tok = next_token(&mut next_tok);
+                            let route = route_of(inbox.as_ref(), id, tok);
+                            add_client(&poll, &mut clients, tok, sock, peer.to_string(), &mut pool, shard, &worker, route)?;
                         }
                         Err(ref e) if would_block(e) => break,
                         Err(e) => {
@@ -126,74 +519,344 @@ fn run_worker_loop(id: usize, addr: SocketAddr, shard: Arc<Shard>) -> Result<()>
                         }
                     }
                 },
-                Token(t) => {
-                    let mut should_remove = false;
-                    if let Some((sock, rbuf, wbuf, cmds)) = clients.get_mut(&t) {
-                        // READ
-                        if ev.is_readable() {
-                            loop {
-                                match sock.read(&mut tmp_buf) {
-                                    Ok(0) => { should_remove = true; break; }
-                                    Ok(n) => {
-                                        rbuf.extend_from_slice(&tmp_buf[..n]);
-                                    }
-                                    Err(ref e) if would_block(e) => break,
-                                    Err(_) => { should_remove = true; break; }
+                #[cfg(unix)]
+                UNIX_LISTENER => {
+                    let Some(listener) = &unix else { continue };
+                    loop {
+                        match listener.accept() {
+                            Ok((sock, _)) => {
+                                // Unix peers are unnamed; report the socket path like Redis
+                                let peer = format!("{}:0", shard.config.unixsocket.as_deref().unwrap_or_default());
+                                let tok = next_token(&mut next_tok);
+                                let route = route_of(inbox.as_ref(), id, tok);
+                                add_client(&poll, &mut clients, tok, Stream::Unix(sock), peer, &mut pool, shard, &worker, route)?;
+                            }
+                            Err(ref e) if would_block(e) => break,
+                            Err(e) => {
+                                eprintln!("Worker {} accept err: {}", id, e);
+                                break;
+                            }
+                        }
+                    }
+                }
+                WAKER => {
+                    // Commands other workers forwarded, and replies to ours
+                    while let Some(msg) = inbox.as_ref().and_then(|i| i.rx.try_recv().ok()) {
+                        match msg {
+                            Message::Exec { cmd, from, token } => {
+                                let reply = run_forwarded(&shards, cmd);
+                                if let Some(inbox) = &inbox {
+                                    inbox.router.send(from, Message::Reply { token, reply });
                                 }
                             }
-                            
-                            // PARSE & EXECUTE (Inline)
-                            if !should_remove {
-                                cmds.clear();
-                                if let Err(e) = parse_many(rbuf, cmds) {
-                                    write_simple(&format!("ERR {}", e), wbuf);
+                            Message::Reply { token, reply } => {
+                                // The connection may have closed meanwhile
+                                let Some(c) = clients.get_mut(&token) else { continue };
+                                let keep = if c.conn.deliver(reply, &shards) {
+                                    serve(c, token, false, &shards, &waker, &poll, &worker, &mut tmp_buf, &mut pending, &mut dirty)
                                 } else {
-                                    for cmd in cmds.drain(..) {
-                                        shard.exec(cmd, wbuf);
-                                    }
-                                }
-                                
-                                // Try to write immediately
-                                if !wbuf.is_empty() {
-                                    match sock.write(wbuf) {
-                                        Ok(n) => { let _ = wbuf.split_to(n); }
-                                        Err(ref e) if would_block(e) => {}
-                                        Err(_) => { should_remove = true; }
-                                    }
+                                    let _ = flush(c, &poll, token, shard, &worker, &mut pending);
+                                    false
+                                };
+                                if keep {
+                                    c.park(token, &mut paused);
+                                } else {
+                                    close(&mut clients, token, &mut pool, &worker);
                                 }
                             }
                         }
-                        
-                        // WRITE
-                        if !should_remove && ev.is_writable() && !wbuf.is_empty() {
-                            match sock.write(wbuf) {
-                                Ok(n) => { let _ = wbuf.split_to(n); }
-                                Err(ref e) if would_block(e) => {}
-                                Err(_) => { should_remove = true; }
-                            }
-                        }
-                        
-                        // Update Interest based on wbuf state
-                        if !should_remove {
-                            let interest = if wbuf.is_empty() {
-                                Interest::READABLE
-                            } else {
-                                Interest::READABLE | Interest::WRITABLE
-                            };
-                            
-                            if let Err(_) = poll.registry().reregister(sock, Token(t), interest) {
-                                should_remove = true;
-                            }
+                    }
+                    // Monitor lines were queued for some of our connections
+                    let mut dead = Vec::new();
+                    for (&t, c) in clients.iter_mut() {
+                        if c.conn.monitor.is_none() { continue }
+                        c.conn.drain_monitor();
+                        if !flush(c, &poll, t, shard, &worker, &mut pending) {
+                            dead.push(t);
                         }
                     }
-                    
-                    if should_remove {
-                        clients.remove(&t);
+                    for t in dead {
+                        close(&mut clients, t, &mut pool, &worker);
                     }
                 }
+                Token(t) => {
+                    let Some(c) = clients.get_mut(&t) else { continue };
+                    let keep = serve(c, t, ev.is_readable(), &shards, &waker, &poll, &worker, &mut tmp_buf, &mut pending, &mut dirty);
+                    if keep {
+                        c.park(t, &mut paused);
+                    } else {
+                        close(&mut clients, t, &mut pool, &worker);
+                    }
+                }
+            }
+        }
+
+        // Connections that used up their read budget get another turn now
+        // that every other ready connection has been served once
+        for t in std::mem::take(&mut backlog) {
+            let Some(c) = clients.get_mut(&t) else { continue };
+            c.backlogged = false;
+            if serve(c, t, true, &shards, &waker, &poll, &worker, &mut tmp_buf, &mut pending, &mut dirty) {
+                c.park(t, &mut paused);
+            } else {
+                close(&mut clients, t, &mut pool, &worker);
+            }
+        }
+        std::mem::swap(&mut backlog, &mut pending);
+
+        // Retry paused connections; the ones still held back are parked
+        // again until the pause ends
+        for t in std::mem::take(&mut paused) {
+            let Some(c) = clients.get_mut(&t) else { continue };
+            c.paused = false;
+            if serve(c, t, false, &shards, &waker, &poll, &worker, &mut tmp_buf, &mut pending, &mut dirty) {
+                c.park(t, &mut paused);
+            } else {
+                close(&mut clients, t, &mut pool, &worker);
+            }
+        }
+
+        // Coalesced mode: one write per connection for everything it produced
+        for t in dirty.drain(..) {
+            let Some(c) = clients.get_mut(&t) else { continue };
+            c.dirty = false;
+            if !flush(c, &poll, t, shard, &worker, &mut pending) {
+                close(&mut clients, t, &mut pool, &worker);
+            }
+        }
+
+        if shard.config.maxmemory_clients > 0 {
+            evict_clients(&mut clients, &mut pool, shard, &worker);
+        }
+    }
+}
+
+/// How a new connection on worker `id` reaches the other workers, in
+/// owned-shard mode
+fn route_of(inbox: Option<&Inbox>, id: usize, tok: usize) -> Option<Route> {
+    inbox.map(|inbox| Route { router: inbox.router.clone(), worker: id, token: tok })
+}
+
+/// Allocate the token of a new connection
+fn next_token(next_tok: &mut usize) -> usize {
+    let tok = *next_tok;
+    *next_tok = next_tok.wrapping_add(1);
+    // Skip 0 (LISTENER) and the tokens reserved at the top of the range
+    if *next_tok == 0 || *next_tok >= UNIX_LISTENER.0 {
+        *next_tok = 1;
+    }
+    tok
+}
+
+/// Register an accepted connection and start tracking it
+#[allow(clippy::too_many_arguments)]
+fn add_client(
+    poll: &Poll,
+    clients: &mut HashMap<usize, Client>,
+    tok: usize,
+    mut sock: Stream,
+    peer: String,
+    pool: &mut BufPool,
+    shard: &Shard,
+    worker: &WorkerStats,
+    route: Option<Route>,
+) -> Result<()> {
+    // Register client socket for READABLE only initially
+    poll.registry().register(&mut sock, Token(tok), Interest::READABLE)?;
+    let mut conn = Conn::new(peer, pool.acquire(), pool.acquire(), shard);
+    conn.route = route;
+    clients.insert(tok, Client {
+        sock,
+        conn,
+        backlogged: false,
+        dirty: false,
+        mem: 0,
+        paused: false,
+        throttled: false,
+    });
+    worker.connected.fetch_add(1, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Drop a connection, returning its buffers to the pool
+fn close(clients: &mut HashMap<usize, Client>, t: usize, pool: &mut BufPool, worker: &WorkerStats) {
+    if let Some(c) = clients.remove(&t) {
+        worker.client_memory.fetch_sub(c.mem, Ordering::Relaxed);
+        worker.connected.fetch_sub(1, Ordering::Relaxed);
+        c.recycle(pool);
+    }
+}
+
+/// Enforce `maxmemory-clients`
+/// 
+/// While the connections of all workers together buffer more than the
+/// limit, the largest connection not marked CLIENT NO-EVICT is dropped.
+/// A worker only ever closes its own connections: when the largest one
+/// lives on another worker, that worker is woken to do it. Buffers only
+/// change while their owner runs, so each worker's published
+/// `largest_client` is accurate from the end of its last iteration.
+fn evict_clients(clients: &mut HashMap<usize, Client>, pool: &mut BufPool, shard: &Shard, worker: &WorkerStats) {
+    loop {
+        let (t, size) = clients
+            .iter()
+            .filter(|(_, c)| !c.conn.no_evict())
+            .map(|(&t, c)| (t, c.mem))
+            .max_by_key(|&(_, size)| size)
+            .unwrap_or((0, 0));
+        worker.largest_client.store(size, Ordering::Relaxed);
+
+        if shard.stats.client_memory() <= shard.config.maxmemory_clients {
+            return;
+        }
+        match shard.stats.largest_client() {
+            Some((largest, owner)) if largest > size => {
+                owner.wake();
+                return;
+            }
+            _ if size == 0 => return,
+            _ => {
+                close(clients, t, pool, worker);
+                shard.stats.evicted_clients.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Read, execute and reply for one connection
+/// 
+/// Reads at most `config.read_budget` bytes per call so a client streaming
+/// a large payload can't monopolize the worker. Since readiness is
+/// edge-triggered, a connection that stops at its budget is queued in
+/// `backlog` and served again on the next loop iteration.
+/// 
+/// With `FlushStrategy::Coalesced`, replies to newly read requests are
+/// not written here; the connection is queued in `dirty` instead.
+/// 
+/// A connection with commands held back by CLIENT PAUSE retries them on
+/// every call, readable or not, and so does one whose forwarded command
+/// just replied. A throttled connection (see [`flush`]) is not read from.
+/// 
+/// # Returns
+/// * `false` if the connection was closed or failed and must be dropped
+#[allow(clippy::too_many_arguments)]
+fn serve(
+    c: &mut Client,
+    t: usize,
+    readable: bool,
+    shards: &ShardSet,
+    waker: &Arc<Waker>,
+    poll: &Poll,
+    worker: &WorkerStats,
+    tmp_buf: &mut [u8],
+    backlog: &mut Vec<usize>,
+    dirty: &mut Vec<usize>,
+) -> bool {
+    let shard = shards.primary();
+    let readable = readable && !c.throttled;
+    if readable {
+        // READ (bounded)
+        let budget = shard.config.read_budget;
+        let mut read = 0;
+        loop {
+            if read >= budget {
+                if !c.backlogged {
+                    c.backlogged = true;
+                    backlog.push(t);
+                }
+                break;
+            }
+            match c.sock.read(&mut tmp_buf[..c.conn.read_size.size()]) {
+                Ok(0) => return false,
+                Ok(n) => {
+                    c.conn.read_size.record(n);
+                    c.conn.rbuf.extend_from_slice(&tmp_buf[..n]);
+                    read += n;
+                }
+                Err(ref e) if would_block(e) => break,
+                Err(_) => return false,
             }
         }
     }
+
+    // Resume a connection held back by CLIENT PAUSE, or waiting for a
+    // forwarded command, as well
+    if readable || c.conn.paused_until.is_some() || c.conn.replied {
+        // PARSE & EXECUTE (Inline)
+        if !c.conn.process(shards, Some(waker)) {
+            // Framing error: deliver the reply, then drop the client
+            let _ = flush(c, poll, t, shard, worker, backlog);
+            return false;
+        }
+
+        if shard.config.flush_strategy == FlushStrategy::Coalesced {
+            if !c.dirty {
+                c.dirty = true;
+                dirty.push(t);
+            }
+            c.account(worker);
+            return true;
+        }
+    }
+
+    // WRITE (immediately after execute, or on writable)
+    // and update Interest based on wbuf state
+    flush(c, poll, t, shard, worker, backlog)
+}
+
+/// Write as much of the pending output as the socket accepts and
+/// re-register interest accordingly
+///
+/// Backpressure: a client whose unsent replies reach
+/// `write_buf_high_water` is throttled, dropping READABLE interest so no
+/// more of its requests are read or executed. Once they drain to
+/// `write_buf_low_water` it's queued in `backlog` to run the rest of its
+/// pipeline and read again (input may have arrived in the meantime), as is
+/// a client whose pipeline was held back but whose replies were written
+/// out at once. Replies piling up past `write_buf_hard_limit` close the
+/// connection.
+///
+/// # Returns
+/// * `false` if the connection failed and must be dropped
+fn flush(c: &mut Client, poll: &Poll, t: usize, shard: &Shard, worker: &WorkerStats, backlog: &mut Vec<usize>) -> bool {
+    let wbuf = &mut c.conn.wbuf;
+    if !wbuf.is_empty() {
+        match c.sock.write(wbuf) {
+            Ok(n) => { let _ = wbuf.split_to(n); }
+            Err(ref e) if would_block(e) => {}
+            Err(_) => return false,
+        }
+    } else if c.sock.send_pending().is_err() {
+        return false;
+    }
+
+    let config = &shard.config;
+    if config.write_buf_hard_limit > 0 && wbuf.len() > config.write_buf_hard_limit {
+        log::warn!(
+            "closing client {}: {} bytes of unsent replies exceed write_buf_hard_limit",
+            c.conn.addr,
+            wbuf.len()
+        );
+        return false;
+    }
+    if !c.throttled && config.write_buf_high_water > 0 && wbuf.len() >= config.write_buf_high_water {
+        c.throttled = true;
+    } else if (c.throttled && wbuf.len() <= config.write_buf_low_water) || (!c.throttled && c.conn.held_back) {
+        c.throttled = false;
+        if !c.backlogged {
+            c.backlogged = true;
+            backlog.push(t);
+        }
+    }
+
+    let interest = if c.throttled {
+        Interest::WRITABLE
+    } else if wbuf.is_empty() && !c.sock.wants_write() {
+        Interest::READABLE
+    } else {
+        Interest::READABLE | Interest::WRITABLE
+    };
+    c.account(worker);
+    poll.registry().reregister(&mut c.sock, Token(t), interest).is_ok()
 }
 
 /// Check if an I/O error indicates the operation would block
@@ -203,4 +866,40 @@ fn would_block(e: &std::io::Error) -> bool {
         e.kind(),
         std::io::ErrorKind::WouldBlock | std::io::ErrorKind::Interrupted
     )
-}
\ No newline at end of file
+}
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::os::fd::AsRawFd;
+
+    fn tcp_option(listener: &TcpListener, name: libc::c_int) -> libc::c_int {
+        let mut value: libc::c_int = 0;
+        let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+        // SAFETY: valid fd, and value/len point to live locals of the right size
+        let rc = unsafe {
+            libc::getsockopt(
+                listener.as_raw_fd(),
+                libc::IPPROTO_TCP,
+                name,
+                &mut value as *mut libc::c_int as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        assert_eq!(rc, 0, "{}", std::io::Error::last_os_error());
+        value
+    }
+
+    #[test]
+    fn listener_options_are_applied_when_configured() {
+        let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let plain = bind_reuseport(addr).unwrap();
+        assert_eq!(tcp_option(&plain, libc::TCP_FASTOPEN), 0);
+        assert_eq!(tcp_option(&plain, libc::TCP_DEFER_ACCEPT), 0);
+
+        let config = Config { tcp_fastopen: 128, tcp_defer_accept: 5, ..Default::default() };
+        let tuned = bind_listener(addr, &config).unwrap();
+        assert_eq!(tcp_option(&tuned, libc::TCP_FASTOPEN), 128);
+        // The kernel rounds the timeout to whole SYN-ACK retransmissions
+        assert!(tcp_option(&tuned, libc::TCP_DEFER_ACCEPT) >= 5);
+    }
+}
@@ -7,42 +7,185 @@
 
 #![cfg(target_os = "linux")]
 
-use crate::shard::Shard;
-use crate::protocol::{parse_many, write_simple, Cmd};
+use crate::conn::Conn;
+use crate::shard_set::ShardSet;
 use anyhow::*;
 use bytes::BytesMut;
-use io_uring::{opcode, types, IoUring};
+use io_uring::{opcode, types, IoUring, SubmissionQueue};
 use slab::Slab;
+use socket2::SockRef;
 use std::net::SocketAddr;
+use std::os::fd::BorrowedFd;
 use std::os::unix::io::AsRawFd;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::net::TcpListener;
+use std::time::{Duration, Instant};
 
-// Operation types for user_data
+// Operation types for user_data: (token << 32) | op
 const OP_ACCEPT: u64 = 0;
-// User data structure: (token << 32) | op_type
-// where op_type: 1 = READ, 2 = WRITE
+const OP_READ: u64 = 1;
+const OP_WRITE: u64 = 2;
+const OP_TIMEOUT: u64 = 3;
+/// Removal of a connection's TIMEOUT; its completion is ignored
+const OP_TIMEOUT_REMOVE: u64 = 4;
+/// The worker's waker fired (not tied to a connection)
+const OP_WAKE: u64 = u64::MAX;
 
-#[derive(Debug)]
+/// A client connection on the ring
+///
+/// The kernel reads from and writes into a connection's buffers
+/// asynchronously, so a buffer must stay put and untouched from the
+/// submission of an operation until its completion is consumed. Each
+/// connection therefore has at most one operation in flight (`in_flight`):
+/// a READ into `read_buffer`, a WRITE from `conn.wbuf`, or a TIMEOUT
+/// waiting out CLIENT PAUSE. The next one is submitted only from the
+/// previous one's completion, once its result was copied out or
+/// consumed, and a connection is only removed (closing its fd and
+/// freeing its buffers) at that point too.
 struct Connection {
     fd: i32,
     // Box provides stable address for io_uring even if Slab reallocates
-    read_buffer: Box<[u8; 4096]>, 
-    read_buf: BytesMut,
-    write_buf: BytesMut,
-    cmds: Vec<Cmd>,
+    read_buffer: Box<[u8]>,
+    conn: Conn,
+    /// Close once the pending replies are written (after a framing error)
+    closing: bool,
+    /// A READ, WRITE or TIMEOUT was submitted and hasn't completed yet
+    in_flight: bool,
+    /// The operation in flight is a TIMEOUT: commands are held back by
+    /// CLIENT PAUSE
+    parked: bool,
+    /// Duration of the TIMEOUT; boxed like `read_buffer`, as the kernel
+    /// reads it once the submission is consumed
+    resume_after: Box<types::Timespec>,
 }
 
-pub fn run_shard(shard_id: usize, addr: SocketAddr, shard: Shard) -> Result<()> {
+impl Connection {
+    /// Parse and execute the requests read so far
+    ///
+    /// Commands held back by CLIENT PAUSE stay queued in `conn`;
+    /// `submit_next` parks the connection until the pause ends.
+    fn process(&mut self, shards: &ShardSet) {
+        // No waker: MONITOR is refused here
+        self.closing = !self.conn.process(shards, None);
+    }
+
+    /// Submit a READ into `read_buffer`
+    fn submit_read(&mut self, key: usize, sq: &mut SubmissionQueue<'_>) {
+        debug_assert!(!self.in_flight, "READ submitted with an operation in flight");
+        let read_op = opcode::Read::new(types::Fd(self.fd), self.read_buffer.as_mut_ptr(), self.read_buffer.len() as _)
+            .build()
+            .user_data(((key as u64) << 32) | OP_READ);
+        // SAFETY: read_buffer is boxed and neither touched nor freed until
+        // this READ completes (see `Connection`)
+        unsafe {
+            sq.push(&read_op).expect("sq full");
+        }
+        self.in_flight = true;
+    }
+
+    /// Submit a WRITE of the unwritten part of `conn.wbuf`
+    fn submit_write(&mut self, key: usize, sq: &mut SubmissionQueue<'_>) {
+        debug_assert!(!self.in_flight, "WRITE submitted with an operation in flight");
+        let write_op = opcode::Write::new(types::Fd(self.fd), self.conn.wbuf.as_ptr(), self.conn.wbuf.len() as _)
+            .build()
+            .user_data(((key as u64) << 32) | OP_WRITE);
+        // SAFETY: wbuf isn't modified (so not reallocated) until this WRITE
+        // completes: no request is processed while it's in flight
+        unsafe {
+            sq.push(&write_op).expect("sq full");
+        }
+        self.in_flight = true;
+    }
+
+    /// Submit a TIMEOUT firing when the pause holding back commands ends
+    fn submit_timeout(&mut self, key: usize, until: Instant, sq: &mut SubmissionQueue<'_>) {
+        debug_assert!(!self.in_flight, "TIMEOUT submitted with an operation in flight");
+        *self.resume_after = until.saturating_duration_since(Instant::now()).into();
+        let timeout_op = opcode::Timeout::new(&*self.resume_after)
+            .build()
+            .user_data(((key as u64) << 32) | OP_TIMEOUT);
+        // SAFETY: resume_after is boxed and only rewritten for the next
+        // TIMEOUT, submitted after this one completed
+        unsafe {
+            sq.push(&timeout_op).expect("sq full");
+        }
+        self.in_flight = true;
+        self.parked = true;
+    }
+
+    /// Write pending replies if there are any, else wait out CLIENT PAUSE
+    /// if it holds back commands, else read the next request
+    fn submit_next(&mut self, key: usize, sq: &mut SubmissionQueue<'_>) {
+        if !self.conn.wbuf.is_empty() {
+            self.submit_write(key, sq);
+        } else if let (false, Some(until)) = (self.closing, self.conn.paused_until) {
+            self.submit_timeout(key, until, sq);
+        } else {
+            self.submit_read(key, sq);
+        }
+    }
+}
+
+/// Watch the epoll fd the worker's waker signals
+fn submit_wake_poll(wake_poll: &mio::Poll, sq: &mut SubmissionQueue<'_>) {
+    let poll_op = opcode::PollAdd::new(types::Fd(wake_poll.as_raw_fd()), libc::POLLIN as _)
+        .build()
+        .user_data(OP_WAKE);
+    // SAFETY: no buffers involved; the Poll outlives the ring
+    unsafe {
+        sq.push(&poll_op).expect("sq full");
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // Connections are removed only on a completion, when no READ or
+        // WRITE on the fd (or into its buffers) is still in flight
+        // SAFETY: the fd came from accept and is owned by this connection
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+/// Render the peer address of an accepted socket for the MONITOR feed
+fn peer_addr(fd: i32) -> String {
+    // SAFETY: fd was just returned by accept and is owned by the caller
+    let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+    SockRef::from(&fd)
+        .peer_addr()
+        .ok()
+        .and_then(|a| a.as_socket())
+        .map_or_else(|| "?".to_string(), |a| a.to_string())
+}
+
+pub fn run_shard(shard_id: usize, addr: SocketAddr, shards: impl Into<ShardSet>) -> Result<()> {
+    let shards = shards.into();
+    let shard = shards.primary();
+    // Encrypted connections are only driven by the mio backend
+    ensure!(
+        shard.config.tls_cert_file.is_none() && shard.config.tls_key_file.is_none(),
+        "TLS is not supported by the io_uring backend"
+    );
+    ensure!(shard.config.unixsocket.is_none(), "unixsocket is not supported by the io_uring backend");
+    ensure!(!shard.config.owned_shards, "owned_shards is not supported by the io_uring backend");
     println!("🚀 Starting Ignix with io_uring backend (Shard {})", shard_id);
     
     // Setup listener
     let listener = TcpListener::bind(addr)?;
     let listener_fd = listener.as_raw_fd();
 
-    // Setup io_uring
+    // Lets other workers wake this one, to resume paused connections on
+    // CLIENT UNPAUSE; the ring watches the epoll fd the waker signals
+    let mut wake_poll = mio::Poll::new()?;
+    let mut wake_events = mio::Events::with_capacity(8);
+    let waker = Arc::new(mio::Waker::new(wake_poll.registry(), mio::Token(0))?);
+
+    // Setup io_uring; declared after the connections so that on return
+    // the ring is torn down before the buffers its operations point into
+    let mut connections: Slab<Connection> = Slab::with_capacity(1024);
     let mut ring = IoUring::new(4096)?;
-    let mut connections = Slab::with_capacity(1024);
+    // Connection gauges reported by INFO
+    let worker = shard.stats.register_worker(Some(waker));
 
     // Initial Accept
     let mut accept_addr = libc::sockaddr { sa_family: 0, sa_data: [0; 14] };
@@ -61,20 +204,35 @@ pub fn run_shard(shard_id: usize, addr: SocketAddr, shard: Shard) -> Result<()>
         unsafe {
             sq.push(&accept_op).expect("submission queue full");
         }
+        submit_wake_poll(&wake_poll, &mut sq);
         sq.sync();
     }
 
     loop {
         ring.submit_and_wait(1)?;
 
-        let mut cq = ring.completion();
-        let mut sq = ring.submission();
+        // Split borrows the ring once for both queues
+        let (_, mut sq, cq) = ring.split();
 
         for cqe in cq {
             let user_data = cqe.user_data();
             let res = cqe.result();
 
-            if user_data == OP_ACCEPT {
+            if user_data == OP_WAKE {
+                // Drain the waker, then cut short the TIMEOUTs of parked
+                // connections so they re-check the pause
+                let _ = wake_poll.poll(&mut wake_events, Some(Duration::ZERO));
+                for key in connections.iter().filter(|(_, c)| c.parked).map(|(key, _)| key as u64) {
+                    let remove_op = opcode::TimeoutRemove::new((key << 32) | OP_TIMEOUT)
+                        .build()
+                        .user_data((key << 32) | OP_TIMEOUT_REMOVE);
+                    // SAFETY: refers to the TIMEOUT by user_data only
+                    unsafe {
+                        sq.push(&remove_op).expect("sq full");
+                    }
+                }
+                submit_wake_poll(&wake_poll, &mut sq);
+            } else if user_data == OP_ACCEPT {
                 if res < 0 {
                     eprintln!("Accept error: {}", res);
                 } else {
@@ -82,20 +240,18 @@ pub fn run_shard(shard_id: usize, addr: SocketAddr, shard: Shard) -> Result<()>
                     let entry = connections.vacant_entry();
                     let key = entry.key();
                     
-                    let mut conn = Connection {
-                        fd,
-                        read_buffer: Box::new([0u8; 4096]),
-                        read_buf: BytesMut::with_capacity(4096),
-                        write_buf: BytesMut::new(),
-                        cmds: Vec::new(),
-                    };
-                    
-                    // Get stable pointer before moving conn into Slab
-                    // Actually, Box pointer is stable even after move.
-                    let buf_ptr = conn.read_buffer.as_mut_ptr();
-                    let buf_len = conn.read_buffer.len();
-
-                    entry.insert(conn);
+                    entry
+                        .insert(Connection {
+                            fd,
+                            read_buffer: vec![0u8; shard.config.read_buf_size].into_boxed_slice(),
+                            conn: Conn::new(peer_addr(fd), BytesMut::with_capacity(shard.config.read_buf_size), BytesMut::new(), shard),
+                            closing: false,
+                            in_flight: false,
+                            parked: false,
+                            resume_after: Box::new(types::Timespec::new()),
+                        })
+                        .submit_read(key, &mut sq);
+                    worker.connected.fetch_add(1, Ordering::Relaxed);
 
                     // Re-submit Accept
                     let accept_op = opcode::Accept::new(
@@ -109,105 +265,54 @@ pub fn run_shard(shard_id: usize, addr: SocketAddr, shard: Shard) -> Result<()>
                     unsafe {
                         sq.push(&accept_op).expect("sq full");
                     }
-                    
-                    // Submit Read
-                    let read_op = opcode::Read::new(
-                        types::Fd(fd),
-                        buf_ptr,
-                        buf_len as _
-                    )
-                    .build()
-                    .user_data(((key as u64) << 32) | 1); // 1 = READ
-
-                    unsafe {
-                        sq.push(&read_op).expect("sq full");
-                    }
                 }
             } else {
                 let key = (user_data >> 32) as usize;
                 let op = user_data & 0xFFFFFFFF;
+                if op == OP_TIMEOUT_REMOVE {
+                    // The TIMEOUT completes by itself, cut short or fired
+                    continue;
+                }
 
-                if connections.contains(key) {
-                    if op == 1 { // READ completion
-                        if res <= 0 {
-                            // EOF or Error
-                            connections.remove(key);
-                            // Close FD - handled by Drop? No, need manual close or impl Drop
-                            // unsafe { libc::close(conn.fd); }
-                        } else {
-                            let conn = connections.get_mut(key).unwrap();
-                            conn.read_buf.extend_from_slice(&conn.read_buffer[..res as usize]);
-                            
-                            // Parse and Execute
-                            if let Ok(_) = parse_many(&mut conn.read_buf, &mut conn.cmds) {
-                                for cmd in conn.cmds.drain(..) {
-                                    shard.exec(cmd, &mut conn.write_buf);
-                                }
-                            }
-
-                            // Submit Write if needed
-                            if !conn.write_buf.is_empty() {
-                                let write_op = opcode::Write::new(
-                                    types::Fd(conn.fd),
-                                    conn.write_buf.as_ptr(),
-                                    conn.write_buf.len() as _
-                                )
-                                .build()
-                                .user_data(((key as u64) << 32) | 2); // 2 = WRITE
-                                
-                                unsafe {
-                                    sq.push(&write_op).expect("sq full");
-                                }
-                            } else {
-                                // Continue Reading
-                                let read_op = opcode::Read::new(
-                                    types::Fd(conn.fd),
-                                    conn.read_buffer.as_mut_ptr(),
-                                    conn.read_buffer.len() as _
-                                )
-                                .build()
-                                .user_data(((key as u64) << 32) | 1);
-
-                                unsafe {
-                                    sq.push(&read_op).expect("sq full");
-                                }
-                            }
-                        }
-                    } else if op == 2 { // WRITE completion
-                         if res < 0 {
-                            connections.remove(key);
-                        } else {
-                            let conn = connections.get_mut(key).unwrap();
-                            let _ = conn.write_buf.split_to(res as usize);
-
-                            if !conn.write_buf.is_empty() {
-                                // Continue Writing
-                                let write_op = opcode::Write::new(
-                                    types::Fd(conn.fd),
-                                    conn.write_buf.as_ptr(),
-                                    conn.write_buf.len() as _
-                                )
-                                .build()
-                                .user_data(((key as u64) << 32) | 2);
-                                
-                                unsafe {
-                                    sq.push(&write_op).expect("sq full");
-                                }
-                            } else {
-                                // Back to Reading
-                                let read_op = opcode::Read::new(
-                                    types::Fd(conn.fd),
-                                    conn.read_buffer.as_mut_ptr(),
-                                    conn.read_buffer.len() as _
-                                )
-                                .build()
-                                .user_data(((key as u64) << 32) | 1);
-
-                                unsafe {
-                                    sq.push(&read_op).expect("sq full");
-                                }
-                            }
+                let Some(conn) = connections.get_mut(key) else { continue };
+                // The buffer this operation used is ours again
+                conn.in_flight = false;
+
+                if op == OP_TIMEOUT {
+                    // The pause ended or was lifted: run what it held back
+                    conn.parked = false;
+                    conn.process(&shards);
+                    conn.submit_next(key, &mut sq);
+                } else if op == OP_READ {
+                    if res <= 0 {
+                        // EOF or Error (dropping the connection closes the fd)
+                        connections.remove(key);
+                        worker.connected.fetch_sub(1, Ordering::Relaxed);
+                        continue;
+                    }
+                    conn.conn.rbuf.extend_from_slice(&conn.read_buffer[..res as usize]);
+
+                    conn.process(&shards);
+                    conn.submit_next(key, &mut sq);
+                } else if op == OP_WRITE {
+                    if res < 0 {
+                        connections.remove(key);
+                        worker.connected.fetch_sub(1, Ordering::Relaxed);
+                        continue;
+                    }
+                    let _ = conn.conn.wbuf.split_to(res as usize);
+
+                    if conn.conn.wbuf.is_empty() && conn.closing {
+                        // Error reply delivered; drop the client
+                        connections.remove(key);
+                        worker.connected.fetch_sub(1, Ordering::Relaxed);
+                    } else {
+                        if conn.conn.wbuf.is_empty() && conn.conn.held_back {
+                            // Replies are out: run the rest of the pipeline
+                            conn.process(&shards);
                         }
+                        // The rest of the replies, or back to reading
+                        conn.submit_next(key, &mut sq);
                     }
                 }
             }
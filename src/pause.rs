@@ -0,0 +1,98 @@
+/*!
+ * Client Pause
+ *
+ * `CLIENT PAUSE <ms> [WRITE|ALL]` holds back command execution on every
+ * connection until a deadline, so a proxy can fail over without losing
+ * in-flight writes. Commands are not rejected: a connection stops at its
+ * first paused command and leaves it, and everything pipelined after it,
+ * queued until the pause ends. The network workers poll the deadline.
+ */
+
+use crate::protocol::Cmd;
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Which commands a CLIENT PAUSE holds back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseMode {
+    /// Only commands that modify the keyspace
+    Write,
+    /// Every command
+    All,
+}
+
+impl PauseMode {
+    /// Mode name as sent by clients
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Write => "WRITE",
+            Self::All => "ALL",
+        }
+    }
+}
+
+/// Shared pause deadline, checked before every command
+#[derive(Debug, Default)]
+pub struct ClientPause {
+    /// Fast path: false whenever no pause is in effect
+    active: AtomicBool,
+    state: Mutex<Option<(Instant, PauseMode)>>,
+}
+
+impl ClientPause {
+    /// Start a pause, or extend the one in effect
+    ///
+    /// As in Redis, overlapping pauses keep the later deadline and the
+    /// more restrictive mode.
+    ///
+    /// # Arguments
+    /// * `duration` - How long commands are held back
+    /// * `mode` - Which commands are held back
+    pub fn pause(&self, duration: Duration, mode: PauseMode) {
+        let until = Instant::now() + duration;
+        let mut state = self.state.lock();
+        let next = match *state {
+            Some((prev, prev_mode)) if prev > Instant::now() => {
+                let mode = if prev_mode == PauseMode::All { PauseMode::All } else { mode };
+                (prev.max(until), mode)
+            }
+            _ => (until, mode),
+        };
+        *state = Some(next);
+        self.active.store(true, Ordering::Release);
+    }
+
+    /// End the pause in effect, if any (`CLIENT UNPAUSE`)
+    pub fn unpause(&self) {
+        let mut state = self.state.lock();
+        *state = None;
+        self.active.store(false, Ordering::Release);
+    }
+
+    /// When `cmd` may run, if the pause in effect holds it back
+    ///
+    /// CLIENT commands are never held back, so a paused server can
+    /// always be unpaused.
+    ///
+    /// # Returns
+    /// * `Some(deadline)` while `cmd` must wait
+    /// * `None` if it can run now
+    #[inline]
+    pub fn blocks(&self, cmd: &Cmd) -> Option<Instant> {
+        if !self.active.load(Ordering::Acquire) {
+            return None;
+        }
+        let mut state = self.state.lock();
+        let (until, mode) = (*state)?;
+        if Instant::now() >= until {
+            *state = None;
+            self.active.store(false, Ordering::Release);
+            return None;
+        }
+        if cmd.name() == "CLIENT" {
+            return None;
+        }
+        (mode == PauseMode::All || cmd.is_write()).then_some(until)
+    }
+}
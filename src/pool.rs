@@ -0,0 +1,102 @@
+/*!
+ * Connection Buffer Pool
+ *
+ * Accepting and closing connections at a high rate would otherwise allocate
+ * and free a pair of `BytesMut` buffers per connection. Each worker keeps a
+ * small free-list of cleared buffers that new connections borrow on accept
+ * and hand back on close. Buffers that grew large (e.g. after a big GET
+ * response) are dropped instead of being retained.
+ */
+
+use bytes::BytesMut;
+
+/// Default number of buffers kept on the free-list
+pub const DEFAULT_POOL_SIZE: usize = 256;
+/// Default capacity above which a returned buffer is not retained
+pub const DEFAULT_MAX_POOLED_CAPACITY: usize = 64 * 1024;
+
+/// Free-list of reusable connection buffers
+///
+/// A pool is owned by a single worker thread, so no locking is needed.
+pub struct BufPool {
+    free: Vec<BytesMut>,
+    /// Capacity of freshly allocated buffers
+    buf_capacity: usize,
+    /// Maximum number of buffers kept on the free-list
+    max_pooled: usize,
+    /// Returned buffers larger than this are dropped
+    max_capacity: usize,
+}
+
+impl BufPool {
+    /// Create an empty pool
+    ///
+    /// # Arguments
+    /// * `buf_capacity` - Capacity of newly allocated buffers
+    /// * `max_pooled` - Maximum number of idle buffers retained
+    /// * `max_capacity` - Returned buffers above this capacity are freed
+    pub fn new(buf_capacity: usize, max_pooled: usize, max_capacity: usize) -> Self {
+        Self {
+            free: Vec::with_capacity(max_pooled),
+            buf_capacity,
+            max_pooled,
+            max_capacity,
+        }
+    }
+
+    /// Borrow an empty buffer, allocating only if the free-list is empty
+    #[inline]
+    pub fn acquire(&mut self) -> BytesMut {
+        self.free
+            .pop()
+            .unwrap_or_else(|| BytesMut::with_capacity(self.buf_capacity))
+    }
+
+    /// Return a buffer to the pool
+    ///
+    /// The buffer is cleared and retained unless the pool is full or the
+    /// buffer grew beyond the retention cap.
+    #[inline]
+    pub fn release(&mut self, mut buf: BytesMut) {
+        if self.free.len() >= self.max_pooled || buf.capacity() > self.max_capacity {
+            return;
+        }
+        buf.clear();
+        self.free.push(buf);
+    }
+
+    /// Number of idle buffers currently held
+    pub fn idle(&self) -> usize {
+        self.free.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn released_buffers_are_reused() {
+        let mut pool = BufPool::new(1024, 4, 4096);
+        let mut b = pool.acquire();
+        b.extend_from_slice(b"hello");
+        let ptr = b.as_ptr();
+        pool.release(b);
+        assert_eq!(pool.idle(), 1);
+
+        let b = pool.acquire();
+        assert!(b.is_empty());
+        assert_eq!(b.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn oversized_and_surplus_buffers_are_dropped() {
+        let mut pool = BufPool::new(16, 1, 1024);
+        pool.release(BytesMut::with_capacity(8192));
+        assert_eq!(pool.idle(), 0);
+
+        pool.release(BytesMut::with_capacity(16));
+        pool.release(BytesMut::with_capacity(16));
+        assert_eq!(pool.idle(), 1);
+    }
+}
@@ -6,6 +6,9 @@
  * including command parsing, validation, and response formatting.
  */
 
+use crate::command::Renames;
+use crate::config::Config;
+use crate::pause::PauseMode;
 use anyhow::*;
 use bytes::{Buf, Bytes, BytesMut, BufMut};
 
@@ -21,18 +24,401 @@ pub enum Cmd {
     Get(Bytes),
     /// SET key value - set a key-value pair
     Set(Bytes, Bytes),
-    /// DEL key - delete a key
-    Del(Bytes),
+    /// SET key value [NX|XX] [GET] - set a key-value pair with options
+    SetWith(Bytes, Bytes, SetOptions),
+    /// DEL key1 key2 ... - delete keys
+    Del(Vec<Bytes>),
+    /// UNLINK key1 key2 ... - delete keys, freeing large values in the background
+    Unlink(Vec<Bytes>),
     /// RENAME oldkey newkey - rename a key
     Rename(Bytes, Bytes),
-    /// EXISTS key - check if key exists
-    Exists(Bytes),
+    /// EXISTS key1 key2 ... - count the given keys that exist
+    Exists(Vec<Bytes>),
     /// INCR key - increment numeric value
     Incr(Bytes),
+    /// INCRBY key delta - add to a numeric value
+    IncrBy(Bytes, i64),
+    /// DECR key - decrement numeric value
+    Decr(Bytes),
+    /// DECRBY key delta - subtract from a numeric value
+    DecrBy(Bytes, i64),
+    /// EXPIRE key seconds - set a key's time to live
+    Expire(Bytes, i64),
+    /// PEXPIREAT key unix-ms - expire a key at a Unix time in milliseconds
+    PExpireAt(Bytes, i64),
+    /// TTL key - seconds left until a key expires
+    Ttl(Bytes),
     /// MGET key1 key2 ... - get multiple keys
     MGet(Vec<Bytes>),
     /// MSET key1 value1 key2 value2 ... - set multiple key-value pairs
     MSet(Vec<(Bytes, Bytes)>),
+    /// MONITOR - stream every executed command to this connection
+    Monitor,
+    /// OBJECT ENCODING key - report the internal encoding of a value
+    ObjectEncoding(Bytes),
+    /// DEBUG OBJECT key - low-level details about a stored value
+    DebugObject(Bytes),
+    /// DEBUG ENCODING key expected - assert a value's OBJECT ENCODING
+    DebugEncoding(Bytes, Bytes),
+    /// DEBUG PANIC - panic while executing (exercises panic isolation)
+    DebugPanic,
+    /// DEBUG CHANGE-REPL-ID - generate a new run_id
+    DebugChangeReplId,
+    /// DEBUG PROTOCOL PLAIN|RESP - redis-cli style replies to inline
+    /// commands on this connection (true for PLAIN)
+    DebugPlainReplies(bool),
+    /// APPEND key value - append to a string, returning its new length
+    Append(Bytes, Bytes),
+    /// LCS key1 key2 [LEN] [IDX [MINMATCHLEN n] [WITHMATCHLEN]] - longest
+    /// common subsequence of two strings
+    Lcs(Bytes, Bytes, LcsOptions),
+    /// SETBIT key offset bit - set or clear a bit, returning its old value
+    SetBit(Bytes, u64, bool),
+    /// GETBIT key offset - read a bit (0 past the end of the string)
+    GetBit(Bytes, u64),
+    /// GETSET key value - set a value, returning the previous one
+    GetSet(Bytes, Bytes),
+    /// DBSIZE - number of keys
+    DbSize,
+    /// KEYS pattern - every key matching a glob pattern
+    Keys(Bytes),
+    /// SCAN cursor [MATCH pattern] [COUNT count] - walk the keyspace a
+    /// batch at a time
+    Scan {
+        /// 0 to start, then the cursor of the previous reply
+        cursor: u64,
+        /// Glob the returned keys must match (applied after a batch is
+        /// picked, as in Redis)
+        pattern: Option<Bytes>,
+        /// Keys to visit per call
+        count: usize,
+    },
+    /// FLUSHDB - remove every key of the current database
+    FlushDb,
+    /// FLUSHALL - remove every key of every database
+    FlushAll,
+    /// INFO [section] - server information and statistics
+    Info(Option<Bytes>),
+    /// CONFIG RESETSTAT - zero the INFO stats counters
+    ConfigResetStat,
+    /// MEMORY PURGE - return the allocator's free pages to the OS
+    MemoryPurge,
+    /// BGREWRITEAOF - compact the AOF in the background
+    BgRewriteAof,
+    /// COMMAND INFO name... - table metadata for the named commands
+    CommandInfo(Vec<Bytes>),
+    /// CLIENT NO-EVICT ON|OFF - exempt the connection from client eviction
+    ClientNoEvict(bool),
+    /// CLIENT PAUSE ms [WRITE|ALL] - hold back commands for a while
+    ClientPause(u64, PauseMode),
+    /// CLIENT UNPAUSE - end a CLIENT PAUSE early
+    ClientUnpause,
+    /// CLIENT LIST - one line per open connection
+    ClientList,
+    /// CLIENT INFO - the line of the calling connection
+    ClientInfo,
+    /// CLUSTER INFO|SLOTS|SHARDS|NODES - standalone answers for cluster probes
+    Cluster(ClusterSubcommand),
+    /// Well-framed request that failed validation (unknown command, wrong
+    /// arity, bad argument); executing it replies with the carried error
+    Invalid(String),
+}
+
+/// Keys a SCAN visits per call when no COUNT is given (as in Redis)
+pub const SCAN_DEFAULT_COUNT: usize = 10;
+
+/// Condition on the key's existence for SET
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetCond {
+    /// NX - only set if the key does not exist
+    Nx,
+    /// XX - only set if the key already exists
+    Xx,
+}
+
+/// CLUSTER subcommands answered by the standalone stub
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClusterSubcommand {
+    /// CLUSTER INFO - reports `cluster_enabled:0`
+    Info,
+    /// CLUSTER SLOTS - no slots served
+    Slots,
+    /// CLUSTER SHARDS - no shards
+    Shards,
+    /// CLUSTER NODES - empty node list (a bulk string, like Redis)
+    Nodes,
+}
+
+impl ClusterSubcommand {
+    /// Subcommand name as sent by clients
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Info => "INFO",
+            Self::Slots => "SLOTS",
+            Self::Shards => "SHARDS",
+            Self::Nodes => "NODES",
+        }
+    }
+}
+
+/// Optional modifiers of SET
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SetOptions {
+    /// NX / XX existence condition
+    pub cond: Option<SetCond>,
+    /// GET - reply with the previous value instead of OK
+    pub get: bool,
+    /// EX / PX / KEEPTTL - what happens to the key's expiry
+    pub expiry: Option<SetExpiry>,
+}
+
+/// Expiry modifier of SET
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetExpiry {
+    /// EX seconds - expire after this many seconds (positive)
+    Ex(u64),
+    /// PX milliseconds - expire after this many milliseconds (positive)
+    Px(u64),
+    /// PXAT unix-ms - expire at this Unix time in milliseconds (positive)
+    PxAt(u64),
+    /// KEEPTTL - keep the expiry the key already has
+    KeepTtl,
+}
+
+impl SetExpiry {
+    /// Render the modifier back into SET arguments
+    pub fn args(&self) -> Vec<Bytes> {
+        match self {
+            Self::Ex(secs) => vec![Bytes::from_static(b"EX"), Bytes::from(secs.to_string())],
+            Self::Px(ms) => vec![Bytes::from_static(b"PX"), Bytes::from(ms.to_string())],
+            Self::PxAt(unix_ms) => vec![Bytes::from_static(b"PXAT"), Bytes::from(unix_ms.to_string())],
+            Self::KeepTtl => vec![Bytes::from_static(b"KEEPTTL")],
+        }
+    }
+}
+
+impl SetOptions {
+    /// Render the options back into SET arguments
+    pub fn args(&self) -> Vec<Bytes> {
+        let mut out = Vec::new();
+        match self.cond {
+            Some(SetCond::Nx) => out.push(Bytes::from_static(b"NX")),
+            Some(SetCond::Xx) => out.push(Bytes::from_static(b"XX")),
+            None => {}
+        }
+        if self.get {
+            out.push(Bytes::from_static(b"GET"));
+        }
+        if let Some(expiry) = self.expiry {
+            out.extend(expiry.args());
+        }
+        out
+    }
+}
+
+/// Optional modifiers of LCS
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LcsOptions {
+    /// LEN - reply with the length only
+    pub len: bool,
+    /// IDX - reply with the matching ranges
+    pub idx: bool,
+    /// MINMATCHLEN - leave out ranges shorter than this
+    pub min_match_len: u64,
+    /// WITHMATCHLEN - include each range's length
+    pub with_match_len: bool,
+}
+
+impl LcsOptions {
+    /// Render the options back into LCS arguments
+    pub fn args(&self) -> Vec<Bytes> {
+        let mut out = Vec::new();
+        if self.len {
+            out.push(Bytes::from_static(b"LEN"));
+        }
+        if self.idx {
+            out.push(Bytes::from_static(b"IDX"));
+        }
+        if self.min_match_len > 0 {
+            out.push(Bytes::from_static(b"MINMATCHLEN"));
+            out.push(Bytes::from(self.min_match_len.to_string()));
+        }
+        if self.with_match_len {
+            out.push(Bytes::from_static(b"WITHMATCHLEN"));
+        }
+        out
+    }
+}
+
+impl Cmd {
+    /// Canonical (uppercase) command name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Cmd::Ping => "PING",
+            Cmd::Get(_) => "GET",
+            Cmd::Set(_, _) | Cmd::SetWith(..) => "SET",
+            Cmd::Del(_) => "DEL",
+            Cmd::Unlink(_) => "UNLINK",
+            Cmd::Rename(_, _) => "RENAME",
+            Cmd::Exists(_) => "EXISTS",
+            Cmd::Incr(_) => "INCR",
+            Cmd::MGet(_) => "MGET",
+            Cmd::MSet(_) => "MSET",
+            Cmd::Monitor => "MONITOR",
+            Cmd::ObjectEncoding(_) => "OBJECT",
+            Cmd::DebugObject(_) | Cmd::DebugEncoding(..) | Cmd::DebugPanic | Cmd::DebugChangeReplId | Cmd::DebugPlainReplies(_) => "DEBUG",
+            Cmd::SetBit(..) => "SETBIT",
+            Cmd::Append(..) => "APPEND",
+            Cmd::IncrBy(..) => "INCRBY",
+            Cmd::Decr(_) => "DECR",
+            Cmd::DecrBy(..) => "DECRBY",
+            Cmd::Expire(..) => "EXPIRE",
+            Cmd::PExpireAt(..) => "PEXPIREAT",
+            Cmd::Ttl(_) => "TTL",
+            Cmd::Lcs(..) => "LCS",
+            Cmd::GetBit(..) => "GETBIT",
+            Cmd::GetSet(_, _) => "GETSET",
+            Cmd::DbSize => "DBSIZE",
+            Cmd::Keys(_) => "KEYS",
+            Cmd::Scan { .. } => "SCAN",
+            Cmd::FlushDb => "FLUSHDB",
+            Cmd::FlushAll => "FLUSHALL",
+            Cmd::Info(_) => "INFO",
+            Cmd::Cluster(_) => "CLUSTER",
+            Cmd::ClientNoEvict(_) | Cmd::ClientPause(..) | Cmd::ClientUnpause | Cmd::ClientList | Cmd::ClientInfo => "CLIENT",
+            Cmd::ConfigResetStat => "CONFIG",
+            Cmd::MemoryPurge => "MEMORY",
+            Cmd::BgRewriteAof => "BGREWRITEAOF",
+            Cmd::CommandInfo(_) => "COMMAND",
+            Cmd::Invalid(_) => "",
+        }
+    }
+
+    /// Whether the command modifies the keyspace (its table entry is `write`)
+    pub fn is_write(&self) -> bool {
+        crate::command::lookup(self.name().as_bytes())
+            .is_some_and(|spec| spec.has_flag(crate::command::WRITE))
+    }
+
+    /// Whether the command may grow the dataset (its table entry is
+    /// `denyoom`), and so is refused once `maxmemory` can't be met
+    pub fn is_denyoom(&self) -> bool {
+        crate::command::lookup(self.name().as_bytes())
+            .is_some_and(|spec| spec.has_flag(crate::command::DENYOOM))
+    }
+
+    /// Keys the command reads or writes, in argument order
+    /// 
+    /// The key positions of its command table entry, read off the parsed
+    /// command; used to route it to the shard owning its keys. DEBUG
+    /// OBJECT and DEBUG ENCODING name a key too.
+    pub fn keys(&self) -> Vec<&Bytes> {
+        match self {
+            Cmd::Get(k)
+            | Cmd::Set(k, _)
+            | Cmd::SetWith(k, _, _)
+            | Cmd::Incr(k)
+            | Cmd::IncrBy(k, _)
+            | Cmd::Decr(k)
+            | Cmd::DecrBy(k, _)
+            | Cmd::Expire(k, _)
+            | Cmd::PExpireAt(k, _)
+            | Cmd::Ttl(k)
+            | Cmd::ObjectEncoding(k)
+            | Cmd::DebugObject(k)
+            | Cmd::DebugEncoding(k, _)
+            | Cmd::Append(k, _)
+            | Cmd::SetBit(k, _, _)
+            | Cmd::GetBit(k, _)
+            | Cmd::GetSet(k, _) => vec![k],
+            Cmd::Rename(a, b) | Cmd::Lcs(a, b, _) => vec![a, b],
+            Cmd::Del(keys) | Cmd::Unlink(keys) | Cmd::Exists(keys) | Cmd::MGet(keys) => keys.iter().collect(),
+            Cmd::MSet(pairs) => pairs.iter().map(|(k, _)| k).collect(),
+            Cmd::Ping
+            | Cmd::Monitor
+            | Cmd::DebugPanic
+            | Cmd::DebugChangeReplId
+            | Cmd::DebugPlainReplies(_)
+            | Cmd::DbSize
+            | Cmd::Keys(_)
+            | Cmd::Scan { .. }
+            | Cmd::FlushDb
+            | Cmd::FlushAll
+            | Cmd::Info(_)
+            | Cmd::ConfigResetStat
+            | Cmd::MemoryPurge
+            | Cmd::BgRewriteAof
+            | Cmd::CommandInfo(_)
+            | Cmd::ClientNoEvict(_)
+            | Cmd::ClientPause(..)
+            | Cmd::ClientUnpause
+            | Cmd::ClientList
+            | Cmd::ClientInfo
+            | Cmd::Cluster(_)
+            | Cmd::Invalid(_) => Vec::new(),
+        }
+    }
+
+    /// Arguments of the command, excluding the command name
+    /// 
+    /// Used wherever a command has to be rendered back into its
+    /// argument vector (e.g. the MONITOR feed). Cloning `Bytes` only
+    /// bumps a reference count.
+    pub fn args(&self) -> Vec<Bytes> {
+        match self {
+            Cmd::Ping | Cmd::Monitor | Cmd::DbSize | Cmd::FlushDb | Cmd::FlushAll | Cmd::BgRewriteAof | Cmd::Invalid(_) => Vec::new(),
+            Cmd::Get(k) | Cmd::Incr(k) | Cmd::Decr(k) | Cmd::Ttl(k) => vec![k.clone()],
+            Cmd::Expire(k, n) | Cmd::PExpireAt(k, n) | Cmd::IncrBy(k, n) | Cmd::DecrBy(k, n) => vec![k.clone(), Bytes::from(n.to_string())],
+            Cmd::ObjectEncoding(k) => vec![Bytes::from_static(b"ENCODING"), k.clone()],
+            Cmd::DebugObject(k) => vec![Bytes::from_static(b"OBJECT"), k.clone()],
+            Cmd::DebugEncoding(k, enc) => vec![Bytes::from_static(b"ENCODING"), k.clone(), enc.clone()],
+            Cmd::DebugPanic => vec![Bytes::from_static(b"PANIC")],
+            Cmd::DebugChangeReplId => vec![Bytes::from_static(b"CHANGE-REPL-ID")],
+            Cmd::DebugPlainReplies(plain) => vec![
+                Bytes::from_static(b"PROTOCOL"),
+                Bytes::from_static(if *plain { b"PLAIN" } else { b"RESP" }),
+            ],
+            Cmd::Cluster(sub) => vec![Bytes::from_static(sub.name().as_bytes())],
+            Cmd::ClientNoEvict(on) => vec![
+                Bytes::from_static(b"NO-EVICT"),
+                Bytes::from_static(if *on { b"ON" } else { b"OFF" }),
+            ],
+            Cmd::ClientPause(ms, mode) => vec![
+                Bytes::from_static(b"PAUSE"),
+                Bytes::from(ms.to_string()),
+                Bytes::from_static(mode.name().as_bytes()),
+            ],
+            Cmd::ClientUnpause => vec![Bytes::from_static(b"UNPAUSE")],
+            Cmd::ClientList => vec![Bytes::from_static(b"LIST")],
+            Cmd::ClientInfo => vec![Bytes::from_static(b"INFO")],
+            Cmd::Set(a, b) | Cmd::Rename(a, b) | Cmd::GetSet(a, b) | Cmd::Append(a, b) => vec![a.clone(), b.clone()],
+            Cmd::SetWith(k, v, opts) => [k.clone(), v.clone()].into_iter().chain(opts.args()).collect(),
+            Cmd::Lcs(a, b, opts) => [a.clone(), b.clone()].into_iter().chain(opts.args()).collect(),
+            Cmd::MGet(keys) | Cmd::Del(keys) | Cmd::Unlink(keys) | Cmd::Exists(keys) => keys.clone(),
+            Cmd::Info(section) => section.iter().cloned().collect(),
+            Cmd::Keys(pattern) => vec![pattern.clone()],
+            Cmd::ConfigResetStat => vec![Bytes::from_static(b"RESETSTAT")],
+            Cmd::MemoryPurge => vec![Bytes::from_static(b"PURGE")],
+            Cmd::CommandInfo(names) => std::iter::once(Bytes::from_static(b"INFO")).chain(names.iter().cloned()).collect(),
+            Cmd::MSet(pairs) => pairs.iter().flat_map(|(k, v)| [k.clone(), v.clone()]).collect(),
+            Cmd::SetBit(k, offset, bit) => vec![
+                k.clone(),
+                Bytes::from(offset.to_string()),
+                Bytes::from_static(if *bit { b"1" } else { b"0" }),
+            ],
+            Cmd::GetBit(k, offset) => vec![k.clone(), Bytes::from(offset.to_string())],
+            Cmd::Scan { cursor, pattern, count } => {
+                let mut args = vec![Bytes::from(cursor.to_string())];
+                if let Some(pattern) = pattern {
+                    args.extend([Bytes::from_static(b"MATCH"), pattern.clone()]);
+                }
+                if *count != SCAN_DEFAULT_COUNT {
+                    args.extend([Bytes::from_static(b"COUNT"), Bytes::from(count.to_string())]);
+                }
+                args
+            }
+        }
+    }
 }
 
 /// Value types that can be stored in Ignix
@@ -48,29 +434,175 @@ pub enum Value {
     Blob(Bytes),
 }
 
+/// Longest string Redis stores with the compact `embstr` encoding
+const EMBSTR_MAX_LEN: usize = 44;
+
+impl Value {
+    /// Redis-compatible name of the value's encoding (as OBJECT ENCODING reports it)
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "int",
+            Value::Str(s) if s.len() <= EMBSTR_MAX_LEN => "embstr",
+            Value::Str(_) | Value::Blob(_) => "raw",
+        }
+    }
+
+    /// Approximate length of the value in a serialized (RDB-style) dump
+    /// 
+    /// Small integers use the compact 1/2/4-byte encodings; everything else
+    /// is a length prefix followed by the raw bytes.
+    pub fn serialized_len(&self) -> usize {
+        fn prefixed(len: usize) -> usize {
+            let prefix = if len < 1 << 6 { 1 } else if len < 1 << 14 { 2 } else { 5 };
+            prefix + len
+        }
+        match self {
+            Value::Int(i) if i8::try_from(*i).is_ok() => 2,
+            Value::Int(i) if i16::try_from(*i).is_ok() => 3,
+            Value::Int(i) if i32::try_from(*i).is_ok() => 5,
+            Value::Int(i) => prefixed(i.to_string().len()),
+            Value::Str(s) | Value::Blob(s) => prefixed(s.len()),
+        }
+    }
+}
+
+/// Default maximum number of elements in a request array (Redis: 1024*1024)
+pub const DEFAULT_MAX_MULTIBULK_LEN: usize = 1024 * 1024;
+
+/// Default maximum size of a single string (Redis proto-max-bulk-len: 512MB)
+pub const DEFAULT_PROTO_MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+
+/// Upper bound on argument slots reserved up front, whatever the header claims
+const MAX_PREALLOC_ITEMS: usize = 64;
+
+/// Longest inline command line accepted while waiting for its newline
+pub const MAX_INLINE_LEN: usize = 64 * 1024;
+
+/// Protocol-level limits enforced while parsing client requests
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtoLimits {
+    /// Maximum element count accepted in a `*<count>` header
+    pub max_multibulk_len: usize,
+    /// Maximum size of a string value (`proto-max-bulk-len`): larger
+    /// `$<len>` headers are rejected before their payload is buffered;
+    /// also bounds how far SETBIT may grow a string
+    pub max_bulk_len: usize,
+}
+
+impl Default for ProtoLimits {
+    fn default() -> Self {
+        Self {
+            max_multibulk_len: DEFAULT_MAX_MULTIBULK_LEN,
+            max_bulk_len: DEFAULT_PROTO_MAX_BULK_LEN,
+        }
+    }
+}
+
+impl ProtoLimits {
+    /// No limits, for input the server wrote itself
+    /// 
+    /// The AOF may hold values accepted under a higher limit than the one
+    /// configured now; it has to load all the same.
+    pub const fn trusted() -> Self {
+        Self { max_multibulk_len: usize::MAX, max_bulk_len: usize::MAX }
+    }
+}
+
+/// Parse a single RESP command from byte data using the default limits
+/// 
+/// See [`parse_one_with`].
+pub fn parse_one(data: &[u8]) -> Result<Option<(usize, Cmd)>> {
+    parse_one_with(data, &ProtoLimits::default())
+}
+
 /// Parse a single RESP command from byte data
 /// 
 /// This function implements the core RESP parsing logic according to the Redis protocol.
 /// It expects commands in the format: *<count>\r\n$<len>\r\n<data>\r\n...
 /// 
+/// Input not starting with `*` is an inline command (`PING\r\n`) as typed
+/// into telnet or sent by simple scripts: whitespace-separated arguments
+/// terminated by `\r\n` or a bare `\n`. Blank inline lines are skipped, as
+/// are empty (`*0`) and null (`*-1`) arrays. Zero-length bulk strings
+/// (`$0\r\n\r\n`) are ordinary empty arguments.
+/// 
 /// # Arguments
 /// * `data` - Raw byte slice containing RESP-formatted command
+/// * `limits` - Protocol limits to enforce
 /// 
 /// # Returns
 /// * `Ok(Some((consumed_bytes, command)))` - Successfully parsed command
 /// * `Ok(None)` - Incomplete data, need more bytes
-/// * `Err(...)` - Protocol error or invalid command
-pub fn parse_one(data: &[u8]) -> Result<Option<(usize, Cmd)>> {
-    // Check if we have any data to parse
-    if data.is_empty() {
-        return Ok(None);
+/// * `Err(...)` - Protocol (framing) error
+/// 
+/// Unknown commands and bad arguments are not errors here: they parse to
+/// `Cmd::Invalid` so the connection can answer them and keep going.
+pub fn parse_one_with(data: &[u8], limits: &ProtoLimits) -> Result<Option<(usize, Cmd)>> {
+    Ok(parse_one_in(data, limits, &Renames::new())?.map(|(n, cmd, _)| (n, cmd)))
+}
+
+/// [`parse_one_with`] resolving command names through `renames`; also
+/// reports whether the command was sent inline
+fn parse_one_in(data: &[u8], limits: &ProtoLimits, renames: &Renames) -> Result<Option<(usize, Cmd, bool)>> {
+    let mut skipped = 0;
+    loop {
+        let rest = &data[skipped..];
+        // Check if we have any data to parse
+        if rest.is_empty() {
+            return Ok(None);
+        }
+        if rest[0] == b'*' {
+            match parse_multibulk(rest, limits, renames)? {
+                None => return Ok(None),
+                // `*0` / `*-1`: nothing to run, same as a blank line
+                Some((n, None)) => skipped += n,
+                Some((n, Some(cmd))) => return Ok(Some((skipped + n, cmd, false))),
+            }
+            continue;
+        }
+        match parse_inline(rest)? {
+            None => return Ok(None),
+            // Blank line: consume it and look at what follows
+            Some((n, items)) if items.is_empty() => skipped += n,
+            Some((n, items)) => return Ok(Some((skipped + n, renames.dispatch(items), true))),
+        }
     }
-    
-    // RESP arrays must start with '*'
-    if data[0] != b'*' {
-        bail!("protocol error: expected array");
+}
+
+/// Split one inline command line into its arguments
+/// 
+/// # Returns
+/// * `Ok(Some((consumed_bytes, args)))` - A full line (args empty if blank)
+/// * `Ok(None)` - No newline yet
+fn parse_inline(data: &[u8]) -> Result<Option<(usize, Vec<Bytes>)>> {
+    // The length check applies whether or not the newline has arrived, so
+    // the outcome doesn't depend on how the line was split across reads
+    let Some(nl) = data.iter().position(|&b| b == b'\n') else {
+        if data.len() > MAX_INLINE_LEN {
+            bail!("Protocol error: too big inline request");
+        }
+        return Ok(None);
+    };
+    if nl > MAX_INLINE_LEN {
+        bail!("Protocol error: too big inline request");
     }
-    
+    // Tolerate LF-only line endings
+    let line = data[..nl].strip_suffix(b"\r").unwrap_or(&data[..nl]);
+    let items = line
+        .split(|b| b.is_ascii_whitespace())
+        .filter(|arg| !arg.is_empty())
+        .map(Bytes::copy_from_slice)
+        .collect();
+    Ok(Some((nl + 1, items)))
+}
+
+/// Parse a RESP multibulk request (`data` starts with `*`)
+/// 
+/// # Returns
+/// * `Ok(Some((consumed_bytes, Some(cmd))))` - A complete request
+/// * `Ok(Some((consumed_bytes, None)))` - An empty or null array to skip
+/// * `Ok(None)` - Incomplete data, need more bytes
+fn parse_multibulk(data: &[u8], limits: &ProtoLimits, renames: &Renames) -> Result<Option<(usize, Option<Cmd>)>> {
     // Read the number of array elements
     let (i, n) = read_decimal_line(&data[1..])?;
     if i == 0 {
@@ -78,12 +610,17 @@ pub fn parse_one(data: &[u8]) -> Result<Option<(usize, Cmd)>> {
     }
     let mut cursor = 1 + i;
     
+    // Redis ignores empty and null request arrays
     if n <= 0 {
-        bail!("empty array");
+        return Ok(Some((cursor, None)));
+    }
+    if n as u64 > limits.max_multibulk_len as u64 {
+        bail!("Protocol error: invalid multibulk length");
     }
     
-    // Pre-allocate vector for better performance
-    let mut items: Vec<Bytes> = Vec::with_capacity(n as usize);
+    // Pre-allocate for the common case only; a hostile header must not
+    // reserve memory for elements that haven't arrived
+    let mut items: Vec<Bytes> = Vec::with_capacity((n as usize).min(MAX_PREALLOC_ITEMS));
     
     // Parse each array element (bulk strings)
     for _ in 0..n {
@@ -104,54 +641,40 @@ pub fn parse_one(data: &[u8]) -> Result<Option<(usize, Cmd)>> {
         }
         cursor += 1 + i2;
         
+        // A negative length would wrap around in the arithmetic below;
+        // zero is a legitimate empty argument. An oversized one is refused
+        // now rather than after buffering gigabytes waiting for it
+        if len < 0 || len as u64 > limits.max_bulk_len as u64 {
+            bail!("Protocol error: invalid bulk length");
+        }
+        let len = len as usize;
+        
         // Calculate total bytes needed (length + \r\n)
-        let need = len as usize + 2;
-        if cursor + need > data.len() {
+        let need = len + 2;
+        if data.len() - cursor < need {
             return Ok(None); // Need more data
         }
+        if &data[cursor + len..cursor + need] != b"\r\n" {
+            bail!("expected CRLF");
+        }
         
         // Extract the payload
-        let payload = &data[cursor..cursor + len as usize];
+        let payload = &data[cursor..cursor + len];
         items.push(Bytes::copy_from_slice(payload));
         cursor += need;
     }
     
-    if items.is_empty() {
-        bail!("empty array body");
-    }
+    // Look the command up in the table and validate its arguments
+    let cmd = renames.dispatch(items);
     
-    // Match command names and validate argument counts
-    // Using case-insensitive comparison without allocation
-    let cmd = if items[0].eq_ignore_ascii_case(b"PING") {
-        Cmd::Ping
-    } else if items[0].eq_ignore_ascii_case(b"GET") && items.len() >= 2 {
-        Cmd::Get(items[1].clone())
-    } else if items[0].eq_ignore_ascii_case(b"SET") && items.len() >= 3 {
-        Cmd::Set(items[1].clone(), items[2].clone())
-    } else if items[0].eq_ignore_ascii_case(b"DEL") && items.len() >= 2 {
-        Cmd::Del(items[1].clone())
-    } else if items[0].eq_ignore_ascii_case(b"RENAME") && items.len() >= 3 {
-        Cmd::Rename(items[1].clone(), items[2].clone())
-    } else if items[0].eq_ignore_ascii_case(b"EXISTS") && items.len() >= 2 {
-        Cmd::Exists(items[1].clone())
-    } else if items[0].eq_ignore_ascii_case(b"INCR") && items.len() >= 2 {
-        Cmd::Incr(items[1].clone())
-    } else if items[0].eq_ignore_ascii_case(b"MGET") && items.len() >= 2 {
-        Cmd::MGet(items[1..].to_vec())
-    } else if items[0].eq_ignore_ascii_case(b"MSET") && items.len() >= 3 && items.len() % 2 == 1 {
-        // MSET requires odd number of args (command + key-value pairs)
-        let mut v = Vec::with_capacity((items.len() - 1) / 2);
-        for pair in items[1..].chunks(2) {
-            if pair.len() == 2 {
-                v.push((pair[0].clone(), pair[1].clone()));
-            }
-        }
-        Cmd::MSet(v)
-    } else {
-        bail!("unknown/invalid command");
-    };
-    
-    Ok(Some((cursor, cmd)))
+    Ok(Some((cursor, Some(cmd))))
+}
+
+/// Parse multiple RESP commands from a buffer using the default limits
+/// 
+/// See [`parse_many_with`].
+pub fn parse_many(buf: &mut bytes::BytesMut, out: &mut Vec<Cmd>) -> Result<()> {
+    parse_many_with(buf, out, &ProtoLimits::default())
 }
 
 /// Parse multiple RESP commands from a buffer
@@ -162,9 +685,39 @@ pub fn parse_one(data: &[u8]) -> Result<Option<(usize, Cmd)>> {
 /// # Arguments
 /// * `buf` - Mutable buffer containing RESP data
 /// * `out` - Vector to store parsed commands
-pub fn parse_many(buf: &mut bytes::BytesMut, out: &mut Vec<Cmd>) -> Result<()> {
+/// * `limits` - Protocol limits to enforce
+pub fn parse_many_with(buf: &mut bytes::BytesMut, out: &mut Vec<Cmd>, limits: &ProtoLimits) -> Result<()> {
+    parse_many_in(buf, out, None, limits, &Renames::new())
+}
+
+/// Parse multiple RESP commands under a server configuration
+/// 
+/// Applies the configured protocol limits and `rename-command` renames;
+/// this is what connections use.
+pub fn parse_many_for(buf: &mut bytes::BytesMut, out: &mut Vec<Cmd>, config: &Config) -> Result<()> {
+    parse_many_in(buf, out, None, &config.proto, &config.renamed_commands)
+}
+
+/// [`parse_many_for`] also recording, per command, whether it was sent
+/// inline (for plain replies)
+pub(crate) fn parse_many_framed(
+    buf: &mut bytes::BytesMut,
+    out: &mut Vec<Cmd>,
+    inline: &mut Vec<bool>,
+    config: &Config,
+) -> Result<()> {
+    parse_many_in(buf, out, Some(inline), &config.proto, &config.renamed_commands)
+}
+
+fn parse_many_in(
+    buf: &mut bytes::BytesMut,
+    out: &mut Vec<Cmd>,
+    mut inline: Option<&mut Vec<bool>>,
+    limits: &ProtoLimits,
+    renames: &Renames,
+) -> Result<()> {
     loop {
-        let (consumed, cmd) = match parse_one(&buf[..])? {
+        let (consumed, cmd, was_inline) = match parse_one_in(&buf[..], limits, renames)? {
             Some(x) => x,
             None => break, // No complete command available
         };
@@ -172,6 +725,9 @@ pub fn parse_many(buf: &mut bytes::BytesMut, out: &mut Vec<Cmd>) -> Result<()> {
         // Remove consumed bytes from buffer
         buf.advance(consumed);
         out.push(cmd);
+        if let Some(inline) = inline.as_deref_mut() {
+            inline.push(was_inline);
+        }
     }
     Ok(())
 }
@@ -193,62 +749,19 @@ fn read_decimal_line(s: &[u8]) -> Result<(usize, i64)> {
         i += 1;
     }
 
-    let start = i;
-
-    // Fast path: Try to read 8 bytes at a time
-    // We only do this if we have at least 8 bytes remaining
-    while i + 8 <= s.len() {
-        // Read 8 bytes as u64 (Little Endian)
-        let chunk = u64::from_le_bytes(s[i..i+8].try_into().unwrap());
-        
-        // Check if all bytes are digits (0x30..=0x39)
-        // Algorithm:
-        // 1. Add 0x46 (0x7F - 0x39) to each byte. If byte was > 0x39, it will overflow into high bit (0x80).
-        // 2. Subtract 0x30 from each byte. If byte was < 0x30, it will underflow (borrow) from high bit?
-        // Actually, a simpler check for "is any byte not a digit":
-        // val < '0' || val > '9'
-        // Using SWAR:
-        // has_less = (chunk - 0x3030303030303030) & 0x8080808080808080
-        // has_more = (chunk + 0x4646464646464646) & 0x8080808080808080
-        // If either is non-zero, we have a non-digit.
-        
-        let val_minus_0 = chunk.wrapping_sub(0x3030303030303030);
-        let val_plus_46 = chunk.wrapping_add(0x4646464646464646); // 0x46 = 127 - 57 ('9')
-        
-        if (val_minus_0 | val_plus_46) & 0x8080808080808080 != 0 {
-            // Found a non-digit in this chunk, fall back to byte-by-byte
-            break;
-        }
-
-        // All 8 bytes are digits. Parse them.
-        // This is tricky to do efficiently in parallel without SIMD intrinsics.
-        // But we can just loop unroll here since we know they are valid.
-        // Or just continue byte-by-byte loop which is now branch-predicted well?
-        // Actually, the main win of SWAR is finding the *end* of the string quickly.
-        // Parsing still needs multiplication.
-        // Let's just fall through to byte loop, but we know the next 8 bytes are valid?
-        // No, let's just use the byte loop. The "SWAR" part here is mostly useful for *skipping* or *finding* delimiters.
-        // For parsing integers, the multiplication dependency chain is the bottleneck.
-        
-        // Let's stick to a simple unrolled loop which compilers vectorize well.
-        break; 
-    }
-
     // Standard loop (compiler usually vectorizes this well if simple)
     while i < s.len() {
         let c = s[i];
         if c.is_ascii_digit() {
-            num = num.wrapping_mul(10).wrapping_add((c - b'0') as i64);
+            // Saturate: an absurd length must fail the limit checks, not
+            // wrap around to a small one
+            num = num.saturating_mul(10).saturating_add((c - b'0') as i64);
             i += 1;
         } else {
             break;
         }
     }
 
-    if i == start {
-        // No digits found
-    }
-
     // Check for \r\n
     if i + 1 < s.len() && s[i] == b'\r' && s[i + 1] == b'\n' {
         Ok((i + 2, num * sign))
@@ -338,6 +851,14 @@ pub fn write_simple(s: &str, out: &mut BytesMut) {
     out.put_slice(b"\r\n");
 }
 
+/// Write an error response (-ERR message\r\n) directly to buffer
+pub fn write_error(msg: &str, out: &mut BytesMut) {
+    out.reserve(1 + msg.len() + 2);
+    out.put_u8(b'-');
+    out.put_slice(msg.as_bytes());
+    out.put_slice(b"\r\n");
+}
+
 /// Write a bulk string response ($<len>\r\n<data>\r\n) directly to buffer
 pub fn write_bulk(b: &[u8], out: &mut BytesMut) {
     let len_str = b.len().to_string();
@@ -354,6 +875,11 @@ pub fn write_null(out: &mut BytesMut) {
     out.extend_from_slice(b"$-1\r\n");
 }
 
+/// Write a null array response (*-1\r\n) directly to buffer
+pub fn write_null_array(out: &mut BytesMut) {
+    out.extend_from_slice(b"*-1\r\n");
+}
+
 /// Write an integer response (:<number>\r\n) directly to buffer
 pub fn write_integer(i: i64, out: &mut BytesMut) {
     let i_str = i.to_string();
@@ -370,4 +896,69 @@ pub fn write_array_len(n: usize, out: &mut BytesMut) {
     out.put_u8(b'*');
     out.put_slice(len_str.as_bytes());
     out.put_slice(b"\r\n");
-}
\ No newline at end of file
+}
+//
+// Plain replies (DEBUG PROTOCOL PLAIN)
+
+/// Render RESP replies the way redis-cli prints them, one per line
+/// 
+/// Meant for humans typing inline commands over telnet: `OK`,
+/// `(integer) 5`, `"hello"`, `(nil)`, `(error) ERR ...` and numbered,
+/// indented array elements.
+/// 
+/// # Returns
+/// * `Some(text)` - Every reply in `resp`, each ending with `\r\n`
+/// * `None` - `resp` is not a sequence of complete replies
+pub fn resp_to_plain(mut resp: &[u8]) -> Option<String> {
+    let mut text = String::new();
+    while !resp.is_empty() {
+        let n = plain_reply(resp, 0, &mut text)?;
+        text.push_str("\r\n");
+        resp = &resp[n..];
+    }
+    Some(text)
+}
+
+/// Render one reply, indenting continuation lines by `indent`
+/// 
+/// # Returns
+/// * Bytes of `resp` consumed
+fn plain_reply(resp: &[u8], indent: usize, text: &mut String) -> Option<usize> {
+    let end = resp.windows(2).position(|w| w == b"\r\n")?;
+    let line = std::str::from_utf8(&resp[1..end]).ok()?;
+    let mut consumed = end + 2;
+    match resp[0] {
+        b'+' => text.push_str(line),
+        b'-' => text.push_str(&format!("(error) {}", line)),
+        b':' => text.push_str(&format!("(integer) {}", line)),
+        b'$' => {
+            let Some(len) = usize::try_from(line.parse::<i64>().ok()?).ok() else {
+                text.push_str("(nil)");
+                return Some(consumed);
+            };
+            let data = resp.get(consumed..consumed + len)?;
+            text.push_str(&format!("\"{}\"", data.escape_ascii()));
+            consumed += len + 2;
+        }
+        b'*' => {
+            let Some(count) = usize::try_from(line.parse::<i64>().ok()?).ok() else {
+                text.push_str("(nil)");
+                return Some(consumed);
+            };
+            if count == 0 {
+                text.push_str("(empty array)");
+            }
+            for i in 1..=count {
+                let prefix = format!("{}) ", i);
+                if i > 1 {
+                    text.push_str("\r\n");
+                    text.push_str(&" ".repeat(indent));
+                }
+                text.push_str(&prefix);
+                consumed += plain_reply(resp.get(consumed..)?, indent + prefix.len(), text)?;
+            }
+        }
+        _ => return None,
+    }
+    (consumed <= resp.len()).then_some(consumed)
+}
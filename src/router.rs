@@ -0,0 +1,83 @@
+/*!
+ * Owned-Shard Routing
+ *
+ * With `Config::owned_shards`, the mio backend starts one worker per shard
+ * of its [`ShardSet`], and worker `i` is the only thread that runs
+ * commands on the keys of shard `i`, so the locks of that shard's
+ * dictionary are never contended. A connection's command whose keys
+ * another worker owns is sent to that worker's inbox, runs there, and its
+ * reply comes back through the inbox of the connection's worker. The rest
+ * of the connection's pipeline waits for that reply, so replies keep the
+ * order of the requests.
+ *
+ * Commands without keys (DBSIZE, KEYS, SCAN, FLUSHALL, INFO, CLIENT ...)
+ * and those refused with CROSSSLOT run on the connection's own worker,
+ * as they do in the shared mode.
+ */
+
+use crate::protocol::Cmd;
+use crate::shard_set::ShardSet;
+use bytes::BytesMut;
+use crossbeam::channel::{unbounded, Receiver, Sender};
+use mio::Waker;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+
+/// What one worker sends another
+pub(crate) enum Message {
+    /// Run a command owned by the receiving worker and send the reply to
+    /// connection `token` of worker `from`
+    Exec { cmd: Cmd, from: usize, token: usize },
+    /// The reply to a command connection `token` forwarded; `None` if the
+    /// command panicked
+    Reply { token: usize, reply: Option<BytesMut> },
+}
+
+/// The inboxes of every worker, indexed like the shards they own
+pub(crate) struct Router {
+    inboxes: Vec<(Sender<Message>, Arc<Waker>)>,
+}
+
+impl Router {
+    /// Create an inbox for each worker, woken through its waker
+    ///
+    /// # Returns
+    /// * The router and the receiving end of each inbox, in worker order
+    pub fn new(wakers: Vec<Arc<Waker>>) -> (Arc<Self>, Vec<Receiver<Message>>) {
+        let (inboxes, receivers) = wakers
+            .into_iter()
+            .map(|waker| {
+                let (tx, rx) = unbounded();
+                ((tx, waker), rx)
+            })
+            .unzip();
+        (Arc::new(Self { inboxes }), receivers)
+    }
+
+    /// Queue a message for a worker and wake it
+    ///
+    /// # Returns
+    /// * `false` if the worker is gone
+    pub fn send(&self, worker: usize, msg: Message) -> bool {
+        let Some((tx, waker)) = self.inboxes.get(worker) else { return false };
+        tx.send(msg).is_ok() && waker.wake().is_ok()
+    }
+}
+
+/// A connection's way to the workers owning the keys of its commands
+pub(crate) struct Route {
+    pub router: Arc<Router>,
+    /// Worker serving the connection, owner of the shard of that index
+    pub worker: usize,
+    /// The connection's token on its worker, which replies are sent to
+    pub token: usize,
+}
+
+/// Run a command forwarded by another worker
+///
+/// # Returns
+/// * The reply, or `None` if the command panicked
+pub(crate) fn run_forwarded(shards: &ShardSet, cmd: Cmd) -> Option<BytesMut> {
+    let mut out = BytesMut::new();
+    catch_unwind(AssertUnwindSafe(|| shards.exec(cmd, &mut out))).ok().map(|()| out)
+}
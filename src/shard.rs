@@ -6,16 +6,29 @@
  * and maintains its own storage and AOF logging.
  */
 
-use crate::aof::{emit_aof_incr, emit_aof_mset, emit_aof_rename, emit_aof_set, AofHandle};
-use crate::protocol::{write_array_len, write_bulk, write_integer, write_null, write_simple, Cmd, Value};
-use crate::storage::Dict;
-use bytes::BytesMut;
+use crate::aof::{emit_aof_append, emit_aof_del, emit_aof_incrby, emit_aof_pexpireat, emit_aof_set_with, emit_aof_flushall, emit_aof_incr, emit_aof_mset, emit_aof_rename, emit_aof_set, emit_aof_setbit, AofHandle};
+use crate::config::{Config, MaxmemoryPolicy};
+use crate::error::CmdError;
+use crate::glob::glob_match;
+use crate::lazyfree::LazyFree;
+use crate::lcs::{lcs, lcs_table_size};
+use crate::monitor::Monitors;
+use crate::pause::ClientPause;
+use crate::stats::{DatasetStats, Stats};
+use crate::protocol::{write_array_len, write_bulk, write_error, write_integer, write_null, write_null_array, write_simple, ClusterSubcommand, Cmd, SetExpiry, Value};
+use crate::storage::{parse_int, Dict, KeyExpiry, WallClock};
+use bytes::{Bytes, BytesMut};
+use parking_lot::RwLock;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// A shard represents a single execution unit
 /// 
 /// Each shard has its own storage dictionary and optional AOF handle
-/// for persistence. In the current implementation, Ignix uses a single
-/// shard, but the architecture supports multiple shards for future scaling.
+/// for persistence. A server runs one shard, or several in a
+/// [`crate::ShardSet`] that routes each command to the shard owning its
+/// keys.
 #[repr(align(64))]
 pub struct Shard {
     /// Unique identifier for this shard
@@ -24,6 +37,27 @@ pub struct Shard {
     pub dict: Dict,
     /// Optional AOF handle for persistence
     pub aof: Option<AofHandle>,
+    /// Connections in MONITOR mode, fed by the network layer
+    pub monitors: Monitors,
+    /// Server configuration
+    pub config: Config,
+    /// Counters reported by INFO (shared by the shards of a set)
+    pub stats: Arc<Stats>,
+    /// Background thread freeing large deleted values
+    pub lazyfree: LazyFree,
+    /// CLIENT PAUSE deadline, checked before each command
+    pub pause: ClientPause,
+    /// Memory-mapped home of values of at least `config.blob_threshold`
+    #[cfg(all(feature = "blob-store", target_os = "linux"))]
+    pub blobs: Option<crate::blob_store::BlobStore>,
+    /// Bytes of write traffic propagated so far (`master_repl_offset`)
+    repl_offset: AtomicU64,
+    /// Held shared by write commands while an AOF is attached, and
+    /// exclusively while a rewrite snapshots the dataset
+    pub(crate) rewrite_gate: RwLock<()>,
+    /// Set while the AOF is being replayed, so loaded commands are not
+    /// logged again
+    loading: AtomicBool,
 }
 
 impl Shard {
@@ -33,10 +67,30 @@ impl Shard {
     /// * `id` - Unique identifier for this shard
     /// * `aof` - Optional AOF handle for command logging
     pub fn new(id: usize, aof: Option<AofHandle>) -> Self {
+        Self::with_config(id, aof, Config::default())
+    }
+
+    /// Create a new shard with an explicit configuration
+    /// 
+    /// # Arguments
+    /// * `id` - Unique identifier for this shard
+    /// * `aof` - Optional AOF handle for command logging
+    /// * `config` - Server configuration
+    pub fn with_config(id: usize, aof: Option<AofHandle>, config: Config) -> Self {
         Self {
             id,
-            dict: Dict::default(),
+            dict: Dict::with_capacity(config.expected_keys),
             aof,
+            monitors: Monitors::default(),
+            config,
+            stats: Arc::default(),
+            lazyfree: LazyFree::default(),
+            pause: ClientPause::default(),
+            #[cfg(all(feature = "blob-store", target_os = "linux"))]
+            blobs: None,
+            repl_offset: AtomicU64::new(0),
+            rewrite_gate: RwLock::new(()),
+            loading: AtomicBool::new(false),
         }
     }
     
@@ -50,12 +104,65 @@ impl Shard {
     /// * `cmd` - Parsed Redis command to execute
     /// * `out` - Buffer to write response to
     pub fn exec(&self, cmd: Cmd, out: &mut BytesMut) {
+        if self.exec_logged(cmd, out) && self.aof_rewrite_due() {
+            let _ = self.rewrite_aof();
+        }
+    }
+
+    /// [`Shard::exec`] without the automatic AOF rewrite, which shards
+    /// sharing an AOF must start over all of them
+    /// 
+    /// # Returns
+    /// * Whether the command was a write executed with an AOF attached
+    pub(crate) fn exec_logged(&self, cmd: Cmd, out: &mut BytesMut) -> bool {
+        match &self.aof {
+            Some(aof) if cmd.is_write() => {
+                // Refuse writes we can't persist while the AOF is failing
+                if aof.is_failed() {
+                    CmdError::Misconf.write(out);
+                    return false;
+                }
+                // Held across the change and its AOF entry, so a rewrite's
+                // snapshot never falls between the two
+                let _gate = self.rewrite_gate.read();
+                self.apply(cmd, out);
+                true
+            }
+            _ => {
+                self.apply(cmd, out);
+                false
+            }
+        }
+    }
+
+    /// Whether a command gets past the checks made before it runs: a
+    /// working AOF for writes (else MISCONF) and, for commands that may
+    /// grow the dataset, room under `maxmemory` or keys to evict (else OOM)
+    /// 
+    /// Side-effect free; [`Shard::exec`] makes the same checks itself,
+    /// evicting keys as needed.
+    pub(crate) fn admits(&self, cmd: &Cmd) -> bool {
+        if cmd.is_write() && self.aof.as_ref().is_some_and(|aof| aof.is_failed()) {
+            return false;
+        }
+        let full = self.config.maxmemory_policy == MaxmemoryPolicy::NoEviction && self.dict.used_memory() > self.config.maxmemory;
+        !(self.config.maxmemory > 0 && cmd.is_denyoom() && full)
+    }
+
+    /// Execute a command whose AOF preconditions have been checked
+    fn apply(&self, cmd: Cmd, out: &mut BytesMut) {
+        // Make room before anything that may grow the dataset; the AOF is
+        // replayed whole
+        if self.config.maxmemory > 0 && !self.loading.load(Ordering::Relaxed) && cmd.is_denyoom() && !self.free_memory() {
+            CmdError::Oom.write(out);
+            return;
+        }
         match cmd {
             // PING command - simple connectivity test
             Cmd::Ping => write_simple("PONG", out),
             
             // GET key - retrieve value for key
-            Cmd::Get(k) => match self.dict.get(&k) {
+            Cmd::Get(k) => match self.lookup_read(&k) {
                 // Return string/blob values as bulk strings
                 Some(Value::Str(v)) | Some(Value::Blob(v)) => write_bulk(&v, out),
                 // Return integer values as Bulk Strings (Redis protocol requirement for GET)
@@ -66,85 +173,184 @@ impl Shard {
             
             // SET key value - store key-value pair
             Cmd::Set(k, v) => {
-                // Log to AOF if persistence is enabled
-                // We do this before moving k and v into the dictionary
-                if let Some(a) = &self.aof {
-                    a.write(&emit_aof_set(&k, &v));
-                }
-
-                // Optimization: Try to store as integer if possible
-                // Fast fail: Integers fit in 20 chars and start with digit or '-'
-                let val = if v.len() <= 20 && !v.is_empty() && (v[0].is_ascii_digit() || v[0] == b'-') {
-                     if let Ok(s) = std::str::from_utf8(&v) {
-                        if let Ok(i) = s.parse::<i64>() {
-                            Value::Int(i)
-                        } else {
-                            Value::Str(v)
-                        }
-                    } else {
-                        Value::Str(v)
+                // Bytes clones are refcount bumps; keep them for the AOF entry
+                let old = self.dict.getset(k.clone(), self.encode(v.clone()));
+                self.release_replaced(old);
+                self.propagate(|| emit_aof_set(&k, &v));
+                write_simple("OK", out);
+            }
+            
+            // SET key value [NX|XX] [GET] - conditional and/or returning the old value
+            Cmd::SetWith(k, v, opts) => {
+                let Some(expiry) = key_expiry(opts.expiry) else {
+                    CmdError::InvalidExpire("set".into()).write(out);
+                    return;
+                };
+                let (old, written) = self.dict.set_with(k.clone(), self.encode(v.clone()), opts.cond, expiry);
+                if written {
+                    // EX/PX are logged as the absolute PXAT, so a replay
+                    // doesn't start the time to live over
+                    let logged = match expiry {
+                        KeyExpiry::At(deadline) => Some(SetExpiry::PxAt(WallClock::now().unix_ms_of(deadline).max(1) as u64)),
+                        KeyExpiry::Keep | KeyExpiry::Clear => opts.expiry,
+                    };
+                    self.propagate(|| emit_aof_set_with(&k, &v, logged));
+                }
+                if opts.get {
+                    // Every stored value is a string today; WRONGTYPE applies
+                    // once other types exist
+                    match &old {
+                        Some(Value::Str(s)) | Some(Value::Blob(s)) => write_bulk(s, out),
+                        Some(Value::Int(i)) => write_bulk(i.to_string().as_bytes(), out),
+                        None => write_null(out),
                     }
+                } else if written {
+                    write_simple("OK", out);
                 } else {
-                    Value::Str(v)
-                };
+                    write_null(out);
+                }
+                if written {
+                    self.release_replaced(old);
+                }
+            }
 
-                self.dict.set(k, val);
-                
-                write_simple("OK", out);
+            // GETSET key value - atomic swap, old value replied as a string
+            Cmd::GetSet(k, v) => {
+                let old = self.dict.getset(k.clone(), self.encode(v.clone()));
+                self.propagate(|| emit_aof_set(&k, &v));
+                match &old {
+                    Some(Value::Str(s)) | Some(Value::Blob(s)) => write_bulk(s, out),
+                    // Integer-encoded values are returned in their string form
+                    Some(Value::Int(i)) => write_bulk(i.to_string().as_bytes(), out),
+                    None => write_null(out),
+                }
+                self.release_replaced(old);
             }
-            
-            // DEL key - delete key
-            Cmd::Del(k) => {
-                // Delete key and return 1 if it existed, 0 if not
-                let removed = self.dict.del(&k) as i64;
-                write_integer(removed, out);
+
+            // DEL key1 key2 ... - number of keys that existed
+            Cmd::Del(keys) => {
+                let mut deleted = Vec::with_capacity(keys.len());
+                for k in keys {
+                    if let Some(v) = self.dict.remove(&k) {
+                        if self.config.lazyfree_lazy_user_del {
+                            self.lazyfree.free(v);
+                        }
+                        deleted.push(k);
+                    }
+                }
+                // Only the keys actually removed are logged
+                if !deleted.is_empty() {
+                    self.propagate(|| emit_aof_del(&deleted));
+                }
+                write_integer(deleted.len() as i64, out);
+            }
+
+            // UNLINK key1 key2 ... - like DEL, but large values are freed off-thread
+            Cmd::Unlink(keys) => {
+                let mut deleted = Vec::with_capacity(keys.len());
+                for k in keys {
+                    if let Some(v) = self.dict.remove(&k) {
+                        self.lazyfree.free(v);
+                        deleted.push(k);
+                    }
+                }
+                if !deleted.is_empty() {
+                    self.propagate(|| emit_aof_del(&deleted));
+                }
+                write_integer(deleted.len() as i64, out);
             }
             
             // RENAME oldkey newkey - rename a key
             Cmd::Rename(from, to) => {
-                if self.aof.is_some() {
-                    let ok = self.dict.rename(from.clone(), to.clone());
-                    if ok {
-                         if let Some(a) = &self.aof {
-                            a.write(&emit_aof_rename(&from, &to));
-                        }
-                        write_simple("OK", out);
-                    } else {
-                        write_simple("ERR no such key", out);
-                    }
+                if self.dict.rename(from.clone(), to.clone()) {
+                    self.propagate(|| emit_aof_rename(&from, &to));
+                    write_simple("OK", out);
                 } else {
-                    // No AOF, we can move directly
-                    let ok = self.dict.rename(from, to);
-                    if ok {
-                        write_simple("OK", out);
-                    } else {
-                        write_simple("ERR no such key", out);
-                    }
+                    CmdError::NoSuchKey.write(out);
                 }
             }
             
-            // EXISTS key - check if key exists
-            Cmd::Exists(k) => write_integer(self.dict.exists(&k) as i64, out),
+            // EXISTS key1 key2 ... - a key given twice is counted twice
+            Cmd::Exists(keys) => write_integer(keys.iter().filter(|k| self.dict.exists(k)).count() as i64, out),
             
             // INCR key - increment numeric value
             Cmd::Incr(k) => {
-                let v = self.dict.incr(&k);
-                
-                // Log increment to AOF
-                if let Some(a) = &self.aof {
-                    a.write(&emit_aof_incr(&k));
+                match self.dict.incr(&k) {
+                    Ok(v) => {
+                        self.propagate(|| emit_aof_incr(&k));
+                        write_integer(v, out);
+                    }
+                    // The value is left untouched and nothing is logged
+                    Err(e) => CmdError::from(e).write(out),
                 }
-                
-                write_integer(v, out);
             }
             
+            // INCRBY / DECR / DECRBY - logged as the equivalent INCRBY
+            Cmd::IncrBy(k, delta) => self.incr_by(k, Some(delta), out),
+            Cmd::Decr(k) => self.incr_by(k, Some(-1), out),
+            // Negating i64::MIN overflows before the value is even read
+            Cmd::DecrBy(k, delta) => self.incr_by(k, delta.checked_neg(), out),
+
+            // EXPIRE key seconds - a deadline in the past deletes the key;
+            // logged as the absolute PEXPIREAT, so a replay after the
+            // deadline doesn't bring the key back
+            Cmd::Expire(k, secs) => {
+                let applied = if secs > 0 {
+                    let Some(deadline) = Instant::now().checked_add(Duration::from_secs(secs as u64)) else {
+                        CmdError::InvalidExpire("expire".into()).write(out);
+                        return;
+                    };
+                    let applied = self.dict.expire_at(&k, deadline);
+                    if applied {
+                        self.propagate(|| emit_aof_pexpireat(&k, WallClock::now().unix_ms_of(deadline)));
+                    }
+                    applied
+                } else {
+                    let applied = self.dict.del(&k);
+                    if applied {
+                        self.propagate(|| emit_aof_del(std::slice::from_ref(&k)));
+                    }
+                    applied
+                };
+                write_integer(applied as i64, out);
+            }
+
+            // PEXPIREAT key unix-ms - a time in the past deletes the key
+            Cmd::PExpireAt(k, unix_ms) => {
+                let clock = WallClock::now();
+                let applied = if unix_ms > clock.unix_ms() {
+                    let Some(deadline) = clock.deadline_of(unix_ms) else {
+                        CmdError::InvalidExpire("pexpireat".into()).write(out);
+                        return;
+                    };
+                    self.dict.expire_at(&k, deadline)
+                } else {
+                    self.dict.del(&k)
+                };
+                if applied {
+                    self.propagate(|| emit_aof_pexpireat(&k, unix_ms));
+                }
+                write_integer(applied as i64, out);
+            }
+
+            // TTL key - -2 for a missing key, -1 for one without expiry
+            Cmd::Ttl(k) => {
+                let ttl = match self.dict.ttl(&k) {
+                    None => -2,
+                    Some(None) => -1,
+                    // Rounded to the nearest second, as Redis does
+                    Some(Some(left)) => ((left.as_millis() + 500) / 1000) as i64,
+                };
+                write_integer(ttl, out);
+            }
+
             // MGET key1 key2 ... - get multiple keys
             Cmd::MGet(keys) => {
                 write_array_len(keys.len(), out);
                 
                 // Get each key and format as RESP
                 for k in keys {
-                    match self.dict.get(&k) {
+                    match self.lookup_read(&k) {
                         Some(Value::Str(v)) | Some(Value::Blob(v)) => write_bulk(&v, out),
                         Some(Value::Int(i)) => write_bulk(i.to_string().as_bytes(), out),
                         None => write_null(out),
@@ -154,34 +360,545 @@ impl Shard {
             
             // MSET key1 value1 key2 value2 ... - set multiple key-value pairs
             Cmd::MSet(pairs) => {
+                for (k, v) in &pairs {
+                    let old = self.dict.getset(k.clone(), self.encode(v.clone()));
+                    self.release_replaced(old);
+                }
                 // Log all sets to AOF as a single operation
-                if let Some(a) = &self.aof {
-                    a.write(&emit_aof_mset(&pairs));
-                }
-
-                // Set all key-value pairs
-                for (k, v) in pairs {
-                    // Optimization: Try to store as integer if possible
-                    // Fast fail: Integers fit in 20 chars and start with digit or '-'
-                    let val = if v.len() <= 20 && !v.is_empty() && (v[0].is_ascii_digit() || v[0] == b'-') {
-                        if let Ok(s) = std::str::from_utf8(&v) {
-                            if let Ok(i) = s.parse::<i64>() {
-                                Value::Int(i)
-                            } else {
-                                Value::Str(v)
-                            }
-                        } else {
-                            Value::Str(v)
+                self.propagate(|| emit_aof_mset(&pairs));
+                write_simple("OK", out);
+            }
+
+            // SETBIT key offset bit - bounded by proto-max-bulk-len
+            Cmd::SetBit(k, offset, bit) => {
+                if !self.bit_offset_ok(offset) {
+                    CmdError::BitOffset.write(out);
+                    return;
+                }
+                let old = self.dict.setbit(&k, offset, bit);
+                self.propagate(|| emit_aof_setbit(&k, offset, bit));
+                write_integer(old as i64, out);
+            }
+
+            // APPEND key value - grows the string in place when possible
+            Cmd::Append(k, v) => match self.dict.append(&k, &v, self.config.proto.max_bulk_len) {
+                Some(len) => {
+                    self.propagate(|| emit_aof_append(&k, &v));
+                    write_integer(len as i64, out);
+                }
+                None => CmdError::StringTooLong.write(out),
+            },
+
+            // LCS key1 key2 [LEN] [IDX ...] - missing keys are empty strings
+            Cmd::Lcs(a, b, opts) => {
+                let a = self.lookup_read(&a).map(string_bytes).unwrap_or_default();
+                let b = self.lookup_read(&b).map(string_bytes).unwrap_or_default();
+                // The table is transient but can be huge; bound it like a value
+                if lcs_table_size(a.len(), b.len()).is_none_or(|n| n > self.config.proto.max_bulk_len) {
+                    CmdError::LcsTooLarge.write(out);
+                    return;
+                }
+                let found = lcs(&a, &b);
+                if opts.len {
+                    write_integer(found.seq.len() as i64, out);
+                } else if opts.idx {
+                    let ranges: Vec<_> = found.matches.iter().filter(|m| m.len() as u64 >= opts.min_match_len).collect();
+                    write_array_len(4, out);
+                    write_bulk(b"matches", out);
+                    write_array_len(ranges.len(), out);
+                    for m in ranges {
+                        write_array_len(if opts.with_match_len { 3 } else { 2 }, out);
+                        for (start, end) in [m.a, m.b] {
+                            write_array_len(2, out);
+                            write_integer(start as i64, out);
+                            write_integer(end as i64, out);
                         }
-                    } else {
-                        Value::Str(v)
-                    };
-                    self.dict.set(k, val);
+                        if opts.with_match_len {
+                            write_integer(m.len() as i64, out);
+                        }
+                    }
+                    write_bulk(b"len", out);
+                    write_integer(found.seq.len() as i64, out);
+                } else {
+                    write_bulk(&found.seq, out);
                 }
-                
+            }
+
+            // GETBIT key offset
+            Cmd::GetBit(k, offset) => {
+                if !self.bit_offset_ok(offset) {
+                    CmdError::BitOffset.write(out);
+                    return;
+                }
+                write_integer(self.dict.getbit(&k, offset) as i64, out);
+            }
+
+            // DBSIZE - O(1), Dict keeps a running key count
+            Cmd::DbSize => write_integer(self.dict.len() as i64, out),
+
+            // KEYS pattern - walks the whole keyspace; SCAN is the
+            // incremental alternative
+            Cmd::Keys(pattern) => write_keys_reply(&self.dict.keys_matching(&pattern), out),
+
+            // SCAN cursor [MATCH pattern] [COUNT count] - see Dict::scan
+            Cmd::Scan { cursor, pattern, count } => {
+                let (keys, next) = self.dict.scan(cursor, count);
+                write_scan_reply(next, keys, pattern.as_deref(), out);
+            }
+
+            // FLUSHDB / FLUSHALL - Ignix has a single database
+            Cmd::FlushDb | Cmd::FlushAll => {
+                self.reset();
+                self.propagate(emit_aof_flushall);
+                write_simple("OK", out);
+            }
+
+            // INFO [section] - only the sections Ignix tracks are rendered
+            Cmd::Info(section) => write_bulk(self.info(section.as_deref()).as_bytes(), out),
+
+            // CONFIG RESETSTAT - zero the INFO stats counters
+            Cmd::ConfigResetStat => {
+                self.stats.reset();
+                write_simple("OK", out);
+            }
+
+            // BGREWRITEAOF - the rewrite itself runs on its own thread
+            Cmd::BgRewriteAof => match self.rewrite_aof() {
+                Ok(()) => write_simple("Background append only file rewriting started", out),
+                Err(e) => e.write(out),
+            },
+
+            // MEMORY PURGE - hand free allocator pages back to the OS
+            Cmd::MemoryPurge => {
+                purge_allocator();
+                write_simple("OK", out);
+            }
+
+            // COMMAND INFO name... - metadata straight from the command table
+            Cmd::CommandInfo(names) => {
+                write_array_len(names.len(), out);
+                for name in names {
+                    match crate::command::lookup(&name) {
+                        Some(spec) => spec.write_info(out),
+                        None => write_null_array(out),
+                    }
+                }
+            }
+
+            // CLUSTER ... - standalone server: cluster-aware clients fall back
+            Cmd::Cluster(ClusterSubcommand::Info) => write_bulk(b"cluster_enabled:0\r\ncluster_state:ok\r\n", out),
+            Cmd::Cluster(ClusterSubcommand::Nodes) => write_bulk(b"", out),
+            Cmd::Cluster(_) => write_array_len(0, out),
+
+            // OBJECT ENCODING key - internal representation of the value
+            Cmd::ObjectEncoding(k) => match self.dict.get(&k) {
+                Some(v) => write_bulk(v.encoding().as_bytes(), out),
+                None => write_null(out),
+            },
+
+            // DEBUG OBJECT key - encoding and size details for operators
+            Cmd::DebugObject(k) => match self.dict.get(&k) {
+                Some(v) => write_simple(
+                    &format!(
+                        "Value at:0x0 refcount:1 encoding:{} serializedlength:{}",
+                        v.encoding(),
+                        v.serialized_len()
+                    ),
+                    out,
+                ),
+                None => CmdError::NoSuchKey.write(out),
+            },
+
+            // DEBUG ENCODING key expected - encoding assertion for tests
+            Cmd::DebugEncoding(k, expected) => match self.dict.get(&k) {
+                Some(v) if v.encoding().as_bytes().eq_ignore_ascii_case(&expected) => write_simple("OK", out),
+                Some(v) => CmdError::EncodingMismatch {
+                    expected: String::from_utf8_lossy(&expected).into_owned(),
+                    found: v.encoding(),
+                }
+                .write(out),
+                None => CmdError::NoSuchKey.write(out),
+            },
+
+            // DEBUG PANIC - the network layer turns this into an error reply
+            Cmd::DebugPanic if self.config.enable_debug_panic => {
+                panic!("DEBUG PANIC called at Unix time {:?}", std::time::SystemTime::now())
+            }
+            Cmd::DebugPanic => CmdError::DebugPanicDisabled.write(out),
+
+            // DEBUG CHANGE-REPL-ID - new process run_id (failover testing)
+            Cmd::DebugChangeReplId => {
+                crate::stats::change_run_id();
                 write_simple("OK", out);
             }
+
+            // MONITOR - connection state lives in the network layer, which
+            // intercepts this command; reaching here means it can't be served
+            Cmd::Monitor => CmdError::Unsupported("MONITOR").write(out),
+            // CLIENT NO-EVICT / CLIENT INFO - likewise connection state
+            Cmd::ClientNoEvict(_) | Cmd::ClientInfo => CmdError::Unsupported("CLIENT").write(out),
+
+            // CLIENT PAUSE ms [WRITE|ALL] - the connections check the
+            // shared deadline before each command
+            Cmd::ClientPause(ms, mode) => {
+                self.pause.pause(Duration::from_millis(ms), mode);
+                write_simple("OK", out);
+            }
+            // CLIENT UNPAUSE - the workers are woken so the connections
+            // held back resume now rather than at the old deadline
+            Cmd::ClientUnpause => {
+                self.pause.unpause();
+                self.stats.wake_workers();
+                write_simple("OK", out);
+            }
+            Cmd::ClientList => {
+                let mut list = String::new();
+                for c in self.stats.clients() {
+                    list.push_str(&c.render());
+                    list.push('\n');
+                }
+                write_bulk(list.as_bytes(), out);
+            }
+            Cmd::DebugPlainReplies(_) => CmdError::Unsupported("DEBUG PROTOCOL").write(out),
+
+            // Rejected at parse time; reply with the validation error
+            Cmd::Invalid(msg) => write_error(&msg, out),
+        }
+    }
+
+    /// Evict keys until the dataset fits in `maxmemory` again
+    /// 
+    /// Victims are picked by `maxmemory_policy`. Each eviction is
+    /// propagated as a DEL, so replaying the AOF doesn't bring the key
+    /// back, and counted in `evicted_keys`.
+    /// 
+    /// # Returns
+    /// * `false` if the dataset still doesn't fit: the policy is
+    ///   `noeviction`, or nothing is left to evict
+    fn free_memory(&self) -> bool {
+        while self.dict.used_memory() > self.config.maxmemory {
+            if self.config.maxmemory_policy == MaxmemoryPolicy::NoEviction {
+                return false;
+            }
+            let Some(k) = self.dict.lru_candidate(self.config.maxmemory_samples) else {
+                return false;
+            };
+            // Gone already if another writer evicted or deleted it
+            if let Some(v) = self.dict.remove(&k) {
+                if self.config.lazyfree_lazy_server_del {
+                    self.lazyfree.free(v);
+                }
+                self.stats.evicted_keys.fetch_add(1, Ordering::Relaxed);
+                self.propagate(|| emit_aof_del(std::slice::from_ref(&k)));
+            }
+        }
+        true
+    }
+
+    /// Drop a value replaced by a write, off-thread if configured
+    #[inline]
+    fn release_replaced(&self, old: Option<Value>) {
+        if let Some(v) = old {
+            if self.config.lazyfree_lazy_server_del {
+                self.lazyfree.free(v);
+            }
+        }
+    }
+
+    /// Add `delta` to a counter and reply with the result; `None` is a
+    /// delta that overflowed while being computed
+    fn incr_by(&self, k: Bytes, delta: Option<i64>, out: &mut BytesMut) {
+        let Some(delta) = delta else {
+            CmdError::Overflow.write(out);
+            return;
+        };
+        match self.dict.incr_by(&k, delta) {
+            Ok(v) => {
+                self.propagate(|| emit_aof_incrby(&k, delta));
+                write_integer(v, out);
+            }
+            // The value is left untouched and nothing is logged
+            Err(e) => CmdError::from(e).write(out),
+        }
+    }
+
+    /// Build the stored representation of a written value, moving large
+    /// ones to the blob store when one is attached
+    fn encode(&self, v: Bytes) -> Value {
+        #[cfg(all(feature = "blob-store", target_os = "linux"))]
+        if let Some(blobs) = &self.blobs {
+            if self.config.blob_threshold > 0 && v.len() >= self.config.blob_threshold {
+                // A full store falls back to keeping the value inline
+                if let Some(b) = blobs.store(&v) {
+                    return Value::Blob(b);
+                }
+            }
+        }
+        encode_value(v)
+    }
+
+    /// Read a key on behalf of a client, counting the keyspace hit or miss
+    #[inline]
+    fn lookup_read(&self, k: &[u8]) -> Option<Value> {
+        let v = self.dict.get(k);
+        self.stats.record_lookup(v.is_some());
+        v
+    }
+
+    /// Render INFO output for a section (`None`, `default`, `all` or
+    /// `everything` select every section; unknown sections render nothing)
+    pub fn info(&self, section: Option<&[u8]>) -> String {
+        info_of(std::slice::from_ref(self), section)
+    }
+
+    /// Exact size and shape of the keyspace
+    /// 
+    /// Walks every key, visiting one DashMap shard lock at a time; prefer
+    /// [`Shard::dataset_stats_sampled`] on very large datasets.
+    pub fn dataset_stats(&self) -> DatasetStats {
+        DatasetStats::collect(&self.dict, usize::MAX)
+    }
+
+    /// Keyspace statistics estimated from at most `sample` keys
+    /// 
+    /// # Arguments
+    /// * `sample` - Maximum number of keys to inspect
+    /// 
+    /// # Returns
+    /// * Exact figures if the keyspace fits in the sample, otherwise
+    ///   totals scaled to `Dict::len()` with `sampled` set
+    pub fn dataset_stats_sampled(&self, sample: usize) -> DatasetStats {
+        DatasetStats::collect(&self.dict, sample.max(1))
+    }
+
+    /// Total length of every write propagated since startup
+    /// 
+    /// Monotonic; becomes the offset replicas acknowledge once
+    /// replication exists.
+    pub fn master_repl_offset(&self) -> u64 {
+        self.repl_offset.load(Ordering::Relaxed)
+    }
+
+    /// Drop every key, leaving configuration, AOF and monitors untouched
+    /// 
+    /// Backs FLUSHDB/FLUSHALL and lets in-process tests reuse a shard
+    /// without seeing keys written by an earlier test. Nothing is logged
+    /// to the AOF; use the commands for a persisted flush.
+    pub fn reset(&self) {
+        self.dict.clear();
+    }
+
+    /// Check a bit offset against the configured maximum string size
+    #[inline]
+    fn bit_offset_ok(&self, offset: u64) -> bool {
+        (offset >> 3) < self.config.proto.max_bulk_len as u64
+    }
+
+    /// Start a background rewrite of the AOF (BGREWRITEAOF)
+    /// 
+    /// Write commands are held back only while the dataset is
+    /// snapshotted in memory; the file is written on another thread, and
+    /// writes made meanwhile are appended to the new file before it
+    /// replaces the old one.
+    pub fn rewrite_aof(&self) -> Result<(), CmdError> {
+        rewrite_aof_of(std::slice::from_ref(self))
+    }
+
+    /// Whether the AOF has outgrown `auto-aof-rewrite-percentage` and
+    /// `auto-aof-rewrite-min-size` and no rewrite is running
+    pub(crate) fn aof_rewrite_due(&self) -> bool {
+        let Some(aof) = &self.aof else { return false };
+        let pct = self.config.aof_rewrite_percentage;
+        if pct == 0 || aof.is_rewriting() {
+            return false;
+        }
+        let (size, base) = (aof.size(), aof.base_size());
+        size >= self.config.aof_rewrite_min_size && size >= base.saturating_add(base.saturating_mul(pct) / 100)
+    }
+
+    /// Mark the shard as replaying its AOF (see [`crate::aof::load_aof`])
+    pub(crate) fn set_loading(&self, loading: bool) {
+        self.loading.store(loading, Ordering::Relaxed);
+    }
+
+    /// Propagate the effect of a committed mutation
+    /// 
+    /// Every write command calls this exactly once, after its change has
+    /// been applied to the dictionary, so the AOF only ever contains
+    /// mutations that actually happened. The entry's length advances
+    /// `master_repl_offset` whether or not persistence is enabled. Nothing
+    /// is propagated while the AOF itself is being loaded.
+    #[inline]
+    pub(crate) fn propagate(&self, entry: impl FnOnce() -> Vec<u8>) {
+        if self.loading.load(Ordering::Relaxed) {
+            return;
         }
+        let entry = entry();
+        self.repl_offset.fetch_add(entry.len() as u64, Ordering::Relaxed);
+        if let Some(a) = &self.aof {
+            a.write(&entry);
+        }
+    }
+}
+
+/// Render INFO over the shards of a server; server-wide figures come
+/// from the first, keyspace figures are summed over all of them
+///
+/// See [`Shard::info`].
+pub(crate) fn info_of(shards: &[Shard], section: Option<&[u8]>) -> String {
+    let all = section.is_none_or(|s| {
+        s.eq_ignore_ascii_case(b"default") || s.eq_ignore_ascii_case(b"all") || s.eq_ignore_ascii_case(b"everything")
+    });
+    let wants = |name: &str| all || section.is_some_and(|s| s.eq_ignore_ascii_case(name.as_bytes()));
+    let server = &shards[0];
+    let sum = |f: fn(&Shard) -> usize| shards.iter().map(f).sum::<usize>();
+
+    let mut out = String::new();
+    if wants("server") {
+        out.push_str("# Server\r\n");
+        out.push_str(&format!("ignix_version:{}\r\n", env!("CARGO_PKG_VERSION")));
+        out.push_str("redis_mode:standalone\r\n");
+        out.push_str(&format!("run_id:{}\r\n", crate::stats::run_id()));
+    }
+    if wants("clients") {
+        if !out.is_empty() {
+            out.push_str("\r\n");
+        }
+        server.stats.render_clients(&mut out);
+    }
+    if wants("memory") {
+        if !out.is_empty() {
+            out.push_str("\r\n");
+        }
+        out.push_str("# Memory\r\n");
+        out.push_str(&format!("used_memory_dataset:{}\r\n", sum(|s| s.dict.used_memory())));
+        out.push_str(&format!("maxmemory:{}\r\n", sum(|s| s.config.maxmemory)));
+        out.push_str(&format!("maxmemory_policy:{}\r\n", server.config.maxmemory_policy.name()));
+        out.push_str(&format!("mem_clients_normal:{}\r\n", server.stats.client_memory()));
+        out.push_str(&format!("maxmemory_clients:{}\r\n", server.config.maxmemory_clients));
+        out.push_str(&format!("lazyfree_pending_objects:{}\r\n", sum(|s| s.lazyfree.pending())));
+        out.push_str(&format!("lazyfreed_objects:{}\r\n", shards.iter().map(|s| s.lazyfree.freed()).sum::<u64>()));
+        // Sizing of the keyspace table, to correlate latency with resizes
+        let capacity = sum(|s| s.dict.capacity());
+        let load = if capacity == 0 { 0.0 } else { sum(|s| s.dict.len()) as f64 / capacity as f64 };
+        out.push_str(&format!("dict_capacity:{}\r\n", capacity));
+        out.push_str(&format!("dict_load_factor:{:.2}\r\n", load));
+    }
+    if wants("stats") {
+        if !out.is_empty() {
+            out.push_str("\r\n");
+        }
+        server.stats.render(&mut out);
+    }
+    if wants("replication") {
+        if !out.is_empty() {
+            out.push_str("\r\n");
+        }
+        out.push_str("# Replication\r\nrole:master\r\nconnected_slaves:0\r\n");
+        let offset: u64 = shards.iter().map(|s| s.master_repl_offset()).sum();
+        out.push_str(&format!("master_repl_offset:{}\r\n", offset));
+    }
+    if wants("cluster") {
+        if !out.is_empty() {
+            out.push_str("\r\n");
+        }
+        out.push_str("# Cluster\r\ncluster_enabled:0\r\n");
+    }
+    if wants("keyspace") {
+        if !out.is_empty() {
+            out.push_str("\r\n");
+        }
+        out.push_str("# Keyspace\r\n");
+        // Like Redis, an empty database is left out
+        let keys = sum(|s| s.dict.len());
+        if keys > 0 {
+            out.push_str(&format!("db0:keys={},expires={},avg_ttl=0\r\n", keys, sum(|s| s.dict.volatile_len())));
+        }
+    }
+    out
+}
+
+/// Start a background rewrite of the AOF the shards share (see
+/// [`Shard::rewrite_aof`])
+///
+/// The snapshot is taken with every shard's writes held back, so it's
+/// consistent across shards.
+pub(crate) fn rewrite_aof_of(shards: &[Shard]) -> Result<(), CmdError> {
+    let Some(aof) = &shards[0].aof else {
+        return Err(CmdError::AofDisabled);
+    };
+    let entries = {
+        // Always taken in shard order; a write only ever holds its own
+        let _gates: Vec<_> = shards.iter().map(|s| s.rewrite_gate.write()).collect();
+        if !aof.begin_rewrite() {
+            return Err(CmdError::RewriteInProgress);
+        }
+        shards.iter().flat_map(|s| s.dict.entries()).collect()
+    };
+    aof.finish_rewrite(entries);
+    Ok(())
+}
+
+/// Write a KEYS reply: an array of the keys
+pub(crate) fn write_keys_reply(keys: &[Bytes], out: &mut BytesMut) {
+    write_array_len(keys.len(), out);
+    for k in keys {
+        write_bulk(k, out);
+    }
+}
+
+/// Write a SCAN reply: the next cursor and the batch's keys that match
+/// the pattern, if one was given
+pub(crate) fn write_scan_reply(next: u64, mut keys: Vec<Bytes>, pattern: Option<&[u8]>, out: &mut BytesMut) {
+    if let Some(pattern) = pattern {
+        keys.retain(|k| glob_match(pattern, k));
+    }
+    write_array_len(2, out);
+    write_bulk(next.to_string().as_bytes(), out);
+    write_keys_reply(&keys, out);
+}
+
+/// The expiry a SET stores, or `None` when its deadline overflows
+fn key_expiry(expiry: Option<SetExpiry>) -> Option<KeyExpiry> {
+    let ttl = match expiry {
+        None => return Some(KeyExpiry::Clear),
+        Some(SetExpiry::KeepTtl) => return Some(KeyExpiry::Keep),
+        Some(SetExpiry::Ex(secs)) => Duration::from_secs(secs),
+        Some(SetExpiry::Px(ms)) => Duration::from_millis(ms),
+        // A time already past stores the key already expired
+        Some(SetExpiry::PxAt(unix_ms)) => return WallClock::now().deadline_of(unix_ms as i64).map(KeyExpiry::At),
+    };
+    Instant::now().checked_add(ttl).map(KeyExpiry::At)
+}
+
+/// The string form of a value (integers in decimal)
+fn string_bytes(v: Value) -> Bytes {
+    match v {
+        Value::Str(s) | Value::Blob(s) => s,
+        Value::Int(i) => Bytes::from(i.to_string()),
+    }
+}
+
+/// Ask mimalloc to return the free pages it holds to the OS
+/// 
+/// The server binary uses mimalloc as its global allocator, so this is
+/// how RSS drops after a large dataset is deleted. When an embedder uses
+/// another allocator, mimalloc's heaps are empty and this does nothing.
+fn purge_allocator() {
+    // SAFETY: mi_collect only walks mimalloc's own heaps; forcing it is
+    // allowed from any thread at any time
+    unsafe { libmimalloc_sys::mi_collect(true) };
+}
+
+/// Build the stored representation of a string value
+/// 
+/// Optimization: values that look like integers are stored as `Value::Int`.
+/// Only canonical forms qualify (see [`parse_int`]), so `007`, `+5` or
+/// `-0` are kept verbatim as strings.
+fn encode_value(v: Bytes) -> Value {
+    match parse_int(&v) {
+        Some(i) => Value::Int(i),
+        None => Value::Str(v),
     }
 }
 
@@ -193,4 +910,96 @@ mod tests {
     fn test_shard_alignment() {
         assert_eq!(std::mem::align_of::<Shard>(), 64, "Shard struct should be aligned to 64 bytes");
     }
-}
\ No newline at end of file
+
+    fn b(s: &str) -> Bytes {
+        Bytes::copy_from_slice(s.as_bytes())
+    }
+
+    /// Sorted snapshot of a shard's keyspace for comparisons
+    fn state(shard: &Shard) -> Vec<(Bytes, Value)> {
+        let mut v: Vec<_> = shard.dict.inner.iter().map(|e| (e.key().clone(), e.value().value.clone())).collect();
+        v.sort_by(|a, b| a.0.cmp(&b.0));
+        v
+    }
+
+    #[test]
+    fn test_aof_replay_matches_live_state() {
+        let (aof, rx) = AofHandle::capture();
+        let live = Shard::new(0, Some(aof));
+        let mut out = BytesMut::new();
+        for cmd in [
+            Cmd::Set(b("a"), b("1")),
+            Cmd::Set(b("s"), b("hello")),
+            Cmd::Incr(b("a")),
+            Cmd::Incr(b("fresh")),
+            Cmd::Rename(b("s"), b("t")),
+            Cmd::Rename(b("missing"), b("u")),
+            Cmd::MSet(vec![(b("m1"), b("x")), (b("m2"), b("42"))]),
+            Cmd::Get(b("a")),
+            Cmd::Exists(vec![b("t")]),
+        ] {
+            live.exec(cmd, &mut out);
+        }
+
+        // Reads and failed mutations must not be logged
+        let entries: Vec<Vec<u8>> = rx.try_iter().collect();
+        assert_eq!(entries.len(), 6);
+
+        let mut buf = BytesMut::from(&entries.concat()[..]);
+        let mut cmds = Vec::new();
+        crate::protocol::parse_many(&mut buf, &mut cmds).unwrap();
+        assert!(buf.is_empty());
+
+        let reloaded = Shard::new(1, None);
+        for cmd in cmds {
+            reloaded.exec(cmd, &mut out);
+        }
+        assert_eq!(state(&reloaded), state(&live));
+    }
+
+    #[test]
+    fn binary_values_survive_aof_round_trip() {
+        let (aof, rx) = AofHandle::capture();
+        let live = Shard::new(0, Some(aof));
+        let key = Bytes::from_static(b"bin\xff\x00key");
+        let val = Bytes::from_static(b"\xc3\x28\xa0\xa1\r\n\x80");
+        let mut out = BytesMut::new();
+        live.exec(Cmd::Set(key.clone(), val.clone()), &mut out);
+        live.exec(Cmd::MSet(vec![(val.clone(), key.clone())]), &mut out);
+        live.exec(Cmd::Rename(val.clone(), b("\u{1F600}")), &mut out);
+
+        let mut buf = BytesMut::from(&rx.try_iter().collect::<Vec<_>>().concat()[..]);
+        let mut cmds = Vec::new();
+        crate::protocol::parse_many(&mut buf, &mut cmds).unwrap();
+        assert!(buf.is_empty());
+
+        let reloaded = Shard::new(1, None);
+        for cmd in cmds {
+            reloaded.exec(cmd, &mut out);
+        }
+        assert_eq!(reloaded.dict.get(&key), Some(Value::Str(val)));
+        assert_eq!(state(&reloaded), state(&live));
+    }
+
+    #[test]
+    fn mset_is_logged_once_after_applying_and_replays_exactly() {
+        let (aof, rx) = AofHandle::capture();
+        let live = Shard::new(0, Some(aof));
+        let mut out = BytesMut::new();
+        // A repeated key: the last value wins, live and on replay
+        live.exec(Cmd::MSet(vec![(b("k1"), b("a")), (b("k2"), b("7")), (b("k1"), b("b"))]), &mut out);
+        assert_eq!(&out[..], b"+OK\r\n");
+
+        let entries: Vec<Vec<u8>> = rx.try_iter().collect();
+        assert_eq!(
+            entries,
+            vec![b"*7\r\n$4\r\nMSET\r\n$2\r\nk1\r\n$1\r\na\r\n$2\r\nk2\r\n$1\r\n7\r\n$2\r\nk1\r\n$1\r\nb\r\n".to_vec()]
+        );
+
+        let reloaded = Shard::new(1, None);
+        let replayed = crate::replay::replay_commands(&entries[0][..], &reloaded).unwrap();
+        assert_eq!(replayed.commands, 1);
+        assert_eq!(reloaded.dict.get(b"k1"), Some(Value::Str(b("b"))));
+        assert_eq!(state(&reloaded), state(&live));
+    }
+}
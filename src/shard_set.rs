@@ -0,0 +1,248 @@
+/*!
+ * Shard Set
+ *
+ * Several shards serving one keyspace, so commands on different keys
+ * don't contend on one dictionary. Keys are assigned like Redis Cluster
+ * assigns them to slots: the CRC16 of the key (or of its `{hash tag}`)
+ * modulo 16384 is its slot, and the slot modulo the shard count picks
+ * the shard.
+ *
+ * Multi-key commands (MGET, MSET, DEL, EXISTS, RENAME, LCS) run only when
+ * all their keys live on one shard, and are refused with CROSSSLOT
+ * otherwise, as in Redis Cluster; a shared hash tag keeps related keys
+ * together (`{user:1}:name`, `{user:1}:email`). DBSIZE, FLUSHALL, INFO,
+ * BGREWRITEAOF, KEYS and SCAN cover every shard. Other commands without keys run on
+ * the first shard, which holds the server-wide state: configuration,
+ * clients, MONITOR feeds and CLIENT PAUSE. The INFO counters and the AOF
+ * are shared by all shards.
+ *
+ * A set of one shard behaves exactly like that shard.
+ */
+
+use crate::aof::{emit_aof_flushall, load_aof_into, AofHandle};
+use crate::config::Config;
+use crate::error::CmdError;
+use crate::protocol::{write_bulk, write_integer, write_simple, Cmd};
+use crate::replay::ReplayStats;
+use crate::shard::{info_of, rewrite_aof_of, write_keys_reply, write_scan_reply, Shard};
+use crate::storage::SCAN_CURSOR_SPARE_BITS;
+use anyhow::*;
+use bytes::BytesMut;
+use std::result::Result::{Ok, Err};
+
+/// Number of hash slots keys are spread over (as in Redis Cluster)
+pub const KEY_SLOTS: u16 = 16384;
+
+/// Most shards a set holds: a SCAN cursor records the shard it is in
+/// within the bits [`crate::Dict::scan`] leaves free
+pub const MAX_SHARDS: usize = 1 << SCAN_CURSOR_SPARE_BITS;
+
+/// Hash slot of a key (Redis' `CLUSTER KEYSLOT`)
+///
+/// When the key has a non-empty hash tag, the part between its first `{`
+/// and the next `}`, only the tag is hashed, so keys sharing a tag share
+/// a slot.
+pub fn key_slot(key: &[u8]) -> u16 {
+    let tag = key.iter().position(|&b| b == b'{').and_then(|open| {
+        let rest = &key[open + 1..];
+        rest.iter().position(|&b| b == b'}').filter(|&close| close > 0).map(|close| &rest[..close])
+    });
+    crc16(tag.unwrap_or(key)) % KEY_SLOTS
+}
+
+/// CRC16-CCITT (XMODEM), the checksum Redis Cluster hashes keys with
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for &b in data {
+        crc ^= (b as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+/// The shards of a server, with commands routed by key
+pub struct ShardSet {
+    /// Never empty; the first holds the server-wide state
+    shards: Vec<Shard>,
+}
+
+impl ShardSet {
+    /// Create `config.shards` shards sharing an AOF, a configuration and
+    /// the INFO counters
+    ///
+    /// # Arguments
+    /// * `aof` - Optional AOF handle every shard logs to
+    /// * `config` - Server configuration; `expected_keys` and `maxmemory`
+    ///   are split between the shards, and between 1 and [`MAX_SHARDS`]
+    ///   shards are created
+    pub fn with_config(aof: Option<AofHandle>, config: Config) -> Self {
+        let count = config.shards.clamp(1, MAX_SHARDS);
+        let config = Config {
+            expected_keys: config.expected_keys.div_ceil(count),
+            maxmemory: config.maxmemory.div_ceil(count),
+            ..config
+        };
+        let mut shards: Vec<Shard> = (0..count).map(|id| Shard::with_config(id, aof.clone(), config.clone())).collect();
+        let stats = shards[0].stats.clone();
+        for shard in &mut shards[1..] {
+            shard.stats = stats.clone();
+        }
+        Self { shards }
+    }
+
+    /// The shard holding the server-wide state
+    pub fn primary(&self) -> &Shard {
+        &self.shards[0]
+    }
+
+    /// Every shard, in routing order
+    pub fn shards(&self) -> &[Shard] {
+        &self.shards
+    }
+
+    /// The shard owning a key
+    pub fn shard_for(&self, key: &[u8]) -> &Shard {
+        &self.shards[self.index_of(key)]
+    }
+
+    /// Execute a command on the shard owning its keys
+    ///
+    /// See the module documentation for commands that span shards.
+    ///
+    /// # Arguments
+    /// * `cmd` - Parsed Redis command to execute
+    /// * `out` - Buffer to write response to
+    pub fn exec(&self, cmd: Cmd, out: &mut BytesMut) {
+        let primary = self.primary();
+        if self.shards.len() == 1 {
+            return primary.exec(cmd, out);
+        }
+        match cmd {
+            Cmd::DbSize => write_integer(self.shards.iter().map(|s| s.dict.len()).sum::<usize>() as i64, out),
+            Cmd::FlushDb | Cmd::FlushAll => self.flush_all(out),
+            Cmd::Info(section) => write_bulk(info_of(&self.shards, section.as_deref()).as_bytes(), out),
+            Cmd::Keys(pattern) => {
+                let keys: Vec<_> = self.shards.iter().flat_map(|s| s.dict.keys_matching(&pattern)).collect();
+                write_keys_reply(&keys, out);
+            }
+            Cmd::Scan { cursor, pattern, count } => self.scan(cursor, pattern.as_deref(), count, out),
+            Cmd::BgRewriteAof => match self.rewrite_aof() {
+                Ok(()) => write_simple("Background append only file rewriting started", out),
+                Err(e) => e.write(out),
+            },
+            cmd => match self.route(&cmd) {
+                Ok(Some(i)) => {
+                    // The AOF is shared: a rewrite has to snapshot every shard
+                    if self.shards[i].exec_logged(cmd, out) && primary.aof_rewrite_due() {
+                        let _ = self.rewrite_aof();
+                    }
+                }
+                Ok(None) => primary.exec(cmd, out),
+                Err(e) => e.write(out),
+            },
+        }
+    }
+
+    /// Index of the shard a command runs on, `None` for commands without
+    /// keys and those refused with CROSSSLOT
+    pub(crate) fn owner(&self, cmd: &Cmd) -> Option<usize> {
+        self.route(cmd).ok().flatten()
+    }
+
+    /// Whether a command gets past the checks made before it runs: its keys
+    /// on one shard (else CROSSSLOT), then [`Shard::admits`] on that shard
+    pub(crate) fn admits(&self, cmd: &Cmd) -> bool {
+        match self.route(cmd) {
+            Ok(Some(i)) => self.shards[i].admits(cmd),
+            Ok(None) => self.primary().admits(cmd),
+            Err(_) => false,
+        }
+    }
+
+    /// Start a background rewrite of the shared AOF (BGREWRITEAOF)
+    ///
+    /// See [`Shard::rewrite_aof`]; the snapshot covers every shard.
+    pub fn rewrite_aof(&self) -> Result<(), CmdError> {
+        rewrite_aof_of(&self.shards)
+    }
+
+    /// Replay an AOF into the set, routing each command like a client's
+    ///
+    /// See [`crate::aof::load_aof`].
+    pub fn load_aof(&self, path: &str) -> Result<ReplayStats> {
+        load_aof_into(path, &self.shards, |cmd, out| self.exec(cmd, out))
+    }
+
+    /// Index of the shard owning a key
+    #[inline]
+    fn index_of(&self, key: &[u8]) -> usize {
+        match self.shards.len() {
+            1 => 0,
+            n => key_slot(key) as usize % n,
+        }
+    }
+
+    /// The shard a command runs on
+    ///
+    /// # Returns
+    /// * `Ok(None)` for a command without keys
+    /// * `Err(CmdError::CrossSlot)` when its keys live on different shards
+    fn route(&self, cmd: &Cmd) -> Result<Option<usize>, CmdError> {
+        let keys = cmd.keys();
+        let Some((first, rest)) = keys.split_first() else {
+            return Ok(None);
+        };
+        let i = self.index_of(first);
+        if rest.iter().any(|k| self.index_of(k) != i) {
+            return Err(CmdError::CrossSlot);
+        }
+        Ok(Some(i))
+    }
+
+    /// SCAN over the shards one after the other
+    ///
+    /// The cursor is a [`crate::Dict::scan`] cursor of the shard whose index is
+    /// kept in its low bits. Moving on to the next shard starts its scan
+    /// from 0, so a reply may carry fewer keys than `count`.
+    fn scan(&self, cursor: u64, pattern: Option<&[u8]>, count: usize, out: &mut BytesMut) {
+        let spare = (1u64 << SCAN_CURSOR_SPARE_BITS) - 1;
+        let i = (cursor & spare) as usize;
+        let Some(shard) = self.shards.get(i) else {
+            // Not a cursor we handed out: nothing left to visit
+            return write_scan_reply(0, Vec::new(), pattern, out);
+        };
+        let (keys, next) = shard.dict.scan(cursor & !spare, count);
+        let next = match next {
+            0 if i + 1 < self.shards.len() => (i + 1) as u64,
+            0 => 0,
+            next => next | i as u64,
+        };
+        write_scan_reply(next, keys, pattern, out);
+    }
+
+    /// FLUSHALL over every shard, logged once
+    fn flush_all(&self, out: &mut BytesMut) {
+        let primary = self.primary();
+        if primary.aof.as_ref().is_some_and(|aof| aof.is_failed()) {
+            CmdError::Misconf.write(out);
+            return;
+        }
+        // Writes to every shard are held back, so none lands between the
+        // flush and its AOF entry
+        let _gates: Vec<_> = self.shards.iter().map(|s| s.rewrite_gate.write()).collect();
+        for shard in &self.shards {
+            shard.reset();
+        }
+        primary.propagate(emit_aof_flushall);
+        write_simple("OK", out);
+    }
+}
+
+impl From<Shard> for ShardSet {
+    /// A set of one shard
+    fn from(shard: Shard) -> Self {
+        Self { shards: vec![shard] }
+    }
+}
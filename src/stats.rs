@@ -0,0 +1,297 @@
+/*!
+ * Server Statistics
+ *
+ * Counters reported by `INFO stats`. They are bumped on the command hot
+ * path from every worker thread, so each one is a relaxed atomic; the
+ * values are only ever read for reporting.
+ *
+ * The process `run_id` reported under `INFO server` lives here too.
+ *
+ * Each connection also publishes a `ClientStats`, rendered by CLIENT LIST
+ * and CLIENT INFO.
+ *
+ * `DatasetStats` is the typed snapshot of the keyspace behind
+ * `Shard::dataset_stats()`, for embedders that monitor without INFO.
+ */
+
+use crate::storage::{value_size, Dict};
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+
+/// Runtime counters shared by all workers
+#[derive(Debug, Default)]
+pub struct Stats {
+    /// Successful key lookups (GET/MGET found the key)
+    pub keyspace_hits: AtomicU64,
+    /// Failed key lookups (GET/MGET found nothing)
+    pub keyspace_misses: AtomicU64,
+    /// Connections dropped to enforce `maxmemory-clients`
+    pub evicted_clients: AtomicU64,
+    /// Keys evicted to stay within `maxmemory`
+    pub evicted_keys: AtomicU64,
+    /// Gauges of each network worker, in registration order
+    workers: Mutex<Vec<Arc<WorkerStats>>>,
+    /// Gauges of each connection; dropped connections leave dead entries
+    /// until the next prune
+    clients: Mutex<Vec<Weak<ClientStats>>>,
+    /// Last connection id handed out
+    last_client_id: AtomicU64,
+}
+
+/// Gauges owned by one network worker
+#[derive(Debug, Default)]
+pub struct WorkerStats {
+    /// Open connections
+    pub connected: AtomicUsize,
+    /// Bytes held in the query and output buffers of its connections
+    pub client_memory: AtomicUsize,
+    /// Buffered bytes of its largest evictable connection (only kept up
+    /// to date while `maxmemory-clients` is set)
+    pub largest_client: AtomicUsize,
+    /// Wakes the worker's event loop (mio backend only)
+    waker: Option<Arc<mio::Waker>>,
+}
+
+impl WorkerStats {
+    /// Ask the worker to run its event loop, e.g. to evict a client
+    pub fn wake(&self) {
+        if let Some(w) = &self.waker {
+            let _ = w.wake();
+        }
+    }
+}
+
+/// Gauges of one connection, owned by its `Conn`
+#[derive(Debug, Default)]
+pub struct ClientStats {
+    /// Unique connection id, starting at 1
+    pub id: u64,
+    /// Peer address
+    pub addr: String,
+    /// Bytes received but not yet parsed (query buffer length)
+    pub qbuf: AtomicUsize,
+    /// Spare capacity of the query buffer
+    pub qbuf_free: AtomicUsize,
+    /// Largest query buffer length seen so far
+    pub qbuf_peak: AtomicUsize,
+    /// Set by CLIENT NO-EVICT ON: exempt from `maxmemory-clients`
+    pub no_evict: AtomicBool,
+}
+
+impl ClientStats {
+    /// Record the current length and capacity of the query buffer
+    #[inline]
+    pub fn record_qbuf(&self, len: usize, capacity: usize) {
+        self.qbuf.store(len, Ordering::Relaxed);
+        self.qbuf_free.store(capacity.saturating_sub(len), Ordering::Relaxed);
+        self.qbuf_peak.fetch_max(len, Ordering::Relaxed);
+    }
+
+    /// One CLIENT LIST line, without the trailing newline
+    pub fn render(&self) -> String {
+        let flags = if self.no_evict.load(Ordering::Relaxed) { "e" } else { "N" };
+        format!(
+            "id={} addr={} flags={} db=0 qbuf={} qbuf-free={} qbuf-peak={}",
+            self.id,
+            self.addr,
+            flags,
+            self.qbuf.load(Ordering::Relaxed),
+            self.qbuf_free.load(Ordering::Relaxed),
+            self.qbuf_peak.load(Ordering::Relaxed),
+        )
+    }
+}
+
+impl Stats {
+    /// Record the outcome of a read lookup
+    #[inline]
+    pub fn record_lookup(&self, hit: bool) {
+        let counter = if hit { &self.keyspace_hits } else { &self.keyspace_misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Register a network worker and get its gauges
+    /// 
+    /// The worker bumps `connected` on accept and drops it on close;
+    /// workers are reported in registration order.
+    /// 
+    /// # Arguments
+    /// * `waker` - Lets other workers wake this one to evict a client
+    pub fn register_worker(&self, waker: Option<Arc<mio::Waker>>) -> Arc<WorkerStats> {
+        let worker = Arc::new(WorkerStats { waker, ..Default::default() });
+        self.workers.lock().push(worker.clone());
+        worker
+    }
+
+    /// Wake every worker, e.g. to resume connections after CLIENT UNPAUSE
+    pub fn wake_workers(&self) {
+        for w in self.workers.lock().iter() {
+            w.wake();
+        }
+    }
+
+    /// Register a new connection and get its gauges
+    /// 
+    /// The connection stays listed until the returned handle is dropped.
+    pub fn register_client(&self, addr: String) -> Arc<ClientStats> {
+        let id = self.last_client_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let client = Arc::new(ClientStats { id, addr, ..Default::default() });
+        let mut clients = self.clients.lock();
+        // Prune closed connections instead of growing, which keeps the
+        // cost amortized O(1) per accept
+        if clients.len() == clients.capacity() {
+            clients.retain(|c| c.strong_count() > 0);
+        }
+        clients.push(Arc::downgrade(&client));
+        client
+    }
+
+    /// Gauges of every open connection, oldest first
+    pub fn clients(&self) -> Vec<Arc<ClientStats>> {
+        self.clients.lock().iter().filter_map(Weak::upgrade).collect()
+    }
+
+    /// Open connections per worker, in registration order
+    pub fn worker_clients(&self) -> Vec<usize> {
+        self.workers.lock().iter().map(|w| w.connected.load(Ordering::Relaxed)).collect()
+    }
+
+    /// Bytes buffered by every connection on every worker
+    pub fn client_memory(&self) -> usize {
+        self.workers.lock().iter().map(|w| w.client_memory.load(Ordering::Relaxed)).sum()
+    }
+
+    /// The worker holding the largest evictable connection, and its size
+    pub fn largest_client(&self) -> Option<(usize, Arc<WorkerStats>)> {
+        self.workers
+            .lock()
+            .iter()
+            .map(|w| (w.largest_client.load(Ordering::Relaxed), w.clone()))
+            .max_by_key(|(size, _)| *size)
+    }
+
+    /// Render the `# Clients` section of INFO
+    pub fn render_clients(&self, out: &mut String) {
+        let per_worker = self.worker_clients();
+        let list: Vec<String> = per_worker.iter().map(|n| n.to_string()).collect();
+        out.push_str("# Clients\r\n");
+        out.push_str(&format!("connected_clients:{}\r\n", per_worker.iter().sum::<usize>()));
+        out.push_str(&format!("worker_connected_clients:{}\r\n", list.join(",")));
+    }
+
+    /// Zero every counter (CONFIG RESETSTAT); connection counts are gauges
+    /// and are left alone
+    pub fn reset(&self) {
+        self.keyspace_hits.store(0, Ordering::Relaxed);
+        self.keyspace_misses.store(0, Ordering::Relaxed);
+        self.evicted_clients.store(0, Ordering::Relaxed);
+        self.evicted_keys.store(0, Ordering::Relaxed);
+    }
+
+    /// Render the `# Stats` section of INFO
+    pub fn render(&self, out: &mut String) {
+        out.push_str("# Stats\r\n");
+        out.push_str(&format!("keyspace_hits:{}\r\n", self.keyspace_hits.load(Ordering::Relaxed)));
+        out.push_str(&format!("keyspace_misses:{}\r\n", self.keyspace_misses.load(Ordering::Relaxed)));
+        out.push_str(&format!("evicted_clients:{}\r\n", self.evicted_clients.load(Ordering::Relaxed)));
+        out.push_str(&format!("evicted_keys:{}\r\n", self.evicted_keys.load(Ordering::Relaxed)));
+    }
+}
+
+/// Process-wide run id, generated on first use
+static RUN_ID: Mutex<String> = Mutex::new(String::new());
+
+/// The 40-character hex id identifying this server process (`run_id`)
+pub fn run_id() -> String {
+    let mut id = RUN_ID.lock();
+    if id.is_empty() {
+        *id = generate_run_id();
+    }
+    id.clone()
+}
+
+/// Replace the run id with a fresh one (`DEBUG CHANGE-REPL-ID`)
+pub fn change_run_id() {
+    *RUN_ID.lock() = generate_run_id();
+}
+
+/// 40 random hex digits
+/// 
+/// Each `RandomState` is seeded with fresh OS randomness, which is enough
+/// for an identifier without pulling in an RNG crate.
+fn generate_run_id() -> String {
+    use std::hash::{BuildHasher, Hasher};
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let mut id = String::with_capacity(48);
+    for i in 0..3u32 {
+        let mut h = std::collections::hash_map::RandomState::new().build_hasher();
+        h.write_u128(nanos);
+        h.write_u32(std::process::id());
+        h.write_u32(i);
+        id.push_str(&format!("{:016x}", h.finish()));
+    }
+    id.truncate(40);
+    id
+}
+
+/// Size and shape of the keyspace at one point in time
+/// 
+/// Byte counts cover key and value payloads (8 bytes per integer), not
+/// allocator or hash table overhead. When computed from a sample, the
+/// totals are extrapolated to the whole keyspace and `largest_value` is
+/// the largest value seen in the sample.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DatasetStats {
+    /// Number of keys
+    pub keys: usize,
+    /// Approximate bytes held by keys and values
+    pub approx_bytes: usize,
+    /// Values with the `int` encoding
+    pub int_values: usize,
+    /// Values with the `embstr` encoding
+    pub embstr_values: usize,
+    /// Values with the `raw` encoding
+    pub raw_values: usize,
+    /// Size in bytes of the largest value
+    pub largest_value: usize,
+    /// Whether the figures were extrapolated from a sample
+    pub sampled: bool,
+}
+
+impl DatasetStats {
+    /// Walk up to `limit` keys of `dict` and extrapolate to its full size
+    pub(crate) fn collect(dict: &Dict, limit: usize) -> Self {
+        let mut st = DatasetStats::default();
+        let mut seen = 0;
+        for e in dict.inner.iter().take(limit) {
+            let len = value_size(&e.value().value);
+            match e.value().value.encoding() {
+                "int" => st.int_values += 1,
+                "embstr" => st.embstr_values += 1,
+                _ => st.raw_values += 1,
+            }
+            st.approx_bytes += e.key().len() + len;
+            st.largest_value = st.largest_value.max(len);
+            seen += 1;
+        }
+
+        let total = dict.len();
+        st.keys = seen;
+        // Only a walk cut short by the limit is a sample; keys created
+        // during a full walk just missed the snapshot
+        if seen == limit && total > seen {
+            let scale = |n: usize| (n as u128 * total as u128 / seen as u128) as usize;
+            st.approx_bytes = scale(st.approx_bytes);
+            st.int_values = scale(st.int_values);
+            st.embstr_values = scale(st.embstr_values);
+            st.raw_values = scale(st.raw_values);
+            st.keys = total;
+            st.sampled = true;
+        }
+        st
+    }
+}
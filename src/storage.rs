@@ -3,11 +3,182 @@
  *
  * This module provides the core storage layer for Ignix, implementing
  * a concurrent in-memory dictionary using DashMap with a fast hasher.
+ *
+ * Key expiry deadlines live in a second map next to the values. Expired
+ * keys are removed lazily, by the first operation that touches them.
+ *
+ * Each value is stored with the time it was last accessed, and the
+ * dictionary keeps a running total of its key and value bytes; together
+ * they drive `maxmemory` eviction (see [`Dict::lru_candidate`]).
  */
 
-use crate::protocol::Value;
-use dashmap::DashMap;
-use bytes::Bytes;
+use crate::error::CmdError;
+use crate::glob::glob_match;
+use crate::protocol::{SetCond, Value};
+use dashmap::{DashMap, SharedValue};
+use hashbrown::raw::RawTable;
+use bytes::{Bytes, BytesMut};
+use rand::Rng;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Low bits of a [`Dict::scan`] cursor that are always zero, left for a
+/// [`crate::ShardSet`] to record which of its shards a scan is in
+pub const SCAN_CURSOR_SPARE_BITS: u32 = 16;
+
+/// Why an increment was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncrError {
+    /// The stored value isn't a canonical 64-bit signed integer
+    NotInteger,
+    /// The result doesn't fit in an i64
+    Overflow,
+}
+
+impl From<IncrError> for CmdError {
+    fn from(e: IncrError) -> Self {
+        match e {
+            IncrError::NotInteger => CmdError::NotInteger,
+            IncrError::Overflow => CmdError::Overflow,
+        }
+    }
+}
+
+/// What a conditional write does to the key's expiry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyExpiry {
+    /// Drop it (plain SET)
+    Clear,
+    /// Leave it as it is (KEEPTTL)
+    Keep,
+    /// Replace it with this deadline (EX / PX)
+    At(Instant),
+}
+
+/// The monotonic and the wall clock, read together
+/// 
+/// Deadlines are kept as `Instant`s, which mean nothing to another
+/// process; the AOF logs them as Unix time in milliseconds instead
+/// (PEXPIREAT, SET PXAT), converted through one such reading so a batch
+/// of deadlines shares the same offset.
+#[derive(Debug, Clone, Copy)]
+pub struct WallClock {
+    instant: Instant,
+    unix_ms: i64,
+}
+
+impl WallClock {
+    pub fn now() -> Self {
+        let unix_ms = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as i64);
+        Self { instant: Instant::now(), unix_ms }
+    }
+
+    /// Unix time of the reading, in milliseconds
+    pub fn unix_ms(&self) -> i64 {
+        self.unix_ms
+    }
+
+    /// Unix time of a deadline, in milliseconds
+    pub fn unix_ms_of(&self, deadline: Instant) -> i64 {
+        match deadline.checked_duration_since(self.instant) {
+            Some(ahead) => self.unix_ms.saturating_add(ahead.as_millis() as i64),
+            None => self.unix_ms.saturating_sub((self.instant - deadline).as_millis() as i64),
+        }
+    }
+
+    /// The deadline at a Unix time in milliseconds
+    /// 
+    /// A time already past maps to the reading itself, which is due at
+    /// once; `None` if the deadline is too far out for an `Instant`.
+    pub fn deadline_of(&self, unix_ms: i64) -> Option<Instant> {
+        let ahead = unix_ms.saturating_sub(self.unix_ms).max(0);
+        self.instant.checked_add(Duration::from_millis(ahead as u64))
+    }
+}
+
+/// Parse a canonical 64-bit signed integer
+/// 
+/// Canonical means the integer prints back as exactly the same bytes, as
+/// Redis requires for both the `int` encoding and INCR: `007`, `+5`,
+/// `-0` and surrounding whitespace are rejected.
+/// Fast fail: integers start with a digit or '-' and fit in 19 digits
+/// (`i64::MAX`), plus the sign (`i64::MIN` is 20 chars).
+#[inline]
+pub fn parse_int(v: &[u8]) -> Option<i64> {
+    // The first-byte check already rules out '+' and whitespace; what's
+    // left to reject is a leading zero ("007", "-0", "-07")
+    let leading_zero = (v.len() > 1 && v[0] == b'0') || v.starts_with(b"-0");
+    let max_len = if v.first() == Some(&b'-') { 20 } else { 19 };
+    if v.len() > max_len || v.is_empty() || !(v[0].is_ascii_digit() || v[0] == b'-') || leading_zero {
+        return None;
+    }
+    std::str::from_utf8(v).ok().and_then(|s| s.parse::<i64>().ok())
+}
+
+/// Bytes a value counts for in the memory accounting (integers count 8)
+#[inline]
+pub(crate) fn value_size(v: &Value) -> usize {
+    match v {
+        Value::Int(_) => 8,
+        Value::Str(s) | Value::Blob(s) => s.len(),
+    }
+}
+
+/// Milliseconds since the first call, the clock access times are kept in
+#[inline]
+fn lru_clock() -> u64 {
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_millis() as u64
+}
+
+/// A stored value and the time it was last accessed
+#[derive(Debug)]
+pub(crate) struct Slot {
+    pub(crate) value: Value,
+    /// [`lru_clock`] reading of the last access; atomic so reads can
+    /// refresh it under the shard's read lock
+    atime: AtomicU64,
+}
+
+impl Slot {
+    #[inline]
+    fn new(value: Value) -> Self {
+        Self { value, atime: AtomicU64::new(lru_clock()) }
+    }
+
+    /// Record an access (skipping the store when the clock hasn't moved,
+    /// so hot keys don't bounce their cache line between readers)
+    #[inline]
+    fn touch(&self) {
+        let now = lru_clock();
+        if self.atime.load(Ordering::Relaxed) != now {
+            self.atime.store(now, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Take a value's string bytes out for in-place mutation
+/// 
+/// Copy-on-write: when the `Bytes` is uniquely owned its buffer (and spare
+/// capacity) is reused, so repeated APPENDs grow it amortized like a
+/// `Vec`. Only when a reader still holds a clone, e.g. a GET reply not
+/// yet written, is the content copied; the reader keeps the old bytes.
+/// A blob's region can't grow, so a blob is copied back to the heap and
+/// its region released to the blob store (counted dead once no reader
+/// holds it). `v` is left as a placeholder until the caller stores the
+/// result.
+fn take_mut(v: &mut Value) -> BytesMut {
+    match std::mem::replace(v, Value::Int(0)) {
+        Value::Str(s) => s.try_into_mut().unwrap_or_else(|shared| BytesMut::from(&shared[..])),
+        Value::Blob(b) => {
+            let buf = BytesMut::from(&b[..]);
+            drop(b);
+            buf
+        }
+        Value::Int(i) => BytesMut::from(i.to_string().as_bytes()),
+    }
+}
 
 /// High-performance in-memory dictionary
 /// 
@@ -16,7 +187,17 @@ use bytes::Bytes;
 #[derive(Default)]
 pub struct Dict {
     /// Concurrent DashMap for optimal performance (sharded locking)
-    pub(crate) inner: DashMap<Bytes, Value>,
+    pub(crate) inner: DashMap<Bytes, Slot, ahash::RandomState>,
+    /// Number of keys, maintained on every insert and removal so DBSIZE
+    /// doesn't have to lock and walk each DashMap shard
+    len: AtomicUsize,
+    /// Key and value bytes stored, maintained like `len`
+    used: AtomicUsize,
+    /// Expiry deadlines of the keys that have one
+    expires: DashMap<Bytes, Instant, ahash::RandomState>,
+    /// Number of entries in `expires`; while it is 0 no operation pays
+    /// for an expiry lookup
+    volatile: AtomicUsize,
 }
 
 impl Dict {
@@ -30,10 +211,97 @@ impl Dict {
     /// * `None` if key doesn't exist
     #[inline]
     pub fn get(&self, k: &[u8]) -> Option<Value> {
-        self.inner.get(k).map(|v| v.clone())
+        self.expire_if_due(k);
+        self.inner.get(k).map(|s| {
+            s.touch();
+            s.value.clone()
+        })
     }
     
     // note: Direct mutable references are not exposed; use entry APIs for atomic updates.
+
+    /// Create a dictionary whose hasher uses a fixed seed
+    /// 
+    /// By default every `Dict` hashes with random keys, so bucket layout
+    /// and iteration order differ between runs. Tests that need a
+    /// reproducible layout (cursor stability, random key selection) can
+    /// pin it here; servers should keep the default so clients can't
+    /// predict hash collisions.
+    /// 
+    /// # Arguments
+    /// * `seed` - Hasher seed; equal seeds give equal layouts for equal
+    ///   insertion sequences within a process
+    pub fn with_seed(seed: u64) -> Self {
+        let hasher = ahash::RandomState::with_seeds(seed, seed ^ 0x9e37_79b9_7f4a_7c15, seed.rotate_left(17), !seed);
+        Self {
+            inner: DashMap::with_hasher(hasher),
+            len: AtomicUsize::new(0),
+            used: AtomicUsize::new(0),
+            expires: DashMap::default(),
+            volatile: AtomicUsize::new(0),
+        }
+    }
+
+    /// Create a dictionary with room for `capacity` keys
+    /// 
+    /// Pre-sizing from an expected key count avoids the latency spikes of
+    /// the table doubling (and rehashing a shard) during the initial load.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: DashMap::with_capacity_and_hasher(capacity, ahash::RandomState::new()),
+            len: AtomicUsize::new(0),
+            used: AtomicUsize::new(0),
+            expires: DashMap::default(),
+            volatile: AtomicUsize::new(0),
+        }
+    }
+
+    /// Keys the table holds before it has to grow, summed over the
+    /// DashMap shards
+    /// 
+    /// Locks each shard briefly; meant for INFO, not the hot path.
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// Number of keys (O(1), read from the maintained counter)
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Whether the dictionary holds no keys
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Bytes of keys and values stored (O(1), read from the maintained
+    /// counter)
+    /// 
+    /// An approximation of the dataset's footprint: integers count as 8
+    /// bytes and per-key overhead isn't counted.
+    #[inline]
+    pub fn used_memory(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Move `used` from `old` bytes to `new` bytes, in one atomic step
+    #[inline]
+    fn account(&self, old: usize, new: usize) {
+        self.used.fetch_add(new.wrapping_sub(old), Ordering::Relaxed);
+    }
+
+    /// Update a stored value in place, keeping `used` and the access time
+    /// current
+    #[inline]
+    fn modify<R>(&self, slot: &mut Slot, update: impl FnOnce(&mut Value) -> R) -> R {
+        let before = value_size(&slot.value);
+        let result = update(&mut slot.value);
+        self.account(before, value_size(&slot.value));
+        slot.touch();
+        result
+    }
     
     /// Set a key-value pair
     /// 
@@ -45,9 +313,80 @@ impl Dict {
     /// * `v` - Value to store
     #[inline]
     pub fn set(&self, k: Bytes, v: Value) {
-        self.inner.insert(k, v);
+        self.getset(k, v);
     }
     
+    /// Replace a key's value, returning the previous one
+    /// 
+    /// The read of the old value and the write of the new one happen
+    /// under the same DashMap shard lock, so no other writer can slip in
+    /// between them. Any expiry of the key is cleared.
+    /// 
+    /// # Arguments
+    /// * `k` - Key as owned Bytes
+    /// * `v` - Value to store
+    /// 
+    /// # Returns
+    /// * `Some(Value)` holding the replaced value if the key existed
+    /// * `None` if the key was created
+    #[inline]
+    pub fn getset(&self, k: Bytes, v: Value) -> Option<Value> {
+        self.set_with(k, v, None, KeyExpiry::Clear).0
+    }
+
+    /// Conditionally store a value, returning the previous one
+    /// 
+    /// The existence check, the read of the old value, the write and the
+    /// expiry update all happen under the key's DashMap shard lock, so
+    /// lazy expiry of the old value can't remove the new one.
+    /// 
+    /// # Arguments
+    /// * `k` - Key as owned Bytes
+    /// * `v` - Value to store
+    /// * `cond` - NX/XX condition, if any
+    /// * `expiry` - What a store does to the key's expiry
+    /// 
+    /// # Returns
+    /// * `(previous value, whether v was stored)`
+    pub fn set_with(&self, k: Bytes, v: Value, cond: Option<SetCond>, expiry: KeyExpiry) -> (Option<Value>, bool) {
+        use dashmap::mapref::entry::Entry;
+        self.expire_if_due(&k);
+        let size = k.len() + value_size(&v);
+        match self.inner.entry(k) {
+            Entry::Occupied(e) if cond == Some(SetCond::Nx) => (Some(e.get().value.clone()), false),
+            Entry::Occupied(mut e) => {
+                self.update_expiry(e.key(), expiry);
+                let old = e.insert(Slot::new(v)).value;
+                self.account(e.key().len() + value_size(&old), size);
+                (Some(old), true)
+            }
+            Entry::Vacant(_) if cond == Some(SetCond::Xx) => (None, false),
+            Entry::Vacant(e) => {
+                self.update_expiry(e.key(), expiry);
+                e.insert(Slot::new(v));
+                self.len.fetch_add(1, Ordering::Relaxed);
+                self.account(0, size);
+                (None, true)
+            }
+        }
+    }
+
+    /// Apply a write's [`KeyExpiry`]; called with the key's shard locked
+    #[inline]
+    fn update_expiry(&self, k: &Bytes, expiry: KeyExpiry) {
+        match expiry {
+            KeyExpiry::Clear => {
+                self.clear_expiry(k);
+            }
+            KeyExpiry::Keep => {}
+            KeyExpiry::At(deadline) => {
+                if self.expires.insert(k.clone(), deadline).is_none() {
+                    self.volatile.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
     /// Delete a key
     /// 
     /// Removes the key and its associated value from the dictionary.
@@ -60,7 +399,34 @@ impl Dict {
     /// * `false` if key didn't exist
     #[inline]
     pub fn del(&self, k: &[u8]) -> bool {
-        self.inner.remove(k).is_some()
+        self.remove(k).is_some()
+    }
+
+    /// Remove a key, handing back its value
+    /// 
+    /// Lets the caller decide where the value is dropped (see `LazyFree`).
+    /// 
+    /// # Returns
+    /// * `Some(Value)` if the key existed
+    /// * `None` if it didn't
+    #[inline]
+    pub fn remove(&self, k: &[u8]) -> Option<Value> {
+        self.expire_if_due(k);
+        self.remove_entry(k)
+    }
+
+    /// Remove a key and its expiry, expired or not
+    /// 
+    /// The expiry is dropped under the key's shard lock, so it can't take
+    /// along the deadline of a key recreated right after.
+    fn remove_entry(&self, k: &[u8]) -> Option<Value> {
+        let (key, slot) = self.inner.remove_if(k, |key, _| {
+            self.clear_expiry(key);
+            true
+        })?;
+        self.len.fetch_sub(1, Ordering::Relaxed);
+        self.account(key.len() + value_size(&slot.value), 0);
+        Some(slot.value)
     }
     
     /// Rename a key
@@ -77,14 +443,28 @@ impl Dict {
     /// * `false` if source key didn't exist
     #[inline]
     pub fn rename(&self, from: Bytes, to: Bytes) -> bool {
+        self.expire_if_due(&from);
+        self.expire_if_due(&to);
         // Handle edge case where source and destination are the same
         if from == to {
-            return true;
+            return self.inner.contains_key(&from);
         }
         
         // Simple remove-then-insert; note this is not atomic across shards
-        if let Some((_, v)) = self.inner.remove(&from) {
-            self.inner.insert(to, v);
+        if let Some((_, slot)) = self.inner.remove(&from) {
+            // The destination takes over the source's expiry, if any
+            let deadline = self.clear_expiry(&from);
+            self.clear_expiry(&to);
+            let size = value_size(&slot.value);
+            self.account(from.len() + size, to.len() + size);
+            // Overwriting an existing destination removes one key overall
+            if let Some(old) = self.inner.insert(to.clone(), slot) {
+                self.len.fetch_sub(1, Ordering::Relaxed);
+                self.account(to.len() + value_size(&old.value), 0);
+            }
+            if let Some(deadline) = deadline {
+                self.expire_at(&to, deadline);
+            }
             true
         } else {
             false
@@ -103,38 +483,451 @@ impl Dict {
     /// * `false` if key doesn't exist
     #[inline]
     pub fn exists(&self, k: &[u8]) -> bool {
+        self.expire_if_due(k);
         self.inner.contains_key(k)
     }
 
+    /// Set a key's expiry deadline
+    /// 
+    /// The deadline is recorded while the key's shard is read-locked, so
+    /// a concurrent delete can't leave it behind for a later key of the
+    /// same name.
+    /// 
+    /// # Returns
+    /// * `true` if the key exists and now expires at `deadline`
+    /// * `false` if the key doesn't exist
+    pub fn expire_at(&self, k: &[u8], deadline: Instant) -> bool {
+        self.expire_if_due(k);
+        let Some(entry) = self.inner.get(k) else { return false };
+        if self.expires.insert(entry.key().clone(), deadline).is_none() {
+            self.volatile.fetch_add(1, Ordering::Relaxed);
+        }
+        true
+    }
+
+    /// Time left until a key expires
+    /// 
+    /// # Returns
+    /// * `None` if the key doesn't exist
+    /// * `Some(None)` if it exists without an expiry
+    /// * `Some(Some(left))` if it expires after `left`
+    pub fn ttl(&self, k: &[u8]) -> Option<Option<Duration>> {
+        self.expire_if_due(k);
+        if !self.inner.contains_key(k) {
+            return None;
+        }
+        Some(self.expires.get(k).map(|d| d.saturating_duration_since(Instant::now())))
+    }
+
+    /// Number of keys with an expiry
+    #[inline]
+    pub fn volatile_len(&self) -> usize {
+        self.volatile.load(Ordering::Relaxed)
+    }
+
+    /// Remove a key whose deadline has passed (lazy expiry)
+    /// 
+    /// The deadline is checked, and dropped, again under the key's shard
+    /// write lock, so a write that replaced the value or its expiry since
+    /// the first look is never removed with it.
+    #[inline]
+    fn expire_if_due(&self, k: &[u8]) {
+        if self.volatile.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        let now = Instant::now();
+        if self.expires.get(k).is_none_or(|d| *d > now) {
+            return;
+        }
+        let removed = self.inner.remove_if(k, |key, _| {
+            let due = self.expires.remove_if(key, |_, d| *d <= now).is_some();
+            if due {
+                self.volatile.fetch_sub(1, Ordering::Relaxed);
+            }
+            due
+        });
+        if let Some((key, slot)) = removed {
+            self.len.fetch_sub(1, Ordering::Relaxed);
+            self.account(key.len() + value_size(&slot.value), 0);
+        }
+    }
+
+    /// Drop a key's expiry, returning the deadline it had
+    #[inline]
+    fn clear_expiry(&self, k: &[u8]) -> Option<Instant> {
+        if self.volatile.load(Ordering::Relaxed) == 0 {
+            return None;
+        }
+        let (_, deadline) = self.expires.remove(k)?;
+        self.volatile.fetch_sub(1, Ordering::Relaxed);
+        Some(deadline)
+    }
+
+    /// Snapshot of every key, in iteration order
+    /// 
+    /// Each DashMap shard is read-locked in turn, so concurrent writes
+    /// may or may not be reflected.
+    pub fn keys(&self) -> Vec<Bytes> {
+        self.inner.iter().map(|e| e.key().clone()).collect()
+    }
+
+    /// Snapshot of every live entry with its expiry deadline, if any
+    /// 
+    /// Like [`Dict::keys`], concurrent writes may or may not be
+    /// reflected; keys already past their deadline are left out.
+    pub fn entries(&self) -> Vec<(Bytes, Value, Option<Instant>)> {
+        let now = Instant::now();
+        self.inner
+            .iter()
+            .filter_map(|e| {
+                let deadline = match self.volatile.load(Ordering::Relaxed) {
+                    0 => None,
+                    _ => self.expires.get(e.key()).map(|d| *d),
+                };
+                if deadline.is_some_and(|d| d <= now) {
+                    return None;
+                }
+                Some((e.key().clone(), e.value().value.clone(), deadline))
+            })
+            .collect()
+    }
+
+    /// Every live key matching a glob pattern (KEYS)
+    /// 
+    /// Like [`Dict::keys`], each DashMap shard is read-locked in turn and
+    /// concurrent writes may or may not be reflected. Patterns and keys
+    /// are compared as bytes (see [`glob_match`]).
+    pub fn keys_matching(&self, pattern: &[u8]) -> Vec<Bytes> {
+        let now = Instant::now();
+        let volatile = self.volatile.load(Ordering::Relaxed) > 0;
+        self.inner
+            .iter()
+            .filter(|e| glob_match(pattern, e.key()))
+            .filter(|e| !volatile || self.expires.get(e.key()).is_none_or(|d| *d > now))
+            .map(|e| e.key().clone())
+            .collect()
+    }
+
+    /// One batch of an incremental walk over the keys (SCAN)
+    /// 
+    /// As in Redis, the cursor is a bucket index with its bits reversed,
+    /// advanced by incrementing the reversed value. A key's bucket in a
+    /// table of 2^k buckets is the low k bits of its hash, so in this
+    /// order the buckets of a grown table come right where the bucket they
+    /// split from did, and a shrunk table's right where the first bucket
+    /// merged into them did: a full scan (from cursor 0 until 0 comes back)
+    /// returns every key present for the whole scan, however the tables
+    /// grow, shrink or rehash meanwhile. The cursor keeps the reversed hash
+    /// bits past the bucket index too, so after a shrink the keys already
+    /// returned are skipped and none comes back twice. Keys added or
+    /// removed meanwhile may or may not be returned.
+    /// 
+    /// The DashMap shards are walked one after the other, the shard index
+    /// in the cursor's top bits. Each call read-locks one shard at a time
+    /// and only probes the buckets it returns the keys of, giving up after
+    /// `count * 10` empty ones like Redis; a bucket's keys are returned
+    /// together, so a batch can hold a few more than `count` keys.
+    /// 
+    /// # Arguments
+    /// * `cursor` - 0 to start, then the cursor the previous batch returned
+    /// * `count` - Keys to return (at least 1)
+    /// 
+    /// # Returns
+    /// * The keys and the cursor to continue from, 0 once the scan is done
+    pub fn scan(&self, cursor: u64, count: usize) -> (Vec<Bytes>, u64) {
+        let count = count.max(1);
+        let now = Instant::now();
+        let volatile = self.volatile.load(Ordering::Relaxed) > 0;
+        let shards = self.inner.shards();
+        let shard_bits = shards.len().trailing_zeros();
+        // Shard index on top, then the reversed hash bits of the next key
+        // to return; the low spare bits are the caller's
+        let mut shard = cursor.checked_shr(64 - shard_bits).unwrap_or(0) as usize;
+        let mut position = (cursor >> SCAN_CURSOR_SPARE_BITS << SCAN_CURSOR_SPARE_BITS) << shard_bits;
+        let mut keys = Vec::with_capacity(count);
+        let mut empty_left = count.saturating_mul(10);
+        while let Some(table) = shards.get(shard).map(|s| s.read()) {
+            let bits = table.buckets().trailing_zeros();
+            debug_assert!(bits + shard_bits + SCAN_CURSOR_SPARE_BITS <= 64, "bucket index doesn't fit a cursor");
+            loop {
+                let bucket = (position.reverse_bits() as usize) & (table.buckets() - 1);
+                let before = keys.len();
+                self.scan_bucket(&table, bucket, position, |key| {
+                    if !(volatile && self.expires.get(key).is_some_and(|d| *d <= now)) {
+                        keys.push(key.clone());
+                    }
+                });
+                // The first position past this bucket
+                let step = 1u64.checked_shl(64 - bits);
+                let Some(next) = step.and_then(|step| (position & !(step - 1)).checked_add(step)) else {
+                    break;
+                };
+                position = next;
+                if keys.len() == before {
+                    empty_left = empty_left.saturating_sub(1);
+                }
+                if keys.len() >= count || empty_left == 0 {
+                    let cursor = (shard as u64).checked_shl(64 - shard_bits).unwrap_or(0) | position >> shard_bits;
+                    return (keys, cursor);
+                }
+            }
+            // This shard is done: the next one starts from its first bucket
+            drop(table);
+            shard += 1;
+            position = 0;
+            if keys.len() >= count && shard < shards.len() {
+                return (keys, (shard as u64) << (64 - shard_bits));
+            }
+        }
+        (keys, 0)
+    }
+
+    /// Hand the keys of one bucket of a DashMap shard's table to `f`,
+    /// those whose reversed hash is at least `position`
+    /// 
+    /// A key is stored in the first free slot on the probe sequence from
+    /// its bucket (the low bits of its hash), and lookups walk that
+    /// sequence only looking at the slots tagged with the top 7 bits of
+    /// the hash. Collecting every key of a bucket hence walks it once per
+    /// tag; each walk usually ends at the first group of slots.
+    fn scan_bucket(&self, table: &RawTable<(Bytes, SharedValue<Slot>)>, bucket: usize, position: u64, mut f: impl FnMut(&Bytes)) {
+        let mask = table.buckets() - 1;
+        for tag in 0..128u64 {
+            // SAFETY: the caller holds the shard's read lock, which keeps
+            // the buckets from being freed or moved
+            for slot in unsafe { table.iter_hash(tag << 57 | bucket as u64) } {
+                let (key, _) = unsafe { slot.as_ref() };
+                // The walk also meets keys of other buckets, probed past theirs
+                let hash = self.inner.hash_usize(key);
+                if hash & mask == bucket && (hash as u64).reverse_bits() >= position {
+                    f(key);
+                }
+            }
+        }
+    }
+
+    /// Remove every key
+    /// 
+    /// Keys are counted out one by one under each shard's lock, so writes
+    /// racing with the flush keep the key counter exact.
+    pub fn clear(&self) {
+        self.expires.retain(|_, _| {
+            self.volatile.fetch_sub(1, Ordering::Relaxed);
+            false
+        });
+        self.inner.retain(|k, slot| {
+            self.len.fetch_sub(1, Ordering::Relaxed);
+            self.account(k.len() + value_size(&slot.value), 0);
+            false
+        });
+    }
+
+    /// The least recently used of up to `samples` keys picked at random
+    /// (approximated LRU, like Redis' `maxmemory-samples`)
+    /// 
+    /// The sample is a run of keys from a random position of a random
+    /// DashMap shard, continuing into the next shards when that one runs
+    /// out; only one shard is read-locked at a time.
+    /// 
+    /// # Returns
+    /// * `None` if the dictionary is empty
+    pub fn lru_candidate(&self, samples: usize) -> Option<Bytes> {
+        let shards = self.inner.shards();
+        let mut rng = rand::thread_rng();
+        let first = rng.gen_range(0..shards.len());
+        let mut oldest: Option<(u64, Bytes)> = None;
+        let mut seen = 0;
+        for i in 0..shards.len() {
+            let table = shards[(first + i) % shards.len()].read();
+            if table.is_empty() {
+                continue;
+            }
+            let buckets = table.buckets();
+            let start = rng.gen_range(0..buckets);
+            for j in 0..buckets {
+                let index = (start + j) % buckets;
+                // SAFETY: index is within the table, and the shard's read
+                // lock keeps its buckets from being freed or moved
+                let (key, slot) = unsafe {
+                    if !table.is_bucket_full(index) {
+                        continue;
+                    }
+                    table.bucket(index).as_ref()
+                };
+                let atime = slot.get().atime.load(Ordering::Relaxed);
+                if oldest.as_ref().is_none_or(|(t, _)| atime < *t) {
+                    oldest = Some((atime, key.clone()));
+                }
+                seen += 1;
+                if seen >= samples.max(1) {
+                    return oldest.map(|(_, k)| k);
+                }
+            }
+        }
+        oldest.map(|(_, k)| k)
+    }
+
+    /// Set or clear a bit in a string value, creating the key if missing
+    /// 
+    /// The string is zero-padded only up to the byte holding `offset`.
+    /// Integer-encoded values are converted to their string form first.
+    /// 
+    /// # Returns
+    /// * The previous value of the bit
+    pub fn setbit(&self, k: &[u8], offset: u64, bit: bool) -> bool {
+        self.expire_if_due(k);
+        let byte = (offset >> 3) as usize;
+        let mask = 0x80u8 >> (offset & 7);
+        let update = |v: &mut Value| {
+            let mut buf = take_mut(v);
+            if buf.len() <= byte {
+                buf.resize(byte + 1, 0);
+            }
+            let old = buf[byte] & mask != 0;
+            if bit {
+                buf[byte] |= mask;
+            } else {
+                buf[byte] &= !mask;
+            }
+            *v = Value::Str(buf.freeze());
+            old
+        };
+
+        if let Some(mut e) = self.inner.get_mut(k) {
+            return self.modify(e.value_mut(), update);
+        }
+        let mut v = Value::Str(Bytes::new());
+        let old = update(&mut v);
+        // Another writer may have created the key meanwhile; apply on top of it
+        match self.inner.entry(Bytes::copy_from_slice(k)) {
+            dashmap::mapref::entry::Entry::Occupied(mut e) => self.modify(e.get_mut(), update),
+            dashmap::mapref::entry::Entry::Vacant(e) => {
+                self.account(0, k.len() + value_size(&v));
+                e.insert(Slot::new(v));
+                self.len.fetch_add(1, Ordering::Relaxed);
+                old
+            }
+        }
+    }
+
+    /// Append to a string value, creating it if missing
+    /// 
+    /// Integers are appended to in their decimal form. The string is
+    /// extended in place unless a reader still holds a clone of it.
+    /// 
+    /// # Arguments
+    /// * `k` - Key
+    /// * `data` - Bytes to append
+    /// * `max_len` - Longest string allowed (`proto-max-bulk-len`)
+    /// 
+    /// # Returns
+    /// * `Some(len)` - The new length of the string
+    /// * `None` - The result would exceed `max_len`; nothing was changed
+    pub fn append(&self, k: &[u8], data: &[u8], max_len: usize) -> Option<usize> {
+        self.expire_if_due(k);
+        let update = |v: &mut Value| {
+            let len = match v {
+                Value::Str(s) | Value::Blob(s) => s.len(),
+                Value::Int(i) => i.to_string().len(),
+            };
+            if len + data.len() > max_len {
+                return None;
+            }
+            let mut buf = take_mut(v);
+            buf.extend_from_slice(data);
+            *v = Value::Str(buf.freeze());
+            Some(len + data.len())
+        };
+
+        if let Some(mut e) = self.inner.get_mut(k) {
+            return self.modify(e.value_mut(), update);
+        }
+        if data.len() > max_len {
+            return None;
+        }
+        match self.inner.entry(Bytes::copy_from_slice(k)) {
+            // Created by another writer since the lookup above
+            dashmap::mapref::entry::Entry::Occupied(mut e) => self.modify(e.get_mut(), update),
+            dashmap::mapref::entry::Entry::Vacant(e) => {
+                e.insert(Slot::new(Value::Str(Bytes::copy_from_slice(data))));
+                self.len.fetch_add(1, Ordering::Relaxed);
+                self.account(0, k.len() + data.len());
+                Some(data.len())
+            }
+        }
+    }
+
+    /// Read a bit from a string value (0 for missing keys or past the end)
+    pub fn getbit(&self, k: &[u8], offset: u64) -> bool {
+        self.expire_if_due(k);
+        let byte = (offset >> 3) as usize;
+        let mask = 0x80u8 >> (offset & 7);
+        let Some(slot) = self.inner.get(k) else { return false };
+        slot.touch();
+        match &slot.value {
+            Value::Str(s) | Value::Blob(s) => s.get(byte).is_some_and(|b| b & mask != 0),
+            Value::Int(i) => i.to_string().as_bytes().get(byte).is_some_and(|b| b & mask != 0),
+        }
+    }
+
     /// Atomically increment an integer-like value stored under key, creating it if missing
-    pub fn incr(&self, k: &[u8]) -> i64 {
+    /// 
+    /// Shorthand for [`Dict::incr_by`] with a delta of 1.
+    #[inline]
+    pub fn incr(&self, k: &[u8]) -> Result<i64, IncrError> {
+        self.incr_by(k, 1)
+    }
+
+    /// Atomically add `delta` to an integer-like value, creating it if missing
+    /// 
+    /// A string counter is converted to `Value::Int` on its first increment,
+    /// so every later one is plain integer arithmetic under the shard lock.
+    /// A missing key counts as 0.
+    /// 
+    /// # Returns
+    /// * `Ok(n)` - The value after the increment
+    /// * `Err(IncrError::NotInteger)` - The value isn't an integer
+    /// * `Err(IncrError::Overflow)` - The result doesn't fit in an i64
+    /// 
+    /// On error the value is left untouched.
+    pub fn incr_by(&self, k: &[u8], delta: i64) -> Result<i64, IncrError> {
+        self.expire_if_due(k);
+        // Fast path: existing key, no owned key needed
+        if let Some(mut e) = self.inner.get_mut(k) {
+            return self.modify(e.value_mut(), |v| Self::incr_value(v, delta));
+        }
+
         use dashmap::mapref::entry::Entry;
-        // We need to convert slice to Bytes for entry API if we insert
-        // But DashMap entry API requires owned key.
-        // We can't avoid allocation/clone here if we use entry API with a slice input.
-        // So we accept the cost for INCR.
+        // Slow path: the entry API requires an owned key, so we accept the
+        // copy here. The key may have been created concurrently in between.
         match self.inner.entry(Bytes::copy_from_slice(k)) {
-            Entry::Occupied(mut e) => match e.get_mut() {
-                Value::Int(i) => {
-                    *i += 1;
-                    *i
-                }
-                Value::Str(s) => {
-                    let mut n = std::str::from_utf8(s)
-                        .ok()
-                        .and_then(|x| x.parse::<i64>().ok())
-                        .unwrap_or(0);
-                    n += 1;
-                    // Optimize: Store as Int now!
-                    *e.get_mut() = Value::Int(n);
-                    n
-                }
-                _ => 0,
-            },
+            Entry::Occupied(mut e) => self.modify(e.get_mut(), |v| Self::incr_value(v, delta)),
             Entry::Vacant(v) => {
-                v.insert(Value::Int(1));
-                1
+                v.insert(Slot::new(Value::Int(delta)));
+                self.len.fetch_add(1, Ordering::Relaxed);
+                self.account(0, k.len() + 8);
+                Ok(delta)
             }
         }
     }
-}
\ No newline at end of file
+
+    /// Add to a stored value in place, converting string counters to `Value::Int`
+    #[inline]
+    fn incr_value(v: &mut Value, delta: i64) -> Result<i64, IncrError> {
+        match v {
+            Value::Int(i) => {
+                *i = i.checked_add(delta).ok_or(IncrError::Overflow)?;
+                Ok(*i)
+            }
+            Value::Str(s) => {
+                let n = parse_int(s).ok_or(IncrError::NotInteger)?;
+                let n = n.checked_add(delta).ok_or(IncrError::Overflow)?;
+                // Store as Int from now on
+                *v = Value::Int(n);
+                Ok(n)
+            }
+            // Too long to hold an integer
+            Value::Blob(_) => Err(IncrError::NotInteger),
+        }
+    }
+}
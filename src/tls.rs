@@ -0,0 +1,120 @@
+/*!
+ * TLS Connections
+ *
+ * Optional encryption of client connections (feature `tls`), done with
+ * rustls over the same non-blocking mio sockets the plaintext path uses.
+ * `Config::tls_cert_file` and `Config::tls_key_file` name PEM files; with
+ * both set every accepted connection is wrapped in a `TlsStream`, which
+ * drives the handshake as the socket becomes readable or writable and
+ * hands the worker decrypted bytes, so the parser and `Conn` never see
+ * ciphertext.
+ */
+
+use anyhow::*;
+use mio::net::TcpStream;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::{ServerConfig, ServerConnection};
+use std::io::{BufReader, ErrorKind, Read, Write};
+use std::result::Result::{Ok, Err};
+use std::sync::Arc;
+
+/// Build the server-side TLS configuration from PEM files
+///
+/// # Arguments
+/// * `cert_path` - Certificate chain, leaf first
+/// * `key_path` - Private key of the leaf certificate (PKCS#8, PKCS#1 or SEC1)
+pub fn server_config(cert_path: &str, key_path: &str) -> Result<Arc<ServerConfig>> {
+    let mut certs = BufReader::new(
+        std::fs::File::open(cert_path).with_context(|| format!("opening tls certificate {}", cert_path))?,
+    );
+    let certs = rustls_pemfile::certs(&mut certs)
+        .collect::<Result<Vec<CertificateDer<'static>>, _>>()
+        .with_context(|| format!("reading tls certificate {}", cert_path))?;
+    ensure!(!certs.is_empty(), "no certificate in {}", cert_path);
+
+    let mut key = BufReader::new(
+        std::fs::File::open(key_path).with_context(|| format!("opening tls key {}", key_path))?,
+    );
+    let key: PrivateKeyDer<'static> = rustls_pemfile::private_key(&mut key)
+        .with_context(|| format!("reading tls key {}", key_path))?
+        .ok_or_else(|| anyhow!("no private key in {}", key_path))?;
+
+    let config = ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context("tls certificate and key don't match")?;
+    Ok(Arc::new(config))
+}
+
+/// A client socket carrying a TLS session
+///
+/// `read` and `write` deal in plaintext and never block: they return
+/// `WouldBlock` like the socket does, while TLS records the socket
+/// didn't take yet stay queued until [`TlsStream::wants_write`] is
+/// cleared by [`TlsStream::send_pending`].
+pub struct TlsStream {
+    pub(crate) sock: TcpStream,
+    session: ServerConnection,
+}
+
+impl TlsStream {
+    /// Start a server-side session on an accepted socket
+    pub fn new(sock: TcpStream, config: Arc<ServerConfig>) -> Result<Self> {
+        Ok(Self { sock, session: ServerConnection::new(config)? })
+    }
+
+    /// Whether TLS records (handshake or data) are waiting for the socket
+    pub fn wants_write(&self) -> bool {
+        self.session.wants_write()
+    }
+
+    /// Write queued TLS records until they're sent or the socket is full
+    pub fn send_pending(&mut self) -> std::io::Result<()> {
+        while self.session.wants_write() {
+            match self.session.write_tls(&mut self.sock) {
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            // Plaintext already decrypted; Ok(0) after the peer's close_notify
+            match self.session.reader().read(buf) {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                res => return res,
+            }
+            if self.session.read_tls(&mut self.sock)? == 0 {
+                return Ok(0);
+            }
+            if let Err(e) = self.session.process_new_packets() {
+                // Let the peer know why (a queued alert), then give up
+                let _ = self.send_pending();
+                return Err(std::io::Error::new(ErrorKind::InvalidData, e));
+            }
+            // Handshake messages in response to what was just read
+            self.send_pending()?;
+        }
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.session.writer().write(buf)?;
+        self.send_pending()?;
+        if n == 0 && !buf.is_empty() {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.send_pending()
+    }
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use common::{connect, resp_cmd, send_cmd, start_server_with};
+use ignix::*;
+use std::sync::{Mutex, Once};
+use std::time::Duration;
+
+/// Collects access log entries so tests can assert on them
+struct Capture;
+
+static LINES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+impl log::Log for Capture {
+    fn enabled(&self, meta: &log::Metadata) -> bool {
+        meta.target() == ACCESS_LOG_TARGET
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            LINES.lock().unwrap().push(record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn captured() -> Vec<String> {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        log::set_logger(&Capture).unwrap();
+        log::set_max_level(log::LevelFilter::Info);
+    });
+    LINES.lock().unwrap().clone()
+}
+
+#[test]
+fn denied_commands_are_logged_with_their_code() {
+    captured();
+    let config = Config { access_log: true, ..Default::default() };
+    let reply = CmdError::WrongType.to_resp();
+    assert_eq!(denial_code(&reply), Some("WRONGTYPE"));
+    access_log::record(&config, "10.0.0.1:5000", "GET", &reply, Duration::from_micros(3));
+    let expected = r#"{"addr":"10.0.0.1:5000","db":0,"cmd":"GET","reason":"WRONGTYPE","duration_us":3}"#;
+    assert!(captured().iter().any(|l| l == expected), "{:?}", captured());
+
+    // Malformed requests and successful replies are not denials
+    assert_eq!(denial_code(&CmdError::Syntax.to_resp()), None);
+    assert_eq!(denial_code(b"+OK\r\n"), None);
+}
+
+#[test]
+fn slow_commands_are_logged_by_the_server() {
+    captured();
+    let config = Config { access_log: true, access_log_slower_than: 1, ..Default::default() };
+    let addr = start_server_with(Shard::with_config(0, None, config));
+    let mut c = connect(addr);
+    let client = c.local_addr().unwrap().to_string();
+
+    let value = "ab".repeat(500);
+    send_cmd(&mut c, &resp_cmd(&[b"SET", b"a", value.as_bytes()]));
+    send_cmd(&mut c, &resp_cmd(&[b"SET", b"b", value.as_bytes()]));
+    assert_eq!(send_cmd(&mut c, &resp_cmd(&[b"LCS", b"a", b"b", b"LEN"])), b":1000\r\n");
+
+    // The entry is logged before the reply is written
+    let needle = format!(r#""addr":"{}","db":0,"cmd":"LCS","reason":"slow""#, client);
+    assert!(captured().iter().any(|l| l.contains(&needle)), "{:?}", captured());
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use common::{connect, resp_cmd, send_cmd, start_server_with};
+use ignix::*;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+const VALUE_LEN: usize = 16 * 1024;
+const GETS: usize = 2000;
+
+/// `mem_clients_normal` from an `INFO` reply
+fn client_memory(s: &mut TcpStream) -> usize {
+    s.write_all(&resp_cmd(&[b"INFO"])).unwrap();
+    let mut reply = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = s.read(&mut buf).unwrap();
+        reply.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&reply).into_owned();
+        let Some((header, body)) = text.split_once("\r\n") else { continue };
+        if body.len() < header[1..].parse::<usize>().unwrap() + 2 {
+            continue;
+        }
+        return body.lines().find_map(|l| l.strip_prefix("mem_clients_normal:")).unwrap().parse().unwrap();
+    }
+}
+
+/// Start a server holding one `VALUE_LEN` value under `k`
+fn start(config: Config) -> SocketAddr {
+    let addr = start_server_with(Shard::with_config(0, None, config));
+    let value = vec![b'v'; VALUE_LEN];
+    assert_eq!(send_cmd(&mut connect(addr), &resp_cmd(&[b"SET", b"k", &value])), b"+OK\r\n");
+    addr
+}
+
+/// Pipeline `GETS` GETs of `k` from another thread, without reading
+fn flood(s: &TcpStream) -> std::thread::JoinHandle<std::io::Result<()>> {
+    let mut writer = s.try_clone().unwrap();
+    std::thread::spawn(move || {
+        let request = resp_cmd(&[b"GET", b"k"]).repeat(GETS);
+        writer.write_all(&request)
+    })
+}
+
+#[test]
+fn slow_readers_stop_being_read() {
+    let config = Config { write_buf_high_water: 64 * 1024, write_buf_low_water: 16 * 1024, ..Default::default() };
+    let addr = start(config);
+    let mut probe = connect(addr);
+
+    // 32MB of replies requested, none read yet
+    let mut s = connect(addr);
+    let sender = flood(&s);
+    std::thread::sleep(Duration::from_millis(300));
+    let buffered = client_memory(&mut probe);
+    assert!(buffered < 512 * 1024, "{} bytes buffered for a client that doesn't read", buffered);
+
+    // Reading resumes the pipeline: every reply arrives, in order
+    let mut expected = format!("${}\r\n", VALUE_LEN).into_bytes();
+    expected.extend(vec![b'v'; VALUE_LEN]);
+    expected.extend_from_slice(b"\r\n");
+    let mut reply = vec![0u8; expected.len()];
+    for _ in 0..GETS {
+        s.read_exact(&mut reply).unwrap();
+        assert!(reply == expected);
+    }
+    sender.join().unwrap().unwrap();
+    assert_eq!(send_cmd(&mut s, &resp_cmd(&[b"PING"])), b"+PONG\r\n");
+}
+
+#[test]
+fn hard_limit_closes_the_connection() {
+    let config = Config { write_buf_high_water: 0, write_buf_hard_limit: 1024 * 1024, ..Default::default() };
+    let addr = start(config);
+
+    let mut s = connect(addr);
+    let sender = flood(&s);
+    std::thread::sleep(Duration::from_millis(300));
+    // Whatever reached the socket before the server gave up, then EOF
+    let mut reply = Vec::new();
+    let _ = s.read_to_end(&mut reply);
+    assert!(reply.len() < GETS * VALUE_LEN, "all {} bytes of replies were delivered", reply.len());
+    let _ = sender.join();
+
+    // Other clients are unaffected
+    assert_eq!(send_cmd(&mut connect(addr), &resp_cmd(&[b"PING"])), b"+PONG\r\n");
+}
@@ -1,39 +1,613 @@
 use ignix::*;
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
+
+fn exec(shard: &Shard, cmd: Cmd) -> Vec<u8> {
+    let mut out = BytesMut::new();
+    shard.exec(cmd, &mut out);
+    out.to_vec()
+}
 
 #[test]
 fn set_get_del_cycle() {
     let shard = Shard::new(0, None);
     assert_eq!(
-        String::from_utf8_lossy(&shard.exec(Cmd::Set(Bytes::from_static(b"a"), Bytes::from_static(b"1")))),
+        String::from_utf8_lossy(&exec(&shard, Cmd::Set(Bytes::from_static(b"a"), Bytes::from_static(b"1")))),
         "+OK\r\n"
     );
     assert_eq!(
-        String::from_utf8_lossy(&shard.exec(Cmd::Get(Bytes::from_static(b"a")))),
+        String::from_utf8_lossy(&exec(&shard, Cmd::Get(Bytes::from_static(b"a")))),
         "$1\r\n1\r\n"
     );
     assert_eq!(
-        String::from_utf8_lossy(&shard.exec(Cmd::Del(Bytes::from_static(b"a")))),
+        String::from_utf8_lossy(&exec(&shard, Cmd::Del(vec![Bytes::from_static(b"a")]))),
         ":1\r\n"
     );
     assert_eq!(
-        String::from_utf8_lossy(&shard.exec(Cmd::Get(Bytes::from_static(b"a")))),
+        String::from_utf8_lossy(&exec(&shard, Cmd::Get(Bytes::from_static(b"a")))),
         "$-1\r\n"
     );
 }
 
+#[test]
+fn del_counts_and_logs_only_existing_keys() {
+    let s = Shard::new(0, None);
+    let b = |k: &'static [u8]| Bytes::from_static(k);
+    exec(&s, Cmd::MSet(vec![(b(b"a"), b(b"1")), (b(b"c"), b(b"3"))]));
+    let before = s.master_repl_offset();
+
+    assert_eq!(exec(&s, Cmd::Del(vec![b(b"a"), b(b"b"), b(b"c"), b(b"a")])), b":2\r\n");
+    assert_eq!(s.dict.len(), 0);
+    assert_eq!(s.master_repl_offset(), before + emit_aof_del(&[b(b"a"), b(b"c")]).len() as u64);
+
+    // Nothing removed, nothing logged
+    assert_eq!(exec(&s, Cmd::Del(vec![b(b"a"), b(b"b")])), b":0\r\n");
+    assert_eq!(s.master_repl_offset(), before + emit_aof_del(&[b(b"a"), b(b"c")]).len() as u64);
+    assert_eq!(emit_aof_del(&[b(b"a"), b(b"c")]), b"*3\r\n$3\r\nDEL\r\n$1\r\na\r\n$1\r\nc\r\n");
+}
+
+#[test]
+fn exists_counts_duplicate_keys() {
+    let s = Shard::new(0, None);
+    let b = |k: &'static [u8]| Bytes::from_static(k);
+    exec(&s, Cmd::MSet(vec![(b(b"a"), b(b"1")), (b(b"b"), b(b"2"))]));
+    assert_eq!(exec(&s, Cmd::Exists(vec![b(b"a"), b(b"b"), b(b"a")])), b":3\r\n");
+    assert_eq!(exec(&s, Cmd::Exists(vec![b(b"a"), b(b"missing"), b(b"a"), b(b"missing")])), b":2\r\n");
+    assert_eq!(exec(&s, Cmd::Exists(vec![b(b"missing")])), b":0\r\n");
+}
+
+#[test]
+fn empty_keys_and_values_are_stored() {
+    let s = Shard::new(0, None);
+    assert_eq!(exec(&s, Cmd::Set(Bytes::new(), Bytes::from_static(b"v"))), b"+OK\r\n");
+    assert_eq!(exec(&s, Cmd::Set(Bytes::from_static(b"k"), Bytes::new())), b"+OK\r\n");
+    assert_eq!(exec(&s, Cmd::Get(Bytes::new())), b"$1\r\nv\r\n");
+    assert_eq!(exec(&s, Cmd::Get(Bytes::from_static(b"k"))), b"$0\r\n\r\n");
+    assert_eq!(exec(&s, Cmd::Exists(vec![Bytes::from_static(b"k")])), protocol::resp_integer(1));
+}
+
 #[test]
 fn rename_exists_incr() {
     let s = Shard::new(0, None);
-    s.exec(Cmd::Set(Bytes::from_static(b"x"), Bytes::from_static(b"41")));
+    exec(&s, Cmd::Set(Bytes::from_static(b"x"), Bytes::from_static(b"41")));
     assert_eq!(
-        s.exec(Cmd::Exists(Bytes::from_static(b"x"))),
+        exec(&s, Cmd::Exists(vec![Bytes::from_static(b"x")])),
         protocol::resp_integer(1)
     );
-    assert_eq!(s.exec(Cmd::Incr(Bytes::from_static(b"x"))), protocol::resp_integer(42));
+    assert_eq!(exec(&s, Cmd::Incr(Bytes::from_static(b"x"))), protocol::resp_integer(42));
     assert_eq!(
-        s.exec(Cmd::Rename(Bytes::from_static(b"x"), Bytes::from_static(b"y"))),
+        exec(&s, Cmd::Rename(Bytes::from_static(b"x"), Bytes::from_static(b"y"))),
         protocol::resp_simple("OK")
     );
-    assert_eq!(s.exec(Cmd::Get(Bytes::from_static(b"y"))), protocol::resp_bulk(b"42"));
+    assert_eq!(exec(&s, Cmd::Get(Bytes::from_static(b"y"))), protocol::resp_bulk(b"42"));
+}
+
+#[test]
+fn incr_converts_string_counter_to_int_encoding() {
+    let s = Shard::new(0, None);
+    s.dict.set(Bytes::from_static(b"n"), Value::Str(Bytes::from_static(b"41")));
+    assert_eq!(exec(&s, Cmd::ObjectEncoding(Bytes::from_static(b"n"))), protocol::resp_bulk(b"embstr"));
+
+    assert_eq!(exec(&s, Cmd::Incr(Bytes::from_static(b"n"))), protocol::resp_integer(42));
+    assert_eq!(exec(&s, Cmd::ObjectEncoding(Bytes::from_static(b"n"))), protocol::resp_bulk(b"int"));
+    assert_eq!(exec(&s, Cmd::ObjectEncoding(Bytes::from_static(b"missing"))), protocol::resp_null());
+}
+
+#[test]
+fn incr_on_a_non_integer_string_errors_and_keeps_the_value() {
+    let s = Shard::new(0, None);
+    let err = CmdError::NotInteger.to_resp();
+    for value in [&b"hello"[..], b"007", &[b'9'; 100]] {
+        let v = Bytes::copy_from_slice(value);
+        exec(&s, Cmd::Set(Bytes::from_static(b"k"), v.clone()));
+        assert_eq!(exec(&s, Cmd::Incr(Bytes::from_static(b"k"))), err);
+        assert_eq!(exec(&s, Cmd::Get(Bytes::from_static(b"k"))), protocol::resp_bulk(&v));
+    }
+}
+
+#[test]
+fn every_counter_command_rejects_non_numeric_strings() {
+    let s = Shard::new(0, None);
+    let k = || Bytes::from_static(b"k");
+    let err = CmdError::NotInteger.to_resp();
+    for value in [&b"abc"[..], b"", b"3.14"] {
+        let v = Bytes::copy_from_slice(value);
+        exec(&s, Cmd::Set(k(), v.clone()));
+        for cmd in [Cmd::Incr(k()), Cmd::IncrBy(k(), 5), Cmd::Decr(k()), Cmd::DecrBy(k(), 5)] {
+            assert_eq!(exec(&s, cmd), err, "{:?}", value);
+        }
+        assert_eq!(exec(&s, Cmd::Get(k())), protocol::resp_bulk(&v));
+        assert_eq!(s.dict.incr_by(b"k", 1), Err(IncrError::NotInteger));
+    }
+
+    // A missing key is not an error: it starts from 0
+    assert_eq!(s.dict.incr_by(b"missing", 1), Ok(1));
+}
+
+#[test]
+fn incrby_decr_and_decrby_share_the_counter() {
+    let s = Shard::new(0, None);
+    let k = || Bytes::from_static(b"n");
+    assert_eq!(exec(&s, Cmd::IncrBy(k(), 10)), protocol::resp_integer(10));
+    assert_eq!(exec(&s, Cmd::Decr(k())), protocol::resp_integer(9));
+    assert_eq!(exec(&s, Cmd::DecrBy(k(), 20)), protocol::resp_integer(-11));
+    assert_eq!(exec(&s, Cmd::Incr(k())), protocol::resp_integer(-10));
+    assert_eq!(exec(&s, Cmd::Decr(Bytes::from_static(b"fresh"))), protocol::resp_integer(-1));
+
+    // String counters are converted like INCR does
+    exec(&s, Cmd::Set(k(), Bytes::from_static(b"5")));
+    assert_eq!(exec(&s, Cmd::IncrBy(k(), -7)), protocol::resp_integer(-2));
+
+    let before = s.master_repl_offset();
+    exec(&s, Cmd::DecrBy(k(), 3));
+    assert_eq!(s.master_repl_offset(), before + emit_aof_incrby(b"n", -3).len() as u64);
+}
+
+#[test]
+fn counters_refuse_to_overflow() {
+    let s = Shard::new(0, None);
+    let k = || Bytes::from_static(b"n");
+    let overflow = CmdError::Overflow.to_resp();
+
+    exec(&s, Cmd::Set(k(), Bytes::from(i64::MAX.to_string())));
+    assert_eq!(exec(&s, Cmd::Incr(k())), overflow);
+    assert_eq!(exec(&s, Cmd::IncrBy(k(), 1)), overflow);
+    assert_eq!(exec(&s, Cmd::Get(k())), protocol::resp_bulk(i64::MAX.to_string().as_bytes()));
+
+    exec(&s, Cmd::Set(k(), Bytes::from(i64::MIN.to_string())));
+    assert_eq!(exec(&s, Cmd::Decr(k())), overflow);
+    assert_eq!(exec(&s, Cmd::Get(k())), protocol::resp_bulk(i64::MIN.to_string().as_bytes()));
+
+    // -i64::MIN doesn't exist, even for a key at 0
+    exec(&s, Cmd::Set(k(), Bytes::from_static(b"0")));
+    assert_eq!(exec(&s, Cmd::DecrBy(k(), i64::MIN)), overflow);
+    assert_eq!(exec(&s, Cmd::IncrBy(k(), i64::MIN)), protocol::resp_integer(i64::MIN));
+}
+
+#[test]
+fn debug_object_reports_encoding_and_length() {
+    let s = Shard::new(0, None);
+    exec(&s, Cmd::Set(Bytes::from_static(b"str"), Bytes::from_static(b"hello")));
+    exec(&s, Cmd::Set(Bytes::from_static(b"num"), Bytes::from_static(b"1000")));
+
+    let reply = String::from_utf8(exec(&s, Cmd::DebugObject(Bytes::from_static(b"str")))).unwrap();
+    assert!(reply.starts_with('+'), "{}", reply);
+    assert!(reply.contains("encoding:embstr"), "{}", reply);
+    assert!(reply.contains("serializedlength:6"), "{}", reply);
+
+    let reply = String::from_utf8(exec(&s, Cmd::DebugObject(Bytes::from_static(b"num")))).unwrap();
+    assert!(reply.contains("encoding:int"), "{}", reply);
+    assert!(reply.contains("serializedlength:3"), "{}", reply);
+
+    assert_eq!(exec(&s, Cmd::DebugObject(Bytes::from_static(b"missing"))), b"-ERR no such key\r\n");
+}
+
+#[test]
+fn setbit_getbit_and_offset_bounds() {
+    let s = Shard::new(0, None);
+    let k = || Bytes::from_static(b"bits");
+
+    assert_eq!(exec(&s, Cmd::SetBit(k(), 7, true)), protocol::resp_integer(0));
+    assert_eq!(exec(&s, Cmd::Get(k())), protocol::resp_bulk(&[0x01]));
+    assert_eq!(exec(&s, Cmd::SetBit(k(), 7, false)), protocol::resp_integer(1));
+    assert_eq!(exec(&s, Cmd::GetBit(k(), 7)), protocol::resp_integer(0));
+
+    // Large but allowed offset only grows the string up to the needed byte
+    let offset = 8 * 1024 * 1024 + 3;
+    assert_eq!(exec(&s, Cmd::SetBit(k(), offset, true)), protocol::resp_integer(0));
+    assert_eq!(exec(&s, Cmd::GetBit(k(), offset)), protocol::resp_integer(1));
+    match s.dict.get(b"bits") {
+        Some(Value::Str(v)) => assert_eq!(v.len() as u64, offset / 8 + 1),
+        other => panic!("unexpected value {:?}", other),
+    }
+
+    // Offsets at or beyond proto-max-bulk-len * 8 are rejected
+    let err = b"-ERR bit offset is not an integer or out of range\r\n".to_vec();
+    let limit = protocol::DEFAULT_PROTO_MAX_BULK_LEN as u64 * 8;
+    assert_eq!(exec(&s, Cmd::SetBit(k(), limit, true)), err);
+    assert_eq!(exec(&s, Cmd::GetBit(k(), limit)), err);
+
+    let mut config = Config::default();
+    config.proto.max_bulk_len = 16;
+    let small = Shard::with_config(0, None, config);
+    assert_eq!(exec(&small, Cmd::SetBit(k(), 127, true)), protocol::resp_integer(0));
+    assert_eq!(exec(&small, Cmd::SetBit(k(), 128, true)), err);
+}
+
+#[test]
+fn getset_returns_int_encoded_value_as_string() {
+    let s = Shard::new(0, None);
+    assert_eq!(exec(&s, Cmd::GetSet(Bytes::from_static(b"g"), Bytes::from_static(b"a"))), protocol::resp_null());
+
+    exec(&s, Cmd::Set(Bytes::from_static(b"g"), Bytes::from_static(b"10")));
+    exec(&s, Cmd::Incr(Bytes::from_static(b"g")));
+    assert_eq!(exec(&s, Cmd::ObjectEncoding(Bytes::from_static(b"g"))), protocol::resp_bulk(b"int"));
+
+    assert_eq!(exec(&s, Cmd::GetSet(Bytes::from_static(b"g"), Bytes::from_static(b"new"))), protocol::resp_bulk(b"11"));
+    assert_eq!(exec(&s, Cmd::Get(Bytes::from_static(b"g"))), protocol::resp_bulk(b"new"));
+    assert_eq!(exec(&s, Cmd::ObjectEncoding(Bytes::from_static(b"g"))), protocol::resp_bulk(b"embstr"));
+}
+
+#[test]
+fn reset_and_flushdb_empty_the_keyspace() {
+    let s = Shard::new(0, None);
+    exec(&s, Cmd::Set(Bytes::from_static(b"a"), Bytes::from_static(b"1")));
+    s.reset();
+    assert_eq!(exec(&s, Cmd::Exists(vec![Bytes::from_static(b"a")])), protocol::resp_integer(0));
+
+    exec(&s, Cmd::Set(Bytes::from_static(b"b"), Bytes::from_static(b"2")));
+    assert_eq!(exec(&s, Cmd::FlushDb), b"+OK\r\n");
+    assert_eq!(exec(&s, Cmd::Get(Bytes::from_static(b"b"))), protocol::resp_null());
+}
+
+/// Read a numeric field out of INFO output
+fn info_field(s: &Shard, section: &str, field: &str) -> u64 {
+    let info = s.info(Some(section.as_bytes()));
+    info.lines()
+        .find_map(|l| l.strip_prefix(&format!("{}:", field)))
+        .unwrap_or_else(|| panic!("{} missing from {}", field, info))
+        .parse()
+        .unwrap()
+}
+
+#[test]
+fn keyspace_hits_and_misses_are_reported_and_reset() {
+    let s = Shard::new(0, None);
+    exec(&s, Cmd::Set(Bytes::from_static(b"hit"), Bytes::from_static(b"1")));
+    for _ in 0..6 {
+        exec(&s, Cmd::Get(Bytes::from_static(b"hit")));
+    }
+    exec(&s, Cmd::Get(Bytes::from_static(b"miss")));
+    // MGET counts every key: one hit, one miss
+    exec(&s, Cmd::MGet(vec![Bytes::from_static(b"hit"), Bytes::from_static(b"miss")]));
+
+    let hits = info_field(&s, "stats", "keyspace_hits");
+    let misses = info_field(&s, "stats", "keyspace_misses");
+    assert_eq!((hits, misses), (7, 2));
+    assert!((hits as f64 / (hits + misses) as f64 - 7.0 / 9.0).abs() < f64::EPSILON);
+
+    let reply = String::from_utf8(exec(&s, Cmd::Info(None))).unwrap();
+    assert!(reply.contains("# Stats\r\nkeyspace_hits:7\r\nkeyspace_misses:2\r\n"), "{}", reply);
+
+    assert_eq!(exec(&s, Cmd::ConfigResetStat), b"+OK\r\n");
+    assert_eq!(info_field(&s, "stats", "keyspace_hits"), 0);
+    assert_eq!(info_field(&s, "stats", "keyspace_misses"), 0);
+}
+
+#[test]
+fn master_repl_offset_advances_only_on_writes() {
+    let s = Shard::new(0, None);
+    assert_eq!(info_field(&s, "replication", "master_repl_offset"), 0);
+
+    exec(&s, Cmd::Set(Bytes::from_static(b"k"), Bytes::from_static(b"v")));
+    let after_set = info_field(&s, "replication", "master_repl_offset");
+    assert_eq!(after_set, emit_aof_set(b"k", b"v").len() as u64);
+
+    exec(&s, Cmd::Incr(Bytes::from_static(b"n")));
+    let after_incr = info_field(&s, "replication", "master_repl_offset");
+    assert_eq!(after_incr, after_set + emit_aof_incr(b"n").len() as u64);
+
+    // Reads and writes that change nothing leave the offset alone
+    exec(&s, Cmd::Get(Bytes::from_static(b"k")));
+    exec(&s, Cmd::MGet(vec![Bytes::from_static(b"k"), Bytes::from_static(b"n")]));
+    exec(&s, Cmd::Exists(vec![Bytes::from_static(b"k")]));
+    exec(&s, Cmd::Rename(Bytes::from_static(b"missing"), Bytes::from_static(b"x")));
+    assert_eq!(s.master_repl_offset(), after_incr);
+}
+
+#[test]
+fn set_with_get_nx_and_xx() {
+    let s = Shard::new(0, None);
+    let k = || Bytes::from_static(b"k");
+    let get = SetOptions { get: true, ..Default::default() };
+    let nx_get = SetOptions { cond: Some(SetCond::Nx), get: true, ..Default::default() };
+    let xx = SetOptions { cond: Some(SetCond::Xx), ..Default::default() };
+
+    // XX on a missing key doesn't write
+    assert_eq!(exec(&s, Cmd::SetWith(k(), Bytes::from_static(b"x"), xx)), protocol::resp_null());
+    assert_eq!(exec(&s, Cmd::Exists(vec![k()])), protocol::resp_integer(0));
+
+    // GET returns the previous value (null when absent) and writes
+    assert_eq!(exec(&s, Cmd::SetWith(k(), Bytes::from_static(b"1"), get)), protocol::resp_null());
+    assert_eq!(exec(&s, Cmd::SetWith(k(), Bytes::from_static(b"2"), get)), protocol::resp_bulk(b"1"));
+
+    // NX GET on an existing key returns the old value without writing
+    assert_eq!(exec(&s, Cmd::SetWith(k(), Bytes::from_static(b"3"), nx_get)), protocol::resp_bulk(b"2"));
+    assert_eq!(exec(&s, Cmd::Get(k())), protocol::resp_bulk(b"2"));
+
+    assert_eq!(exec(&s, Cmd::SetWith(k(), Bytes::from_static(b"4"), xx)), b"+OK\r\n");
+    assert_eq!(exec(&s, Cmd::Get(k())), protocol::resp_bulk(b"4"));
+}
+
+#[test]
+fn non_canonical_integers_are_stored_verbatim() {
+    let s = Shard::new(0, None);
+    for raw in [&b"007"[..], b"+5", b" 5", b"5 ", b"-0", b"-07", b"00"] {
+        let k = Bytes::from_static(b"n");
+        exec(&s, Cmd::Set(k.clone(), Bytes::copy_from_slice(raw)));
+        assert_eq!(exec(&s, Cmd::Get(k.clone())), protocol::resp_bulk(raw), "{:?}", raw);
+        assert_ne!(exec(&s, Cmd::ObjectEncoding(k)), protocol::resp_bulk(b"int"), "{:?}", raw);
+    }
+
+    // Canonical forms still get the integer encoding
+    for raw in [&b"0"[..], b"7", b"-5", b"9223372036854775807"] {
+        let k = Bytes::from_static(b"n");
+        exec(&s, Cmd::Set(k.clone(), Bytes::copy_from_slice(raw)));
+        assert_eq!(exec(&s, Cmd::ObjectEncoding(k.clone())), protocol::resp_bulk(b"int"), "{:?}", raw);
+        assert_eq!(exec(&s, Cmd::Get(k)), protocol::resp_bulk(raw));
+    }
+}
+
+#[test]
+fn integer_encoding_holds_at_the_i64_bounds() {
+    let s = Shard::new(0, None);
+    let cases: &[(&[u8], &[u8])] = &[
+        (b"-9223372036854775808", b"int"),
+        (b"9223372036854775807", b"int"),
+        // 20 chars, positive: past i64::MAX
+        (b"99999999999999999999", b"embstr"),
+        // One past either bound
+        (b"9223372036854775808", b"embstr"),
+        (b"-9223372036854775809", b"embstr"),
+    ];
+    for (raw, enc) in cases {
+        let k = Bytes::from_static(b"n");
+        exec(&s, Cmd::Set(k.clone(), Bytes::copy_from_slice(raw)));
+        assert_eq!(exec(&s, Cmd::ObjectEncoding(k.clone())), protocol::resp_bulk(enc), "{:?}", raw);
+        assert_eq!(exec(&s, Cmd::Get(k)), protocol::resp_bulk(raw));
+    }
+    assert_eq!(storage::parse_int(b"-9223372036854775808"), Some(i64::MIN));
+    assert_eq!(storage::parse_int(b"9223372036854775807"), Some(i64::MAX));
+    assert_eq!(storage::parse_int(b"99999999999999999999"), None);
+}
+
+#[test]
+fn debug_encoding_asserts_the_current_encoding() {
+    let s = Shard::new(0, None);
+    let check = |k: &'static [u8], enc: &'static [u8]| {
+        exec(&s, Cmd::DebugEncoding(Bytes::from_static(k), Bytes::from_static(enc)))
+    };
+    exec(&s, Cmd::Set(Bytes::from_static(b"n"), Bytes::from_static(b"12345")));
+    assert_eq!(check(b"n", b"int"), b"+OK\r\n");
+    assert_eq!(check(b"n", b"embstr"), b"-ERR encoding mismatch: expected 'embstr', found 'int'\r\n");
+    assert_eq!(check(b"missing", b"int"), b"-ERR no such key\r\n");
+}
+
+#[test]
+fn dbsize_stays_exact_under_concurrent_writes() {
+    let s = Shard::new(0, None);
+    let key = |t: usize, i: usize| Bytes::from(format!("k:{}:{}", t % 2, i));
+
+    // Pairs of threads race on the same keys through every mutating path
+    std::thread::scope(|scope| {
+        for t in 0..4 {
+            let s = &s;
+            scope.spawn(move || {
+                for i in 0..2000 {
+                    exec(s, Cmd::Set(key(t, i), Bytes::from_static(b"v")));
+                    if i % 3 == 0 {
+                        exec(s, Cmd::Del(vec![key(t, i)]));
+                    }
+                    if i % 5 == 0 {
+                        exec(s, Cmd::Incr(key(t, i + 1)));
+                        exec(s, Cmd::Rename(key(t, i + 1), key(t, i + 2)));
+                    }
+                    exec(s, Cmd::SetBit(key(t, i + 3), 1, true));
+                }
+            });
+        }
+    });
+
+    let actual = (0..2)
+        .flat_map(|t| (0..2003).map(move |i| key(t, i)))
+        .filter(|k| s.dict.exists(k))
+        .count();
+    assert_eq!(exec(&s, Cmd::DbSize), protocol::resp_integer(actual as i64));
+    assert!(s.info(Some(b"keyspace")).contains(&format!("db0:keys={},", actual)));
+
+    assert_eq!(exec(&s, Cmd::FlushAll), b"+OK\r\n");
+    assert_eq!(exec(&s, Cmd::DbSize), protocol::resp_integer(0));
+    assert_eq!(s.info(Some(b"keyspace")), "# Keyspace\r\n");
+}
+
+#[test]
+fn dataset_stats_describe_the_keyspace() {
+    let s = Shard::new(0, None);
+    assert_eq!(s.dataset_stats(), DatasetStats::default());
+
+    exec(&s, Cmd::Set(Bytes::from_static(b"n"), Bytes::from_static(b"12345")));
+    exec(&s, Cmd::Set(Bytes::from_static(b"short"), Bytes::from_static(b"hello")));
+    exec(&s, Cmd::Set(Bytes::from_static(b"long"), Bytes::from(vec![b'x'; 100])));
+    exec(&s, Cmd::Incr(Bytes::from_static(b"ctr")));
+
+    let st = s.dataset_stats();
+    assert_eq!(st, DatasetStats {
+        keys: 4,
+        // keys: 1 + 5 + 4 + 3, values: 8 + 5 + 100 + 8
+        approx_bytes: 13 + 121,
+        int_values: 2,
+        embstr_values: 1,
+        raw_values: 1,
+        largest_value: 100,
+        sampled: false,
+    });
+
+    // A sample covering everything is exact; a partial one scales up
+    assert_eq!(s.dataset_stats_sampled(10), st);
+    let partial = s.dataset_stats_sampled(2);
+    assert!(partial.sampled);
+    assert_eq!(partial.keys, 4);
+    assert_eq!(partial.int_values + partial.embstr_values + partial.raw_values, 4);
+}
+
+#[test]
+fn run_id_is_stable_until_changed() {
+    let s = Shard::new(0, None);
+    let run_id = |s: &Shard| {
+        let info = s.info(Some(b"server"));
+        let id = info.lines().find_map(|l| l.strip_prefix("run_id:")).unwrap().to_string();
+        assert_eq!(id.len(), 40, "{}", info);
+        assert!(id.bytes().all(|b| b.is_ascii_hexdigit() && !b.is_ascii_uppercase()));
+        id
+    };
+
+    let first = run_id(&s);
+    // Process-wide: every shard reports the same id
+    assert_eq!(run_id(&Shard::new(1, None)), first);
+    assert_eq!(run_id(&s), first);
+
+    assert_eq!(exec(&s, Cmd::DebugChangeReplId), b"+OK\r\n");
+    assert_ne!(run_id(&s), first);
+}
+
+#[test]
+fn seeded_dicts_iterate_in_the_same_order() {
+    let keys: Vec<Bytes> = (0..500).map(|i| Bytes::from(format!("key:{}", i))).collect();
+    let order = |d: &Dict| {
+        for k in &keys {
+            d.set(k.clone(), Value::Int(1));
+        }
+        d.keys()
+    };
+    let a = order(&Dict::with_seed(42));
+    assert_eq!(a.len(), keys.len());
+    assert_eq!(order(&Dict::with_seed(42)), a);
+    assert_ne!(order(&Dict::with_seed(7)), a);
+}
+
+#[test]
+fn dict_capacity_and_load_factor_track_inserts() {
+    let load_factor = |s: &Shard| -> f64 {
+        let info = s.info(Some(b"memory"));
+        info.lines().find_map(|l| l.strip_prefix("dict_load_factor:")).unwrap().parse().unwrap()
+    };
+    let s = Shard::new(0, None);
+    let empty = info_field(&s, "memory", "dict_capacity");
+    assert_eq!(load_factor(&s), 0.0);
+
+    for i in 0..10_000 {
+        exec(&s, Cmd::Set(Bytes::from(format!("key:{}", i)), Bytes::from_static(b"v")));
+    }
+    let grown = info_field(&s, "memory", "dict_capacity");
+    assert!(grown >= 10_000 && grown > empty, "capacity {} -> {}", empty, grown);
+    let load = load_factor(&s);
+    assert!(load > 0.0 && load <= 1.0, "load factor {}", load);
+}
+
+#[test]
+fn expected_keys_presizes_the_dict() {
+    let config = Config { expected_keys: 50_000, ..Default::default() };
+    let s = Shard::with_config(0, None, config);
+    let presized = s.dict.capacity();
+    assert!(presized >= 50_000, "capacity {}", presized);
+
+    // Loading the expected keys never resizes the table
+    for i in 0..40_000 {
+        exec(&s, Cmd::Set(Bytes::from(format!("key:{}", i)), Bytes::from_static(b"v")));
+    }
+    assert_eq!(s.dict.capacity(), presized);
+}
+
+#[test]
+fn append_extends_strings_and_leaves_held_clones_alone() {
+    let s = Shard::new(0, None);
+    let k = || Bytes::from_static(b"k");
+    assert_eq!(exec(&s, Cmd::Append(k(), Bytes::from_static(b"hello"))), protocol::resp_integer(5));
+    // A reader still holds the current bytes while the value changes
+    let held = s.dict.get(b"k").unwrap();
+    assert_eq!(exec(&s, Cmd::Append(k(), Bytes::from_static(b" world"))), protocol::resp_integer(11));
+    assert_eq!(held, Value::Str(Bytes::from_static(b"hello")));
+    assert_eq!(exec(&s, Cmd::Get(k())), protocol::resp_bulk(b"hello world"));
+
+    // Integers are appended to in decimal form
+    exec(&s, Cmd::Set(Bytes::from_static(b"n"), Bytes::from_static(b"12")));
+    assert_eq!(exec(&s, Cmd::Append(Bytes::from_static(b"n"), Bytes::from_static(b"3"))), protocol::resp_integer(3));
+    assert_eq!(exec(&s, Cmd::Get(Bytes::from_static(b"n"))), protocol::resp_bulk(b"123"));
+}
+
+#[test]
+fn append_reuses_a_uniquely_owned_buffer() {
+    let s = Shard::new(0, None);
+    let ptr = |s: &Shard| match s.dict.get(b"k").unwrap() {
+        Value::Str(b) => b.as_ptr(),
+        v => panic!("{:?}", v),
+    };
+    exec(&s, Cmd::Append(Bytes::from_static(b"k"), Bytes::from(vec![b'a'; 100])));
+    // The first append to it reallocates with spare capacity
+    exec(&s, Cmd::Append(Bytes::from_static(b"k"), Bytes::from_static(b"b")));
+    let before = ptr(&s);
+    for _ in 0..10 {
+        exec(&s, Cmd::Append(Bytes::from_static(b"k"), Bytes::from_static(b"c")));
+    }
+    assert_eq!(ptr(&s), before);
+    assert_eq!(exec(&s, Cmd::Append(Bytes::from_static(b"k"), Bytes::new())), protocol::resp_integer(111));
+}
+
+#[test]
+fn append_is_bounded_by_proto_max_bulk_len() {
+    let mut config = Config::default();
+    config.proto.max_bulk_len = 8;
+    let s = Shard::with_config(0, None, config);
+    assert_eq!(exec(&s, Cmd::Append(Bytes::from_static(b"k"), Bytes::from_static(b"12345"))), protocol::resp_integer(5));
+    assert_eq!(exec(&s, Cmd::Append(Bytes::from_static(b"k"), Bytes::from_static(b"6789"))), CmdError::StringTooLong.to_resp());
+    assert_eq!(exec(&s, Cmd::Get(Bytes::from_static(b"k"))), protocol::resp_bulk(b"12345"));
+}
+
+#[test]
+fn memory_purge_after_deleting_a_large_dataset() {
+    let s = Shard::new(0, None);
+    for i in 0..1000 {
+        exec(&s, Cmd::Set(Bytes::from(format!("key:{}", i)), Bytes::from(vec![b'x'; 64 * 1024])));
+    }
+    exec(&s, Cmd::FlushAll);
+    assert_eq!(s.dict.len(), 0);
+    assert_eq!(exec(&s, Cmd::MemoryPurge), b"+OK\r\n");
+}
+
+/// Shard holding the pair from the Redis LCS documentation
+fn lcs_shard() -> Shard {
+    let s = Shard::new(0, None);
+    exec(&s, Cmd::Set(Bytes::from_static(b"key1"), Bytes::from_static(b"ohmytext")));
+    exec(&s, Cmd::Set(Bytes::from_static(b"key2"), Bytes::from_static(b"mynewtext")));
+    s
+}
+
+fn lcs_cmd(opts: LcsOptions) -> Cmd {
+    Cmd::Lcs(Bytes::from_static(b"key1"), Bytes::from_static(b"key2"), opts)
+}
+
+#[test]
+fn lcs_replies_with_the_subsequence_or_its_length() {
+    let s = lcs_shard();
+    assert_eq!(exec(&s, lcs_cmd(LcsOptions::default())), protocol::resp_bulk(b"mytext"));
+    assert_eq!(exec(&s, lcs_cmd(LcsOptions { len: true, ..Default::default() })), protocol::resp_integer(6));
+
+    // Missing keys are empty strings
+    let missing = Cmd::Lcs(Bytes::from_static(b"key1"), Bytes::from_static(b"nope"), LcsOptions::default());
+    assert_eq!(exec(&s, missing), protocol::resp_bulk(b""));
+}
+
+#[test]
+fn lcs_idx_reports_match_ranges_from_the_end() {
+    let s = lcs_shard();
+    let expected: &[u8] = b"*4\r\n$7\r\nmatches\r\n*2\r\n\
+        *2\r\n*2\r\n:4\r\n:7\r\n*2\r\n:5\r\n:8\r\n\
+        *2\r\n*2\r\n:2\r\n:3\r\n*2\r\n:0\r\n:1\r\n\
+        $3\r\nlen\r\n:6\r\n";
+    assert_eq!(exec(&s, lcs_cmd(LcsOptions { idx: true, ..Default::default() })), expected);
+
+    let opts = LcsOptions { idx: true, min_match_len: 4, with_match_len: true, ..Default::default() };
+    let expected: &[u8] = b"*4\r\n$7\r\nmatches\r\n*1\r\n\
+        *3\r\n*2\r\n:4\r\n:7\r\n*2\r\n:5\r\n:8\r\n:4\r\n\
+        $3\r\nlen\r\n:6\r\n";
+    assert_eq!(exec(&s, lcs_cmd(opts)), expected);
+}
+
+#[test]
+fn lcs_table_is_bounded_by_proto_max_bulk_len() {
+    let mut config = Config::default();
+    config.proto.max_bulk_len = 1024;
+    let s = Shard::with_config(0, None, config);
+    exec(&s, Cmd::Set(Bytes::from_static(b"key1"), Bytes::from(vec![b'a'; 100])));
+    exec(&s, Cmd::Set(Bytes::from_static(b"key2"), Bytes::from(vec![b'a'; 100])));
+    assert_eq!(exec(&s, lcs_cmd(LcsOptions::default())), CmdError::LcsTooLarge.to_resp());
 }
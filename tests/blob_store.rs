@@ -0,0 +1,105 @@
+#![cfg(target_os = "linux")]
+
+use bytes::{Bytes, BytesMut};
+use ignix::*;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// System allocator that tracks the bytes currently allocated
+struct Counting;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        LIVE.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn exec(shard: &Shard, cmd: Cmd) -> Vec<u8> {
+    let mut out = BytesMut::new();
+    shard.exec(cmd, &mut out);
+    out.to_vec()
+}
+
+fn blob_shard(name: &str, capacity: usize) -> Shard {
+    let path = std::env::temp_dir().join(format!("ignix-{}-{}.blobs", name, std::process::id()));
+    let config = Config { blob_threshold: 1024, ..Default::default() };
+    let mut shard = Shard::with_config(0, None, config);
+    shard.blobs = Some(BlobStore::open(path.to_str().unwrap(), capacity).unwrap());
+    // The mapping outlives the file name
+    let _ = std::fs::remove_file(&path);
+    shard
+}
+
+#[test]
+fn large_values_live_outside_the_heap() {
+    const SIZE: usize = 8 * 1024 * 1024;
+    let s = blob_shard("heap", 2 * SIZE);
+    let value: Vec<u8> = (0..SIZE).map(|i| (i % 251) as u8).collect();
+
+    let before = LIVE.load(Ordering::Relaxed);
+    exec(&s, Cmd::Set(Bytes::from_static(b"big"), Bytes::copy_from_slice(&value)));
+    let grown = LIVE.load(Ordering::Relaxed).saturating_sub(before);
+    assert!(grown < SIZE / 2, "heap grew by {} bytes", grown);
+
+    assert_eq!(exec(&s, Cmd::Get(Bytes::from_static(b"big"))), protocol::resp_bulk(&value));
+    assert!(matches!(s.dict.get(b"big"), Some(Value::Blob(_))));
+
+    // Small values stay inline
+    exec(&s, Cmd::Set(Bytes::from_static(b"small"), Bytes::from_static(b"v")));
+    assert!(matches!(s.dict.get(b"small"), Some(Value::Str(_))));
+}
+
+#[test]
+fn deleted_blobs_are_counted_for_compaction() {
+    let s = blob_shard("dead", 4096);
+    exec(&s, Cmd::Set(Bytes::from_static(b"a"), Bytes::from(vec![b'a'; 3000])));
+    let blobs = s.blobs.as_ref().unwrap();
+    assert_eq!((blobs.used(), blobs.dead()), (3000, 0));
+
+    exec(&s, Cmd::Del(vec![Bytes::from_static(b"a")]));
+    assert_eq!((blobs.used(), blobs.dead()), (3000, 3000));
+
+    // Without room left the value is kept inline
+    exec(&s, Cmd::Set(Bytes::from_static(b"b"), Bytes::from(vec![b'b'; 2000])));
+    assert!(matches!(s.dict.get(b"b"), Some(Value::Str(_))));
+    assert_eq!(exec(&s, Cmd::Get(Bytes::from_static(b"b"))), protocol::resp_bulk(&[b'b'; 2000]));
+}
+
+#[test]
+fn mutating_a_blob_moves_it_back_to_the_heap() {
+    let s = blob_shard("mutate", 1 << 20);
+    exec(&s, Cmd::Set(Bytes::from_static(b"a"), Bytes::from(vec![b'a'; 3000])));
+    exec(&s, Cmd::Set(Bytes::from_static(b"b"), Bytes::from(vec![0u8; 2000])));
+    let blobs = s.blobs.as_ref().unwrap();
+    assert_eq!(blobs.live(), 5000);
+    let before = s.dict.used_memory();
+
+    assert_eq!(exec(&s, Cmd::Append(Bytes::from_static(b"a"), Bytes::from_static(b"xyz"))), b":3003\r\n");
+    assert!(matches!(s.dict.get(b"a"), Some(Value::Str(_))));
+    assert_eq!((blobs.used(), blobs.live()), (5000, 2000));
+    assert_eq!(s.dict.used_memory(), before + 3);
+
+    // Later appends grow the heap copy without touching the store
+    exec(&s, Cmd::Append(Bytes::from_static(b"a"), Bytes::from_static(b"!")));
+    let mut expected = vec![b'a'; 3000];
+    expected.extend_from_slice(b"xyz!");
+    assert_eq!(exec(&s, Cmd::Get(Bytes::from_static(b"a"))), protocol::resp_bulk(&expected));
+    assert_eq!(blobs.used(), 5000);
+
+    // SETBIT releases the region the same way
+    exec(&s, Cmd::SetBit(Bytes::from_static(b"b"), 7, true));
+    assert!(matches!(s.dict.get(b"b"), Some(Value::Str(_))));
+    assert_eq!(blobs.live(), 0);
+    assert_eq!(s.dict.used_memory(), before + 4);
+}
@@ -0,0 +1,52 @@
+use ignix::pool::{BufPool, DEFAULT_MAX_POOLED_CAPACITY, DEFAULT_POOL_SIZE};
+use bytes::BytesMut;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Counts every allocation made by this test binary
+struct Counting;
+
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const CYCLES: usize = 10_000;
+
+fn churn(mut acquire: impl FnMut() -> BytesMut, mut release: impl FnMut(BytesMut)) -> usize {
+    let before = ALLOCS.load(Ordering::Relaxed);
+    for _ in 0..CYCLES {
+        let mut rbuf = acquire();
+        let mut wbuf = acquire();
+        rbuf.extend_from_slice(b"*1\r\n$4\r\nPING\r\n");
+        wbuf.extend_from_slice(b"+PONG\r\n");
+        release(rbuf);
+        release(wbuf);
+    }
+    ALLOCS.load(Ordering::Relaxed) - before
+}
+
+#[test]
+fn connect_disconnect_soak_reuses_buffers() {
+    let fresh = churn(|| BytesMut::with_capacity(4096), drop);
+
+    let pool = std::cell::RefCell::new(BufPool::new(4096, DEFAULT_POOL_SIZE, DEFAULT_MAX_POOLED_CAPACITY));
+    // Warm up so the steady state is measured
+    pool.borrow_mut().release(BytesMut::with_capacity(4096));
+    pool.borrow_mut().release(BytesMut::with_capacity(4096));
+    let pooled = churn(|| pool.borrow_mut().acquire(), |b| pool.borrow_mut().release(b));
+
+    assert!(fresh >= 2 * CYCLES, "fresh buffers allocated {} times", fresh);
+    assert!(pooled < CYCLES / 100, "pooled buffers allocated {} times", pooled);
+}
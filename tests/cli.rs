@@ -0,0 +1,96 @@
+use ignix::*;
+
+fn parse(args: &[&str]) -> anyhow::Result<CliAction> {
+    parse_args(args.iter().map(|a| a.to_string()))
+}
+
+fn run_options(args: &[&str]) -> ServerOptions {
+    match parse(args).unwrap() {
+        CliAction::Run(opts) => *opts,
+        CliAction::Help => panic!("{:?} asked for help", args),
+    }
+}
+
+#[test]
+fn no_arguments_keep_the_defaults() {
+    let opts = run_options(&[]);
+    assert_eq!(opts.addr, DEFAULT_ADDR.parse().unwrap());
+    assert_eq!(opts.aof_path.as_deref(), Some("ignix.aof"));
+    assert_eq!(opts.appendfsync, AofPolicy::EverySec);
+    assert!(!opts.uring);
+    assert_eq!(opts.config.shards, 1);
+    assert!(!opts.config.owned_shards);
+    assert!(opts.config.unixsocket.is_none());
+}
+
+#[test]
+fn values_are_taken_inline_or_from_the_next_argument() {
+    let opts = run_options(&["--bind", "127.0.0.1", "--port=7000", "--aof-path", "/tmp/a.aof"]);
+    assert_eq!(opts.addr, "127.0.0.1:7000".parse().unwrap());
+    assert_eq!(opts.aof_path.as_deref(), Some("/tmp/a.aof"));
+
+    let opts = run_options(&["--bind=::1", "--port", "7001"]);
+    assert_eq!(opts.addr, "[::1]:7001".parse().unwrap());
+}
+
+#[test]
+fn server_settings_reach_the_config() {
+    let opts = run_options(&[
+        "--no-aof",
+        "--appendfsync=always",
+        "--unixsocket",
+        "/tmp/ignix.sock",
+        "--tls-cert-file=c.pem",
+        "--tls-key-file=k.pem",
+        "--backend=uring",
+        "--shards",
+        "4",
+        "--owned-shards",
+        "--maxmemory=100mb",
+        "--maxmemory-policy",
+        "allkeys-lru",
+    ]);
+    assert_eq!(opts.aof_path, None);
+    assert_eq!(opts.appendfsync, AofPolicy::Always);
+    assert_eq!(opts.config.unixsocket.as_deref(), Some("/tmp/ignix.sock"));
+    assert_eq!(opts.config.tls_cert_file.as_deref(), Some("c.pem"));
+    assert_eq!(opts.config.tls_key_file.as_deref(), Some("k.pem"));
+    assert!(opts.uring);
+    assert_eq!(opts.config.shards, 4);
+    assert!(opts.config.owned_shards);
+    assert_eq!(opts.config.maxmemory, 100 * 1024 * 1024);
+    assert_eq!(opts.config.maxmemory_policy, MaxmemoryPolicy::AllKeysLru);
+    assert_eq!(run_options(&["--maxmemory", "2G"]).config.maxmemory, 2_000_000_000);
+    assert_eq!(run_options(&["--maxmemory", "4096"]).config.maxmemory, 4096);
+}
+
+#[test]
+fn help_is_recognised() {
+    assert!(matches!(parse(&["--port", "7000", "-h"]).unwrap(), CliAction::Help));
+    assert!(matches!(parse(&["--help"]).unwrap(), CliAction::Help));
+}
+
+#[test]
+fn bad_input_is_rejected() {
+    for (args, msg) in [
+        (&["--bind", "localhost:7379"][..], "invalid bind address 'localhost:7379'"),
+        (&["--bind", "300.0.0.1"][..], "invalid bind address"),
+        (&["--port", "0"][..], "invalid port '0'"),
+        (&["--port=70000"][..], "invalid port '70000'"),
+        (&["--port"][..], "--port needs a value"),
+        (&["--appendfsync", "sometimes"][..], "invalid appendfsync policy"),
+        (&["--backend=epoll"][..], "invalid backend 'epoll'"),
+        (&["--no-aof=yes"][..], "--no-aof takes no value"),
+        (&["--shards=0"][..], "invalid shard count '0'"),
+        (&["--owned-shards=yes"][..], "--owned-shards takes no value"),
+        (&["--maxmemory", "lots"][..], "invalid maxmemory 'lots'"),
+        (&["--maxmemory", "1tb"][..], "invalid maxmemory '1tb'"),
+        (&["--maxmemory-policy", "volatile-lru"][..], "invalid maxmemory-policy 'volatile-lru'"),
+        (&["--tls-cert-file", "c.pem"][..], "must be given together"),
+        (&["--verbose"][..], "unknown argument '--verbose'"),
+        (&["7379"][..], "unknown argument '7379'"),
+    ] {
+        let err = parse(args).unwrap_err().to_string();
+        assert!(err.contains(msg), "{:?}: {}", args, err);
+    }
+}
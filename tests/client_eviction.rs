@@ -0,0 +1,105 @@
+mod common;
+
+use common::{connect, resp_cmd, send_cmd, start_server_with};
+use ignix::*;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+
+const LIMIT: usize = 1_000_000;
+
+/// Read one numeric field from an `INFO` reply
+fn info_field(s: &mut TcpStream, field: &str) -> usize {
+    s.write_all(&resp_cmd(&[b"INFO"])).unwrap();
+    let mut reply = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = s.read(&mut buf).unwrap();
+        reply.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&reply).into_owned();
+        let Some((header, body)) = text.split_once("\r\n") else { continue };
+        if body.len() < header[1..].parse::<usize>().unwrap() + 2 {
+            continue;
+        }
+        let prefix = format!("{}:", field);
+        return body.lines().find_map(|l| l.strip_prefix(&prefix)).unwrap().parse().unwrap();
+    }
+}
+
+/// Poll INFO until `field` reaches `expected`
+fn wait_for(probe: &mut TcpStream, field: &str, expected: usize) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let value = info_field(probe, field);
+        if value == expected {
+            return;
+        }
+        assert!(Instant::now() < deadline, "{} stuck at {} (expected {})", field, value, expected);
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Open a client that leaves `size` bytes of an unfinished SET in its query buffer
+fn buffering_client(addr: SocketAddr, probe: &mut TcpStream, size: usize, no_evict: bool) -> TcpStream {
+    let mut c = connect(addr);
+    if no_evict {
+        assert_eq!(send_cmd(&mut c, &resp_cmd(&[b"CLIENT", b"NO-EVICT", b"on"])), b"+OK\r\n");
+    }
+    let before = info_field(probe, "mem_clients_normal");
+    let mut partial = b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$10000000\r\n".to_vec();
+    partial.resize(size, b'x');
+    c.write_all(&partial).unwrap();
+    // Wait until the whole chunk sits in the server's query buffer, unless
+    // it pushed the total over the limit and got the client evicted
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while info_field(probe, "mem_clients_normal") < before + size && info_field(probe, "evicted_clients") == 0 {
+        assert!(Instant::now() < deadline, "query buffer never filled");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    c
+}
+
+/// Whether the server closed the connection
+fn is_closed(c: &mut TcpStream) -> bool {
+    c.set_read_timeout(Some(Duration::from_millis(200))).unwrap();
+    let mut buf = [0u8; 16];
+    match c.read(&mut buf) {
+        Ok(0) => true,
+        Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => false,
+        Err(_) => true,
+        Ok(_) => panic!("unexpected reply"),
+    }
+}
+
+fn start() -> (SocketAddr, TcpStream) {
+    let config = Config { maxmemory_clients: LIMIT, ..Default::default() };
+    let addr = start_server_with(Shard::with_config(0, None, config));
+    let probe = connect(addr);
+    (addr, probe)
+}
+
+#[test]
+fn largest_client_is_evicted_first() {
+    let (addr, mut probe) = start();
+    let mut small = buffering_client(addr, &mut probe, 200_000, false);
+    let mut medium = buffering_client(addr, &mut probe, 300_000, false);
+    assert_eq!(info_field(&mut probe, "evicted_clients"), 0);
+
+    // 1.2MB in total: the 700KB client goes, the others stay under the limit
+    let mut large = buffering_client(addr, &mut probe, 700_000, false);
+    wait_for(&mut probe, "evicted_clients", 1);
+    assert!(is_closed(&mut large));
+    assert!(!is_closed(&mut small));
+    assert!(!is_closed(&mut medium));
+    assert_eq!(info_field(&mut probe, "mem_clients_normal"), 500_000);
+}
+
+#[test]
+fn no_evict_clients_are_skipped() {
+    let (addr, mut probe) = start();
+    let mut protected = buffering_client(addr, &mut probe, 900_000, true);
+    let mut other = buffering_client(addr, &mut probe, 200_000, false);
+    wait_for(&mut probe, "evicted_clients", 1);
+    assert!(is_closed(&mut other));
+    assert!(!is_closed(&mut protected));
+}
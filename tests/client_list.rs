@@ -0,0 +1,61 @@
+mod common;
+
+use common::{connect, resp_cmd, send_cmd, start_server};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+/// Send `req` and read until the reply ends with a CLIENT LIST/INFO bulk
+fn send_until_client_lines(s: &mut TcpStream, req: &[u8]) -> String {
+    s.write_all(req).unwrap();
+    let mut reply = Vec::new();
+    let mut buf = [0u8; 64 * 1024];
+    while !reply.ends_with(b"\n\r\n") {
+        let n = s.read(&mut buf).unwrap();
+        assert!(n > 0, "connection closed");
+        reply.extend_from_slice(&buf[..n]);
+    }
+    let text = String::from_utf8(reply).unwrap();
+    let start = text.rfind('$').unwrap();
+    text[start..].split_once("\r\n").unwrap().1.trim_end().to_string()
+}
+
+/// Value of `field` in a CLIENT LIST line
+fn field<'a>(line: &'a str, field: &str) -> &'a str {
+    let prefix = format!("{}=", field);
+    line.split(' ').find_map(|kv| kv.strip_prefix(&prefix)).unwrap()
+}
+
+#[test]
+fn client_info_reports_the_query_buffer_peak() {
+    let addr = start_server();
+    let mut c = connect(addr);
+    let mut pipeline = Vec::new();
+    for _ in 0..10_000 {
+        pipeline.extend(resp_cmd(&[b"GET", b"k"]));
+    }
+    pipeline.extend(resp_cmd(&[b"CLIENT", b"INFO"]));
+
+    let line = send_until_client_lines(&mut c, &pipeline);
+    assert!(field(&line, "qbuf-peak").parse::<usize>().unwrap() > 0, "{}", line);
+    // Everything was parsed by the time CLIENT INFO ran
+    assert_eq!(field(&line, "qbuf"), "0", "{}", line);
+    field(&line, "qbuf-free").parse::<usize>().unwrap();
+}
+
+#[test]
+fn client_list_shows_every_connection() {
+    let addr = start_server();
+    let mut a = connect(addr);
+    let mut b = connect(addr);
+    assert_eq!(send_cmd(&mut b, &resp_cmd(&[b"CLIENT", b"NO-EVICT", b"on"])), b"+OK\r\n");
+    let own = send_until_client_lines(&mut b, &resp_cmd(&[b"CLIENT", b"INFO"]));
+    assert_eq!(field(&own, "flags"), "e", "{}", own);
+
+    let list = send_until_client_lines(&mut a, &resp_cmd(&[b"CLIENT", b"LIST"]));
+    let lines: Vec<&str> = list.lines().collect();
+    assert_eq!(lines.len(), 2, "{}", list);
+    assert!(lines.iter().any(|l| field(l, "id") == field(&own, "id")), "{}", list);
+    let peer = a.local_addr().unwrap().to_string();
+    let a_line = lines.iter().find(|l| field(l, "addr") == peer).unwrap();
+    assert_eq!(field(a_line, "flags"), "N");
+}
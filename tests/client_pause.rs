@@ -0,0 +1,95 @@
+mod common;
+
+use common::{connect, resp_cmd, send_cmd, start_server};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+#[test]
+fn write_pause_delays_writes_but_not_reads() {
+    write_pause_delays_writes_but_not_reads_on(start_server());
+}
+
+/// The backend's event loop must keep serving reads while a paused write
+/// waits
+#[cfg(target_os = "linux")]
+#[test]
+fn write_pause_delays_writes_but_not_reads_on_uring() {
+    write_pause_delays_writes_but_not_reads_on(common::start_uring_server());
+}
+
+fn write_pause_delays_writes_but_not_reads_on(addr: SocketAddr) {
+    let mut admin = connect(addr);
+    let mut writer = connect(addr);
+    let mut reader = connect(addr);
+
+    assert_eq!(send_cmd(&mut admin, &resp_cmd(&[b"CLIENT", b"PAUSE", b"300", b"WRITE"])), b"+OK\r\n");
+    let start = Instant::now();
+
+    // Reads keep flowing during a WRITE pause, also while a write waits
+    assert_eq!(send_cmd(&mut reader, &resp_cmd(&[b"GET", b"k"])), b"$-1\r\n");
+    assert!(start.elapsed() < Duration::from_millis(200), "GET waited {:?}", start.elapsed());
+    let held = std::thread::spawn(move || send_cmd(&mut writer, &resp_cmd(&[b"SET", b"k", b"v"])));
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(send_cmd(&mut reader, &resp_cmd(&[b"GET", b"k"])), b"$-1\r\n");
+    assert!(start.elapsed() < Duration::from_millis(250), "GET waited {:?}", start.elapsed());
+
+    // The SET is held until the pause ends, then applied
+    assert_eq!(held.join().unwrap(), b"+OK\r\n");
+    let waited = start.elapsed();
+    assert!(waited >= Duration::from_millis(250), "SET replied after {:?}", waited);
+    assert!(waited < Duration::from_secs(5), "SET replied after {:?}", waited);
+    assert_eq!(send_cmd(&mut reader, &resp_cmd(&[b"GET", b"k"])), b"$1\r\nv\r\n");
+}
+
+#[test]
+fn all_pause_holds_the_rest_of_the_pipeline_in_order() {
+    let addr = start_server();
+    let mut admin = connect(addr);
+    let mut client = connect(addr);
+
+    assert_eq!(send_cmd(&mut admin, &resp_cmd(&[b"CLIENT", b"PAUSE", b"300"])), b"+OK\r\n");
+    let start = Instant::now();
+    let mut pipeline = resp_cmd(&[b"PING"]);
+    pipeline.extend(resp_cmd(&[b"SET", b"k", b"v"]));
+    pipeline.extend(resp_cmd(&[b"GET", b"k"]));
+    let expected: &[u8] = b"+PONG\r\n+OK\r\n$1\r\nv\r\n";
+    assert_eq!(send_cmd(&mut client, &pipeline), expected);
+    assert!(start.elapsed() >= Duration::from_millis(250), "replied after {:?}", start.elapsed());
+}
+
+#[test]
+fn unpause_releases_held_commands() {
+    unpause_releases_held_commands_on(start_server());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn unpause_releases_held_commands_on_uring() {
+    unpause_releases_held_commands_on(common::start_uring_server());
+}
+
+fn unpause_releases_held_commands_on(addr: SocketAddr) {
+    let mut admin = connect(addr);
+    let mut client = connect(addr);
+
+    assert_eq!(send_cmd(&mut admin, &resp_cmd(&[b"CLIENT", b"PAUSE", b"60000", b"ALL"])), b"+OK\r\n");
+    let start = Instant::now();
+    let held = std::thread::spawn(move || send_cmd(&mut client, &resp_cmd(&[b"PING"])));
+    std::thread::sleep(Duration::from_millis(100));
+
+    // CLIENT itself is never paused
+    assert_eq!(send_cmd(&mut admin, &resp_cmd(&[b"CLIENT", b"UNPAUSE"])), b"+OK\r\n");
+    assert_eq!(held.join().unwrap(), b"+PONG\r\n");
+    assert!(start.elapsed() < Duration::from_secs(10), "PING replied after {:?}", start.elapsed());
+}
+
+#[test]
+fn pause_arguments_are_validated() {
+    let addr = start_server();
+    let mut c = connect(addr);
+    assert_eq!(
+        send_cmd(&mut c, &resp_cmd(&[b"CLIENT", b"PAUSE", b"soon"])),
+        b"-ERR timeout is not an integer or out of range\r\n"
+    );
+    assert_eq!(send_cmd(&mut c, &resp_cmd(&[b"CLIENT", b"PAUSE", b"10", b"READ"])), b"-ERR syntax error\r\n");
+}
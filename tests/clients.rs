@@ -0,0 +1,72 @@
+mod common;
+
+use common::{connect, resp_cmd, start_server, start_server_with};
+use ignix::*;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+/// Fetch `INFO clients` and return the per-worker connection counts
+fn worker_counts(s: &mut TcpStream) -> Vec<usize> {
+    s.write_all(&resp_cmd(&[b"INFO", b"clients"])).unwrap();
+    let mut reply = Vec::new();
+    let mut buf = [0u8; 1024];
+    // Read until the whole bulk reply (header, body, CRLF) has arrived
+    loop {
+        let n = s.read(&mut buf).unwrap();
+        reply.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&reply).into_owned();
+        let Some((header, body)) = text.split_once("\r\n") else { continue };
+        let len: usize = header[1..].parse().unwrap();
+        if body.len() < len + 2 {
+            continue;
+        }
+        let line = body.lines().find_map(|l| l.strip_prefix("worker_connected_clients:")).unwrap();
+        return line.split(',').map(|n| n.parse().unwrap()).collect();
+    }
+}
+
+#[test]
+fn info_reports_per_worker_connection_counts() {
+    let addr = start_server();
+    let workers = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+    let mut probe = connect(addr);
+    let others: Vec<_> = (0..64).map(|_| connect(addr)).collect();
+
+    // Accepts complete asynchronously; wait until every connection is counted
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let counts = loop {
+        let counts = worker_counts(&mut probe);
+        if counts.iter().sum::<usize>() == others.len() + 1 || Instant::now() > deadline {
+            break counts;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    };
+    assert_eq!(counts.len(), workers, "{:?}", counts);
+    assert_eq!(counts.iter().sum::<usize>(), others.len() + 1, "{:?}", counts);
+
+    drop(others);
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while worker_counts(&mut probe).iter().sum::<usize>() != 1 {
+        assert!(Instant::now() < deadline, "closed connections still counted");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn configured_worker_count_overrides_detection() {
+    let config = Config { worker_threads: 3, ..Default::default() };
+    let addr = start_server_with(Shard::with_config(0, None, config));
+    let mut probe = connect(addr);
+
+    // Workers register as they start; wait until all of them have
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let counts = loop {
+        let counts = worker_counts(&mut probe);
+        if counts.len() >= 3 || Instant::now() > deadline {
+            break counts;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    };
+    assert_eq!(counts.len(), 3, "{:?}", counts);
+}
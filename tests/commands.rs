@@ -0,0 +1,179 @@
+use bytes::{Bytes, BytesMut};
+use ignix::command::{lookup, COMMANDS};
+use ignix::*;
+
+/// Encode an argument vector as a RESP request
+fn request(args: &[Bytes]) -> BytesMut {
+    let mut buf = BytesMut::new();
+    buf.extend_from_slice(format!("*{}\r\n", args.len()).as_bytes());
+    for a in args {
+        buf.extend_from_slice(format!("${}\r\n", a.len()).as_bytes());
+        buf.extend_from_slice(a);
+        buf.extend_from_slice(b"\r\n");
+    }
+    buf
+}
+
+/// Smallest valid argument vector for a table entry
+fn minimal_args(name: &str, arity: i32) -> Vec<Bytes> {
+    let argc = arity.unsigned_abs() as usize;
+    let mut args = vec![Bytes::from(name.to_string())];
+    match name {
+        // Commands dispatching on a subcommand
+        "OBJECT" => args.extend([Bytes::from_static(b"ENCODING"), Bytes::from_static(b"k")]),
+        "COMMAND" => args.push(Bytes::from_static(b"INFO")),
+        "CONFIG" => args.push(Bytes::from_static(b"RESETSTAT")),
+        "MEMORY" => args.push(Bytes::from_static(b"PURGE")),
+        "CLUSTER" => args.push(Bytes::from_static(b"SLOTS")),
+        "CLIENT" => args.extend([Bytes::from_static(b"NO-EVICT"), Bytes::from_static(b"ON")]),
+        "DEBUG" => args.extend([Bytes::from_static(b"OBJECT"), Bytes::from_static(b"k")]),
+        // Bit value must be 0 or 1
+        "SETBIT" => args.extend([Bytes::from_static(b"k"), Bytes::from_static(b"7"), Bytes::from_static(b"1")]),
+        _ => args.extend((1..argc).map(|i| Bytes::from(i.to_string()))),
+    }
+    args
+}
+
+fn parse(args: &[Bytes]) -> Cmd {
+    let mut buf = request(args);
+    let mut cmds = Vec::new();
+    protocol::parse_many(&mut buf, &mut cmds).unwrap();
+    assert!(buf.is_empty());
+    cmds.pop().unwrap()
+}
+
+#[test]
+fn table_is_sorted_for_lookup() {
+    assert!(COMMANDS.windows(2).all(|w| w[0].name < w[1].name));
+    assert!(lookup(b"get").is_some());
+    assert!(lookup(b"GeT").is_some());
+    assert!(lookup(b"NOPE").is_none());
+}
+
+#[test]
+fn every_entry_round_trips_through_parse() {
+    for spec in COMMANDS {
+        let args = minimal_args(spec.name, spec.arity);
+        let cmd = parse(&args);
+        assert_eq!(cmd.name(), spec.name, "{:?}", cmd);
+
+        // Rendering the command back and re-parsing yields the same command
+        let mut again = vec![Bytes::from_static(spec.name.as_bytes())];
+        again.extend(cmd.args());
+        assert_eq!(parse(&again), cmd);
+    }
+}
+
+#[test]
+fn arities_are_enforced() {
+    for spec in COMMANDS {
+        let argc = spec.arity.unsigned_abs() as usize;
+        let expected = format!("ERR wrong number of arguments for '{}' command", spec.name.to_ascii_lowercase());
+        if argc > 1 {
+            let mut args = minimal_args(spec.name, spec.arity);
+            args.truncate(argc - 1);
+            assert_eq!(parse(&args), Cmd::Invalid(expected.clone()), "{} with too few args", spec.name);
+        }
+        if spec.arity > 0 {
+            let mut args = minimal_args(spec.name, spec.arity);
+            args.push(Bytes::from_static(b"extra"));
+            assert_eq!(parse(&args), Cmd::Invalid(expected), "{} with too many args", spec.name);
+        }
+    }
+}
+
+#[test]
+fn unknown_command_is_answered_and_consumed() {
+    let mut buf = request(&[Bytes::from_static(b"NOPE"), Bytes::from_static(b"a")]);
+    buf.extend_from_slice(b"*1\r\n$4\r\nPING\r\n");
+    let mut cmds = Vec::new();
+    protocol::parse_many(&mut buf, &mut cmds).unwrap();
+    assert_eq!(cmds, vec![
+        Cmd::Invalid("ERR unknown command 'NOPE', with args beginning with: 'a'".into()),
+        Cmd::Ping,
+    ]);
+
+    let mut out = BytesMut::new();
+    Shard::new(0, None).exec(cmds.remove(0), &mut out);
+    assert_eq!(&out[..], b"-ERR unknown command 'NOPE', with args beginning with: 'a'\r\n");
+}
+
+#[test]
+fn set_options_are_parsed() {
+    let args = |a: &[&'static str]| a.iter().map(|s| Bytes::from_static(s.as_bytes())).collect::<Vec<_>>();
+    assert_eq!(parse(&args(&["SET", "k", "v"])), Cmd::Set("k".into(), "v".into()));
+    assert_eq!(
+        parse(&args(&["set", "k", "v", "nx", "GET"])),
+        Cmd::SetWith("k".into(), "v".into(), SetOptions { cond: Some(SetCond::Nx), get: true, ..Default::default() })
+    );
+    assert_eq!(
+        parse(&args(&["SET", "k", "v", "px", "1500", "XX"])),
+        Cmd::SetWith(
+            "k".into(),
+            "v".into(),
+            SetOptions { cond: Some(SetCond::Xx), expiry: Some(SetExpiry::Px(1500)), ..Default::default() }
+        )
+    );
+    assert_eq!(
+        parse(&args(&["SET", "k", "v", "PXAT", "1700000000000"])),
+        Cmd::SetWith("k".into(), "v".into(), SetOptions { expiry: Some(SetExpiry::PxAt(1_700_000_000_000)), ..Default::default() })
+    );
+    assert_eq!(
+        parse(&args(&["SET", "k", "v", "KEEPTTL"])),
+        Cmd::SetWith("k".into(), "v".into(), SetOptions { expiry: Some(SetExpiry::KeepTtl), ..Default::default() })
+    );
+    for bad in [
+        &["SET", "k", "v", "NX", "XX"][..],
+        &["SET", "k", "v", "BOGUS"],
+        &["SET", "k", "v", "EX"],
+        &["SET", "k", "v", "EX", "10", "PX", "10"],
+        &["SET", "k", "v", "EX", "10", "KEEPTTL"],
+    ] {
+        assert_eq!(parse(&args(bad)), Cmd::Invalid("ERR syntax error".into()), "{:?}", bad);
+    }
+    for bad in [&["SET", "k", "v", "EX", "0"][..], &["SET", "k", "v", "PX", "-5"], &["SET", "k", "v", "PXAT", "0"]] {
+        assert_eq!(parse(&args(bad)), Cmd::Invalid("ERR invalid expire time in 'set' command".into()), "{:?}", bad);
+    }
+    assert_eq!(
+        parse(&args(&["SET", "k", "v", "EX", "soon"])),
+        Cmd::Invalid("ERR value is not an integer or out of range".into())
+    );
+}
+
+#[test]
+fn command_info_reports_table_metadata() {
+    let cmd = parse(&[
+        Bytes::from_static(b"COMMAND"),
+        Bytes::from_static(b"info"),
+        Bytes::from_static(b"set"),
+        Bytes::from_static(b"GET"),
+        Bytes::from_static(b"nope"),
+    ]);
+    let mut out = BytesMut::new();
+    Shard::new(0, None).exec(cmd, &mut out);
+    let reply = String::from_utf8(out.to_vec()).unwrap();
+
+    let set = "*6\r\n$3\r\nset\r\n:-3\r\n*2\r\n+write\r\n+denyoom\r\n:1\r\n:1\r\n:1\r\n";
+    let get = "*6\r\n$3\r\nget\r\n:2\r\n*2\r\n+readonly\r\n+fast\r\n:1\r\n:1\r\n:1\r\n";
+    assert_eq!(reply, format!("*3\r\n{}{}*-1\r\n", set, get));
+}
+
+#[test]
+fn cluster_probes_report_standalone_mode() {
+    let shard = Shard::new(0, None);
+    let run = |args: &[&'static str]| {
+        let args: Vec<Bytes> = args.iter().map(|s| Bytes::from_static(s.as_bytes())).collect();
+        let mut out = BytesMut::new();
+        shard.exec(parse(&args), &mut out);
+        String::from_utf8(out.to_vec()).unwrap()
+    };
+
+    let info = run(&["CLUSTER", "info"]);
+    assert!(info.starts_with('$') && info.contains("cluster_enabled:0\r\n"), "{}", info);
+    for sub in ["SLOTS", "shards"] {
+        assert_eq!(run(&["CLUSTER", sub]), "*0\r\n");
+    }
+    assert_eq!(run(&["CLUSTER", "nodes"]), "$0\r\n\r\n");
+    assert!(run(&["CLUSTER", "MEET"]).starts_with("-ERR unknown subcommand 'MEET'"));
+    assert!(shard.info(Some(b"cluster")).contains("cluster_enabled:0\r\n"));
+}
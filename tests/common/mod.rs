@@ -0,0 +1,84 @@
+#![allow(dead_code)]
+
+use ignix::*;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::Duration;
+
+/// Start an in-process server on a free local port and wait until it accepts
+pub fn start_server() -> SocketAddr {
+    start_server_with(Shard::new(0, None))
+}
+
+/// Start an in-process server around the given shard (or shard set)
+pub fn start_server_with(shard: impl Into<ShardSet> + Send + 'static) -> SocketAddr {
+    // Let the OS pick a free port, then hand it to the server
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .and_then(|l| l.local_addr())
+        .expect("Failed to reserve port");
+    std::thread::spawn(move || {
+        let _ = run_shard(0, addr, shard);
+    });
+    wait_until_listening(addr)
+}
+
+/// Start an in-process io_uring server on a free local port
+#[cfg(target_os = "linux")]
+pub fn start_uring_server() -> SocketAddr {
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .and_then(|l| l.local_addr())
+        .expect("Failed to reserve port");
+    std::thread::spawn(move || {
+        let _ = ignix::net_uring::run_shard(0, addr, Shard::new(0, None));
+    });
+    wait_until_listening(addr)
+}
+
+/// Poll until the server accepts connections
+fn wait_until_listening(addr: SocketAddr) -> SocketAddr {
+    for _ in 0..200 {
+        if TcpStream::connect(addr).is_ok() {
+            return addr;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    panic!("server did not start on {}", addr);
+}
+
+/// Connect a client with generous timeouts
+pub fn connect(addr: SocketAddr) -> TcpStream {
+    let stream = TcpStream::connect(addr).expect("Failed to connect");
+    stream.set_read_timeout(Some(Duration::from_secs(30))).expect("Failed to set read timeout");
+    stream.set_write_timeout(Some(Duration::from_secs(30))).expect("Failed to set write timeout");
+    stream
+}
+
+/// Connect to a server of its own, with an empty keyspace
+///
+/// Every test gets a private in-process server, so keys written by other
+/// tests running in parallel can never be observed. The FLUSHALL guards
+/// against a server that was started pre-populated.
+pub fn isolated_client() -> TcpStream {
+    let mut stream = connect(start_server());
+    assert_eq!(send_cmd(&mut stream, &resp_cmd(&[b"FLUSHALL"])), b"+OK\r\n");
+    stream
+}
+
+/// Encode a command as a RESP array of bulk strings
+pub fn resp_cmd(args: &[&[u8]]) -> Vec<u8> {
+    let mut out = format!("*{}\r\n", args.len()).into_bytes();
+    for a in args {
+        out.extend_from_slice(format!("${}\r\n", a.len()).as_bytes());
+        out.extend_from_slice(a);
+        out.extend_from_slice(b"\r\n");
+    }
+    out
+}
+
+/// Send a command and return whatever the first read yields
+pub fn send_cmd(stream: &mut TcpStream, cmd: &[u8]) -> Vec<u8> {
+    stream.write_all(cmd).expect("Failed to write command");
+    let mut buf = [0u8; 1024];
+    let n = stream.read(&mut buf).expect("Failed to read response");
+    buf[..n].to_vec()
+}
@@ -0,0 +1,67 @@
+use bytes::{Bytes, BytesMut};
+use ignix::*;
+
+fn exec(shard: &Shard, cmd: Cmd) -> Vec<u8> {
+    let mut out = BytesMut::new();
+    shard.exec(cmd, &mut out);
+    out.to_vec()
+}
+
+#[test]
+fn error_replies_match_redis_wording() {
+    let cases: &[(CmdError, &[u8])] = &[
+        (CmdError::WrongType, b"-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+        (CmdError::NoSuchKey, b"-ERR no such key\r\n"),
+        (CmdError::NotInteger, b"-ERR value is not an integer or out of range\r\n"),
+        (CmdError::Syntax, b"-ERR syntax error\r\n"),
+        (CmdError::OutOfRange, b"-ERR value is out of range\r\n"),
+        (CmdError::Overflow, b"-ERR increment or decrement would overflow\r\n"),
+        (CmdError::Oom, b"-OOM command not allowed when used memory > 'maxmemory'.\r\n"),
+        (CmdError::StringTooLong, b"-ERR string exceeds maximum allowed size (proto-max-bulk-len)\r\n"),
+        (CmdError::BitOffset, b"-ERR bit offset is not an integer or out of range\r\n"),
+        (CmdError::BitValue, b"-ERR bit is not an integer or out of range\r\n"),
+        (CmdError::Timeout, b"-ERR timeout is not an integer or out of range\r\n"),
+        (CmdError::Misconf, b"-MISCONF Errors writing to the AOF file\r\n"),
+        (CmdError::Internal, b"-ERR internal error\r\n"),
+        (CmdError::AofDisabled, b"-ERR Append only file is disabled\r\n"),
+        (CmdError::RewriteInProgress, b"-ERR Background append only file rewriting already in progress\r\n"),
+        (CmdError::CrossSlot, b"-CROSSSLOT Keys in request don't hash to the same slot\r\n"),
+        (CmdError::InvalidCursor, b"-ERR invalid cursor\r\n"),
+        (CmdError::LcsLenAndIdx, b"-ERR If you want both the length and indexes, please just use IDX.\r\n"),
+        (
+            CmdError::LcsTooLarge,
+            b"-ERR Insufficient memory, transient memory for LCS exceeds proto-max-bulk-len\r\n",
+        ),
+        (CmdError::InvalidExpire("expire".into()), b"-ERR invalid expire time in 'expire' command\r\n"),
+        (CmdError::WrongArity("mset".into()), b"-ERR wrong number of arguments for 'mset' command\r\n"),
+        (CmdError::Unsupported("MONITOR"), b"-ERR MONITOR is not supported on this connection\r\n"),
+        (
+            CmdError::EncodingMismatch { expected: "int".into(), found: "embstr" },
+            b"-ERR encoding mismatch: expected 'int', found 'embstr'\r\n",
+        ),
+    ];
+    for (err, resp) in cases {
+        assert_eq!(err.to_resp(), *resp, "{:?}", err);
+    }
+}
+
+#[test]
+fn parse_errors_carry_the_same_text() {
+    let parse = |args: &[&'static [u8]]| command::dispatch(args.iter().map(|a| Bytes::from_static(a)).collect());
+    assert_eq!(parse(&[b"SET", b"k", b"v", b"BOGUS"]), Cmd::Invalid(CmdError::Syntax.to_string()));
+    assert_eq!(parse(&[b"MSET", b"k"]), Cmd::Invalid(CmdError::WrongArity("mset".into()).to_string()));
+    assert_eq!(parse(&[b"SETBIT", b"k", b"x", b"1"]), Cmd::Invalid(CmdError::BitOffset.to_string()));
+    assert_eq!(parse(&[b"INCRBY", b"k", b"1.5"]), Cmd::Invalid(CmdError::NotInteger.to_string()));
+    assert_eq!(parse(&[b"DECRBY", b"k", b"ten"]), Cmd::Invalid(CmdError::NotInteger.to_string()));
+    assert_eq!(parse(&[b"LCS", b"a", b"b", b"LEN", b"IDX"]), Cmd::Invalid(CmdError::LcsLenAndIdx.to_string()));
+    assert_eq!(parse(&[b"SCAN", b"-1"]), Cmd::Invalid(CmdError::InvalidCursor.to_string()));
+    assert_eq!(parse(&[b"SCAN", b"0", b"COUNT", b"0"]), Cmd::Invalid(CmdError::Syntax.to_string()));
+    assert_eq!(parse(&[b"SCAN", b"0", b"MATCH"]), Cmd::Invalid(CmdError::Syntax.to_string()));
+}
+
+#[test]
+fn rename_of_a_missing_key_is_an_error_reply() {
+    let s = Shard::new(0, None);
+    let reply = exec(&s, Cmd::Rename(Bytes::from_static(b"missing"), Bytes::from_static(b"b")));
+    assert_eq!(reply, CmdError::NoSuchKey.to_resp());
+}
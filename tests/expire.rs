@@ -0,0 +1,188 @@
+use bytes::{Bytes, BytesMut};
+use ignix::*;
+use std::time::{Duration, Instant};
+
+fn exec(shard: &Shard, cmd: Cmd) -> Vec<u8> {
+    let mut out = BytesMut::new();
+    shard.exec(cmd, &mut out);
+    out.to_vec()
+}
+
+fn key(k: &'static [u8]) -> Bytes {
+    Bytes::from_static(k)
+}
+
+#[test]
+fn expire_and_ttl_report_the_deadline() {
+    let s = Shard::new(0, None);
+    assert_eq!(exec(&s, Cmd::Expire(key(b"k"), 100)), b":0\r\n");
+    assert_eq!(exec(&s, Cmd::Ttl(key(b"k"))), b":-2\r\n");
+
+    exec(&s, Cmd::Set(key(b"k"), key(b"v")));
+    assert_eq!(exec(&s, Cmd::Ttl(key(b"k"))), b":-1\r\n");
+    assert_eq!(exec(&s, Cmd::Expire(key(b"k"), 100)), b":1\r\n");
+    assert_eq!(exec(&s, Cmd::Ttl(key(b"k"))), b":100\r\n");
+    assert_eq!(s.dict.volatile_len(), 1);
+    assert!(s.info(Some(b"keyspace")).contains("db0:keys=1,expires=1,"));
+
+    // Overwriting the value clears the expiry
+    exec(&s, Cmd::Set(key(b"k"), key(b"w")));
+    assert_eq!(exec(&s, Cmd::Ttl(key(b"k"))), b":-1\r\n");
+    assert_eq!(s.dict.volatile_len(), 0);
+}
+
+#[test]
+fn expired_keys_vanish_on_access() {
+    let s = Shard::new(0, None);
+    exec(&s, Cmd::Set(key(b"k"), key(b"v")));
+    exec(&s, Cmd::Set(key(b"n"), key(b"1")));
+    assert!(s.dict.expire_at(b"k", Instant::now() + Duration::from_millis(20)));
+    assert!(s.dict.expire_at(b"n", Instant::now() + Duration::from_millis(20)));
+    std::thread::sleep(Duration::from_millis(50));
+
+    assert_eq!(exec(&s, Cmd::Get(key(b"k"))), b"$-1\r\n");
+    assert_eq!(exec(&s, Cmd::Exists(vec![key(b"k")])), b":0\r\n");
+    // Updates start from a missing key
+    assert_eq!(exec(&s, Cmd::Incr(key(b"n"))), b":1\r\n");
+    assert_eq!(exec(&s, Cmd::Ttl(key(b"n"))), b":-1\r\n");
+    assert_eq!(s.dict.len(), 1);
+    assert_eq!(s.dict.volatile_len(), 0);
+}
+
+#[test]
+fn non_positive_expire_deletes_the_key() {
+    let s = Shard::new(0, None);
+    exec(&s, Cmd::Set(key(b"k"), key(b"v")));
+    assert_eq!(exec(&s, Cmd::Expire(key(b"k"), -1)), b":1\r\n");
+    assert_eq!(exec(&s, Cmd::Ttl(key(b"k"))), b":-2\r\n");
+    assert_eq!(exec(&s, Cmd::Expire(key(b"k"), 0)), b":0\r\n");
+}
+
+#[test]
+fn rename_carries_the_expiry() {
+    let s = Shard::new(0, None);
+    exec(&s, Cmd::Set(key(b"a"), key(b"v")));
+    exec(&s, Cmd::Set(key(b"b"), key(b"old")));
+    exec(&s, Cmd::Expire(key(b"a"), 50));
+    exec(&s, Cmd::Rename(key(b"a"), key(b"b")));
+    assert_eq!(exec(&s, Cmd::Ttl(key(b"b"))), b":50\r\n");
+    assert_eq!(exec(&s, Cmd::Ttl(key(b"a"))), b":-2\r\n");
+    assert_eq!(s.dict.volatile_len(), 1);
+}
+
+#[test]
+fn overflowing_expire_is_rejected() {
+    let s = Shard::new(0, None);
+    exec(&s, Cmd::Set(key(b"k"), key(b"v")));
+    assert_eq!(exec(&s, Cmd::Expire(key(b"k"), i64::MAX)), CmdError::InvalidExpire("expire".into()).to_resp());
+    assert_eq!(exec(&s, Cmd::Ttl(key(b"k"))), b":-1\r\n");
+}
+
+#[test]
+fn expire_is_logged_as_an_absolute_deadline() {
+    assert_eq!(emit_aof_pexpireat(b"k", 1_700_000_000_000), b"*3\r\n$9\r\nPEXPIREAT\r\n$1\r\nk\r\n$13\r\n1700000000000\r\n");
+    let clock = WallClock::now();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let unix_ms = clock.unix_ms_of(deadline);
+    assert!((unix_ms - clock.unix_ms() - 10_000).abs() <= 1, "{}", unix_ms - clock.unix_ms());
+    let back = clock.deadline_of(unix_ms).unwrap();
+    assert!(back <= deadline && deadline - back <= Duration::from_millis(1));
+}
+
+#[test]
+fn pexpireat_in_the_past_deletes_the_key() {
+    let s = Shard::new(0, None);
+    let now = WallClock::now().unix_ms();
+    exec(&s, Cmd::Set(key(b"k"), key(b"v")));
+    assert_eq!(exec(&s, Cmd::PExpireAt(key(b"k"), now + 50_000)), b":1\r\n");
+    assert_eq!(exec(&s, Cmd::Ttl(key(b"k"))), b":50\r\n");
+    assert_eq!(exec(&s, Cmd::PExpireAt(key(b"k"), now - 1)), b":1\r\n");
+    assert_eq!(exec(&s, Cmd::Ttl(key(b"k"))), b":-2\r\n");
+    assert_eq!(exec(&s, Cmd::PExpireAt(key(b"k"), now + 50_000)), b":0\r\n");
+}
+
+fn set(opts: SetOptions) -> Cmd {
+    Cmd::SetWith(key(b"k"), key(b"v"), opts)
+}
+
+#[test]
+fn set_ex_px_and_keepttl() {
+    let s = Shard::new(0, None);
+    exec(&s, set(SetOptions { expiry: Some(SetExpiry::Ex(100)), ..Default::default() }));
+    assert_eq!(exec(&s, Cmd::Ttl(key(b"k"))), b":100\r\n");
+
+    exec(&s, set(SetOptions { expiry: Some(SetExpiry::Px(30_000)), ..Default::default() }));
+    assert_eq!(exec(&s, Cmd::Ttl(key(b"k"))), b":30\r\n");
+
+    // KEEPTTL leaves the expiry alone, any other write clears it
+    exec(&s, set(SetOptions { expiry: Some(SetExpiry::KeepTtl), ..Default::default() }));
+    assert_eq!(exec(&s, Cmd::Ttl(key(b"k"))), b":30\r\n");
+    exec(&s, set(SetOptions { get: true, ..Default::default() }));
+    assert_eq!(exec(&s, Cmd::Ttl(key(b"k"))), b":-1\r\n");
+
+    // A short PX really expires
+    exec(&s, set(SetOptions { expiry: Some(SetExpiry::Px(20)), ..Default::default() }));
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(exec(&s, Cmd::Get(key(b"k"))), b"$-1\r\n");
+}
+
+#[test]
+fn set_nx_get_with_expiry() {
+    let s = Shard::new(0, None);
+    let nx_get = SetOptions { cond: Some(SetCond::Nx), get: true, expiry: Some(SetExpiry::Ex(10)) };
+
+    // Absent: stored, null reply, expiry set
+    assert_eq!(exec(&s, set(nx_get)), b"$-1\r\n");
+    assert_eq!(exec(&s, Cmd::Ttl(key(b"k"))), b":10\r\n");
+
+    // Present: not stored, old value replied, expiry untouched
+    exec(&s, Cmd::Expire(key(b"k"), 50));
+    assert_eq!(exec(&s, Cmd::SetWith(key(b"k"), key(b"w"), nx_get)), b"$1\r\nv\r\n");
+    assert_eq!(exec(&s, Cmd::Get(key(b"k"))), b"$1\r\nv\r\n");
+    assert_eq!(exec(&s, Cmd::Ttl(key(b"k"))), b":50\r\n");
+}
+
+#[test]
+fn set_pxat_expires_at_the_given_time() {
+    let s = Shard::new(0, None);
+    let now = WallClock::now().unix_ms();
+    exec(&s, set(SetOptions { expiry: Some(SetExpiry::PxAt(now as u64 + 40_000)), ..Default::default() }));
+    assert_eq!(exec(&s, Cmd::Ttl(key(b"k"))), b":40\r\n");
+    // A time already past leaves nothing behind
+    exec(&s, set(SetOptions { expiry: Some(SetExpiry::PxAt(now as u64 - 1)), ..Default::default() }));
+    assert_eq!(exec(&s, Cmd::Get(key(b"k"))), b"$-1\r\n");
+}
+
+#[test]
+fn set_with_expiry_is_logged_with_it() {
+    assert_eq!(
+        emit_aof_set_with(b"k", b"v", Some(SetExpiry::PxAt(5))),
+        b"*5\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n$4\r\nPXAT\r\n$1\r\n5\r\n"
+    );
+    assert_eq!(emit_aof_set_with(b"k", b"v", None), emit_aof_set(b"k", b"v"));
+}
+
+#[test]
+fn lazy_expiry_never_removes_a_concurrent_set() {
+    let dict = Dict::default();
+    for _ in 0..2000 {
+        let due = Instant::now();
+        dict.set_with(key(b"k"), Value::Str(key(b"old")), None, KeyExpiry::At(due));
+        let start = std::sync::Barrier::new(2);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                start.wait();
+                dict.set(key(b"k"), Value::Str(key(b"new")));
+            });
+            s.spawn(|| {
+                start.wait();
+                for _ in 0..50 {
+                    dict.exists(b"k");
+                }
+            });
+        });
+        assert_eq!(dict.get(b"k"), Some(Value::Str(key(b"new"))));
+        assert_eq!(dict.ttl(b"k"), Some(None));
+        assert_eq!((dict.len(), dict.volatile_len()), (1, 0));
+    }
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use common::{connect, resp_cmd, send_cmd, start_server};
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+/// A client streaming a large value must not stall small requests
+/// served by the same worker
+#[test]
+fn bulk_upload_does_not_starve_small_clients() {
+    let addr = start_server();
+    let value_len = 64 * 1024 * 1024;
+
+    let bulk = std::thread::spawn(move || {
+        let mut s = connect(addr);
+        s.write_all(format!("*3\r\n$3\r\nSET\r\n$4\r\nbulk\r\n${}\r\n", value_len).as_bytes())
+            .unwrap();
+        let chunk = vec![b'x'; 256 * 1024];
+        let mut sent = 0;
+        while sent < value_len {
+            let n = chunk.len().min(value_len - sent);
+            s.write_all(&chunk[..n]).unwrap();
+            sent += n;
+        }
+        s.write_all(b"\r\n").unwrap();
+        let mut buf = [0u8; 16];
+        let n = s.read(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"+OK\r\n");
+    });
+
+    let small: Vec<_> = (0..4)
+        .map(|_| {
+            std::thread::spawn(move || {
+                let mut s = connect(addr);
+                let ping = resp_cmd(&[b"PING"]);
+                let mut worst = Duration::ZERO;
+                for _ in 0..200 {
+                    let start = Instant::now();
+                    assert_eq!(send_cmd(&mut s, &ping), b"+PONG\r\n");
+                    worst = worst.max(start.elapsed());
+                }
+                worst
+            })
+        })
+        .collect();
+
+    for h in small {
+        let worst = h.join().unwrap();
+        assert!(worst < Duration::from_millis(500), "PING took {:?}", worst);
+    }
+    bulk.join().unwrap();
+}
@@ -0,0 +1,25 @@
+mod common;
+
+use common::{connect, resp_cmd, start_server_with};
+use ignix::*;
+use std::io::{Read, Write};
+
+#[test]
+fn coalesced_flush_answers_every_pipelined_request() {
+    let config = Config { flush_strategy: FlushStrategy::Coalesced, ..Default::default() };
+    let mut s = connect(start_server_with(Shard::with_config(0, None, config)));
+
+    let mut batch = Vec::new();
+    for i in 0..100 {
+        batch.extend_from_slice(&resp_cmd(&[b"SET", format!("k{}", i).as_bytes(), b"v"]));
+    }
+    batch.extend_from_slice(&resp_cmd(&[b"GET", b"k99"]));
+    s.write_all(&batch).unwrap();
+
+    let mut expected = b"+OK\r\n".repeat(100);
+    expected.extend_from_slice(b"$1\r\nv\r\n");
+    let mut reply = vec![0u8; expected.len()];
+    s.read_exact(&mut reply).unwrap();
+    assert_eq!(reply, expected);
+    assert!("coalesced".parse::<FlushStrategy>().is_ok() && "sometimes".parse::<FlushStrategy>().is_err());
+}
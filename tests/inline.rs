@@ -0,0 +1,37 @@
+mod common;
+
+use common::{connect, resp_cmd, send_cmd, start_server};
+
+#[test]
+fn lf_terminated_inline_ping_gets_pong() {
+    let mut s = connect(start_server());
+    assert_eq!(send_cmd(&mut s, b"PING\n"), b"+PONG\r\n");
+    assert_eq!(send_cmd(&mut s, b"SET k v\r\n"), b"+OK\r\n");
+    assert_eq!(send_cmd(&mut s, b"GET k\n"), b"$1\r\nv\r\n");
+}
+
+#[test]
+fn plain_replies_are_readable_and_leave_resp_requests_alone() {
+    let mut s = connect(start_server());
+    assert_eq!(send_cmd(&mut s, b"DEBUG PROTOCOL plain\r\n"), b"OK\r\n");
+    assert_eq!(send_cmd(&mut s, b"SET k hello\r\n"), b"OK\r\n");
+    assert_eq!(send_cmd(&mut s, b"GET k\r\n"), b"\"hello\"\r\n");
+    assert_eq!(send_cmd(&mut s, b"GET missing\r\n"), b"(nil)\r\n");
+    assert_eq!(send_cmd(&mut s, b"EXISTS k\r\n"), b"(integer) 1\r\n");
+    assert_eq!(send_cmd(&mut s, b"MGET k missing\r\n"), b"1) \"hello\"\r\n2) (nil)\r\n");
+    assert_eq!(send_cmd(&mut s, b"INCR k\r\n"), b"(error) ERR value is not an integer or out of range\r\n");
+
+    // The same connection still gets RESP for RESP-framed requests
+    assert_eq!(send_cmd(&mut s, &resp_cmd(&[b"GET", b"k"])), b"$5\r\nhello\r\n");
+
+    assert_eq!(send_cmd(&mut s, b"DEBUG PROTOCOL resp\r\n"), b"+OK\r\n");
+    assert_eq!(send_cmd(&mut s, b"GET k\r\n"), b"$5\r\nhello\r\n");
+}
+
+#[test]
+fn plain_rendering_of_nested_arrays_follows_redis_cli() {
+    let resp = b"*2\r\n*2\r\n$1\r\na\r\n:1\r\n*0\r\n";
+    assert_eq!(ignix::resp_to_plain(resp).unwrap(), "1) 1) \"a\"\r\n   2) (integer) 1\r\n2) (empty array)\r\n");
+    assert_eq!(ignix::resp_to_plain(b"$3\r\na\"\n\r\n").unwrap(), "\"a\\\"\\n\"\r\n");
+    assert!(ignix::resp_to_plain(b"$10\r\nshort\r\n").is_none());
+}
@@ -0,0 +1,97 @@
+use bytes::{Bytes, BytesMut};
+use ignix::*;
+use std::time::{Duration, Instant};
+
+fn exec(shard: &Shard, cmd: Cmd) -> Vec<u8> {
+    let mut out = BytesMut::new();
+    shard.exec(cmd, &mut out);
+    out.to_vec()
+}
+
+fn key(k: &'static [u8]) -> Bytes {
+    Bytes::from_static(k)
+}
+
+/// Keys of a dict matching a pattern, sorted for comparison
+fn matching(d: &Dict, pattern: &[u8]) -> Vec<Bytes> {
+    let mut keys = d.keys_matching(pattern);
+    keys.sort();
+    keys
+}
+
+fn dict_of(keys: &[&'static [u8]]) -> Dict {
+    let d = Dict::default();
+    for k in keys {
+        d.set(key(k), Value::Int(1));
+    }
+    d
+}
+
+#[test]
+fn star_matches_every_key() {
+    let d = dict_of(&[b"a", b"user:1", b""]);
+    assert_eq!(matching(&d, b"*"), vec![key(b""), key(b"a"), key(b"user:1")]);
+    assert_eq!(matching(&d, b"**"), matching(&d, b"*"));
+}
+
+#[test]
+fn prefix_pattern_matches_the_prefix() {
+    let d = dict_of(&[b"user:1", b"user:22", b"user:", b"users:1", b"session:user:1"]);
+    assert_eq!(matching(&d, b"user:*"), vec![key(b"user:"), key(b"user:1"), key(b"user:22")]);
+    assert_eq!(matching(&d, b"*:1"), vec![key(b"session:user:1"), key(b"user:1"), key(b"users:1")]);
+}
+
+#[test]
+fn question_mark_matches_one_byte() {
+    let d = dict_of(&[b"hello", b"hallo", b"hxllo", b"hllo", b"heello"]);
+    assert_eq!(matching(&d, b"h?llo"), vec![key(b"hallo"), key(b"hello"), key(b"hxllo")]);
+}
+
+#[test]
+fn classes_ranges_and_escapes() {
+    assert!(glob_match(b"h[ae]llo", b"hallo") && glob_match(b"h[ae]llo", b"hello"));
+    assert!(!glob_match(b"h[ae]llo", b"hillo"));
+    assert!(glob_match(b"h[^e]llo", b"hallo") && !glob_match(b"h[^e]llo", b"hello"));
+    assert!(glob_match(b"h[a-b]llo", b"hbllo") && !glob_match(b"h[a-b]llo", b"hcllo"));
+    // Reversed ranges match like the ordered ones, as in Redis
+    assert!(glob_match(b"h[b-a]llo", b"hallo"));
+    assert!(glob_match(b"h\\*llo", b"h*llo") && !glob_match(b"h\\*llo", b"hello"));
+    assert!(glob_match(b"[\\]]", b"]") && glob_match(b"a\\", b"a\\"));
+    assert!(glob_match(b"*a*b*", b"xaybz") && !glob_match(b"*a*b*", b"xbya"));
+    assert!(glob_match(b"", b"") && !glob_match(b"", b"a") && !glob_match(b"?", b""));
+}
+
+#[test]
+fn binary_keys_are_matched_byte_by_byte() {
+    let d = dict_of(&[b"bin:\xff\x00\xfe", b"bin:\xc3\xa9", b"txt:a"]);
+    assert_eq!(matching(&d, b"bin:*"), vec![key(b"bin:\xc3\xa9"), key(b"bin:\xff\x00\xfe")]);
+    // `?` takes a single byte, not a UTF-8 character
+    assert_eq!(matching(&d, b"bin:??"), vec![key(b"bin:\xc3\xa9")]);
+    assert_eq!(matching(&d, b"bin:\xff?[\xf0-\xff]"), vec![key(b"bin:\xff\x00\xfe")]);
+}
+
+#[test]
+fn keys_replies_with_an_array_and_skips_expired_keys() {
+    let s = Shard::new(0, None);
+    assert_eq!(exec(&s, Cmd::Keys(key(b"*"))), b"*0\r\n");
+    exec(&s, Cmd::Set(key(b"user:1"), key(b"v")));
+    exec(&s, Cmd::Set(key(b"other"), key(b"v")));
+    assert_eq!(exec(&s, Cmd::Keys(key(b"user:*"))), b"*1\r\n$6\r\nuser:1\r\n");
+
+    assert!(s.dict.expire_at(b"user:1", Instant::now() + Duration::from_millis(10)));
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(exec(&s, Cmd::Keys(key(b"user:*"))), b"*0\r\n");
+}
+
+#[test]
+fn keys_covers_every_shard_of_a_set() {
+    let set = ShardSet::with_config(None, Config { shards: 4, ..Default::default() });
+    let mut out = BytesMut::new();
+    for i in 0..100 {
+        let k = Bytes::from(format!("key:{}", i));
+        set.exec(Cmd::Set(k.clone(), k), &mut out);
+    }
+    out.clear();
+    set.exec(Cmd::Keys(key(b"key:*")), &mut out);
+    assert!(out.starts_with(b"*100\r\n"));
+}
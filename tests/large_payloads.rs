@@ -1,24 +1,8 @@
+mod common;
+
+use common::{isolated_client, send_cmd};
 use std::io::{Read, Write};
 use std::net::TcpStream;
-use std::time::Duration;
-
-fn get_client() -> TcpStream {
-    let stream = TcpStream::connect("127.0.0.1:7379").expect("Failed to connect");
-    stream.set_read_timeout(Some(Duration::from_secs(30))).expect("Failed to set read timeout");
-    stream.set_write_timeout(Some(Duration::from_secs(30))).expect("Failed to set write timeout");
-    stream
-}
-
-fn send_cmd(stream: &mut TcpStream, cmd: &[u8]) -> Vec<u8> {
-    stream.write_all(cmd).expect("Failed to write command");
-    
-    // Read response
-    // Simple implementation: read until we get a complete RESP response
-    // For this test, we assume the server behaves nicely
-    let mut buf = [0u8; 1024];
-    let n = stream.read(&mut buf).expect("Failed to read response");
-    buf[..n].to_vec()
-}
 
 // Helper to read a large bulk string response
 fn read_bulk_string(stream: &mut TcpStream) -> Vec<u8> {
@@ -43,7 +27,7 @@ fn read_bulk_string(stream: &mut TcpStream) -> Vec<u8> {
 
 #[test]
 fn test_large_payload_100kb() {
-    let mut stream = get_client();
+    let mut stream = isolated_client();
     let size = 100 * 1024;
     let data = "x".repeat(size);
     let key = "large_100kb";
@@ -64,7 +48,7 @@ fn test_large_payload_100kb() {
 
 #[test]
 fn test_large_payload_1mb() {
-    let mut stream = get_client();
+    let mut stream = isolated_client();
     let size = 1024 * 1024;
     // Create random-ish data to avoid compression shortcuts if any (though Ignix doesn't compress)
     let data = "a".repeat(size); 
@@ -95,7 +79,7 @@ fn test_large_payload_1mb() {
 
 #[test]
 fn test_large_payload_10mb() {
-    let mut stream = get_client();
+    let mut stream = isolated_client();
     let size = 10 * 1024 * 1024;
     let key = "large_10mb";
     
@@ -123,3 +107,57 @@ fn test_large_payload_10mb() {
     assert_eq!(received.len(), size);
     assert_eq!(received[0], b'z');
 }
+
+#[test]
+fn large_reply_survives_many_partial_writes() {
+    use socket2::{Domain, Socket, Type};
+
+    let addr = common::start_server();
+    // A tiny receive window forces the server to write the reply in many
+    // pieces, each resumed from a WRITABLE event
+    let sock = Socket::new(Domain::IPV4, Type::STREAM, None).unwrap();
+    sock.set_recv_buffer_size(4096).unwrap();
+    sock.connect(&addr.into()).unwrap();
+    let mut stream: TcpStream = sock.into();
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(30))).unwrap();
+
+    let size = 10 * 1024 * 1024;
+    let value: Vec<u8> = (0..size).map(|i| (i * 31 % 251) as u8).collect();
+    let mut set = format!("*3\r\n$3\r\nSET\r\n$7\r\npartial\r\n${}\r\n", size).into_bytes();
+    set.extend_from_slice(&value);
+    set.extend_from_slice(b"\r\n");
+    assert_eq!(send_cmd(&mut stream, &set), b"+OK\r\n");
+
+    // Pipeline a PING behind the GET: it must come after the full value
+    stream.write_all(b"*2\r\n$3\r\nGET\r\n$7\r\npartial\r\n*1\r\n$4\r\nPING\r\n").unwrap();
+    let header = format!("${}\r\n", size);
+    let expected_len = header.len() + size + 2 + b"+PONG\r\n".len();
+    let mut reply = Vec::with_capacity(expected_len);
+    let mut buf = [0u8; 1024];
+    let mut reads = 0;
+    while reply.len() < expected_len {
+        let n = stream.read(&mut buf).unwrap();
+        assert!(n > 0, "connection closed after {} bytes", reply.len());
+        reply.extend_from_slice(&buf[..n]);
+        reads += 1;
+        // Read slowly so the server keeps hitting a full socket buffer
+        if reads % 256 == 0 {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+    }
+
+    assert_eq!(reply.len(), expected_len);
+    assert!(reply.starts_with(header.as_bytes()));
+    assert!(reply[header.len()..header.len() + size] == value[..], "value corrupted");
+    assert!(reply.ends_with(b"\r\n+PONG\r\n"));
+}
+
+#[test]
+fn oversized_bulk_header_gets_an_error_not_a_wait() {
+    let mut stream = isolated_client();
+    // No payload follows: the server must answer from the header alone
+    stream.write_all(b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$999999999999\r\n").unwrap();
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply).unwrap();
+    assert_eq!(reply, b"-ERR Protocol error: invalid bulk length\r\n");
+}
@@ -0,0 +1,77 @@
+use bytes::{Bytes, BytesMut};
+use ignix::*;
+use std::time::{Duration, Instant};
+
+fn exec(shard: &Shard, cmd: Cmd) -> Vec<u8> {
+    let mut out = BytesMut::new();
+    shard.exec(cmd, &mut out);
+    out.to_vec()
+}
+
+fn big() -> Bytes {
+    Bytes::from(vec![b'x'; 32 * 1024 * 1024])
+}
+
+/// Wait for the background thread to drain its queue
+fn wait_freed(s: &Shard, expected: u64) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while s.lazyfree.freed() < expected || s.lazyfree.pending() > 0 {
+        assert!(Instant::now() < deadline, "lazyfree thread did not drain");
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn unlink_frees_large_values_off_thread() {
+    let s = Shard::new(0, None);
+    exec(&s, Cmd::Set(Bytes::from_static(b"big"), big()));
+    exec(&s, Cmd::Set(Bytes::from_static(b"small"), Bytes::from_static(b"v")));
+
+    assert_eq!(exec(&s, Cmd::Unlink(vec![Bytes::from_static(b"big")])), b":1\r\n");
+    assert_eq!(exec(&s, Cmd::Get(Bytes::from_static(b"big"))), protocol::resp_null());
+    wait_freed(&s, 1);
+
+    // Small values are dropped in place
+    assert_eq!(exec(&s, Cmd::Unlink(vec![Bytes::from_static(b"small")])), b":1\r\n");
+    assert_eq!(exec(&s, Cmd::Unlink(vec![Bytes::from_static(b"missing")])), b":0\r\n");
+    assert_eq!(s.lazyfree.freed(), 1);
+
+    let info = s.info(Some(b"memory"));
+    assert!(info.contains("lazyfree_pending_objects:0\r\n"), "{}", info);
+    assert!(info.contains("lazyfreed_objects:1\r\n"), "{}", info);
+}
+
+#[test]
+fn unlink_removes_every_key_given() {
+    let s = Shard::new(0, None);
+    let b = |k: &'static [u8]| Bytes::from_static(k);
+    exec(&s, Cmd::Set(b(b"a"), big()));
+    exec(&s, Cmd::Set(b(b"c"), big()));
+    exec(&s, Cmd::Set(b(b"d"), b(b"v")));
+    let before = s.master_repl_offset();
+
+    assert_eq!(exec(&s, Cmd::Unlink(vec![b(b"a"), b(b"b"), b(b"c"), b(b"a")])), b":2\r\n");
+    assert_eq!(s.dict.len(), 1);
+    wait_freed(&s, 2);
+    // Only the keys actually removed are logged
+    assert_eq!(s.master_repl_offset(), before + emit_aof_del(&[b(b"a"), b(b"c")]).len() as u64);
+}
+
+#[test]
+fn del_and_overwrite_are_lazy_only_when_configured() {
+    let s = Shard::new(0, None);
+    exec(&s, Cmd::Set(Bytes::from_static(b"a"), big()));
+    exec(&s, Cmd::Set(Bytes::from_static(b"a"), Bytes::from_static(b"v")));
+    exec(&s, Cmd::Set(Bytes::from_static(b"b"), big()));
+    assert_eq!(exec(&s, Cmd::Del(vec![Bytes::from_static(b"b")])), b":1\r\n");
+    assert_eq!(s.lazyfree.freed() + s.lazyfree.pending() as u64, 0);
+
+    let config = Config { lazyfree_lazy_user_del: true, lazyfree_lazy_server_del: true, ..Default::default() };
+    let s = Shard::with_config(0, None, config);
+    exec(&s, Cmd::Set(Bytes::from_static(b"a"), big()));
+    exec(&s, Cmd::Set(Bytes::from_static(b"a"), Bytes::from_static(b"v")));
+    exec(&s, Cmd::MSet(vec![(Bytes::from_static(b"b"), big())]));
+    exec(&s, Cmd::GetSet(Bytes::from_static(b"b"), big()));
+    assert_eq!(exec(&s, Cmd::Del(vec![Bytes::from_static(b"b")])), b":1\r\n");
+    wait_freed(&s, 3);
+}
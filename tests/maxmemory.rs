@@ -0,0 +1,117 @@
+use bytes::{Bytes, BytesMut};
+use ignix::*;
+use std::time::Duration;
+
+fn b(s: &str) -> Bytes {
+    Bytes::copy_from_slice(s.as_bytes())
+}
+
+fn exec(shard: &Shard, cmd: Cmd) -> Vec<u8> {
+    let mut out = BytesMut::new();
+    shard.exec(cmd, &mut out);
+    out.to_vec()
+}
+
+/// A shard allowed `maxmemory` bytes under `policy`, sampling every key
+/// so eviction follows exact LRU order
+fn limited(maxmemory: usize, policy: MaxmemoryPolicy) -> Shard {
+    let config = Config { maxmemory, maxmemory_policy: policy, maxmemory_samples: 1000, ..Default::default() };
+    Shard::with_config(0, None, config)
+}
+
+/// `key:NNN` keys holding 100-byte values: 107 bytes each
+fn fill(shard: &Shard, range: std::ops::Range<usize>) {
+    for i in range {
+        exec(shard, Cmd::Set(b(&format!("key:{:03}", i)), Bytes::from(vec![b'v'; 100])));
+    }
+}
+
+#[test]
+fn used_memory_follows_every_write() {
+    let shard = Shard::new(0, None);
+    let check = |what: &str| assert_eq!(shard.dict.used_memory(), shard.dataset_stats().approx_bytes, "after {}", what);
+
+    exec(&shard, Cmd::Set(b("a"), b("hello")));
+    check("SET");
+    exec(&shard, Cmd::Set(b("a"), b("hello world")));
+    check("overwrite");
+    exec(&shard, Cmd::Append(b("a"), b("!!")));
+    check("APPEND");
+    exec(&shard, Cmd::Set(b("n"), b("12345678901234")));
+    exec(&shard, Cmd::Incr(b("n")));
+    check("INCR of a string");
+    exec(&shard, Cmd::SetBit(b("bits"), 100, true));
+    check("SETBIT");
+    exec(&shard, Cmd::Rename(b("a"), b("bits")));
+    check("RENAME over a key");
+    exec(&shard, Cmd::Del(vec![b("n")]));
+    check("DEL");
+    exec(&shard, Cmd::FlushAll);
+    assert_eq!(shard.dict.used_memory(), 0);
+}
+
+#[test]
+fn noeviction_refuses_writes_that_grow_the_dataset() {
+    let shard = limited(1000, MaxmemoryPolicy::NoEviction);
+    fill(&shard, 0..10);
+    let oom = b"-OOM command not allowed when used memory > 'maxmemory'.\r\n";
+    assert_eq!(exec(&shard, Cmd::Set(b("more"), b("x"))), oom);
+    assert_eq!(exec(&shard, Cmd::Incr(b("counter"))), oom);
+    assert_eq!(shard.dict.len(), 10);
+
+    // Reads and deletes still work, and deleting makes room again
+    assert!(exec(&shard, Cmd::Get(b("key:000"))).starts_with(b"$100\r\n"));
+    assert_eq!(exec(&shard, Cmd::Del(vec![b("key:000"), b("key:001")])), b":2\r\n");
+    assert_eq!(exec(&shard, Cmd::Set(b("more"), b("x"))), b"+OK\r\n");
+}
+
+#[test]
+fn allkeys_lru_evicts_the_least_recently_used_keys() {
+    let shard = limited(100 * 107, MaxmemoryPolicy::AllKeysLru);
+    fill(&shard, 0..100);
+    std::thread::sleep(Duration::from_millis(5));
+    for i in 0..50 {
+        exec(&shard, Cmd::Get(b(&format!("key:{:03}", i))));
+    }
+    std::thread::sleep(Duration::from_millis(5));
+
+    fill(&shard, 100..150);
+    assert!(shard.dict.used_memory() <= 100 * 107 + 107, "{} bytes used", shard.dict.used_memory());
+    // The untouched keys went first
+    for i in 0..50 {
+        assert!(shard.dict.exists(format!("key:{:03}", i).as_bytes()), "key:{:03} was evicted", i);
+    }
+    // Like Redis, eviction starts once the limit is exceeded, so one
+    // write's worth over it remains
+    assert_eq!(shard.dict.len(), 101);
+    let info = shard.info(Some(b"stats"));
+    assert!(info.contains("evicted_keys:49\r\n"), "{}", info);
+}
+
+#[test]
+fn evicted_keys_stay_gone_after_an_aof_reload() {
+    let path = std::env::temp_dir().join(format!("ignix-maxmemory-{}.aof", std::process::id()));
+    let path = path.to_str().unwrap();
+    let _ = std::fs::remove_file(path);
+    let aof = spawn_aof_writer(path, AofPolicy::EverySec).unwrap();
+    let config = Config { maxmemory: 20 * 107, maxmemory_policy: MaxmemoryPolicy::AllKeysLru, ..Default::default() };
+    let shard = Shard::with_config(0, Some(aof.clone()), config);
+    fill(&shard, 0..100);
+    aof.shutdown();
+
+    let reloaded = Shard::new(0, None);
+    load_aof(path, &reloaded).unwrap();
+    assert_eq!(reloaded.dict.len(), shard.dict.len());
+    assert_eq!(reloaded.dict.used_memory(), shard.dict.used_memory());
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn info_reports_the_dataset_size_and_limit() {
+    let shard = limited(1 << 20, MaxmemoryPolicy::AllKeysLru);
+    fill(&shard, 0..3);
+    let info = shard.info(Some(b"memory"));
+    assert!(info.contains("used_memory_dataset:321\r\n"), "{}", info);
+    assert!(info.contains("maxmemory:1048576\r\n"), "{}", info);
+    assert!(info.contains("maxmemory_policy:allkeys-lru\r\n"), "{}", info);
+}
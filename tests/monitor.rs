@@ -0,0 +1,57 @@
+mod common;
+
+use common::{connect, resp_cmd, send_cmd, start_server, start_server_with};
+use ignix::*;
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+#[test]
+fn monitor_sees_other_clients_commands() {
+    let addr = start_server();
+    let mut mon = connect(addr);
+    assert_eq!(send_cmd(&mut mon, &resp_cmd(&[b"MONITOR"])), b"+OK\r\n");
+
+    let mut client = connect(addr);
+    assert_eq!(send_cmd(&mut client, &resp_cmd(&[b"SET", b"foo", b"bar"])), b"+OK\r\n");
+
+    // The feed line may arrive in pieces; read until it is complete
+    let mut feed = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !feed.ends_with(b"\r\n") && Instant::now() < deadline {
+        let mut buf = [0u8; 256];
+        let n = mon.read(&mut buf).expect("Failed to read monitor feed");
+        feed.extend_from_slice(&buf[..n]);
+    }
+    let line = String::from_utf8_lossy(&feed);
+    assert!(line.starts_with('+'), "unexpected feed: {}", line);
+    assert!(line.contains("[0 127.0.0.1:"), "missing client addr: {}", line);
+    assert!(line.ends_with("\"SET\" \"foo\" \"bar\"\r\n"), "unexpected feed: {}", line);
+}
+
+#[test]
+fn monitor_skips_refused_commands_and_itself() {
+    let config = Config { maxmemory: 1, ..Default::default() };
+    let addr = start_server_with(Shard::with_config(0, None, config));
+    let mut mon = connect(addr);
+    assert_eq!(send_cmd(&mut mon, &resp_cmd(&[b"MONITOR"])), b"+OK\r\n");
+
+    let mut client = connect(addr);
+    assert_eq!(send_cmd(&mut client, &resp_cmd(&[b"SET", b"a", b"1"])), b"+OK\r\n");
+    // Over maxmemory with noeviction: refused before it runs
+    assert!(send_cmd(&mut client, &resp_cmd(&[b"SET", b"b", b"2"])).starts_with(b"-OOM"));
+    assert!(send_cmd(&mut client, &resp_cmd(&[b"NOPE"])).starts_with(b"-ERR unknown command"));
+    assert!(send_cmd(&mut client, &resp_cmd(&[b"GET"])).starts_with(b"-ERR wrong number"));
+    assert_eq!(send_cmd(&mut connect(addr), &resp_cmd(&[b"MONITOR"])), b"+OK\r\n");
+    assert_eq!(send_cmd(&mut client, &resp_cmd(&[b"GET", b"a"])), b"$1\r\n1\r\n");
+
+    let mut feed = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !feed.ends_with(b"\"GET\" \"a\"\r\n") && Instant::now() < deadline {
+        let mut buf = [0u8; 1024];
+        let n = mon.read(&mut buf).expect("Failed to read monitor feed");
+        feed.extend_from_slice(&buf[..n]);
+    }
+    let feed = String::from_utf8_lossy(&feed);
+    let commands: Vec<_> = feed.lines().map(|l| l.split_once("] ").map_or(l, |(_, cmd)| cmd)).collect();
+    assert_eq!(commands, ["\"SET\" \"a\" \"1\"", "\"GET\" \"a\""], "feed: {}", feed);
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use common::{connect, resp_cmd, send_cmd, start_server, start_server_with};
+use ignix::{Config, Shard};
+use std::io::Read;
+
+#[test]
+fn debug_panic_is_refused_by_default() {
+    let mut c = connect(start_server());
+    assert_eq!(
+        send_cmd(&mut c, &resp_cmd(&[b"DEBUG", b"PANIC"])),
+        b"-ERR DEBUG PANIC is disabled (enable-debug-panic)\r\n"
+    );
+    assert_eq!(send_cmd(&mut c, &resp_cmd(&[b"PING"])), b"+PONG\r\n");
+}
+
+#[test]
+fn panicking_command_only_closes_its_own_connection() {
+    let config = Config { enable_debug_panic: true, ..Default::default() };
+    let addr = start_server_with(Shard::with_config(0, None, config));
+    let mut bystanders: Vec<_> = (0..8).map(|_| connect(addr)).collect();
+    for s in &mut bystanders {
+        assert_eq!(send_cmd(s, &resp_cmd(&[b"SET", b"k", b"v"])), b"+OK\r\n");
+    }
+
+    // The reply to the panicking command is an error, then the connection ends
+    let mut victim = connect(addr);
+    assert_eq!(send_cmd(&mut victim, &resp_cmd(&[b"DEBUG", b"PANIC"])), b"-ERR internal error\r\n");
+    let mut buf = [0u8; 16];
+    assert_eq!(victim.read(&mut buf).unwrap(), 0);
+
+    // Every other connection, on whichever worker, is still served
+    for s in &mut bystanders {
+        assert_eq!(send_cmd(s, &resp_cmd(&[b"GET", b"k"])), b"$1\r\nv\r\n");
+    }
+    assert_eq!(send_cmd(&mut connect(addr), &resp_cmd(&[b"PING"])), b"+PONG\r\n");
+}
@@ -0,0 +1,127 @@
+//! Randomized parser checks, the stable-toolchain counterpart of the
+//! `fuzz/` cargo-fuzz target
+//!
+//! Streams are built from the fuzz seed corpus, spliced and mutated with a
+//! fixed RNG seed, then fed to `parse_many` whole, byte by byte and in
+//! random chunks. Parsing must never panic and every split must yield the
+//! same commands and the same framing error.
+
+use bytes::BytesMut;
+use ignix::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::path::Path;
+
+/// The seeds under `fuzz/corpus/parse_many`
+fn corpus() -> Vec<Vec<u8>> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus/parse_many");
+    let mut seeds: Vec<Vec<u8>> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|e| std::fs::read(e.unwrap().path()).unwrap())
+        .collect();
+    seeds.sort();
+    assert!(!seeds.is_empty());
+    seeds
+}
+
+/// Commands parsed from `chunks` fed one after another, and the framing
+/// error that stopped parsing
+fn parse_chunks<'a>(chunks: impl Iterator<Item = &'a [u8]>) -> (Vec<Cmd>, Option<String>) {
+    let mut buf = BytesMut::new();
+    let mut cmds = Vec::new();
+    for chunk in chunks {
+        buf.extend_from_slice(chunk);
+        if let Err(e) = parse_many(&mut buf, &mut cmds) {
+            return (cmds, Some(e.to_string()));
+        }
+    }
+    (cmds, None)
+}
+
+/// Split `stream` at random points
+fn random_chunks<'a>(stream: &'a [u8], rng: &mut StdRng) -> Vec<&'a [u8]> {
+    let mut chunks = Vec::new();
+    let mut rest = stream;
+    while !rest.is_empty() {
+        let (chunk, tail) = rest.split_at(rng.gen_range(1..=rest.len().min(64)));
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks
+}
+
+/// Assert every way of feeding `stream` parses the same
+fn assert_split_invariant(stream: &[u8], rng: &mut StdRng) {
+    let whole = parse_chunks(std::iter::once(stream));
+    let bytewise = parse_chunks(stream.chunks(1));
+    assert_eq!(whole, bytewise, "byte by byte: {:?}", stream.escape_ascii().to_string());
+    let chunks = random_chunks(stream, rng);
+    let chunked = parse_chunks(chunks.iter().copied());
+    assert_eq!(whole, chunked, "chunks {:?}", chunks.iter().map(|c| c.escape_ascii().to_string()).collect::<Vec<_>>());
+}
+
+/// A random stream: spliced seeds with bytes flipped, inserted and dropped
+fn mutated_stream(seeds: &[Vec<u8>], rng: &mut StdRng) -> Vec<u8> {
+    let mut stream = Vec::new();
+    for _ in 0..rng.gen_range(1..4) {
+        stream.extend_from_slice(&seeds[rng.gen_range(0..seeds.len())]);
+    }
+    // Bytes that matter to the framing are more interesting than noise
+    const INTERESTING: &[u8] = b"*$-+:\r\n0123456789 ";
+    for _ in 0..rng.gen_range(0..4) {
+        if stream.is_empty() {
+            break;
+        }
+        let at = rng.gen_range(0..stream.len());
+        let byte = if rng.gen_bool(0.8) { INTERESTING[rng.gen_range(0..INTERESTING.len())] } else { rng.gen() };
+        match rng.gen_range(0..3) {
+            0 => stream[at] = byte,
+            1 => stream.insert(at, byte),
+            _ => {
+                stream.remove(at);
+            }
+        }
+    }
+    if rng.gen_bool(0.2) {
+        stream.truncate(rng.gen_range(0..=stream.len()));
+    }
+    stream
+}
+
+#[test]
+fn seed_corpus_parses_the_same_however_it_is_split() {
+    let mut rng = StdRng::seed_from_u64(0x1981);
+    for seed in corpus() {
+        assert_split_invariant(&seed, &mut rng);
+    }
+}
+
+#[test]
+fn mutated_streams_never_panic_or_desync() {
+    let seeds = corpus();
+    let mut rng = StdRng::seed_from_u64(0x5eed);
+    for _ in 0..5000 {
+        let stream = mutated_stream(&seeds, &mut rng);
+        assert_split_invariant(&stream, &mut rng);
+    }
+}
+
+#[test]
+fn random_bytes_never_panic() {
+    let mut rng = StdRng::seed_from_u64(42);
+    for _ in 0..2000 {
+        let stream: Vec<u8> = (0..rng.gen_range(0..256)).map(|_| rng.gen()).collect();
+        assert_split_invariant(&stream, &mut rng);
+    }
+}
+
+#[test]
+fn overlong_inline_lines_fail_however_they_are_split() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut line = vec![b'x'; MAX_INLINE_LEN + 1];
+    line.push(b'\n');
+    let whole = parse_chunks(std::iter::once(&line[..]));
+    assert_eq!(whole.1.as_deref(), Some("Protocol error: too big inline request"));
+    let chunks = random_chunks(&line, &mut rng);
+    assert_eq!(parse_chunks(chunks.iter().copied()), whole);
+}
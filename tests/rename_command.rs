@@ -0,0 +1,44 @@
+mod common;
+use common::*;
+use ignix::*;
+
+fn renamed_server() -> std::net::TcpStream {
+    let mut config = Config::default();
+    config.rename_command("FLUSHALL", "").unwrap();
+    config.rename_command("config", "s3cret-config").unwrap();
+    connect(start_server_with(Shard::with_config(0, None, config)))
+}
+
+#[test]
+fn disabled_command_is_unknown() {
+    let mut c = renamed_server();
+    assert_eq!(send_cmd(&mut c, &resp_cmd(&[b"SET", b"k", b"v"])), b"+OK\r\n");
+    assert_eq!(
+        send_cmd(&mut c, &resp_cmd(&[b"FLUSHALL"])),
+        b"-ERR unknown command 'FLUSHALL', with args beginning with:\r\n"
+    );
+    assert_eq!(send_cmd(&mut c, &resp_cmd(&[b"GET", b"k"])), b"$1\r\nv\r\n");
+    // Inline requests go through the same renames
+    assert!(send_cmd(&mut c, b"flushall\r\n").starts_with(b"-ERR unknown command 'flushall'"));
+}
+
+#[test]
+fn renamed_command_only_answers_to_its_new_name() {
+    let mut c = renamed_server();
+    assert_eq!(
+        send_cmd(&mut c, &resp_cmd(&[b"CONFIG", b"RESETSTAT"])),
+        b"-ERR unknown command 'CONFIG', with args beginning with: 'RESETSTAT'\r\n"
+    );
+    assert_eq!(send_cmd(&mut c, &resp_cmd(&[b"S3CRET-config", b"RESETSTAT"])), b"+OK\r\n");
+}
+
+#[test]
+fn invalid_renames_are_rejected() {
+    let mut config = Config::default();
+    assert!(config.rename_command("NOPE", "x").is_err());
+    // The target name must not shadow a reachable command
+    assert!(config.rename_command("DEL", "get").is_err());
+    config.rename_command("GET", "").unwrap();
+    assert!(config.rename_command("get", "fetch").is_err());
+    config.rename_command("DEL", "get").unwrap();
+}
@@ -0,0 +1,42 @@
+use bytes::Bytes;
+use ignix::*;
+
+#[test]
+fn replay_executes_a_resp_trace() {
+    let mut trace = Vec::new();
+    trace.extend_from_slice(b"*3\r\n$3\r\nSET\r\n$1\r\na\r\n$1\r\n1\r\n");
+    trace.extend_from_slice(b"*2\r\n$4\r\nINCR\r\n$1\r\na\r\n");
+    trace.extend_from_slice(b"*5\r\n$4\r\nMSET\r\n$1\r\nb\r\n$1\r\nx\r\n$1\r\nc\r\n$1\r\ny\r\n");
+    trace.extend_from_slice(b"*2\r\n$3\r\nDEL\r\n$1\r\nc\r\n");
+    trace.extend_from_slice(b"*2\r\n$3\r\nGET\r\n$1\r\nb\r\n");
+    // Inline commands and rejected commands are part of a trace too
+    trace.extend_from_slice(b"PING\r\nNOPE x\r\n");
+
+    let shard = Shard::new(0, None);
+    // A tiny reader splits commands across reads
+    let stats = replay_commands(Chunked(&trace[..], 7), &shard).unwrap();
+    assert_eq!(stats.commands, 7);
+    assert_eq!(stats.bytes, trace.len() as u64);
+
+    assert_eq!(shard.dict.len(), 2);
+    assert_eq!(shard.dict.get(b"a"), Some(Value::Int(2)));
+    assert_eq!(shard.dict.get(b"b"), Some(Value::Str(Bytes::from_static(b"x"))));
+    assert!(!shard.dict.exists(b"c"));
+}
+
+#[test]
+fn truncated_trace_is_an_error() {
+    let shard = Shard::new(0, None);
+    let err = replay_commands(&b"*2\r\n$3\r\nGET\r\n$1\r\n"[..], &shard).unwrap_err();
+    assert!(err.to_string().contains("incomplete command"), "{}", err);
+}
+
+/// Reader yielding at most `n` bytes per call
+struct Chunked<R>(R, usize);
+
+impl<R: std::io::Read> std::io::Read for Chunked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = buf.len().min(self.1);
+        self.0.read(&mut buf[..n])
+    }
+}
@@ -19,3 +19,98 @@ fn parse_ping_and_set_get() {
     assert!(matches!(cmds[1], Cmd::Set(_, _)));
     assert!(matches!(cmds[2], Cmd::Get(_)));
 }
+
+#[test]
+fn enormous_multibulk_header_is_rejected() {
+    // No element data follows; the header alone must trip the guard
+    let err = protocol::parse_one(b"*1000000000\r\n").unwrap_err();
+    assert_eq!(err.to_string(), "Protocol error: invalid multibulk length");
+
+    let mut buf = BytesMut::from(&b"*1000000000\r\n$3\r\nGET\r\n"[..]);
+    let mut cmds = Vec::new();
+    assert!(protocol::parse_many(&mut buf, &mut cmds).is_err());
+    assert!(cmds.is_empty());
+}
+
+#[test]
+fn overflowing_lengths_are_rejected_not_wrapped() {
+    // 2^64 + 1 used to wrap around to a one-element array
+    let err = protocol::parse_one(b"*18446744073709551617\r\n$4\r\nPING\r\n").unwrap_err();
+    assert_eq!(err.to_string(), "Protocol error: invalid multibulk length");
+    assert!(protocol::parse_one(b"*-9223372036854775808\r\n").is_ok());
+}
+
+#[test]
+fn multibulk_limit_is_configurable() {
+    let limits = ProtoLimits { max_multibulk_len: 2, ..Default::default() };
+    assert!(protocol::parse_one_with(b"*2\r\n$3\r\nGET\r\n$1\r\na\r\n", &limits).unwrap().is_some());
+    assert!(protocol::parse_one_with(b"*3\r\n$3\r\nSET\r\n$1\r\na\r\n$1\r\nb\r\n", &limits).is_err());
+}
+
+#[test]
+fn enormous_bulk_length_is_rejected() {
+    // The header alone must trip the guard, before any payload arrives
+    let err = protocol::parse_one(b"*2\r\n$3\r\nGET\r\n$999999999999\r\n").unwrap_err();
+    assert_eq!(err.to_string(), "Protocol error: invalid bulk length");
+    let err = protocol::parse_one(b"*1\r\n$18446744073709551617\r\n").unwrap_err();
+    assert_eq!(err.to_string(), "Protocol error: invalid bulk length");
+
+    // Exactly the limit is still a length to wait for
+    let header = format!("*2\r\n$3\r\nGET\r\n${}\r\n", DEFAULT_PROTO_MAX_BULK_LEN);
+    assert!(protocol::parse_one(header.as_bytes()).unwrap().is_none());
+}
+
+#[test]
+fn bulk_limit_is_configurable() {
+    let limits = ProtoLimits { max_bulk_len: 3, ..Default::default() };
+    assert!(protocol::parse_one_with(b"*2\r\n$3\r\nGET\r\n$3\r\nabc\r\n", &limits).unwrap().is_some());
+    let err = protocol::parse_one_with(b"*2\r\n$3\r\nGET\r\n$4\r\nabcd\r\n", &limits).unwrap_err();
+    assert_eq!(err.to_string(), "Protocol error: invalid bulk length");
+}
+
+#[test]
+fn inline_commands_accept_crlf_and_bare_lf() {
+    let mut buf = BytesMut::from(&b"PING\r\n\nSET a  1\n*2\r\n$3\r\nGET\r\n$1\r\na\r\nget a"[..]);
+    let mut cmds = Vec::new();
+    protocol::parse_many(&mut buf, &mut cmds).unwrap();
+    assert_eq!(cmds, vec![
+        Cmd::Ping,
+        Cmd::Set("a".into(), "1".into()),
+        Cmd::Get("a".into()),
+    ]);
+    // The unterminated inline line waits for its newline
+    assert_eq!(&buf[..], b"get a");
+
+    let oversized = vec![b'x'; protocol::MAX_INLINE_LEN + 1];
+    assert!(protocol::parse_one(&oversized).is_err());
+}
+
+#[test]
+fn zero_length_bulk_strings_are_empty_arguments() {
+    let (n, cmd) = protocol::parse_one(b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$0\r\n\r\n").unwrap().unwrap();
+    assert_eq!(n, 26);
+    assert_eq!(cmd, Cmd::Set("k".into(), "".into()));
+
+    let (_, cmd) = protocol::parse_one(b"*3\r\n$3\r\nSET\r\n$0\r\n\r\n$1\r\nv\r\n").unwrap().unwrap();
+    assert_eq!(cmd, Cmd::Set("".into(), "v".into()));
+
+    // The trailing CRLF of an empty bulk may arrive in a later read
+    assert!(protocol::parse_one(b"*2\r\n$3\r\nGET\r\n$0\r\n").unwrap().is_none());
+    assert!(protocol::parse_one(b"*2\r\n$3\r\nGET\r\n$0\r\n\r").unwrap().is_none());
+    let (_, cmd) = protocol::parse_one(b"*2\r\n$3\r\nGET\r\n$0\r\n\r\n").unwrap().unwrap();
+    assert_eq!(cmd, Cmd::Get("".into()));
+}
+
+#[test]
+fn empty_arrays_are_skipped_and_bad_bulk_lengths_rejected() {
+    let mut buf = BytesMut::from(&b"*0\r\n*-1\r\n*1\r\n$4\r\nPING\r\n"[..]);
+    let mut cmds = Vec::new();
+    protocol::parse_many(&mut buf, &mut cmds).unwrap();
+    assert_eq!(cmds, vec![Cmd::Ping]);
+    assert!(buf.is_empty());
+
+    let err = protocol::parse_one(b"*2\r\n$3\r\nGET\r\n$-5\r\nabc\r\n").unwrap_err();
+    assert_eq!(err.to_string(), "Protocol error: invalid bulk length");
+    // A payload longer than its declared length is a framing error
+    assert!(protocol::parse_one(b"*2\r\n$3\r\nGET\r\n$0\r\nx\r\n").is_err());
+}
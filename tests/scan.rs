@@ -0,0 +1,212 @@
+use bytes::{Bytes, BytesMut};
+use ignix::*;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+fn b(s: &str) -> Bytes {
+    Bytes::copy_from_slice(s.as_bytes())
+}
+
+fn scan_cmd(cursor: u64, count: usize) -> Cmd {
+    Cmd::Scan { cursor, pattern: None, count }
+}
+
+/// Split a SCAN reply into its cursor and keys
+fn parse_reply(reply: &[u8]) -> (u64, Vec<Bytes>) {
+    let mut lines = reply.split(|&c| c == b'\n').map(|l| l.strip_suffix(b"\r").unwrap_or(l));
+    assert_eq!(lines.next(), Some(&b"*2"[..]));
+    lines.next();
+    let cursor = std::str::from_utf8(lines.next().unwrap()).unwrap().parse().unwrap();
+    let len: usize = std::str::from_utf8(&lines.next().unwrap()[1..]).unwrap().parse().unwrap();
+    let keys = (0..len).map(|_| {
+        lines.next();
+        Bytes::copy_from_slice(lines.next().unwrap())
+    });
+    (cursor, keys.collect())
+}
+
+/// Every key of a full SCAN through `exec`, in the order returned
+fn full_scan(exec: impl Fn(Cmd) -> Vec<u8>, count: usize) -> Vec<Bytes> {
+    let (mut cursor, mut all) = (0, Vec::new());
+    loop {
+        let (next, keys) = parse_reply(&exec(scan_cmd(cursor, count)));
+        all.extend(keys);
+        if next == 0 {
+            return all;
+        }
+        cursor = next;
+    }
+}
+
+#[test]
+fn full_scan_returns_every_key_once() {
+    let s = Shard::new(0, None);
+    let exec = |cmd| {
+        let mut out = BytesMut::new();
+        s.exec(cmd, &mut out);
+        out.to_vec()
+    };
+    assert_eq!(exec(scan_cmd(0, 10)), b"*2\r\n$1\r\n0\r\n*0\r\n");
+
+    for i in 0..1000 {
+        exec(Cmd::Set(b(&format!("key:{}", i)), b("v")));
+    }
+    let keys = full_scan(exec, 10);
+    assert_eq!(keys.len(), 1000);
+    assert_eq!(keys.iter().collect::<HashSet<_>>().len(), 1000);
+}
+
+#[test]
+fn batches_hold_count_keys() {
+    let d = Dict::default();
+    for i in 0..100 {
+        d.set(b(&format!("key:{}", i)), Value::Int(i));
+    }
+    // Whole buckets are returned, so a batch may run a little over
+    let (keys, next) = d.scan(0, 30);
+    assert!((30..40).contains(&keys.len()), "{} keys", keys.len());
+    assert_ne!(next, 0);
+    // Resuming from the same cursor repeats the batch
+    assert_eq!(d.scan(next, 30), d.scan(next, 30));
+    let (all, next) = d.scan(0, 1000);
+    assert_eq!((all.len(), next), (100, 0));
+}
+
+#[test]
+fn keys_present_throughout_survive_concurrent_growth() {
+    let d = Dict::with_seed(42);
+    let old: HashSet<Bytes> = (0..500).map(|i| b(&format!("old:{}", i))).collect();
+    for k in &old {
+        d.set(k.clone(), Value::Int(1));
+    }
+    let capacity = d.capacity();
+
+    let (mut cursor, mut seen, mut round) = (0, Vec::new(), 0);
+    loop {
+        let (keys, next) = d.scan(cursor, 50);
+        seen.extend(keys);
+        // Grow the tables and churn the new keys between batches, so
+        // buckets are reshuffled under the cursor; fewer keys are added
+        // than a batch takes, so the scan still ends
+        for i in 0..45 {
+            d.set(b(&format!("new:{}:{}", round, i)), Value::Int(1));
+        }
+        for i in 0..5 {
+            d.del(format!("new:{}:{}", round, i * 9).as_bytes());
+        }
+        round += 1;
+        if next == 0 {
+            break;
+        }
+        cursor = next;
+    }
+    assert!(d.capacity() >= capacity * 2, "tables grew from {} to {}", capacity, d.capacity());
+    let seen_old: Vec<_> = seen.iter().filter(|k| old.contains(*k)).collect();
+    assert_eq!(seen_old.len(), old.len());
+    assert_eq!(seen_old.into_iter().collect::<HashSet<_>>().len(), old.len());
+}
+
+#[test]
+fn keys_present_throughout_survive_churn() {
+    let d = Dict::with_seed(7);
+    let old: HashSet<Bytes> = (0..1000).map(|i| b(&format!("old:{}", i))).collect();
+    for k in &old {
+        d.set(k.clone(), Value::Int(1));
+    }
+
+    // Keys come and go at a steady size: the tables fill with tombstones
+    // and rehash in place, moving keys between slots under the cursor
+    let (mut cursor, mut seen, mut round) = (0, Vec::new(), 0);
+    loop {
+        let (keys, next) = d.scan(cursor, 20);
+        seen.extend(keys);
+        for i in 0..50 {
+            d.set(b(&format!("tmp:{}:{}", round, i)), Value::Int(1));
+        }
+        for i in 0..50 {
+            d.del(format!("tmp:{}:{}", round, i).as_bytes());
+        }
+        round += 1;
+        if next == 0 {
+            break;
+        }
+        cursor = next;
+    }
+    let seen_old: Vec<_> = seen.iter().filter(|k| old.contains(*k)).collect();
+    assert_eq!(seen_old.len(), old.len());
+    assert_eq!(seen_old.into_iter().collect::<HashSet<_>>().len(), old.len());
+}
+
+#[test]
+fn match_filters_the_batch() {
+    let s = Shard::new(0, None);
+    let exec = |cmd| {
+        let mut out = BytesMut::new();
+        s.exec(cmd, &mut out);
+        out.to_vec()
+    };
+    for i in 0..50 {
+        exec(Cmd::Set(b(&format!("user:{}", i)), b("v")));
+        exec(Cmd::Set(b(&format!("session:{}", i)), b("v")));
+    }
+
+    let (next, keys) = parse_reply(&exec(Cmd::Scan { cursor: 0, pattern: Some(b("user:*")), count: 1000 }));
+    assert_eq!((next, keys.len()), (0, 50));
+    assert!(keys.iter().all(|k| k.starts_with(b"user:")));
+
+    // The pattern is applied after the batch is picked, so small batches
+    // may come back empty while the scan goes on
+    let (mut cursor, mut matched) = (0, 0);
+    loop {
+        let (next, keys) = parse_reply(&exec(Cmd::Scan { cursor, pattern: Some(b("user:1?")), count: 5 }));
+        assert!(keys.len() < 10);
+        matched += keys.len();
+        if next == 0 {
+            break;
+        }
+        cursor = next;
+    }
+    assert_eq!(matched, 10);
+}
+
+#[test]
+fn expired_keys_are_skipped() {
+    let d = Dict::default();
+    d.set(b("live"), Value::Int(1));
+    d.set(b("gone"), Value::Int(1));
+    assert!(d.expire_at(b"gone", Instant::now() + Duration::from_millis(10)));
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(d.scan(0, 10), (vec![b("live")], 0));
+}
+
+#[test]
+fn scan_covers_every_shard_of_a_set() {
+    let set = ShardSet::with_config(None, Config { shards: 4, ..Default::default() });
+    let exec = |cmd| {
+        let mut out = BytesMut::new();
+        set.exec(cmd, &mut out);
+        out.to_vec()
+    };
+    for i in 0..1000 {
+        exec(Cmd::Set(b(&format!("key:{}", i)), b("v")));
+    }
+    for count in [1, 10, 5000] {
+        let keys = full_scan(exec, count);
+        assert_eq!(keys.len(), 1000, "COUNT {}", count);
+        assert_eq!(keys.iter().collect::<HashSet<_>>().len(), 1000, "COUNT {}", count);
+    }
+    // A shard index the set doesn't have ends the scan
+    assert_eq!(exec(scan_cmd(7, 10)), b"*2\r\n$1\r\n0\r\n*0\r\n");
+}
+
+#[test]
+fn scan_arguments_are_parsed() {
+    let parse = |args: &[&'static [u8]]| command::dispatch(args.iter().map(|a| Bytes::from_static(a)).collect());
+    assert_eq!(parse(&[b"SCAN", b"0"]), scan_cmd(0, SCAN_DEFAULT_COUNT));
+    assert_eq!(
+        parse(&[b"scan", b"42", b"count", b"100", b"match", b"a*"]),
+        Cmd::Scan { cursor: 42, pattern: Some(b("a*")), count: 100 }
+    );
+    assert_eq!(parse(&[b"SCAN", b"0", b"COUNT", b"x"]), Cmd::Invalid(CmdError::NotInteger.to_string()));
+    assert_eq!(parse(&[b"SCAN", b"0", b"TYPE", b"string"]), Cmd::Invalid(CmdError::Syntax.to_string()));
+}
@@ -0,0 +1,210 @@
+mod common;
+
+use bytes::{Bytes, BytesMut};
+use common::{connect, resp_cmd, send_cmd, start_server_with};
+use ignix::*;
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+fn set_of(shards: usize) -> ShardSet {
+    ShardSet::with_config(None, Config { shards, ..Default::default() })
+}
+
+fn exec(set: &ShardSet, cmd: Cmd) -> Vec<u8> {
+    let mut out = BytesMut::new();
+    set.exec(cmd, &mut out);
+    out.to_vec()
+}
+
+fn b(s: &str) -> Bytes {
+    Bytes::copy_from_slice(s.as_bytes())
+}
+
+/// Two keys owned by different shards of the set
+fn keys_on_different_shards(set: &ShardSet) -> (Bytes, Bytes) {
+    let first = b("k0");
+    let owner = set.shard_for(&first).id;
+    let other = (1..).map(|i| b(&format!("k{}", i))).find(|k| set.shard_for(k).id != owner).unwrap();
+    (first, other)
+}
+
+#[test]
+fn key_slots_match_redis_cluster() {
+    assert_eq!(key_slot(b"foo"), 12182);
+    assert_eq!(key_slot(b"123456789"), 12739);
+    // Only a non-empty hash tag is hashed
+    assert_eq!(key_slot(b"{user1}.following"), key_slot(b"user1"));
+    assert_eq!(key_slot(b"foo{{bar}}zap"), key_slot(b"{bar"));
+    assert_ne!(key_slot(b"foo{}{bar}"), key_slot(b"bar"));
+}
+
+#[test]
+fn keys_are_spread_over_the_shards() {
+    let set = set_of(4);
+    for i in 0..1000 {
+        let k = b(&format!("key:{}", i));
+        assert_eq!(exec(&set, Cmd::Set(k.clone(), k.clone())), b"+OK\r\n");
+        assert!(set.shard_for(&k).dict.get(&k).is_some());
+    }
+    for shard in set.shards() {
+        let keys = shard.dict.len();
+        assert!(keys > 150, "shard {} owns {} of 1000 keys", shard.id, keys);
+    }
+    assert_eq!(exec(&set, Cmd::Get(b("key:7"))), b"$5\r\nkey:7\r\n");
+    assert_eq!(exec(&set, Cmd::DbSize), b":1000\r\n");
+}
+
+#[test]
+fn multi_key_commands_must_stay_on_one_shard() {
+    let set = set_of(4);
+    let (a, z) = keys_on_different_shards(&set);
+    let crossslot = b"-CROSSSLOT Keys in request don't hash to the same slot\r\n";
+    assert_eq!(exec(&set, Cmd::MSet(vec![(a.clone(), b("1")), (z.clone(), b("2"))])), crossslot);
+    assert_eq!(exec(&set, Cmd::Del(vec![a.clone(), z.clone()])), crossslot);
+    assert_eq!(exec(&set, Cmd::Rename(a.clone(), z.clone())), crossslot);
+    // Refused commands don't run at all
+    assert_eq!(exec(&set, Cmd::DbSize), b":0\r\n");
+
+    // A shared hash tag puts keys on one shard
+    let name = b("{user:1}:name");
+    let email = b("{user:1}:email");
+    assert_eq!(exec(&set, Cmd::MSet(vec![(name.clone(), b("ann")), (email.clone(), b("a@x"))])), b"+OK\r\n");
+    assert_eq!(exec(&set, Cmd::MGet(vec![name, email])), b"*2\r\n$3\r\nann\r\n$3\r\na@x\r\n");
+}
+
+#[test]
+fn flushall_and_info_cover_every_shard() {
+    let set = set_of(3);
+    for i in 0..100 {
+        exec(&set, Cmd::Set(b(&format!("k{}", i)), b("v")));
+    }
+    let info = exec(&set, Cmd::Info(Some("keyspace".into())));
+    assert!(String::from_utf8_lossy(&info).contains("db0:keys=100,"), "{}", String::from_utf8_lossy(&info));
+
+    assert_eq!(exec(&set, Cmd::FlushAll), b"+OK\r\n");
+    assert!(set.shards().iter().all(|s| s.dict.is_empty()));
+    assert_eq!(exec(&set, Cmd::DbSize), b":0\r\n");
+}
+
+#[test]
+fn aof_of_a_set_reloads_into_any_number_of_shards() {
+    let path = std::env::temp_dir().join(format!("ignix-shard-set-{}.aof", std::process::id()));
+    let path = path.to_str().unwrap();
+    let _ = std::fs::remove_file(path);
+    let aof = spawn_aof_writer(path, AofPolicy::EverySec).unwrap();
+    let config = Config { shards: 4, aof_rewrite_percentage: 0, ..Default::default() };
+    let set = ShardSet::with_config(Some(aof.clone()), config);
+    for i in 0..50 {
+        exec(&set, Cmd::Set(b(&format!("old{}", i)), b("x")));
+    }
+    exec(&set, Cmd::FlushAll);
+    for i in 0..200 {
+        exec(&set, Cmd::Incr(b(&format!("n{}", i % 20))));
+    }
+    assert_eq!(exec(&set, Cmd::BgRewriteAof), b"+Background append only file rewriting started\r\n");
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while aof.is_rewriting() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert!(!aof.is_rewriting(), "rewrite never finished");
+    exec(&set, Cmd::Set(b("after"), b("rewrite")));
+    aof.shutdown();
+
+    for shards in [1, 3] {
+        let reloaded = set_of(shards);
+        reloaded.load_aof(path).unwrap();
+        assert_eq!(exec(&reloaded, Cmd::DbSize), b":21\r\n");
+        assert_eq!(exec(&reloaded, Cmd::Get(b("n7"))), b"$2\r\n10\r\n");
+        assert_eq!(exec(&reloaded, Cmd::Get(b("after"))), b"$7\r\nrewrite\r\n");
+    }
+
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn server_serves_a_shard_set() {
+    let set = set_of(4);
+    let (a, z) = keys_on_different_shards(&set);
+    let addr = start_server_with(set);
+    let mut s = connect(addr);
+    assert_eq!(send_cmd(&mut s, &resp_cmd(&[b"SET", &a, b"1"])), b"+OK\r\n");
+    assert_eq!(send_cmd(&mut s, &resp_cmd(&[b"SET", &z, b"2"])), b"+OK\r\n");
+    assert_eq!(send_cmd(&mut s, &resp_cmd(&[b"GET", &z])), b"$1\r\n2\r\n");
+    assert_eq!(send_cmd(&mut s, &resp_cmd(&[b"DBSIZE"])), b":2\r\n");
+    let reply = send_cmd(&mut s, &resp_cmd(&[b"MGET", &a, &z]));
+    assert!(reply.starts_with(b"-CROSSSLOT"), "{}", String::from_utf8_lossy(&reply));
+}
+
+fn owned_server(shards: usize) -> std::net::SocketAddr {
+    start_server_with(ShardSet::with_config(None, Config { shards, owned_shards: true, ..Default::default() }))
+}
+
+/// Send a pipeline and read back exactly `expected`
+fn pipeline(s: &mut std::net::TcpStream, requests: &[Vec<u8>], expected: &[u8]) {
+    s.write_all(&requests.concat()).unwrap();
+    let mut reply = vec![0u8; expected.len()];
+    s.read_exact(&mut reply).unwrap();
+    assert_eq!(String::from_utf8_lossy(&reply), String::from_utf8_lossy(expected));
+}
+
+#[test]
+fn owned_shards_keep_pipelined_replies_in_order() {
+    let addr = owned_server(4);
+    let clients: Vec<_> = (0..8)
+        .map(|c| {
+            std::thread::spawn(move || {
+                let mut s = connect(addr);
+                let keys: Vec<String> = (0..200).map(|i| format!("c{}:k{}", c, i)).collect();
+                let sets: Vec<_> = keys.iter().map(|k| resp_cmd(&[b"SET", k.as_bytes(), k.as_bytes()])).collect();
+                pipeline(&mut s, &sets, &b"+OK\r\n".repeat(keys.len()));
+
+                // Reads of keys owned by every worker, interleaved with a
+                // key-less command and a refused one
+                let mut requests = Vec::new();
+                let mut expected = Vec::new();
+                for k in &keys {
+                    requests.push(resp_cmd(&[b"GET", k.as_bytes()]));
+                    expected.extend_from_slice(format!("${}\r\n{}\r\n", k.len(), k).as_bytes());
+                    requests.push(resp_cmd(&[b"PING"]));
+                    expected.extend_from_slice(b"+PONG\r\n");
+                }
+                requests.push(resp_cmd(&[b"MGET", keys[0].as_bytes(), keys[1].as_bytes(), keys[2].as_bytes()]));
+                expected.extend_from_slice(b"-CROSSSLOT Keys in request don't hash to the same slot\r\n");
+                pipeline(&mut s, &requests, &expected);
+            })
+        })
+        .collect();
+    for c in clients {
+        c.join().unwrap();
+    }
+    let mut s = connect(addr);
+    assert_eq!(send_cmd(&mut s, &resp_cmd(&[b"DBSIZE"])), b":1600\r\n");
+}
+
+#[test]
+fn owned_shards_run_each_key_on_one_worker() {
+    let addr = owned_server(3);
+    let clients: Vec<_> = (0..6)
+        .map(|_| {
+            std::thread::spawn(move || {
+                let mut s = connect(addr);
+                let incrs = resp_cmd(&[b"INCR", b"hits"]).repeat(500);
+                s.write_all(&incrs).unwrap();
+                // One integer line per INCR
+                let mut reply = Vec::new();
+                while reply.iter().filter(|&&b| b == b'\n').count() < 500 {
+                    let mut buf = [0u8; 4096];
+                    let n = s.read(&mut buf).unwrap();
+                    assert!(n > 0, "connection closed");
+                    reply.extend_from_slice(&buf[..n]);
+                }
+                assert!(reply.split(|&b| b == b'\n').filter(|l| !l.is_empty()).all(|l| l.starts_with(b":")));
+            })
+        })
+        .collect();
+    for c in clients {
+        c.join().unwrap();
+    }
+    let mut s = connect(addr);
+    assert_eq!(send_cmd(&mut s, &resp_cmd(&[b"GET", b"hits"])), b"$4\r\n3000\r\n");
+}
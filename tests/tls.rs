@@ -0,0 +1,70 @@
+mod common;
+
+use common::*;
+use ignix::*;
+use rustls::pki_types::{CertificateDer, ServerName};
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::Arc;
+
+const CERT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls.crt");
+const KEY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls.key");
+
+fn start_tls_server() -> SocketAddr {
+    let config = Config { tls_cert_file: Some(CERT.into()), tls_key_file: Some(KEY.into()), ..Default::default() };
+    start_server_with(Shard::with_config(0, None, config))
+}
+
+/// Connect over TLS, trusting only the fixture certificate
+fn connect_tls(addr: SocketAddr) -> StreamOwned<ClientConnection, TcpStream> {
+    let mut roots = RootCertStore::empty();
+    let pem = std::fs::read(CERT).unwrap();
+    for cert in rustls_pemfile::certs(&mut &pem[..]) {
+        let cert: CertificateDer<'static> = cert.unwrap();
+        roots.add(cert).unwrap();
+    }
+    let config = ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let session = ClientConnection::new(Arc::new(config), ServerName::try_from("localhost").unwrap()).unwrap();
+    StreamOwned::new(session, connect(addr))
+}
+
+/// Read until `n` bytes of reply arrived
+fn read_reply<R: Read>(stream: &mut R, n: usize) -> Vec<u8> {
+    let mut reply = vec![0u8; n];
+    stream.read_exact(&mut reply).unwrap();
+    reply
+}
+
+#[test]
+fn commands_run_over_tls() {
+    let mut stream = connect_tls(start_tls_server());
+
+    stream.write_all(&resp_cmd(&[b"PING"])).unwrap();
+    assert_eq!(read_reply(&mut stream, 7), b"+PONG\r\n");
+
+    // Large enough to span many TLS records and fill the socket buffer
+    let value = vec![b'x'; 1 << 20];
+    stream.write_all(&resp_cmd(&[b"SET", b"big", &value])).unwrap();
+    assert_eq!(read_reply(&mut stream, 5), b"+OK\r\n");
+    stream.write_all(&resp_cmd(&[b"GET", b"big"])).unwrap();
+    let mut expected = format!("${}\r\n", value.len()).into_bytes();
+    expected.extend_from_slice(&value);
+    expected.extend_from_slice(b"\r\n");
+    assert_eq!(read_reply(&mut stream, expected.len()), expected);
+}
+
+#[test]
+fn plaintext_clients_are_dropped() {
+    let mut stream = connect(start_tls_server());
+    stream.write_all(&resp_cmd(&[b"PING"])).unwrap();
+    // The request isn't a TLS handshake: no reply, just a closed (or
+    // alerted and closed) connection
+    let mut reply = Vec::new();
+    let _ = stream.read_to_end(&mut reply);
+    assert!(!reply.starts_with(b"+PONG"), "{:?}", reply);
+}
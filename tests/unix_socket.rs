@@ -0,0 +1,96 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::*;
+use ignix::*;
+use std::io::{Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A socket path unique to the test
+fn socket_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("ignix-{}-{}.sock", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn unix_config(path: &Path) -> Config {
+    Config { unixsocket: Some(path.to_str().unwrap().into()), ..Default::default() }
+}
+
+/// Poll until the Unix socket accepts connections
+fn connect_unix(path: &Path) -> UnixStream {
+    for _ in 0..200 {
+        if let Ok(stream) = UnixStream::connect(path) {
+            stream.set_read_timeout(Some(Duration::from_secs(30))).unwrap();
+            return stream;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    panic!("server did not listen on {}", path.display());
+}
+
+fn unix_cmd(stream: &mut UnixStream, cmd: &[u8]) -> Vec<u8> {
+    stream.write_all(cmd).unwrap();
+    let mut buf = vec![0u8; 1024];
+    let n = stream.read(&mut buf).unwrap();
+    buf.truncate(n);
+    buf
+}
+
+#[test]
+fn clients_connect_over_the_unix_socket_and_tcp() {
+    let path = socket_path("serve");
+    let addr = start_server_with(Shard::with_config(0, None, unix_config(&path)));
+
+    let mut unix = connect_unix(&path);
+    assert_eq!(unix_cmd(&mut unix, &resp_cmd(&[b"PING"])), b"+PONG\r\n");
+    assert_eq!(unix_cmd(&mut unix, &resp_cmd(&[b"SET", b"k", b"v"])), b"+OK\r\n");
+
+    // Both listeners serve the same keyspace
+    let mut tcp = connect(addr);
+    assert_eq!(send_cmd(&mut tcp, &resp_cmd(&[b"GET", b"k"])), b"$1\r\nv\r\n");
+
+    let info = unix_cmd(&mut unix, &resp_cmd(&[b"CLIENT", b"INFO"]));
+    let expected = format!("addr={}:0", path.display());
+    assert!(String::from_utf8_lossy(&info).contains(&expected), "{}", String::from_utf8_lossy(&info));
+}
+
+#[test]
+fn stale_socket_file_is_replaced() {
+    let path = socket_path("stale");
+    // Bound and closed: the file stays behind with nobody accepting
+    drop(UnixListener::bind(&path).unwrap());
+    assert!(path.exists());
+
+    start_server_with(Shard::with_config(0, None, unix_config(&path)));
+    let mut unix = connect_unix(&path);
+    assert_eq!(unix_cmd(&mut unix, &resp_cmd(&[b"PING"])), b"+PONG\r\n");
+}
+
+#[test]
+fn a_file_that_is_not_a_socket_is_left_alone() {
+    let path = socket_path("regular");
+    std::fs::write(&path, b"data").unwrap();
+
+    let addr = "127.0.0.1:0".parse().unwrap();
+    let err = run_shard(0, addr, Shard::with_config(0, None, unix_config(&path))).unwrap_err();
+    assert!(err.to_string().contains("not a socket"), "{}", err);
+    assert_eq!(std::fs::read(&path).unwrap(), b"data");
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn socket_file_is_removed_when_the_server_stops() {
+    let path = socket_path("cleanup");
+    // A plain listener on the port makes every worker fail to bind, so
+    // run_shard returns right after creating the socket file
+    let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = taken.local_addr().unwrap();
+    let config = Config { worker_threads: 1, ..unix_config(&path) };
+
+    run_shard(0, addr, Shard::with_config(0, None, config)).unwrap();
+    assert!(!path.exists());
+}